@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use log_engine::export::ViewExportOptions;
+use log_engine::log_content::LogContent;
+use log_engine::user_settings::{FilterSpec, UserSettings};
+
+use crate::LogalyzerArgs;
+
+// Colors only when writing to a terminal that can show them, see https://no-color.org.
+fn stdout_supports_colors() -> bool {
+    std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+        && std::env::var("TERM").is_ok_and(|term| term != "dumb")
+}
+
+fn user_settings_from_args(args: &LogalyzerArgs) -> Option<UserSettings> {
+    let mut user_settings = UserSettings::default();
+
+    if let Some(config_path_str) = &args.config_path {
+        let config_path = Path::new(config_path_str);
+        if !config_path.exists() {
+            eprintln!("Specified config file does not exist: {}", config_path_str);
+            return None;
+        }
+
+        match log_engine::configuration_load(config_path) {
+            Ok(loaded_user_settings) => user_settings = loaded_user_settings,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        }
+    }
+
+    if let Some(file_path) = &args.file_path {
+        user_settings.file_path = file_path.clone();
+    }
+
+    // Added to the filters from the config, so both have to match.
+    if let Some(filter_term) = &args.filter {
+        user_settings.filters.push(FilterSpec {
+            term: filter_term.clone(),
+            ..Default::default()
+        });
+    }
+
+    Some(user_settings)
+}
+
+fn read_stdin(user_settings: &UserSettings) -> Option<LogContent> {
+    let mut stdin_bytes = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut stdin_bytes) {
+        eprintln!("Error reading stdin: {}", e);
+        return None;
+    }
+
+    let stdin_content = log_engine::encoding::decode(&stdin_bytes, &user_settings.encoding);
+
+    Some(LogContent::from_string(stdin_content.into_owned()))
+}
+
+// Runs the same handlers as the GUI and prints the visible lines, i.e.
+// "logalyzer --file x.log --filter error --no-gui".
+pub fn run_cli(args: &LogalyzerArgs) -> ExitCode {
+    let Some(user_settings) = user_settings_from_args(args) else {
+        return ExitCode::FAILURE;
+    };
+
+    let (content, log_comments) = if args.stdin {
+        let Some(content) = read_stdin(&user_settings) else {
+            return ExitCode::FAILURE;
+        };
+        (content, HashMap::new())
+    } else if user_settings.file_path.is_empty() {
+        eprintln!("No log file specified, use --file or --stdin.");
+        return ExitCode::FAILURE;
+    } else {
+        let opened_file = match log_engine::load_file(&user_settings) {
+            Ok(opened_file) => opened_file,
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        (opened_file.content, opened_file.log_comments)
+    };
+
+    let processed_lines = log_engine::process_log_lines(&content, &user_settings);
+    let options = ViewExportOptions {
+        include_comments: true,
+        ..Default::default()
+    };
+
+    let output = if stdout_supports_colors() {
+        log_engine::export::view_to_ansi(&processed_lines, &log_comments, &options)
+    } else {
+        log_engine::export::view_to_text(&processed_lines, &log_comments, &options)
+    };
+
+    // A closed pipe (i.e. "| head") is not an error worth reporting.
+    let write_result = std::io::stdout().lock().write_all(output.as_bytes());
+    if let Err(e) = write_result
+        && e.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        eprintln!("Error writing to stdout: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
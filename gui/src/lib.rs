@@ -6,10 +6,48 @@ use egui::containers::scroll_area::ScrollBarVisibility;
 use egui::text::{LayoutJob, TextWrapping};
 use egui::{Vec2, scroll_area};
 use log_engine::OpenedFileMetadata;
+use log_engine::key_bindings::{KeyBinding, ShortcutAction};
+use log_engine::thousands_separated;
 use log_engine::user_settings::UserSettings;
+use std::borrow::Cow;
 use std::path::Path;
+use std::process::ExitCode;
 
-pub fn run_gui() {
+mod cli;
+
+// Shown next to the line numbers on the rows a wrapped line or comment continues on.
+const WRAP_INDICATOR: &str = "\n↪";
+
+const LINE_NUMBERS_SLACK_CHARS: usize = 2;
+
+// Byte length is only an estimate of the width (tabs, wide chars), so several of the longest
+// lines are measured.
+const MAX_LINE_WIDTH_CANDIDATES: usize = 16;
+
+// Below this share of matched lines the log format pattern is most likely wrong.
+const LOG_FORMAT_LOW_MATCH_RATE: f64 = 0.5;
+
+const LOG_FORMAT_PREVIEW_LINES: usize = 10;
+
+const STATS_TOP_LINES: usize = 10;
+
+const TOP_PATTERNS: usize = 30;
+
+// How fast the smooth scroll eases, each frame scrolls 1 - e^(-rate * dt) of the rest.
+const SMOOTH_SCROLL_RATE: f32 = 12.0;
+
+pub fn run() -> ExitCode {
+    let args = LogalyzerArgs::parse();
+    if args.no_gui {
+        return cli::run_cli(&args);
+    }
+
+    run_gui(args);
+
+    ExitCode::SUCCESS
+}
+
+fn run_gui(args: LogalyzerArgs) {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1200.0, 800.0]),
         ..Default::default()
@@ -20,7 +58,7 @@ pub fn run_gui() {
     let run_result = eframe::run_native(
         &app_name,
         options,
-        Box::new(|_cc| Ok(Box::new(LogalyzerGUI::new()) as Box<dyn eframe::App>)),
+        Box::new(|_cc| Ok(Box::new(LogalyzerGUI::new(args)) as Box<dyn eframe::App>)),
     );
 
     if run_result.is_err() {
@@ -32,6 +70,7 @@ enum FocusRequests {
     None,
     Search,
     Filter,
+    GoToLine,
 }
 
 #[derive(Default)]
@@ -43,22 +82,73 @@ struct AddCommentRequest {
 struct LogalyzerState {
     vertical_scroll_offset: f32,
     opened_file: Option<OpenedFileMetadata>,
-    line_no_jobs: Vec<LayoutJob>,
-    log_jobs: Vec<LayoutJob>,
-    search_found: Vec<log_engine::PointOfInterest>,
+    file_load_error: Option<log_engine::FileLoadError>,
+    jobs: log_engine::LogJobs,
     search_found_showing_index: usize,
     search_found_last_shown_index: Option<usize>,
     win_log_format_open: bool,
     panel_token_colors_open: bool,
     win_histogram_open: bool,
     win_histogram_should_focus: bool,
+    histogram_line_range_limited: bool,
+    histogram_line_range: (usize, usize), // first and last original line, both included
+    histogram_cache: Option<(HistogramKey, HistogramBars)>,
     log_format_mode_selected: usize,
-    lines_wrapped: usize,
     log_scroll_area_width: f32,
     focus_request: FocusRequests,
     add_comment_request: Option<AddCommentRequest>,
     add_comment_window_open: bool,
-    visible_line_offsets: log_engine::VisibleLineOffsets,
+    line_stream: Option<log_engine::line_stream::LineStream>,
+    following_paused: bool,
+    paused_lines: Vec<String>, // received while paused, appended on resume
+    win_open_stream_open: bool,
+    stream_address_input: String,
+    stream_address: Option<String>, // of the connected TCP stream, to reconnect to
+    stream_connect_error: Option<String>,
+    #[cfg(feature = "journald")]
+    win_open_journal_open: bool,
+    #[cfg(feature = "journald")]
+    journal_unit_input: String,
+    #[cfg(feature = "journald")]
+    journal_open_error: Option<String>,
+    file_watcher: Option<log_engine::file_watcher::FileWatcher>,
+    reload_requested: bool,
+    file_loader: Option<log_engine::file_loader::FileLoader>, // while a file is being loaded
+    last_typed_at: Option<std::time::Instant>,                // in the search or filter inputs
+    scroll_to_bottom_requested: bool,
+    top_visible_row: usize,
+    top_line_restore_requested: Option<usize>, // original line number from a loaded config
+    top_row_scroll_requested: Option<usize>,
+    window_title: String,
+    recalc_worker: log_engine::recalc_worker::RecalcWorker,
+    wrap_cache: log_engine::wrap_cache::WrapCache,
+    max_line_width_cache: log_engine::wrap_cache::MaxLineWidthCache,
+    go_to_line_input: String,
+    go_to_line_target: Option<usize>, // visible line number
+    go_to_line_message: String,
+    fold_first_line_no: Option<usize>, // where the fold being selected starts, original line number
+    config_status: Option<(Result<String, String>, std::time::Instant)>, // outcome, when it happened
+    win_export_view_open: bool,
+    win_stats_open: bool,
+    stats: Option<log_engine::stats::LogStats>, // calculated when the window is opened
+    win_patterns_open: bool,
+    top_patterns: Vec<(String, usize)>, // calculated when the window is opened
+    view_export_options: log_engine::export::ViewExportOptions,
+    token_palette: log_engine::user_settings::TokenPalette, // for the added tokens too
+    column_table: Option<log_engine::columns::ColumnTable>, // for the table view, of the jobs
+    column_table_error: Option<String>, // why the table can't be shown with these settings
+    table_header_indent: f32,           // width of the line numbers
+    table_scroll_x: f32,                // of the log, the header scrolls along
+    table_sort: Option<(String, bool)>, // the column name and whether it's descending
+    win_import_tokens_open: bool,
+    import_tokens_text: String,
+    import_tokens_errors: Vec<String>, // of the last import, the bad lines stay in the text
+    win_key_bindings_open: bool,
+    key_binding_capture: Option<ShortcutAction>, // waiting for the keys of this action
+    focused_last_frame: bool,                    // any widget, see text_input_focused()
+    smooth_scroll_remaining: egui::Vec2, // still to scroll towards the target of the scrolling keys
+    log_line_menu_word: Option<String>,  // under the pointer when the line's menu was opened
+    search_from: Option<(usize, usize)>, // visible line and byte offset to show the next match after
 }
 
 impl Default for LogalyzerState {
@@ -66,22 +156,73 @@ impl Default for LogalyzerState {
         Self {
             vertical_scroll_offset: 0.0,
             opened_file: None,
-            line_no_jobs: vec![LayoutJob::default()],
-            log_jobs: vec![log_engine::default_log_content()],
-            search_found: Vec::new(),
+            file_load_error: None,
+            jobs: log_engine::default_log_jobs(&log_engine::key_bindings::default_key_bindings()),
             search_found_showing_index: 0,
             search_found_last_shown_index: None,
             win_log_format_open: false,
             panel_token_colors_open: false,
             win_histogram_open: false,
             win_histogram_should_focus: false,
+            histogram_line_range_limited: false,
+            histogram_line_range: (1, 1),
+            histogram_cache: None,
             log_format_mode_selected: 0, // 0 means manual regex
-            lines_wrapped: 0,
             log_scroll_area_width: 0.0,
             focus_request: FocusRequests::None,
             add_comment_request: None,
             add_comment_window_open: false,
-            visible_line_offsets: log_engine::VisibleLineOffsets::default(),
+            line_stream: None,
+            following_paused: false,
+            paused_lines: Vec::new(),
+            win_open_stream_open: false,
+            stream_address_input: String::new(),
+            stream_address: None,
+            stream_connect_error: None,
+            #[cfg(feature = "journald")]
+            win_open_journal_open: false,
+            #[cfg(feature = "journald")]
+            journal_unit_input: String::new(),
+            #[cfg(feature = "journald")]
+            journal_open_error: None,
+            file_watcher: None,
+            reload_requested: false,
+            file_loader: None,
+            last_typed_at: None,
+            scroll_to_bottom_requested: false,
+            top_visible_row: 0,
+            top_line_restore_requested: None,
+            top_row_scroll_requested: None,
+            window_title: String::new(),
+            recalc_worker: log_engine::recalc_worker::RecalcWorker::new(),
+            wrap_cache: log_engine::wrap_cache::WrapCache::default(),
+            max_line_width_cache: log_engine::wrap_cache::MaxLineWidthCache::default(),
+            go_to_line_input: String::new(),
+            go_to_line_target: None,
+            go_to_line_message: String::new(),
+            fold_first_line_no: None,
+            config_status: None,
+            win_export_view_open: false,
+            win_stats_open: false,
+            stats: None,
+            win_patterns_open: false,
+            top_patterns: Vec::new(),
+            view_export_options: log_engine::export::ViewExportOptions::default(),
+            token_palette: log_engine::user_settings::TokenPalette::default(),
+            column_table: None,
+            column_table_error: None,
+            table_header_indent: 0.0,
+            table_scroll_x: 0.0,
+            table_sort: None,
+            win_import_tokens_open: false,
+            import_tokens_text: String::new(),
+            import_tokens_errors: Vec::new(),
+            win_key_bindings_open: false,
+            key_binding_capture: None,
+            focused_last_frame: false,
+            smooth_scroll_remaining: egui::Vec2::ZERO,
+            log_line_menu_word: None,
+            search_from: None,
         }
     }
 }
@@ -103,30 +244,139 @@ struct LogalyzerArgs {
     /// Path to the configuration file to load.
     #[arg(short, long, long = "config")]
     config_path: Option<String>,
+    /// Read the log from stdin, i.e. "journalctl -f | logalyzer --stdin".
+    #[arg(long)]
+    stdin: bool,
+    /// Show only the lines matching this filter, on top of the ones from the configuration.
+    #[arg(long)]
+    filter: Option<String>,
+    /// Print the processed log to stdout instead of opening the window.
+    #[arg(long)]
+    no_gui: bool,
 }
 
 // (line_range_start, line_range_end, number_of_entries)
 type HistogramMatch = (usize, usize, usize);
 
+// The bars with their labels, or why there are none.
+type HistogramBars = Result<(Vec<HistogramMatch>, Vec<String>), String>;
+
+// What the histogram was counted for, the lines are counted again only when any of it changes.
+#[derive(PartialEq)]
+struct HistogramKey {
+    search_term: String,
+    match_case: bool,
+    regex: bool,
+    time_buckets: bool,
+    timestamp_pattern: String,
+    log_format_pattern: String, // the timestamps are taken from it without a timestamp pattern
+    number_of_bars: usize,
+    line_range: std::ops::Range<usize>,
+    file_path: String,
+    committed_bytes: u64,
+    line_count: usize, // grows while following
+    dropped_line_count: usize,
+}
+
+// The engine gives only colored runs, jobs are built just for the rows being shown.
+fn layout_job_from_line(line: &log_engine::LineVec, row_height: f32) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for (part_str, part_format) in line {
+        job.append(part_str, 0.0, part_format.into());
+    }
+    set_row_height(&mut job, row_height);
+
+    job
+}
+
+// All rows are laid out with the same height, so the line numbers and the log stay aligned.
+fn set_row_height(job: &mut LayoutJob, row_height: f32) {
+    for section in job.sections.iter_mut() {
+        section.format.line_height = Some(row_height);
+    }
+}
+
+// Color picker for the engine colors, they are kept unmultiplied like egui shows them.
+fn color_edit_button(ui: &mut egui::Ui, color: &mut log_engine::style::Color) -> egui::Response {
+    let mut rgba = [color.r, color.g, color.b, color.a];
+    let response = ui.color_edit_button_srgba_unmultiplied(&mut rgba);
+    let [r, g, b, a] = rgba;
+    *color = log_engine::style::Color::from_rgba_unmultiplied(r, g, b, a);
+
+    response
+}
+
+// Byte offset in the line text under the pointer, the job is laid out the same as in the label.
+// The job can show the whitespace as glyphs, these replace the characters one for one.
+fn hovered_byte_offset(
+    ui: &egui::Ui,
+    mut job: LayoutJob,
+    wrapping: &TextWrapping,
+    label_resp: &egui::Response,
+    line_text: &str,
+) -> Option<usize> {
+    let hover_pos = label_resp.hover_pos()?;
+
+    job.wrap = wrapping.clone();
+    let galley = ui.fonts_mut(|fonts| fonts.layout_job(job));
+    let char_index = galley
+        .cursor_from_pos(hover_pos - label_resp.rect.min)
+        .index;
+    line_text
+        .char_indices()
+        .nth(char_index)
+        .map(|(byte_offset, _)| byte_offset)
+}
+
+// The link under the pointer, the job is laid out again the way the label did it.
+fn hovered_link(
+    ui: &egui::Ui,
+    job: LayoutJob,
+    wrapping: &TextWrapping,
+    label_resp: &egui::Response,
+    line_text: &str,
+    links: &[log_engine::links::Link],
+) -> Option<log_engine::links::Link> {
+    let byte_offset = hovered_byte_offset(ui, job, wrapping, label_resp, line_text)?;
+
+    log_engine::links::link_at(links, byte_offset).cloned()
+}
+
 impl LogalyzerGUI {
-    fn new() -> Self {
+    fn new(args: LogalyzerArgs) -> Self {
         let mut new_self = Self::default();
 
-        let args = LogalyzerArgs::parse();
+        // A missing file is reported by the load itself, in the window.
         if let Some(file_path) = args.file_path {
-            if !Path::new(&file_path).exists() {
-                println!("Specified log file does not exist: {}", file_path);
-            } else {
-                new_self.user_settings.file_path = file_path;
-            }
+            new_self.user_settings.file_path = file_path;
+        }
+
+        if args.stdin {
+            let reader = std::io::BufReader::new(std::io::stdin());
+            let (opened_file, line_stream) = log_engine::load_stream("<stdin>", reader);
+
+            // Path matches the opened "file", so it's never reloaded from disk.
+            new_self.user_settings.file_path = opened_file.path.clone();
+            new_self.state.jobs =
+                log_engine::recalculate_log_job(&opened_file.content, &new_self.user_settings)
+                    .unwrap_or_default();
+            new_self.state.opened_file = Some(opened_file);
+            new_self.state.line_stream = Some(line_stream);
         }
 
         if let Some(config_path_str) = args.config_path {
             if !Path::new(&config_path_str).exists() {
-                println!("Specified config file does not exist: {}", config_path_str);
+                let message = format!("Specified config file does not exist: {}", config_path_str);
+                new_self.set_config_status(Err(message));
             } else {
                 let config_path = Path::new(&config_path_str);
                 let user_settings_res = log_engine::configuration_load(config_path);
+                new_self.set_config_status(
+                    user_settings_res
+                        .as_ref()
+                        .map(|_| "Configuration loaded.".to_string())
+                        .map_err(|e| e.to_string()),
+                );
                 if let Ok(loaded_user_settings) = user_settings_res {
                     let orig_file_path = new_self.user_settings.file_path.clone();
 
@@ -138,115 +388,471 @@ impl LogalyzerGUI {
                     // Preserve currently opened file path.
                     new_self.user_settings.file_path = orig_file_path.clone();
                     new_self.user_settings_staging.file_path = orig_file_path;
+                    new_self.state.top_line_restore_requested =
+                        Some(new_self.user_settings.top_line_no);
+                    new_self.refresh_welcome_message();
                 }
             }
         }
 
+        if let Some(filter_term) = args.filter {
+            new_self
+                .user_settings
+                .filters
+                .push(log_engine::user_settings::FilterSpec {
+                    term: filter_term,
+                    ..Default::default()
+                });
+        }
+
         new_self
     }
 
+    // The welcome text lists the shortcuts, it's redone when they're rebound.
+    fn refresh_welcome_message(&mut self) {
+        if self.state.opened_file.is_some() {
+            return;
+        }
+
+        self.state.jobs = log_engine::default_log_jobs(&self.user_settings.key_bindings);
+        self.state.wrap_cache.clear();
+        self.state.max_line_width_cache.clear();
+    }
+
+    // The key of the action pressed with exactly its modifiers, so i.e. Alt + W doesn't scroll too.
+    fn shortcut_pressed(&self, ui: &egui::Ui, action: ShortcutAction) -> bool {
+        let Some(key_binding) = self.user_settings.key_bindings.get(&action) else {
+            return false;
+        };
+        // Keys without Ctrl or Alt are typed into the text fields instead.
+        if !key_binding.ctrl && !key_binding.alt && self.text_input_focused(ui.ctx()) {
+            return false;
+        }
+        let Some(key) = egui::Key::from_name(&key_binding.key) else {
+            return false;
+        };
+
+        ui.input(|i| {
+            i.key_pressed(key)
+                && i.modifiers.ctrl == key_binding.ctrl
+                && i.modifiers.alt == key_binding.alt
+                && i.modifiers.shift == key_binding.shift
+        })
+    }
+
+    // The one place deciding whether the keys go to a text field (search, filter, go to line,
+    // comment, histogram, log format, ...). The focus moves while the frame is drawn, i.e. a field
+    // drawn after the check takes it, so the focus at the end of the last frame counts too.
+    fn text_input_focused(&self, ctx: &egui::Context) -> bool {
+        let comment_being_added =
+            self.state.add_comment_window_open && self.state.add_comment_request.is_some();
+
+        ctx.memory(|mem| mem.focused().is_some())
+            || self.state.focused_last_frame
+            || comment_being_added
+            || self.state.key_binding_capture.is_some()
+    }
+
+    // See log_engine::key_bindings::default_key_bindings() for the keys.
     fn check_keyboard_shortcuts(&mut self, ui: &egui::Ui) {
-        // Ctrl + F => focus search box
-        // Ctrl + G => focus filter box
-        // Ctrl + T => open tokens panel
-        // Ctrl + H => open histogram window
-
-        let ctrl_pressed = ui.input(|i| i.modifiers.ctrl);
-        if ctrl_pressed {
-            if ui.input(|i| i.key_pressed(egui::Key::F)) {
-                self.state.focus_request = FocusRequests::Search;
-            }
+        // The keys pressed are being bound to an action.
+        if self.state.key_binding_capture.is_some() {
+            return;
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::I)) {
-                self.state.focus_request = FocusRequests::Filter;
-            }
+        if self.shortcut_pressed(ui, ShortcutAction::FocusSearch) {
+            self.state.focus_request = FocusRequests::Search;
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::T)) {
-                self.state.panel_token_colors_open = !self.state.panel_token_colors_open;
-            }
+        if self.shortcut_pressed(ui, ShortcutAction::FocusFilter) {
+            self.state.focus_request = FocusRequests::Filter;
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::H)) {
-                if self.state.opened_file.is_some() {
-                    self.state.win_histogram_open = !self.state.win_histogram_open;
-                    if self.state.win_histogram_open {
-                        self.state.win_histogram_should_focus = true;
-                    }
-                }
+        if self.shortcut_pressed(ui, ShortcutAction::FocusGoToLine) {
+            self.state.focus_request = FocusRequests::GoToLine;
+        }
+
+        if self.shortcut_pressed(ui, ShortcutAction::ToggleTokens) {
+            self.state.panel_token_colors_open = !self.state.panel_token_colors_open;
+        }
+
+        if self.shortcut_pressed(ui, ShortcutAction::ToggleHistogram)
+            && self.state.opened_file.is_some()
+        {
+            self.state.win_histogram_open = !self.state.win_histogram_open;
+            if self.state.win_histogram_open {
+                self.state.win_histogram_should_focus = true;
             }
         }
+
+        // Changing the settings is enough, the search runs again on its own.
+        if self.shortcut_pressed(ui, ShortcutAction::ToggleSearchMatchCase) {
+            self.user_settings.search_match_case = !self.user_settings.search_match_case;
+        }
+
+        if self.shortcut_pressed(ui, ShortcutAction::ToggleSearchWholeWord) {
+            self.user_settings.search_whole_word = !self.user_settings.search_whole_word;
+        }
     }
 
     fn get_scroll_delta_based_on_keypress(
         &self,
-        ctx: &egui::Context,
         ui: &egui::Ui,
         height: f32,
         width: f32,
     ) -> egui::Vec2 {
         let mut scroll_delta = egui::Vec2::ZERO;
-        let mut anything_focused = false;
-
-        ctx.memory(|mem| {
-            anything_focused = mem.focused().is_some();
-        });
-
-        if anything_focused {
-            return scroll_delta;
-        }
 
         // These should be pretty big steps, so the user can navigate quickly.
         let scroll_delta_vertical = height * 0.4;
         let scroll_delta_horizontal = width * 0.3;
 
-        if !anything_focused {
-            if ui.input(|i| i.key_pressed(egui::Key::A)) {
-                scroll_delta += egui::vec2(scroll_delta_horizontal, 0.0);
-            }
+        // Not while typing, see shortcut_pressed().
+        if self.shortcut_pressed(ui, ShortcutAction::ScrollLeft) {
+            scroll_delta += egui::vec2(scroll_delta_horizontal, 0.0);
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::D)) {
-                scroll_delta += egui::vec2(-scroll_delta_horizontal, 0.0);
-            }
+        if self.shortcut_pressed(ui, ShortcutAction::ScrollRight) {
+            scroll_delta += egui::vec2(-scroll_delta_horizontal, 0.0);
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::W)) {
-                scroll_delta += egui::vec2(0.0, scroll_delta_vertical);
-            }
+        if self.shortcut_pressed(ui, ShortcutAction::ScrollUp) {
+            scroll_delta += egui::vec2(0.0, scroll_delta_vertical);
+        }
 
-            if ui.input(|i| i.key_pressed(egui::Key::S)) {
-                scroll_delta += egui::vec2(0.0, -scroll_delta_vertical);
-            }
+        if self.shortcut_pressed(ui, ShortcutAction::ScrollDown) {
+            scroll_delta += egui::vec2(0.0, -scroll_delta_vertical);
         }
 
         scroll_delta
     }
 
-    fn determine_wrapping(&self, ctx: &egui::Context, ui: &egui::Ui, row_index: usize) -> usize {
-        let mut line_wrapped_by = 0;
+    // Shift held lets the text be selected again.
+    fn drag_scrolling_active(&self, ctx: &egui::Context) -> bool {
+        self.user_settings.drag_scrolling && !ctx.input(|i| i.modifiers.shift)
+    }
 
-        // This is a pretty costly operation, could be cached.
+    // The log area only, the other scroll areas keep the defaults. egui keeps the drag momentum
+    // going only while dragging is a scroll source, so without kinetic scrolling it's enabled
+    // just while the button is held.
+    fn log_scroll_sources(&self, ctx: &egui::Context) -> scroll_area::ScrollSource {
+        let drag = self.drag_scrolling_active(ctx)
+            && (self.user_settings.kinetic_scrolling || ctx.input(|i| i.pointer.primary_down()));
 
-        if self.user_settings.wrap_text {
-            if let Some(job) = self.state.log_jobs.get(row_index) {
-                let mut job_with_wrapping = job.clone();
-                job_with_wrapping.wrap = TextWrapping {
-                    break_anywhere: false,
-                    max_width: if self.state.log_scroll_area_width == 0.0 {
-                        ui.available_width() - 1.0
-                    } else {
-                        self.state.log_scroll_area_width
-                    },
-                    ..Default::default()
+        scroll_area::ScrollSource {
+            drag,
+            ..self.scroll_sources_allowed
+        }
+    }
+
+    // With smooth scrolling the keys move only the target, the view eases towards it over a few
+    // frames.
+    fn smooth_scroll_step(&mut self, ctx: &egui::Context, scroll_delta: egui::Vec2) -> egui::Vec2 {
+        if !self.user_settings.smooth_scrolling {
+            self.state.smooth_scroll_remaining = egui::Vec2::ZERO;
+            return scroll_delta;
+        }
+
+        self.state.smooth_scroll_remaining += scroll_delta;
+        let remaining = self.state.smooth_scroll_remaining;
+        if remaining.length() < 1.0 {
+            self.state.smooth_scroll_remaining = egui::Vec2::ZERO;
+            return remaining;
+        }
+
+        // Long frames (i.e. the first one after idling) would jump, so dt is capped.
+        let dt = ctx.input(|i| i.stable_dt).min(0.1);
+        let step = remaining * (1.0 - (-SMOOTH_SCROLL_RATE * dt).exp());
+        self.state.smooth_scroll_remaining -= step;
+        ctx.request_repaint();
+
+        step
+    }
+
+    fn determine_wrapping(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &egui::Ui,
+        row_index: usize,
+    ) -> usize {
+        if row_index >= log_engine::visible_line_count(&self.state.jobs) {
+            return 0;
+        }
+
+        let wrapping = self.text_wrapping(self.wrap_width(ui));
+        let row_height = self.row_height(ctx);
+        // The glyphs aren't whitespace, the lines don't break at them.
+        let whitespace_color = self
+            .user_settings
+            .show_whitespace
+            .then_some(self.user_settings.whitespace_color);
+        let wrapping_lines = self.wrapping_lines();
+        let jobs = &self.state.jobs;
+
+        // The row is built only when it's not cached yet.
+        self.state.wrap_cache.get_or_calculate(
+            row_index,
+            &wrapping,
+            self.user_settings.font_size,
+            wrapping_lines,
+            || {
+                let line = log_engine::log_line(jobs, row_index).unwrap_or_default();
+
+                // Pretty printed JSON takes several rows even when not wrapped.
+                if !wrapping_lines {
+                    return line
+                        .iter()
+                        .map(|(part_str, _)| part_str.matches('\n').count())
+                        .sum();
+                }
+
+                let drawn_line = match whitespace_color {
+                    Some(whitespace_color) => Cow::Owned(log_engine::whitespace::whitespace_shown(
+                        &line,
+                        whitespace_color,
+                    )),
+                    None => Cow::Borrowed(&line),
                 };
+                log_engine::wrap_cache::calculate_line_wrapped_by(
+                    ctx,
+                    &layout_job_from_line(&drawn_line, row_height),
+                    &wrapping,
+                )
+            },
+        )
+    }
+
+    fn max_log_line_width(&mut self, ctx: &egui::Context) -> f32 {
+        let row_height = self.row_height(ctx);
+        let jobs = &self.state.jobs;
+
+        self.state.max_line_width_cache.get_or_calculate(
+            self.user_settings.font_size,
+            log_engine::visible_line_count(jobs),
+            || {
+                log_engine::longest_rows(jobs, MAX_LINE_WIDTH_CANDIDATES)
+                    .into_iter()
+                    .filter_map(|row_index| log_engine::log_line(jobs, row_index))
+                    .map(|line| {
+                        log_engine::wrap_cache::calculate_line_width(
+                            ctx,
+                            &layout_job_from_line(&line, row_height),
+                        )
+                    })
+                    .fold(0.0, f32::max)
+            },
+        )
+    }
+
+    // Height of a single (not wrapped) row of the log and of the line numbers.
+    fn row_height(&self, ctx: &egui::Context) -> f32 {
+        let font_row_height = ctx.fonts_mut(|fonts| {
+            fonts.row_height(&egui::FontId::monospace(self.user_settings.font_size))
+        });
+
+        font_row_height * self.user_settings.line_spacing
+    }
+
+    // The table rows are never wrapped, the columns would break up.
+    fn wrapping_lines(&self) -> bool {
+        self.user_settings.wrap_text && self.column_table().is_none()
+    }
+
+    fn column_table(&self) -> Option<&log_engine::columns::ColumnTable> {
+        self.state
+            .column_table
+            .as_ref()
+            .filter(|_| self.user_settings.table_view)
+    }
+
+    // Extracts the cells of the rows added since the last frame, the whole table is built again
+    // when the rows are (see receive_recalculated_jobs()).
+    fn update_column_table(&mut self) {
+        if !self.user_settings.table_view {
+            self.state.column_table = None;
+            self.state.column_table_error = None;
+            return;
+        }
+
+        // Tried once for the settings the rows were processed with.
+        if self.state.column_table_error.is_some() {
+            return;
+        }
+
+        let Some(opened_file) = &self.state.opened_file else {
+            return;
+        };
 
-                let galley = ctx.fonts_mut(|fonts| fonts.layout_job(job_with_wrapping.clone()));
-                let wrap_amount = galley.rows.len();
-                line_wrapped_by = wrap_amount - 1;
+        if self.state.column_table.is_none() {
+            match log_engine::columns::ColumnTable::new(&self.user_settings) {
+                Ok(mut column_table) => {
+                    column_table.sort_by(self.state.table_sort.as_ref().and_then(
+                        |(sort_name, descending)| {
+                            let column_index = column_table
+                                .names
+                                .iter()
+                                .position(|name| name == sort_name)?;
+                            Some((column_index, *descending))
+                        },
+                    ));
+                    self.state.column_table = Some(column_table);
+                }
+                Err(e) => {
+                    self.state.column_table_error = Some(e);
+                    return;
+                }
             }
         }
 
-        line_wrapped_by
+        if let Some(column_table) = &mut self.state.column_table {
+            column_table.update(&opened_file.content, &self.state.jobs, &self.user_settings);
+        }
+    }
+
+    // The visible row shown at the row of the log view, they differ when the table is sorted.
+    fn row_at(&self, shown_row: usize) -> usize {
+        self.column_table().map_or(shown_row, |column_table| {
+            column_table.visible_row(shown_row)
+        })
+    }
+
+    fn shown_row(&self, row_index: usize) -> usize {
+        self.column_table()
+            .map_or(row_index, |column_table| column_table.table_row(row_index))
+    }
+
+    // Clicking a column sorts by it ascending, then descending, then back in the log order.
+    fn sort_table_by(&mut self, column_index: usize) {
+        let Some(column_table) = &mut self.state.column_table else {
+            return;
+        };
+
+        let sort = match column_table.sort() {
+            Some((sort_column, false)) if sort_column == column_index => Some((column_index, true)),
+            Some((sort_column, true)) if sort_column == column_index => None,
+            _ => Some((column_index, false)),
+        };
+        column_table.sort_by(sort);
+        self.state.table_sort = sort.map(|(column_index, descending)| {
+            (column_table.names[column_index].clone(), descending)
+        });
+    }
+
+    fn show_table_header(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let Some(column_table) = self.column_table() else {
+            return;
+        };
+        let char_width = self.monospace_char_width(ctx);
+        let mut clicked_column = None;
+
+        // Scrolled along with the log, which was shown last frame.
+        ui.horizontal(|ui| {
+            ui.add_space(self.state.table_header_indent);
+            egui::ScrollArea::horizontal()
+                .id_salt("table_header")
+                .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
+                .horizontal_scroll_offset(self.state.table_scroll_x)
+                .scroll_source(scroll_area::ScrollSource::NONE)
+                .animated(false)
+                .show(ui, |ui| {
+                    let header_job =
+                        layout_job_from_line(&column_table.header_line(), self.row_height(ctx));
+                    let header_resp = ui
+                        .add(
+                            egui::Label::new(header_job)
+                                .wrap_mode(egui::TextWrapMode::Extend)
+                                .sense(egui::Sense::click()),
+                        )
+                        .on_hover_text("Click a column to sort the rows by it");
+                    if header_resp.clicked()
+                        && let Some(pointer_pos) = header_resp.interact_pointer_pos()
+                    {
+                        let char_offset = (pointer_pos.x - header_resp.rect.left()) / char_width;
+                        clicked_column = column_table.column_at(char_offset as usize);
+                    }
+                });
+        });
+        ui.separator();
+
+        if let Some(column_index) = clicked_column {
+            self.sort_table_by(column_index);
+        }
+    }
+
+    fn monospace_char_width(&self, ctx: &egui::Context) -> f32 {
+        ctx.fonts_mut(|fonts| {
+            fonts.glyph_width(&egui::FontId::monospace(self.user_settings.font_size), '0')
+        })
+    }
+
+    // Wide enough for the longest line number, i.e. "123 (456)" when some lines are filtered out,
+    // with room left for the wrap indicator and comment markers.
+    fn line_numbers_chars(visible_log_lines: usize, line_count: usize) -> usize {
+        let digits = |number: usize| number.max(1).ilog10() as usize + 1;
+
+        let line_number_chars = if visible_log_lines < line_count {
+            digits(visible_log_lines) + digits(line_count) + " ()".len()
+        } else {
+            digits(line_count)
+        };
+
+        line_number_chars + LINE_NUMBERS_SLACK_CHARS
+    }
+
+    fn wrap_indicator_format(&self, ctx: &egui::Context) -> egui::TextFormat {
+        egui::TextFormat {
+            font_id: egui::FontId::monospace(self.user_settings.font_size),
+            line_height: Some(self.row_height(ctx)),
+            color: egui::Color32::DARK_GRAY,
+            ..Default::default()
+        }
+    }
+
+    fn wrap_width(&self, ui: &egui::Ui) -> f32 {
+        if self.state.log_scroll_area_width == 0.0 {
+            ui.available_width() - 1.0
+        } else {
+            self.state.log_scroll_area_width
+        }
+    }
+
+    // The same for measuring the rows and for showing them, see wrap_cache::text_wrapping().
+    fn text_wrapping(&self, max_width: f32) -> TextWrapping {
+        log_engine::wrap_cache::text_wrapping(max_width, self.user_settings.wrap_break_anywhere)
+    }
+
+    // Comments are few, so they're not cached like the log lines.
+    fn determine_comment_wrapping(
+        &self,
+        ctx: &egui::Context,
+        ui: &egui::Ui,
+        comment_text: &str,
+    ) -> usize {
+        if !self.wrapping_lines() {
+            return 0;
+        }
+
+        log_engine::wrap_cache::calculate_line_wrapped_by(
+            ctx,
+            &self.comment_layout_job(ctx, comment_text),
+            &self.text_wrapping(self.wrap_width(ui)),
+        )
+    }
+
+    fn comment_layout_job(&self, ctx: &egui::Context, comment_text: &str) -> LayoutJob {
+        let mut comment_job = log_engine::wrap_cache::comment_layout_job(
+            comment_text,
+            self.user_settings.font_size,
+            self.user_settings.comment_color,
+        );
+        set_row_height(&mut comment_job, self.row_height(ctx));
+
+        comment_job
     }
 
     fn show_log_format_window(&mut self, ctx: &egui::Context) {
+        let row_height = self.row_height(ctx);
+
         egui::Window::new("Log Format")
                 .auto_sized()
                 .collapsible(false)
@@ -255,12 +861,13 @@ impl LogalyzerGUI {
                     ui.vertical(|ui| {
                         ui.horizontal(|ui| {
                             ui.label("Please select log format mode:");
+                            let presets = &log_engine::log_format_presets::LOG_FORMAT_PRESETS;
+                            let mode_selected_previously = self.state.log_format_mode_selected;
+
                             egui::ComboBox::from_id_salt("log_format_mode")
                                 .selected_text(match self.state.log_format_mode_selected {
                                     0 => "Manual Regex",
-                                    1 => "[number.number] log message",
-                                    2 => "YYYY-MM-DD HH:MM:SS log message",
-                                    _ => "Manual Regex",
+                                    i => presets.get(i - 1).map_or("Manual Regex", |preset| preset.0),
                                 })
                                 .show_ui(ui, |ui| {
                                     ui.selectable_value(
@@ -268,42 +875,49 @@ impl LogalyzerGUI {
                                         0,
                                         "Manual Regex",
                                     );
-                                    ui.selectable_value(
-                                        &mut self.state.log_format_mode_selected,
-                                        1,
-                                        "[number.number] log message",
-                                    );
-                                    ui.selectable_value(
-                                        &mut self.state.log_format_mode_selected,
-                                        2,
-                                        "YYYY-MM-DD HH:MM:SS log message",
-                                    );
+                                    for (i, preset) in presets.iter().enumerate() {
+                                        ui.selectable_value(
+                                            &mut self.state.log_format_mode_selected,
+                                            i + 1,
+                                            preset.0,
+                                        );
+                                    }
                                 });
+
+                            // Selecting a preset replaces the pattern and seeds its colors.
+                            if self.state.log_format_mode_selected != mode_selected_previously
+                                && let Some(preset) =
+                                    presets.get(self.state.log_format_mode_selected.wrapping_sub(1))
+                            {
+                                self.user_settings_staging.log_format =
+                                    log_engine::log_format_presets::log_format_from_preset(preset);
+                            }
                         });
 
                         ui.add_space(10.0);
-                        ui.label("If you are defining your own regex, please make sure it has captures for each character in\nthe log line, as anything not captured will be removed.");
+                        ui.label("If you are defining your own regex, please make sure it has captures for each character in\nthe log line, as anything not captured will be removed unless kept below.");
+                        ui.add_space(5.0);
+                        ui.checkbox(
+                            &mut self.user_settings_staging.log_format.keep_uncaptured_text,
+                            "Keep uncaptured text",
+                        );
                         ui.add_space(5.0);
                         ui.label("Use transparency setting in color picker for groups you don't want to highlight.");
                         ui.add_space(10.0);
 
-                        self.user_settings_staging.log_format.pattern = match {
-                            self.state.log_format_mode_selected
-                        } {
-                            0 => self.user_settings_staging.log_format.pattern.clone(),
-                            1 => r"^(\[\s*[0-9]*)(\.)([0-9]*\])(\s.*)$".to_string(),
-                            2 => r"^(\d{4}-\d{2}-\d{2}\s+\d{2}:\d{2}:\d{2})(\s+)(.*)$".to_string(),
-                            _ => r"".to_string(), // impossible
-                        };
-
                         ui.horizontal(|ui| {
                             ui.label("Log Format Regex:");
-                            ui.add_sized(
+                            let pattern_edit = ui.add_sized(
                                 [400.0, 20.0],
                                 egui::TextEdit::singleline(
                                     &mut self.user_settings_staging.log_format.pattern,
                                 ),
                             );
+
+                            // Editing a preset makes it a manual one.
+                            if pattern_edit.changed() {
+                                self.state.log_format_mode_selected = 0;
+                            }
                         });
 
                         let compiled_regex =
@@ -318,32 +932,62 @@ impl LogalyzerGUI {
                             }
                         }
 
+                        // Counted while processing, so it's known only for the applied pattern.
+                        let (lines_matched, lines_processed) =
+                            log_engine::log_format_match_count(&self.state.jobs);
+                        if lines_processed > 0 {
+                            let match_rate = lines_matched as f64 / lines_processed as f64;
+                            let match_text = format!(
+                                "Pattern matched {} / {} lines ({:.0}%)",
+                                thousands_separated(lines_matched),
+                                thousands_separated(lines_processed),
+                                match_rate * 100.0
+                            );
+
+                            if self.user_settings_staging.log_format.pattern
+                                != self.user_settings.log_format.pattern
+                            {
+                                ui.label(format!("{} before the pattern was edited.", match_text));
+                            } else if match_rate < LOG_FORMAT_LOW_MATCH_RATE {
+                                ui.colored_label(
+                                    egui::Color32::ORANGE,
+                                    format!("{}, most lines are left uncolored.", match_text),
+                                );
+                            } else {
+                                ui.label(format!("{}.", match_text));
+                            }
+                        }
+
                         egui::Grid::new("log_format_grid").show(ui, |ui| {
-                            if !self.user_settings_staging.log_format.pattern.is_empty() {
-                                if compiled_regex_valid {
+                            if !self.user_settings_staging.log_format.pattern.is_empty()
+                                && compiled_regex_valid {
                                     let regex = compiled_regex.unwrap();
                                     let capture_group_count = regex.captures_len() - 1;
 
+                                    // Keeps the colors of named groups even if they moved.
                                     self.user_settings_staging
                                             .log_format
-                                            .pattern_coloring
-                                            .resize(capture_group_count, egui::Color32::RED);
-
-                                    self.user_settings_staging
-                                        .log_format
-                                        .pattern_coloring_text
-                                        .resize(capture_group_count, egui::Color32::GRAY);
-
-                                    self.user_settings_staging
-                                        .log_format
-                                        .pattern_coloring_text_use_original
-                                        .resize(capture_group_count, true);
+                                            .update_groups(&regex);
 
                                     for i in 0..capture_group_count {
-                                        ui.label(format!("Group #{}:", i + 1));
-                                        ui.label(format!("Background Color:"));
+                                        let log_format = &self.user_settings_staging.log_format;
+                                        let group_name = &log_format.pattern_group_names[i];
+
+                                        // The group label doubles as the color legend.
+                                        let group_label = if group_name.is_empty() {
+                                            format!("Group #{}", i + 1)
+                                        } else {
+                                            group_name.clone()
+                                        };
+                                        ui.label(
+                                            egui::RichText::new(group_label)
+                                                .background_color(egui::Color32::from(log_format.pattern_coloring[i]))
+                                                .color(egui::Color32::from(log_format.pattern_coloring_text[i])),
+                                        );
+                                        ui.label("Background Color:".to_string());
 
-                                        ui.color_edit_button_srgba(
+                                        color_edit_button(
+                                            ui,
                                             &mut self
                                                 .user_settings_staging
                                                 .log_format
@@ -352,7 +996,8 @@ impl LogalyzerGUI {
 
                                         ui.label("Text Color:");
 
-                                        ui.color_edit_button_srgba(
+                                        color_edit_button(
+                                            ui,
                                             &mut self
                                                 .user_settings_staging
                                                 .log_format
@@ -366,12 +1011,42 @@ impl LogalyzerGUI {
                                             "Use original text color",
                                         );
 
+                                        ui.checkbox(
+                                            &mut self.user_settings_staging
+                                                .log_format
+                                                .pattern_hidden[i],
+                                            "Hide",
+                                        );
+
                                         ui.end_row();
                                     }
                                 }
-                            }
                         });
 
+                        // Colored with the staged format, so the pattern can be tried before applying it.
+                        if let Some(opened_file) = &self.state.opened_file {
+                            ui.add_space(10.0);
+                            ui.label("Preview:");
+
+                            let preview = log_engine::log_format_preview(
+                                &opened_file.content,
+                                &self.user_settings_staging,
+                                LOG_FORMAT_PREVIEW_LINES,
+                            );
+                            egui::ScrollArea::horizontal()
+                                .id_salt("log_format_preview")
+                                .max_width(600.0)
+                                .show(ui, |ui| {
+                                    for line in &preview {
+                                        ui.add(
+                                            egui::Label::new(layout_job_from_line(line, row_height))
+                                                .extend(),
+                                        );
+                                    }
+                                });
+                            ui.add_space(10.0);
+                        }
+
                         ui.horizontal(|ui| {
                             let button_ok =
                                 ui.add_enabled(compiled_regex_valid, egui::Button::new("OK"));
@@ -400,20 +1075,44 @@ impl LogalyzerGUI {
     fn show_bottom_panel_first_row(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             let button_file = ui.button("Open File");
-            if button_file.clicked() {
-                if let Some(path) = rfd::FileDialog::new().pick_file() {
-                    println!("Selected file: {:?}", path);
-                    self.user_settings.file_path = path.to_string_lossy().to_string();
-                }
+            if button_file.clicked()
+                && let Some(path) = rfd::FileDialog::new().pick_file()
+            {
+                println!("Selected file: {:?}", path);
+                self.user_settings.file_path = path.to_string_lossy().to_string();
+                // Picking the same file again retries a failed load.
+                self.state.file_load_error = None;
+            }
+
+            let button_merge = ui
+                .button("Merge Files")
+                .on_hover_text("Open several files, their lines interleaved by the timestamps.");
+            if button_merge.clicked()
+                && let Some(paths) = rfd::FileDialog::new().pick_files()
+            {
+                self.merge_files(&paths);
             }
 
             // TODO: append file / prepend file options?
 
-            // Maybe later ;)
-            // let button_stream = ui.button("Open Stream");
-            // if button_stream.clicked() {
-            //     println!("not implemented");
-            // }
+            let button_stream = ui
+                .button("Open Stream")
+                .on_hover_text("Read the log from a TCP connection.");
+            if button_stream.clicked() {
+                self.state.win_open_stream_open = true;
+                self.state.stream_connect_error = None;
+            }
+
+            #[cfg(feature = "journald")]
+            {
+                let button_journal = ui
+                    .button("Open Journal")
+                    .on_hover_text("Follow the systemd journal of a unit.");
+                if button_journal.clicked() {
+                    self.state.win_open_journal_open = true;
+                    self.state.journal_open_error = None;
+                }
+            }
 
             let button_log_format = ui.button("Log Format");
             if button_log_format.clicked() {
@@ -435,10 +1134,28 @@ impl LogalyzerGUI {
                 }
             }
 
-            // let button_stats = ui.add_enabled(file_opened, egui::Button::new("Stats"));
-            // if button_stats.clicked() {
-            //     println!("not implemented");
-            // }
+            let button_export_view = ui.add_enabled(file_opened, egui::Button::new("Export view"));
+            if button_export_view.clicked() {
+                self.state.win_export_view_open = !self.state.win_export_view_open;
+            }
+
+            let button_stats = ui.add_enabled(file_opened, egui::Button::new("Stats"));
+            if button_stats.clicked() {
+                self.state.win_stats_open = !self.state.win_stats_open;
+                if self.state.win_stats_open {
+                    self.calculate_stats();
+                }
+            }
+
+            let button_patterns = ui
+                .add_enabled(file_opened, egui::Button::new("Patterns"))
+                .on_hover_text("The most frequent lines, with the numbers and ids masked out.");
+            if button_patterns.clicked() {
+                self.state.win_patterns_open = !self.state.win_patterns_open;
+                if self.state.win_patterns_open {
+                    self.calculate_top_patterns();
+                }
+            }
 
             let button_save_config = ui.button("Save config");
             if button_save_config.clicked() {
@@ -446,7 +1163,15 @@ impl LogalyzerGUI {
                     .add_filter("Logalyzer Config", &["logalyzercfg"])
                     .save_file();
                 if let Some(path) = selected_save_file {
-                    log_engine::configuration_save(&path, &self.user_settings);
+                    // Saved along, so reopening the file with this config restores the place.
+                    let mut user_settings_to_save = self.user_settings.clone();
+                    user_settings_to_save.top_line_no = self.top_original_line_no();
+                    let save_result = log_engine::configuration_save(&path, &user_settings_to_save);
+                    self.set_config_status(
+                        save_result
+                            .map(|_| "Configuration saved.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
                 }
             }
 
@@ -458,6 +1183,12 @@ impl LogalyzerGUI {
 
                 if let Some(path) = selected_load_file {
                     let user_settings_res = log_engine::configuration_load(&path);
+                    self.set_config_status(
+                        user_settings_res
+                            .as_ref()
+                            .map(|_| "Configuration loaded.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
                     if let Ok(loaded_user_settings) = user_settings_res {
                         let orig_file_path = self.user_settings.file_path.clone();
 
@@ -469,25 +1200,287 @@ impl LogalyzerGUI {
                         // Preserve currently opened file path.
                         self.user_settings.file_path = orig_file_path.clone();
                         self.user_settings_staging.file_path = orig_file_path;
+                        self.state.top_line_restore_requested =
+                            Some(self.user_settings.top_line_no);
+                        self.refresh_welcome_message();
                     }
                 }
             }
 
-            ui.add_enabled(
-                file_opened,
-                egui::Checkbox::new(&mut self.user_settings.wrap_text, "Wrap"),
-            );
-
-            // Maybe later ;)
-            // ui.add_enabled(
-            //     false, // This should be on only if a stream is opened.
-            //     egui::Checkbox::new(&mut self.user_settings.autoscroll, "Autoscroll"),
-            // );
-
+            let button_save_profile = ui.button("Save profile");
+            if button_save_profile.clicked() {
+                let selected_save_file = rfd::FileDialog::new()
+                    .add_filter("Logalyzer Profile", &["logalyzerprofile"])
+                    .save_file();
+                if let Some(path) = selected_save_file {
+                    let save_result = log_engine::profile_save(&path, &self.user_settings);
+                    self.set_config_status(
+                        save_result
+                            .map(|_| "Profile saved.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
+                }
+            }
+
+            let button_load_profile = ui.button("Load profile");
+            if button_load_profile.clicked() {
+                let selected_load_file = rfd::FileDialog::new()
+                    .add_filter("Logalyzer Profile", &["logalyzerprofile"])
+                    .pick_file();
+
+                if let Some(path) = selected_load_file {
+                    let user_settings_res = log_engine::profile_load(&path, &self.user_settings);
+                    self.set_config_status(
+                        user_settings_res
+                            .as_ref()
+                            .map(|_| "Profile loaded.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
+                    if let Ok(loaded_user_settings) = user_settings_res {
+                        // Only the profile part, unapplied edits of the rest stay staged.
+                        self.user_settings_staging
+                            .apply_profile(&loaded_user_settings);
+                        self.user_settings = loaded_user_settings;
+                    }
+                }
+            }
+
+            let button_shortcuts = ui.button("Shortcuts");
+            if button_shortcuts.clicked() {
+                self.state.win_key_bindings_open = !self.state.win_key_bindings_open;
+            }
+
+            self.show_config_status(ui);
+
+            ui.add_enabled(
+                file_opened,
+                egui::Checkbox::new(&mut self.user_settings.wrap_text, "Wrap"),
+            );
+            ui.add_enabled(
+                file_opened && self.user_settings.wrap_text,
+                egui::Checkbox::new(
+                    &mut self.user_settings.wrap_break_anywhere,
+                    "Break anywhere",
+                ),
+            )
+            .on_hover_text("Wrap in the middle of words too, instead of between them.");
+
+            ui.add_enabled(
+                file_opened,
+                egui::Checkbox::new(&mut self.user_settings.show_whitespace, "Whitespace"),
+            )
+            .on_hover_text(
+                "Show the spaces as · and the tabs as →, the trailing ones highlighted.\nTabs are shown only with the tab width 0, otherwise they're expanded to spaces.",
+            );
+            color_edit_button(ui, &mut self.user_settings.whitespace_color)
+                .on_hover_text("Color of the whitespace");
+
+            self.show_table_view_controls(ui, file_opened);
+
+            ui.label("Line spacing:");
+            ui.add(
+                egui::DragValue::new(&mut self.user_settings.line_spacing)
+                    .range(1.0..=3.0)
+                    .speed(0.05),
+            )
+            .on_hover_text("Height of the log rows, relative to the font.");
+
+            ui.label("Tab width:");
+            ui.add(egui::DragValue::new(&mut self.user_settings.tab_width).range(0..=16))
+                .on_hover_text("Tabs are expanded to the next multiple of this many columns, 0 keeps them.");
+
+            egui::ComboBox::from_id_salt("ansi_codes")
+                .selected_text(format!("ANSI codes: {}", self.user_settings.ansi_codes.name()))
+                .show_ui(ui, |ui| {
+                    for ansi_codes in log_engine::user_settings::AnsiCodes::ALL {
+                        ui.selectable_value(
+                            &mut self.user_settings.ansi_codes,
+                            ansi_codes,
+                            ansi_codes.name(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Color escape sequences written into the log, i.e. \\x1b[31m.");
+
+            // Streams always follow, compressed files can't be followed as they're decompressed whole.
+            let followable = self.state.opened_file.as_ref().is_some_and(|opened_file| {
+                !opened_file.is_stream
+                    && opened_file.compression == log_engine::compression::CompressionKind::None
+            });
+
+            let mut following = self.state.line_stream.is_some();
+            let checkbox_follow = ui
+                .add_enabled(followable, egui::Checkbox::new(&mut following, "Follow"))
+                .on_hover_text("Keep reading lines appended to the file, like tail -f.");
+            if checkbox_follow.changed() {
+                self.state.file_watcher = None;
+                self.toggle_follow();
+            }
+
+            // Following would pick up appended lines only, a rewritten file needs a reload.
+            let mut reloading = self.state.file_watcher.is_some();
+            let checkbox_reload = ui
+                .add_enabled(
+                    followable || reloading,
+                    egui::Checkbox::new(&mut reloading, "Reload on change"),
+                )
+                .on_hover_text("Reload the whole file when it's modified on disk.");
+            if checkbox_reload.changed() {
+                self.toggle_reload_on_change();
+            }
+
+            ui.add_enabled(
+                self.state.line_stream.is_some(),
+                egui::Checkbox::new(&mut self.user_settings.autoscroll, "Autoscroll"),
+            );
+
+            let pause_label = if self.state.following_paused {
+                "Resume"
+            } else {
+                "Pause"
+            };
+            let button_pause = ui
+                .add_enabled(
+                    self.state.line_stream.is_some(),
+                    egui::Button::new(pause_label),
+                )
+                .on_hover_text("Keep the view still, the new lines are shown on resume.");
+            if button_pause.clicked() {
+                if self.state.following_paused {
+                    self.resume_following();
+                } else {
+                    self.state.following_paused = true;
+                }
+            }
+            if self.state.following_paused {
+                ui.label(format!(
+                    "{} lines buffered",
+                    thousands_separated(self.state.paused_lines.len())
+                ));
+            }
+
+            let streaming = self
+                .state
+                .opened_file
+                .as_ref()
+                .is_some_and(|opened_file| opened_file.is_stream);
+            let max_lines = if streaming {
+                &mut self.user_settings.stream_max_lines
+            } else {
+                &mut self.user_settings.follow_max_lines
+            };
+            ui.label("Max lines:");
+            ui.add(egui::DragValue::new(max_lines).speed(1000))
+                .on_hover_text(
+                    "The oldest lines are dropped once there are more (0 keeps all).\nSet separately for the files and the streams.",
+                );
+
             ui.add_enabled(
                 file_opened,
                 egui::Checkbox::new(&mut self.user_settings.comments_visible, "Comments"),
             );
+            color_edit_button(ui, &mut self.user_settings.comment_color)
+                .on_hover_text("Color of the comments");
+
+            let checkbox_collapse = ui
+                .add_enabled(
+                    file_opened,
+                    egui::Checkbox::new(
+                        &mut self.user_settings.collapse_duplicates,
+                        "Collapse duplicates",
+                    ),
+                )
+                .on_hover_text(
+                    "Show repeated lines (ignoring the timestamp) as a single row, click it to expand.",
+                );
+            if checkbox_collapse.changed() {
+                self.user_settings.expanded_duplicates.clear();
+            }
+
+            let checkbox_json = ui
+                .add_enabled(
+                    file_opened,
+                    egui::Checkbox::new(&mut self.user_settings.json_mode, "JSON"),
+                )
+                .on_hover_text(
+                    "Color the lines that are JSON objects or arrays, click a line to pretty print it.",
+                );
+            if checkbox_json.changed() {
+                self.user_settings.expanded_json_lines.clear();
+            }
+
+            let button_export_comments =
+                ui.add_enabled(file_opened, egui::Button::new("Export comments"));
+            if button_export_comments.clicked()
+                && let Some(opened_file) = &self.state.opened_file
+            {
+                let comments_path = log_engine::comments_sidecar_path(&opened_file.path);
+
+                let mut save_file_dialog = rfd::FileDialog::new().add_filter(
+                    "Logalyzer Comments",
+                    &[log_engine::COMMENTS_SIDECAR_EXTENSION],
+                );
+                if let Some(comments_dir) = comments_path.parent() {
+                    save_file_dialog = save_file_dialog.set_directory(comments_dir);
+                }
+                if let Some(comments_file_name) = comments_path.file_name() {
+                    save_file_dialog =
+                        save_file_dialog.set_file_name(comments_file_name.to_string_lossy());
+                }
+
+                let selected_save_file = save_file_dialog.save_file();
+                if let Some(path) = selected_save_file {
+                    let save_result = log_engine::comments_save(&path, &opened_file.log_comments);
+                    self.set_config_status(
+                        save_result
+                            .map(|_| "Comments exported.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
+                }
+            }
+
+            let button_import_comments =
+                ui.add_enabled(file_opened, egui::Button::new("Import comments"));
+            if button_import_comments.clicked() && self.state.opened_file.is_some() {
+                let selected_load_file = rfd::FileDialog::new()
+                    .add_filter(
+                        "Logalyzer Comments",
+                        &[log_engine::COMMENTS_SIDECAR_EXTENSION],
+                    )
+                    .pick_file();
+                if let Some(path) = selected_load_file {
+                    let load_result = log_engine::comments_load(&path);
+                    self.set_config_status(
+                        load_result
+                            .as_ref()
+                            .map(|_| "Comments imported.".to_string())
+                            .map_err(|e| e.to_string()),
+                    );
+                    if let Ok(log_comments) = load_result
+                        && let Some(opened_file) = self.state.opened_file.as_mut()
+                    {
+                        opened_file.log_comments = log_comments;
+                    }
+                }
+            }
+
+            egui::ComboBox::from_id_salt("encoding")
+                .selected_text(format!("Encoding: {}", self.user_settings.encoding))
+                .show_ui(ui, |ui| {
+                    for encoding in log_engine::encoding::SUPPORTED_ENCODINGS {
+                        ui.selectable_value(
+                            &mut self.user_settings.encoding,
+                            encoding.to_string(),
+                            *encoding,
+                        );
+                    }
+                });
+
+            if self.state.recalc_worker.is_busy() {
+                ui.spinner();
+                ui.label("Processing…");
+            }
         });
     }
 
@@ -504,12 +1497,16 @@ impl LogalyzerGUI {
                         .id_salt("search_input"),
                 );
 
+                if textedit_search.changed() {
+                    self.state.last_typed_at = Some(std::time::Instant::now());
+                }
+
                 if textedit_search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
                     // On enter in search input move to next result.
-                    if !self.state.search_found.is_empty() {
+                    if !self.state.jobs.points_of_interest.is_empty() {
                         self.state.search_found_showing_index =
                             (self.state.search_found_showing_index + 1)
-                                % self.state.search_found.len();
+                                % self.state.jobs.points_of_interest.len();
                     }
 
                     // Keep the focus.
@@ -521,62 +1518,217 @@ impl LogalyzerGUI {
                     self.state.focus_request = FocusRequests::None;
                 }
 
-                ui.checkbox(&mut self.user_settings.search_match_case, "Match Case");
-                ui.checkbox(&mut self.user_settings.search_whole_word, "Whole Word");
+                let key_binding_label = |action| {
+                    self.user_settings
+                        .key_bindings
+                        .get(&action)
+                        .map(KeyBinding::label)
+                        .unwrap_or_default()
+                };
+                let match_case_keys = key_binding_label(ShortcutAction::ToggleSearchMatchCase);
+                let whole_word_keys = key_binding_label(ShortcutAction::ToggleSearchWholeWord);
+                ui.checkbox(&mut self.user_settings.search_match_case, "Match Case")
+                    .on_hover_text(match_case_keys);
+                ui.checkbox(&mut self.user_settings.search_whole_word, "Whole Word")
+                    .on_hover_text(whole_word_keys);
+
+                let mut underline = self.user_settings.search_highlight_style
+                    == log_engine::user_settings::SearchHighlightStyle::Underline;
+                if ui
+                    .checkbox(&mut underline, "Underline")
+                    .on_hover_text("Underline the matches instead of coloring their background")
+                    .changed()
+                {
+                    self.user_settings.search_highlight_style = if underline {
+                        log_engine::user_settings::SearchHighlightStyle::Underline
+                    } else {
+                        log_engine::user_settings::SearchHighlightStyle::Background
+                    };
+                }
+
+                color_edit_button(ui, &mut self.user_settings.search_highlight_background)
+                    .on_hover_text("Background (or underline) of the matches");
+                ui.add_enabled(
+                    !underline,
+                    |ui: &mut egui::Ui| {
+                        color_edit_button(ui, &mut self.user_settings.search_highlight_text)
+                    },
+                )
+                .on_hover_text("Text of the matches");
 
                 let search_prev_button = ui.add_enabled(
-                    !self.state.search_found.is_empty(),
+                    !self.state.jobs.points_of_interest.is_empty(),
                     egui::Button::new("Previous"),
                 );
                 if search_prev_button.clicked() {
                     self.state.search_found_showing_index =
                         if self.state.search_found_showing_index == 0 {
-                            self.state.search_found.len() - 1
+                            self.state.jobs.points_of_interest.len() - 1
                         } else {
                             self.state.search_found_showing_index - 1
                         }
                 }
 
                 let search_next_button = ui.add_enabled(
-                    !self.state.search_found.is_empty(),
+                    !self.state.jobs.points_of_interest.is_empty(),
                     egui::Button::new("Next"),
                 );
                 if search_next_button.clicked() {
                     self.state.search_found_showing_index =
-                        (self.state.search_found_showing_index + 1) % self.state.search_found.len();
+                        (self.state.search_found_showing_index + 1) % self.state.jobs.points_of_interest.len();
                 }
 
-                if !self.state.search_found.is_empty() {
+                if !self.state.jobs.points_of_interest.is_empty() {
+                    // Only the first matches are kept, there are more than counted then.
+                    let capped = if self.state.jobs.points_of_interest_capped { "+" } else { "" };
+                    let lines = if self.state.jobs.search_matched_lines == 1 { "line" } else { "lines" };
                     ui.label(format!(
-                        "Result {} of {}",
+                        "Result {} of {}{} ({}{} {})",
                         self.state.search_found_showing_index + 1,
-                        self.state.search_found.len()
+                        thousands_separated(self.state.jobs.points_of_interest.len()),
+                        capped,
+                        thousands_separated(self.state.jobs.search_matched_lines),
+                        capped,
+                        lines
                     ));
                 }
-            });
 
-            ui.horizontal(|ui| {
-                ui.add_sized(search_and_filter_label_size, egui::Label::new("Filter:"));
-                let textedit_filter = ui.add_sized(
-                    search_and_filter_input_size,
-                    egui::TextEdit::singleline(&mut self.user_settings.filter_term)
-                        .id_salt("filter_input"),
+                ui.separator();
+
+                ui.label("Go to line:");
+                let textedit_go_to_line = ui.add_sized(
+                    Vec2::new(80.0, 20.0),
+                    egui::TextEdit::singleline(&mut self.state.go_to_line_input)
+                        .id_salt("go_to_line_input"),
                 );
 
-                if let FocusRequests::Filter = self.state.focus_request {
-                    textedit_filter.request_focus();
+                if textedit_go_to_line.changed() {
+                    self.state.go_to_line_message.clear();
+                }
+
+                if textedit_go_to_line.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))
+                {
+                    self.go_to_line();
+                }
+
+                if let FocusRequests::GoToLine = self.state.focus_request {
+                    textedit_go_to_line.request_focus();
                     self.state.focus_request = FocusRequests::None;
                 }
 
-                ui.checkbox(&mut self.user_settings.filter_match_case, "Match Case");
-                ui.checkbox(&mut self.user_settings.filter_whole_word, "Whole Word");
-                ui.checkbox(&mut self.user_settings.filter_negative, "Negative")
-                    .on_hover_text("Show lines that DO NOT match the filter term.");
-                ui.checkbox(&mut self.user_settings.filter_extended, "Extended")
-                    .on_hover_text(
-                        "Enable simple extended filtering with either only && clauses or only || clauses.\nExample: \"error && failed && stack trace\"\nExample: \"error || warning || info\"",
+                if !self.state.go_to_line_message.is_empty() {
+                    ui.colored_label(egui::Color32::RED, &self.state.go_to_line_message);
+                }
+            });
+
+            let mut filter_to_remove = None;
+
+            for (filter_index, filter) in self.user_settings.filters.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let filter_label = if filter_index == 0 { "Filter:" } else { "" };
+                    ui.add_sized(search_and_filter_label_size, egui::Label::new(filter_label));
+                    let textedit_filter = ui.add_sized(
+                        search_and_filter_input_size,
+                        egui::TextEdit::singleline(&mut filter.term)
+                            .id_salt(("filter_input", filter_index)),
                     );
-                // TODO: maybe option to show N lines before/after match
+
+                    if textedit_filter.changed() {
+                        self.state.last_typed_at = Some(std::time::Instant::now());
+                    }
+
+                    if filter_index == 0
+                        && let FocusRequests::Filter = self.state.focus_request
+                    {
+                        textedit_filter.request_focus();
+                        self.state.focus_request = FocusRequests::None;
+                    }
+
+                    ui.checkbox(&mut filter.match_case, "Match Case");
+                    ui.checkbox(&mut filter.whole_word, "Whole Word");
+                    ui.checkbox(&mut filter.negative, "Negative")
+                        .on_hover_text("Show lines that DO NOT match the filter term.");
+                    ui.add_enabled(!filter.regex && !filter.numeric, egui::Checkbox::new(&mut filter.extended, "Extended"))
+                        .on_hover_text(
+                            "Enable extended filtering with &&, ||, ! and parentheses.\nExample: \"error && failed && stack trace\"\nExample: \"(error || warning) && !heartbeat\"",
+                        );
+                    ui.add_enabled(!filter.numeric, egui::Checkbox::new(&mut filter.regex, "Regex"))
+                        .on_hover_text("Treat the filter term as a regular expression.");
+                    ui.checkbox(&mut filter.numeric, "Numeric")
+                        .on_hover_text(
+                            "Compare a number in the lines, taken from a named group of the log format or found by a regex (its first group).\nExample: \"latency > 500\"\nExample: \"took (\\d+)ms <= 20\"\nExample: \"status 400..499\"",
+                        );
+
+                    if filter.numeric
+                        && !filter.term.is_empty()
+                        && let Err(e) = log_engine::line_handlers::parse_numeric_condition(&filter.term)
+                    {
+                        ui.colored_label(egui::Color32::RED, "Invalid condition")
+                            .on_hover_text(e);
+                    }
+
+                    if filter.extended
+                        && !filter.regex
+                        && !filter.numeric
+                        && !filter.term.is_empty()
+                        && let Err(e) = log_engine::line_handlers::parse_filter_expression(&filter.term)
+                    {
+                        ui.colored_label(egui::Color32::RED, "Invalid expression")
+                            .on_hover_text(e);
+                    }
+
+                    if ui.button("Remove").clicked() {
+                        filter_to_remove = Some(filter_index);
+                    }
+                });
+            }
+
+            if let Some(filter_index) = filter_to_remove {
+                self.user_settings.filters.remove(filter_index);
+            }
+
+            ui.horizontal(|ui| {
+                ui.add_sized(search_and_filter_label_size, egui::Label::new(""));
+                if ui.button("Add Filter").clicked() {
+                    self.user_settings
+                        .filters
+                        .push(log_engine::user_settings::FilterSpec::default());
+                }
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Context lines before:");
+                    ui.add(egui::DragValue::new(&mut self.user_settings.filter_context_before).range(0..=100));
+                    ui.label("after:");
+                    ui.add(egui::DragValue::new(&mut self.user_settings.filter_context_after).range(0..=100));
+                })
+                .response
+                .on_hover_text("Also show the lines around the filter matches, dimmed.");
+
+                ui.separator();
+
+                ui.checkbox(&mut self.user_settings.filter_dim_non_matches, "Dim instead of hiding")
+                    .on_hover_text("Show the lines the filters would hide, dimmed.");
+            });
+
+            ui.horizontal(|ui| {
+                ui.add_sized(search_and_filter_label_size, egui::Label::new(""));
+                ui.label("Continuation lines:");
+                ui.add_sized(
+                    [200.0, 20.0],
+                    egui::TextEdit::singleline(&mut self.user_settings.continuation_pattern)
+                        .hint_text("i.e. ^\\s"),
+                )
+                .on_hover_text(
+                    "Regex, matching lines belong to the entry above them (i.e. stack traces).\nThe filters and the search see the whole entry.",
+                );
+
+                if !self.user_settings.continuation_pattern.is_empty()
+                    && regex::Regex::new(&self.user_settings.continuation_pattern).is_err()
+                {
+                    ui.colored_label(egui::Color32::RED, "Regex invalid!");
+                }
             });
         });
     }
@@ -593,25 +1745,332 @@ impl LogalyzerGUI {
                     ui.separator();
                     ui.add_space(5.0);
 
-                    egui::Grid::new("tokens_grid").show(ui, |ui| {
-                        for i in 0..self.user_settings_staging.token_colors.capacity() {
-                            let token_color = &mut self.user_settings_staging.token_colors[i];
+                    let mut token_color_remove_index = None;
+
+                    // Counted for the applied tokens, so shown only for the ones not edited since.
+                    let token_counts = log_engine::token_counts(&self.state.jobs);
+                    let applied_token_colors = &self.user_settings.token_colors;
+
+                    // The list can grow, keep the level colors and buttons below reachable.
+                    egui::ScrollArea::vertical()
+                        .id_salt("tokens_scroll")
+                        .max_height(ui.available_height() * 0.6)
+                        .show(ui, |ui| {
+                            egui::Grid::new("tokens_grid").show(ui, |ui| {
+                                for (i, token_color) in self
+                                    .user_settings_staging
+                                    .token_colors
+                                    .iter_mut()
+                                    .enumerate()
+                                {
+                                    ui.label(format!("#{}:", i + 1));
+                                    ui.add_sized(
+                                        [100.0, 20.0],
+                                        egui::TextEdit::singleline(&mut token_color.token),
+                                    );
+
+                                    let token_count = applied_token_colors
+                                        .get(i)
+                                        .filter(|applied| {
+                                            applied.token == token_color.token
+                                                && applied.match_case == token_color.match_case
+                                                && applied.whole_word == token_color.whole_word
+                                                && applied.regex == token_color.regex
+                                        })
+                                        .and_then(|_| token_counts.get(i));
+                                    ui.label(
+                                        token_count
+                                            .map(|count| thousands_separated(*count))
+                                            .unwrap_or_default(),
+                                    )
+                                    .on_hover_text("Occurrences in the visible lines.");
+
+                                    color_edit_button(ui, &mut token_color.color);
+                                    ui.checkbox(&mut token_color.match_case, "Match Case");
+                                    ui.checkbox(&mut token_color.whole_word, "Whole Word");
+                                    ui.checkbox(&mut token_color.regex, "Regex");
+
+                                    if token_color.regex
+                                        && !token_color.token.is_empty()
+                                        && let Err(e) =
+                                            log_engine::line_handlers::compile_token_regex(
+                                                token_color,
+                                            )
+                                    {
+                                        ui.colored_label(egui::Color32::RED, "Invalid regex")
+                                            .on_hover_text(e.to_string());
+                                    }
+
+                                    let button_remove = ui.button("Remove token");
+                                    if button_remove.clicked() {
+                                        token_color_remove_index = Some(i);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        });
+
+                    if let Some(i) = token_color_remove_index {
+                        self.user_settings_staging.token_colors.remove(i);
+                    }
+
+                    ui.horizontal(|ui| {
+                        let button_add = ui.button("Add token");
+                        if button_add.clicked() {
+                            let token_count = self.user_settings_staging.token_colors.len();
+                            self.user_settings_staging.token_colors.push(
+                                log_engine::user_settings::TokenColor::new_empty_from(
+                                    token_count,
+                                    self.state.token_palette,
+                                ),
+                            );
+                        }
+
+                        egui::ComboBox::from_id_salt("token_palette")
+                            .selected_text(format!("Palette: {}", self.state.token_palette.name()))
+                            .show_ui(ui, |ui| {
+                                for palette in log_engine::user_settings::TokenPalette::ALL {
+                                    ui.selectable_value(
+                                        &mut self.state.token_palette,
+                                        palette,
+                                        palette.name(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("Colorblind-safe colors for the added tokens.");
+
+                        let button_recolor = ui.button("Recolor tokens");
+                        if button_recolor.clicked() {
+                            self.user_settings_staging
+                                .recolor_tokens(self.state.token_palette);
+                        }
+
+                        let button_import = ui.button("Import tokens").on_hover_text(
+                            "Add a list of tokens, i.e. \"token,#RRGGBB\" per line.",
+                        );
+                        if button_import.clicked() {
+                            self.state.win_import_tokens_open = true;
+                        }
+                    });
+
+                    ui.add_space(5.0);
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Level Colors");
+                    });
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    let mut level_color_remove_index = None;
+                    egui::Grid::new("levels_grid").show(ui, |ui| {
+                        for (i, level_color) in self
+                            .user_settings_staging
+                            .level_colors
+                            .iter_mut()
+                            .enumerate()
+                        {
+                            ui.add_sized(
+                                [100.0, 20.0],
+                                egui::TextEdit::singleline(&mut level_color.0).hint_text("keyword"),
+                            );
+                            color_edit_button(ui, &mut level_color.1);
+
+                            if ui.button("Remove").clicked() {
+                                level_color_remove_index = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                    if let Some(i) = level_color_remove_index {
+                        self.user_settings_staging.level_colors.remove(i);
+                    }
+
+                    let button_add_level = ui
+                        .button("Add level")
+                        .on_hover_text("Lines with the keyword get the background, i.e. NOTICE.");
+                    if button_add_level.clicked() {
+                        self.user_settings_staging.level_colors.push((
+                            String::new(),
+                            log_engine::style::Color::from_rgb(60, 60, 90),
+                        ));
+                    }
+
+                    ui.add_space(5.0);
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Key-Value Pairs");
+                    });
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    ui.checkbox(
+                        &mut self.user_settings_staging.key_value_pairs,
+                        "Color keys and values",
+                    );
+                    egui::Grid::new("key_value_grid").show(ui, |ui| {
+                        ui.label("Separators:");
+                        ui.add_sized(
+                            [100.0, 20.0],
+                            egui::TextEdit::singleline(
+                                &mut self.user_settings_staging.key_value_separators,
+                            ),
+                        )
+                        .on_hover_text("Each character separates a key from its value.");
+                        ui.end_row();
+
+                        ui.label("Hidden keys:");
+                        ui.add_sized(
+                            [100.0, 20.0],
+                            egui::TextEdit::singleline(
+                                &mut self.user_settings_staging.key_value_hidden_keys,
+                            ),
+                        )
+                        .on_hover_text("Comma separated, these pairs are removed from the lines.");
+                        ui.end_row();
+                    });
+
+                    ui.add_space(5.0);
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Links");
+                    });
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    ui.checkbox(
+                        &mut self.user_settings_staging.links_clickable,
+                        "Clickable URLs and file:line",
+                    );
+                    egui::Grid::new("links_grid").show(ui, |ui| {
+                        ui.label("Editor:");
+                        ui.add_sized(
+                            [200.0, 20.0],
+                            egui::TextEdit::singleline(
+                                &mut self.user_settings_staging.editor_command,
+                            ),
+                        )
+                        .on_hover_text(
+                            "Opens the file:line links, {file} and {line} are filled in.",
+                        );
+                        ui.end_row();
+                    });
+
+                    ui.add_space(5.0);
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Redaction");
+                    });
+                    ui.separator();
+                    ui.add_space(5.0);
 
-                            ui.label(format!("#{}:", i + 1));
+                    let mut redact_rule_remove_index = None;
+                    egui::Grid::new("redact_grid").show(ui, |ui| {
+                        for (i, redact_rule) in self
+                            .user_settings_staging
+                            .redact_rules
+                            .iter_mut()
+                            .enumerate()
+                        {
                             ui.add_sized(
                                 [100.0, 20.0],
-                                egui::TextEdit::singleline(&mut token_color.0),
+                                egui::TextEdit::singleline(&mut redact_rule.pattern)
+                                    .hint_text("regex"),
                             );
-                            ui.color_edit_button_srgba(&mut token_color.1);
+                            ui.add_sized(
+                                [60.0, 20.0],
+                                egui::TextEdit::singleline(&mut redact_rule.replacement)
+                                    .hint_text("***"),
+                            )
+                            .on_hover_text("Can refer to the capture groups, i.e. \"$1=***\".");
+
+                            if ui.button("Remove").clicked() {
+                                redact_rule_remove_index = Some(i);
+                            }
+
+                            if let Err(e) = regex::Regex::new(&redact_rule.pattern) {
+                                ui.colored_label(egui::Color32::RED, "Invalid regex")
+                                    .on_hover_text(e.to_string());
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                    if let Some(i) = redact_rule_remove_index {
+                        self.user_settings_staging.redact_rules.remove(i);
+                    }
+
+                    let button_add_rule = ui
+                        .button("Add rule")
+                        .on_hover_text("Matching text is replaced in the view and in the exports.");
+                    if button_add_rule.clicked() {
+                        self.user_settings_staging
+                            .redact_rules
+                            .push(log_engine::user_settings::RedactRule::default());
+                    }
+
+                    ui.add_space(5.0);
+                    ui.vertical_centered(|ui| {
+                        ui.heading("Handler Order");
+                    });
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    let handler_order = &mut self.user_settings_staging.handler_order;
+                    let mut handler_move = None; // (from, to)
+                    egui::Grid::new("handler_order_grid").show(ui, |ui| {
+                        for (i, handler_type) in handler_order.iter().enumerate() {
+                            ui.label(format!("{}. {}", i + 1, handler_type.name()));
+
+                            if ui.add_enabled(i > 0, egui::Button::new("Up")).clicked() {
+                                handler_move = Some((i, i - 1));
+                            }
+                            if ui
+                                .add_enabled(i + 1 < handler_order.len(), egui::Button::new("Down"))
+                                .clicked()
+                            {
+                                handler_move = Some((i, i + 1));
+                            }
                             ui.end_row();
                         }
                     });
 
+                    if let Some((from, to)) = handler_move {
+                        handler_order.swap(from, to);
+                    }
+
+                    if let Some(problem) =
+                        log_engine::line_handlers::handler_order_problem(handler_order)
+                    {
+                        ui.colored_label(
+                            egui::Color32::ORANGE,
+                            format!("Default order used, {}.", problem),
+                        );
+                    }
+
+                    if ui.button("Reset order").clicked() {
+                        *handler_order = log_engine::line_handlers::DEFAULT_HANDLER_ORDER.to_vec();
+                    }
+
+                    ui.add_space(5.0);
                     ui.horizontal(|ui| {
                         let button_apply = ui.button("Apply");
                         if button_apply.clicked() {
                             self.user_settings.token_colors =
                                 self.user_settings_staging.token_colors.clone();
+                            self.user_settings.level_colors =
+                                self.user_settings_staging.level_colors.clone();
+                            self.user_settings.key_value_pairs =
+                                self.user_settings_staging.key_value_pairs;
+                            self.user_settings.key_value_separators =
+                                self.user_settings_staging.key_value_separators.clone();
+                            self.user_settings.key_value_hidden_keys =
+                                self.user_settings_staging.key_value_hidden_keys.clone();
+                            self.user_settings.redact_rules =
+                                self.user_settings_staging.redact_rules.clone();
+                            self.user_settings.handler_order =
+                                self.user_settings_staging.handler_order.clone();
+                            self.user_settings.links_clickable =
+                                self.user_settings_staging.links_clickable;
+                            self.user_settings.editor_command =
+                                self.user_settings_staging.editor_command.clone();
                         }
 
                         let button_close = ui.button("Close");
@@ -623,67 +2082,154 @@ impl LogalyzerGUI {
         }
     }
 
+    fn histogram_line_matcher(&self, match_case: bool) -> Result<impl Fn(&str) -> bool, String> {
+        // Compiled once here, not for every line.
+        let line_regex = log_engine::histogram::histogram_line_regex(
+            &self.user_settings_staging.histogram_search_term,
+            match_case,
+            self.user_settings_staging.histogram_regex,
+        )?;
+
+        Ok(move |line: &str| line_regex.is_match(line))
+    }
+
+    fn histogram_key(&self, number_of_bars: usize) -> HistogramKey {
+        let opened_file = self.state.opened_file.as_ref();
+
+        HistogramKey {
+            search_term: self.user_settings_staging.histogram_search_term.clone(),
+            match_case: self.user_settings_staging.histogram_match_case,
+            regex: self.user_settings_staging.histogram_regex,
+            time_buckets: self.user_settings_staging.histogram_time_buckets,
+            timestamp_pattern: self
+                .user_settings_staging
+                .histogram_timestamp_pattern
+                .clone(),
+            log_format_pattern: self.user_settings.log_format.pattern.clone(),
+            number_of_bars,
+            line_range: self.histogram_line_range(),
+            file_path: opened_file.map_or(String::new(), |opened_file| opened_file.path.clone()),
+            committed_bytes: opened_file.map_or(0, |opened_file| opened_file.committed_bytes),
+            line_count: opened_file.map_or(0, |opened_file| opened_file.content.line_count()),
+            dropped_line_count: opened_file
+                .map_or(0, |opened_file| opened_file.content.dropped_line_count()),
+        }
+    }
+
+    // Lines the histogram counts, 0-based.
+    fn histogram_line_range(&self) -> std::ops::Range<usize> {
+        if !self.state.histogram_line_range_limited {
+            return 0..usize::MAX;
+        }
+
+        // The range is in the original line numbers, the histogram takes the line indices.
+        let dropped_line_count = self
+            .state
+            .opened_file
+            .as_ref()
+            .map_or(0, |opened_file| opened_file.content.dropped_line_count());
+        let (first_line_no, last_line_no) = self.state.histogram_line_range;
+        first_line_no.saturating_sub(dropped_line_count + 1)
+            ..last_line_no.saturating_sub(dropped_line_count)
+    }
+
     fn histogram_find_matches(
         &self,
         number_of_bars: usize,
         match_case: bool,
-    ) -> Vec<HistogramMatch> {
+    ) -> Result<Vec<HistogramMatch>, String> {
         let mut matches = Vec::new();
 
         if self.user_settings_staging.histogram_search_term.is_empty() {
-            return matches;
+            return Ok(matches);
         }
 
+        let line_matches = self.histogram_line_matcher(match_case)?;
+
         if let Some(opened_file) = &self.state.opened_file {
-            let line_range_size = ((opened_file.content_line_count as f64)
-                / (number_of_bars as f64))
-                .floor() as usize;
-
-            for bar_index in 0..number_of_bars {
-                let line_range_start = bar_index * line_range_size;
-                let mut line_range_end = (bar_index + 1) * line_range_size;
-                if bar_index == number_of_bars - 1 {
-                    line_range_end = opened_file.content_line_count;
-                }
+            matches = log_engine::histogram::line_histogram(
+                &opened_file.content,
+                self.histogram_line_range(),
+                line_matches,
+                number_of_bars,
+            )
+            .iter()
+            .map(|bucket| (bucket.first_line_no, bucket.last_line_no, bucket.count))
+            .collect();
+        }
 
-                // Grab all lines from the range.
-                let lines_in_range = opened_file
-                    .content
-                    .lines()
-                    .skip(line_range_start)
-                    .take(line_range_end - line_range_start)
-                    .map(|line| {
-                        if !match_case {
-                            line.to_lowercase()
-                        } else {
-                            line.to_string()
-                        }
-                    });
+        Ok(matches)
+    }
 
-                let search_term = if !match_case {
-                    self.user_settings_staging
-                        .histogram_search_term
-                        .to_lowercase()
-                } else {
-                    self.user_settings_staging.histogram_search_term.clone()
-                };
+    // Same as histogram_find_matches, but the bars are time intervals. The matches have the bar
+    // index as the range, the time range labels are returned separately.
+    fn histogram_find_time_matches(
+        &self,
+        number_of_bars: usize,
+        match_case: bool,
+    ) -> Result<(Vec<HistogramMatch>, Vec<String>), String> {
+        if self.user_settings_staging.histogram_search_term.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
 
-                let matches_in_range = lines_in_range
-                    .filter(|line| line.contains(&search_term))
-                    .count();
+        let line_matches = self.histogram_line_matcher(match_case)?;
 
-                matches.push((line_range_start + 1, line_range_end, matches_in_range));
-            }
+        let timestamp_pattern = if self
+            .user_settings_staging
+            .histogram_timestamp_pattern
+            .is_empty()
+        {
+            &self.user_settings.log_format.pattern
+        } else {
+            &self.user_settings_staging.histogram_timestamp_pattern
+        };
+
+        if timestamp_pattern.is_empty() {
+            return Err("No timestamp pattern or log format".to_string());
         }
 
-        matches
+        let timestamp_regex = regex::Regex::new(timestamp_pattern)
+            .map_err(|_| "Invalid timestamp pattern".to_string())?;
+
+        let Some(opened_file) = &self.state.opened_file else {
+            return Ok((Vec::new(), Vec::new()));
+        };
+
+        let Some((buckets, timestamp_kind)) = log_engine::histogram::time_histogram(
+            &opened_file.content,
+            self.histogram_line_range(),
+            &timestamp_regex,
+            line_matches,
+            number_of_bars,
+        ) else {
+            return Err("No timestamps found".to_string());
+        };
+
+        let matches = buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket_index, bucket)| (bucket_index, bucket_index, bucket.count))
+            .collect();
+
+        let labels = buckets
+            .iter()
+            .map(|bucket| {
+                format!(
+                    "{} - {}",
+                    log_engine::histogram::format_timestamp(bucket.start, timestamp_kind),
+                    log_engine::histogram::format_timestamp(bucket.end, timestamp_kind)
+                )
+            })
+            .collect();
+
+        Ok((matches, labels))
     }
 
-    fn histogram_matches_calc_color(matches: &Vec<HistogramMatch>) -> Vec<egui::Color32> {
-        let mut matches_cloned = matches.clone();
+    fn histogram_matches_calc_color(matches: &[HistogramMatch]) -> Vec<egui::Color32> {
+        let mut matches_cloned = matches.to_vec();
 
         // Sort ascending by count.
-        matches_cloned.sort_by(|a, b| a.2.cmp(&b.2));
+        matches_cloned.sort_by_key(|a| a.2);
 
         let mut matches_sorted_with_color: Vec<(HistogramMatch, egui::Color32)> =
             Vec::with_capacity(matches.len());
@@ -724,29 +2270,61 @@ impl LogalyzerGUI {
 
         let matches_sorted_by_range: Vec<((usize, usize, usize), egui::Color32)> = {
             let mut v = matches_sorted_with_color.clone();
-            v.sort_by(|a, b| a.0.cmp(&b.0)); // sort ascending by line range start
+            v.sort_by_key(|a| a.0); // sort ascending by line range start
             v
         };
 
-        let colors = matches_sorted_by_range
+        matches_sorted_by_range
             .iter()
             .map(|(_, color)| *color)
+            .collect()
+    }
+
+    // Bars with their labels, or why there are none. Empty without a term.
+    fn histogram_bars(&self, number_of_bars: usize) -> HistogramBars {
+        if self.user_settings_staging.histogram_search_term.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let match_case = self.user_settings_staging.histogram_match_case;
+        if self.user_settings_staging.histogram_time_buckets {
+            return self.histogram_find_time_matches(number_of_bars, match_case);
+        }
+
+        let matches = self.histogram_find_matches(number_of_bars, match_case)?;
+        let labels = matches
+            .iter()
+            .map(|(hist_start, hist_end, _)| format!("{} - {}", hist_start, hist_end))
             .collect();
 
-        colors
+        Ok((matches, labels))
     }
 
     fn show_histogram_window(&mut self, ctx: &egui::Context) {
-        let mut histogram_matches: Vec<(usize, usize, usize)> = Vec::new();
         let number_of_bars = 10;
 
-        if !self.user_settings_staging.histogram_search_term.is_empty() {
-            histogram_matches = self.histogram_find_matches(
-                number_of_bars,
-                self.user_settings_staging.histogram_match_case,
-            );
+        // Counted again only when the term, the options or the file change, not every frame.
+        let histogram_key = self.histogram_key(number_of_bars);
+        if self
+            .state
+            .histogram_cache
+            .as_ref()
+            .is_none_or(|(cached_key, _)| *cached_key != histogram_key)
+        {
+            let histogram_bars = self.histogram_bars(number_of_bars);
+            self.state.histogram_cache = Some((histogram_key, histogram_bars));
         }
 
+        // An error (i.e. invalid regex) counts as no matches at all.
+        let (histogram_matches, histogram_labels, histogram_error) =
+            match self.state.histogram_cache.as_ref().map(|(_, bars)| bars) {
+                Some(Ok((matches, labels))) => (matches.clone(), labels.clone(), None),
+                Some(Err(e)) => (Vec::new(), Vec::new(), Some(e.clone())),
+                None => (Vec::new(), Vec::new(), None),
+            };
+
+        let top_original_line_no = self.top_original_line_no();
+
         egui::Window::new("Histogram")
             .auto_sized()
             .collapsible(false)
@@ -773,8 +2351,69 @@ impl LogalyzerGUI {
                             &mut self.user_settings_staging.histogram_match_case,
                             "Match case",
                         );
+                        ui.checkbox(&mut self.user_settings_staging.histogram_regex, "Regex");
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(
+                            &mut self.user_settings_staging.histogram_time_buckets,
+                            "Bucket by time",
+                        );
+
+                        ui.label("Timestamp pattern:");
+                        ui.add_enabled(
+                            self.user_settings_staging.histogram_time_buckets,
+                            egui::TextEdit::singleline(
+                                &mut self.user_settings_staging.histogram_timestamp_pattern,
+                            )
+                            .hint_text("first group of the log format")
+                            .id_salt("histogram_timestamp_pattern_input"),
+                        )
+                        .on_hover_text(
+                            "Regex, the first capture group (or the whole match) is the timestamp.\nSupported: \"2024-01-31 12:34:56\", \"12:34:56\", \"12.345\" (seconds).",
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        // The lines dropped from a stream can't be counted anymore.
+                        let (line_no_min, line_no_max) = self
+                            .state
+                            .opened_file
+                            .as_ref()
+                            .map_or((1, 1), |opened_file| {
+                                let content = &opened_file.content;
+                                let line_no_min = content.dropped_line_count() + 1;
+                                (line_no_min, line_no_min + content.line_count().max(1) - 1)
+                            });
+
+                        // Starts from the line on top of the view, it's usually where the incident is.
+                        if ui
+                            .checkbox(&mut self.state.histogram_line_range_limited, "Only lines")
+                            .on_hover_text("Count only the lines in the range, i.e. around an incident.")
+                            .changed()
+                            && self.state.histogram_line_range_limited
+                        {
+                            self.state.histogram_line_range = (top_original_line_no, line_no_max);
+                        }
+
+                        let (first_line_no, last_line_no) = &mut self.state.histogram_line_range;
+                        ui.add_enabled_ui(self.state.histogram_line_range_limited, |ui| {
+                            ui.add(
+                                egui::DragValue::new(first_line_no)
+                                    .range(line_no_min..=line_no_max),
+                            );
+                            ui.label("to");
+                            ui.add(
+                                egui::DragValue::new(last_line_no).range(line_no_min..=line_no_max),
+                            );
+                        });
+                        *last_line_no = (*last_line_no).max(*first_line_no);
                     });
 
+                    if let Some(histogram_error) = &histogram_error {
+                        ui.colored_label(egui::Color32::RED, histogram_error);
+                    }
+
                     let user_input_for_histogram_lay_width =
                         user_input_for_histogram_lay.response.rect.width();
 
@@ -806,12 +2445,14 @@ impl LogalyzerGUI {
 
                             ui.end_row();
 
-                            if histogram_matches.len() != 0 {
-                                for (hist_start, hist_end, hist_count) in histogram_matches.iter() {
+                            if !histogram_matches.is_empty() {
+                                for ((_, _, hist_count), hist_label) in
+                                    histogram_matches.iter().zip(&histogram_labels)
+                                {
                                     let lay1 = ui.with_layout(
                                         egui::Layout::right_to_left(egui::Align::RIGHT),
                                         |ui| {
-                                            ui.label(format!("{} - {}", hist_start, hist_end));
+                                            ui.label(hist_label);
                                         },
                                     );
 
@@ -836,7 +2477,7 @@ impl LogalyzerGUI {
                                             label_count_width
                                         };
 
-                                    let bar_height = self.user_settings.font.size;
+                                    let bar_height = self.user_settings.font_size;
                                     let bar_width_max = user_input_for_histogram_lay_width
                                         - first_two_columns_width
                                         - ui.spacing().item_spacing.x * 3.0
@@ -885,251 +2526,1696 @@ impl LogalyzerGUI {
                                         },
                                     );
 
-                                    ui.with_layout(
-                                        egui::Layout::right_to_left(egui::Align::RIGHT),
-                                        |ui| {
-                                            ui.label("0");
-                                        },
-                                    );
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::RIGHT),
+                                        |ui| {
+                                            ui.label("0");
+                                        },
+                                    );
+
+                                    ui.label("");
+                                    ui.end_row();
+                                }
+                            }
+                        });
+                });
+            });
+    }
+
+    fn recalculate_logfile_display(&mut self) {
+        if !self.user_settings.file_path.is_empty() {
+            let encoding_changed = self.state.opened_file.as_ref().is_some_and(|opened_file| {
+                !opened_file.is_stream && opened_file.encoding != self.user_settings.encoding
+            });
+
+            if self.state.opened_file.is_none()
+                || self.state.opened_file.as_ref().unwrap().path != self.user_settings.file_path
+                || encoding_changed
+                || self.state.reload_requested
+            {
+                // Don't retry a failed load on every frame, only once another file is picked.
+                if self.state.opened_file.is_none()
+                    && !self.state.reload_requested
+                    && self
+                        .state
+                        .file_load_error
+                        .as_ref()
+                        .is_some_and(|e| e.path == self.user_settings.file_path)
+                {
+                    return;
+                }
+
+                // Already being loaded, the file is opened once it's read.
+                if !self.state.reload_requested
+                    && self.state.file_loader.as_ref().is_some_and(|file_loader| {
+                        file_loader.path == self.user_settings.file_path
+                            && file_loader.encoding == self.user_settings.encoding
+                    })
+                {
+                    return;
+                }
+
+                self.state.reload_requested = false;
+                if self
+                    .state
+                    .file_watcher
+                    .as_ref()
+                    .is_some_and(|file_watcher| file_watcher.path != self.user_settings.file_path)
+                {
+                    self.state.file_watcher = None;
+                }
+
+                // Reload file if it was requested, or the path or encoding has changed. A load
+                // still running for another file is cancelled.
+                self.state.file_loader = Some(log_engine::file_loader::FileLoader::start(
+                    &self.user_settings,
+                ));
+            } else if self.user_settings != self.user_settings_cached
+                && self.state.opened_file.is_some()
+                && self
+                    .user_settings
+                    .differs_only_in_layout(&self.user_settings_cached)
+            {
+                // The line widths are cached by the font size, the lines keep everything but the
+                // font. A pending recalculation is relaid out once it's done. The wrapping changes
+                // with the whitespace shown too.
+                self.user_settings_cached = self.user_settings.clone();
+                self.state.wrap_cache.clear();
+                log_engine::relayout_log_jobs(&mut self.state.jobs, &self.user_settings);
+                if let Some(column_table) = &mut self.state.column_table {
+                    column_table.set_font_size(self.user_settings.font_size);
+                }
+            } else if self.user_settings != self.user_settings_cached
+                && self.state.opened_file.is_some()
+                && self
+                    .user_settings
+                    .differs_only_in_search(&self.user_settings_cached)
+            {
+                // Just find the matches again, a pending recalculation is searched once it's done.
+                self.user_settings_cached = self.user_settings.clone();
+                log_engine::search_log_jobs(&mut self.state.jobs, &self.user_settings);
+                self.state.search_found_showing_index = 0;
+                self.state.search_found_last_shown_index = None;
+                self.show_search_result_after_search_from();
+            } else if self.user_settings != self.user_settings_cached
+                && let Some(opened_file) = self.state.opened_file.as_ref()
+            {
+                self.user_settings_cached = self.user_settings.clone();
+                self.state
+                    .recalc_worker
+                    .request(opened_file.content.clone(), &self.user_settings);
+            }
+        }
+    }
+
+    fn receive_loaded_file(&mut self, ctx: &egui::Context) {
+        let Some(file_loader) = &self.state.file_loader else {
+            return;
+        };
+        let Some(loaded) = file_loader.poll_result() else {
+            // The progress moves on its own, so keep repainting while loading.
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+            return;
+        };
+        self.state.file_loader = None;
+
+        let loaded_file_meta = match loaded {
+            Ok(loaded_file_meta) => {
+                self.state.file_load_error = None;
+                Some(loaded_file_meta)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                // Nothing is shown instead of the previous file, so it's not mistaken
+                // for the failed one.
+                self.state.jobs = log_engine::LogJobs::default();
+                self.state.wrap_cache.clear();
+                self.state.max_line_width_cache.clear();
+                self.state.file_load_error = Some(e);
+                None
+            }
+        };
+        let previous_file = std::mem::replace(&mut self.state.opened_file, loaded_file_meta);
+        self.state.line_stream = None;
+
+        // Same file decoded differently, the comments still apply.
+        if let (Some(previous_file), Some(opened_file)) =
+            (previous_file, self.state.opened_file.as_mut())
+            && previous_file.path == opened_file.path
+        {
+            opened_file.log_comments = previous_file.log_comments;
+        } else {
+            // Expanded and folded rows are kept by line numbers, these mean nothing in another file.
+            self.user_settings.expanded_duplicates.clear();
+            self.user_settings.expanded_json_lines.clear();
+            self.user_settings.folded_ranges.clear();
+            self.state.fold_first_line_no = None;
+        }
+
+        if let Some(opened_file) = self.state.opened_file.as_ref() {
+            self.user_settings_cached = self.user_settings.clone();
+            self.state
+                .recalc_worker
+                .request(opened_file.content.clone(), &self.user_settings);
+        }
+    }
+
+    // Back to the file shown before (or to the welcome screen), so the load isn't started again.
+    fn cancel_file_load(&mut self) {
+        if self.state.file_loader.take().is_none() {
+            return;
+        }
+
+        match &self.state.opened_file {
+            Some(opened_file) => {
+                self.user_settings.file_path = opened_file.path.clone();
+                if !opened_file.is_stream {
+                    self.user_settings.encoding = opened_file.encoding.clone();
+                }
+            }
+            None => self.user_settings.file_path.clear(),
+        }
+    }
+
+    fn show_file_loading_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(file_loader) = &self.state.file_loader else {
+            return;
+        };
+        let progress = file_loader.progress();
+        let loading_label = format!("Loading {}… {:.0}%", file_loader.path, progress * 100.0);
+
+        let mut cancel_clicked = false;
+        egui::Frame::new()
+            .fill(egui::Color32::from_gray(40))
+            .inner_margin(6.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    ui.label(loading_label);
+                    ui.add(egui::ProgressBar::new(progress).desired_width(200.0));
+                    cancel_clicked = ui.button("Cancel").clicked();
+                });
+            });
+
+        if cancel_clicked {
+            self.cancel_file_load();
+        }
+    }
+
+    fn receive_recalculated_jobs(&mut self, ctx: &egui::Context) {
+        if self.state.recalc_worker.is_busy() {
+            // The worker can't trigger a repaint by itself, so poll until the result is there.
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+
+        let Some(jobs) = self.state.recalc_worker.poll_result() else {
+            return;
+        };
+
+        self.state.jobs = jobs;
+        self.state.wrap_cache.clear();
+        self.state.max_line_width_cache.clear();
+        self.state.column_table = None;
+        self.state.column_table_error = None;
+        self.state.search_found_showing_index = 0;
+        self.state.search_found_last_shown_index = None;
+        self.show_search_result_after_search_from();
+
+        // Lines could have been appended to a followed file while the worker was busy.
+        if let Some(opened_file) = self.state.opened_file.as_ref() {
+            log_engine::recalculate_log_job_incremental(
+                &mut self.state.jobs,
+                &opened_file.content,
+                &self.user_settings,
+            );
+        }
+
+        // The search or the font could have changed while the worker was busy.
+        log_engine::search_log_jobs(&mut self.state.jobs, &self.user_settings);
+        log_engine::relayout_log_jobs(&mut self.state.jobs, &self.user_settings);
+    }
+
+    fn input_debounce_remaining(&self) -> Option<std::time::Duration> {
+        let last_typed_at = self.state.last_typed_at?;
+        let debounce = std::time::Duration::from_millis(self.user_settings.input_debounce_ms);
+
+        debounce
+            .checked_sub(last_typed_at.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    fn set_config_status(&mut self, status: Result<String, String>) {
+        if let Err(message) = &status {
+            eprintln!("{}", message);
+        }
+
+        self.state.config_status = Some((status, std::time::Instant::now()));
+    }
+
+    // Outcome of the last config save/load, errors stay a bit longer so they can be read.
+    fn show_config_status(&mut self, ui: &mut egui::Ui) {
+        let Some((status, shown_at)) = &self.state.config_status else {
+            return;
+        };
+
+        let shown_for = match status {
+            Ok(_) => std::time::Duration::from_secs(3),
+            Err(_) => std::time::Duration::from_secs(10),
+        };
+
+        let elapsed = shown_at.elapsed();
+        if elapsed >= shown_for {
+            self.state.config_status = None;
+            return;
+        }
+
+        match status {
+            Ok(message) => ui.colored_label(egui::Color32::GREEN, message),
+            Err(message) => ui.colored_label(egui::Color32::RED, message),
+        };
+
+        // Nothing else may repaint, so make sure the message goes away.
+        ui.ctx().request_repaint_after(shown_for - elapsed);
+    }
+
+    fn show_status_bar(&self, ui: &mut egui::Ui) {
+        let Some(opened_file) = &self.state.opened_file else {
+            ui.label("No file opened");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Lines: {}",
+                thousands_separated(opened_file.content.line_count())
+            ));
+            let dropped_line_count = opened_file.content.dropped_line_count();
+            if dropped_line_count > 0 {
+                ui.label(format!(
+                    "({} dropped)",
+                    thousands_separated(dropped_line_count)
+                ))
+                .on_hover_text("The oldest lines over the max lines");
+            }
+            ui.separator();
+            ui.label(format!(
+                "Visible: {}",
+                thousands_separated(log_engine::visible_line_count(&self.state.jobs))
+            ))
+            .on_hover_text("Rows left after filtering, collapsing and folding");
+            ui.separator();
+            ui.label(format!(
+                "Top line: {}",
+                thousands_separated(self.top_original_line_no())
+            ));
+            ui.separator();
+            ui.label(format!(
+                "Matched: {}",
+                thousands_separated(self.state.jobs.search_matched_lines)
+            ))
+            .on_hover_text("Lines with a search match");
+
+            if !opened_file.is_stream {
+                ui.separator();
+                ui.label(format!(
+                    "Size: {}",
+                    log_engine::bytes_readable(opened_file.file_size)
+                ));
+            }
+        });
+    }
+
+    // Index and path of the merged file the line came from, None unless files were merged.
+    fn line_source(&self, original_line_no: usize) -> Option<(usize, &str)> {
+        let opened_file = self.state.opened_file.as_ref()?;
+        let source_index = *opened_file
+            .line_sources
+            .get(original_line_no.checked_sub(1)?)?;
+
+        Some((
+            source_index,
+            opened_file.source_paths.get(source_index)?.as_str(),
+        ))
+    }
+
+    fn top_original_line_no(&self) -> usize {
+        if self.state.opened_file.is_none() {
+            return 0;
+        }
+
+        self.state
+            .jobs
+            .visible_line_offsets
+            .get_original_line_for_row(self.state.top_visible_row)
+    }
+
+    // Waits until the loaded file is processed, the file may have changed since the config was saved.
+    fn restore_top_line(&mut self) {
+        if self.state.top_line_restore_requested.is_none()
+            || self.state.opened_file.is_none()
+            || self.state.recalc_worker.is_busy()
+        {
+            return;
+        }
+
+        let top_line_no = self.state.top_line_restore_requested.take().unwrap();
+        if top_line_no == 0 {
+            return;
+        }
+
+        if let Some(visible_line_no) = self
+            .state
+            .jobs
+            .visible_line_offsets
+            .get_nearest_visible_line_for_original_line(
+                top_line_no,
+                log_engine::visible_line_count(&self.state.jobs),
+            )
+        {
+            self.state.top_row_scroll_requested = Some(self.shown_row(visible_line_no - 1));
+            self.state.scroll_to_bottom_requested = false;
+        }
+    }
+
+    // Into the first empty filter, if there's one.
+    fn add_filter(&mut self, filter_spec: log_engine::user_settings::FilterSpec) {
+        match self
+            .user_settings
+            .filters
+            .iter_mut()
+            .find(|filter| filter.term.is_empty())
+        {
+            Some(empty_filter) => *empty_filter = filter_spec,
+            None => self.user_settings.filters.push(filter_spec),
+        }
+    }
+
+    fn show_log_line_menu(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        original_line_no: usize,
+        line_text: &str,
+    ) {
+        if ui.button("Copy line").clicked() {
+            ctx.copy_text(line_text.to_string());
+        }
+
+        // Same as the lines of the exported matches.
+        if ui.button("Copy with line number").clicked() {
+            ctx.copy_text(format!("{}: {}", original_line_no, line_text));
+        }
+
+        if ui.button("Add comment").clicked() {
+            self.state.add_comment_request = Some(AddCommentRequest {
+                line_no: original_line_no,
+                ..Default::default()
+            });
+            self.state.add_comment_window_open = true;
+        }
+
+        ui.separator();
+
+        // The line has to be matched as it is.
+        if ui.button("Filter by this line").clicked() {
+            self.add_filter(log_engine::user_settings::FilterSpec {
+                term: line_text.to_string(),
+                match_case: true,
+                ..Default::default()
+            });
+        }
+
+        if let Some(word) = self.state.log_line_menu_word.clone()
+            && ui.button(format!("Search for \"{}\"", word)).clicked()
+        {
+            self.user_settings.search_term = word;
+        }
+    }
+
+    // The matches are found when the settings are checked for changes, unless it's searched for
+    // already.
+    fn search_word(&mut self, word: &str, visible_line_no: usize, byte_offset: usize) {
+        self.state.search_from = Some((visible_line_no, byte_offset));
+        self.user_settings.search_term = word.to_string();
+
+        if self.user_settings == self.user_settings_cached {
+            self.show_search_result_after_search_from();
+        }
+    }
+
+    fn show_search_result_after_search_from(&mut self) {
+        let Some((visible_line_no, byte_offset)) = self.state.search_from.take() else {
+            return;
+        };
+
+        if let Some(poi_index) = log_engine::next_point_of_interest(
+            &self.state.jobs.points_of_interest,
+            visible_line_no,
+            byte_offset,
+        ) {
+            self.state.search_found_showing_index = poi_index;
+        }
+    }
+
+    fn open_link(&self, ctx: &egui::Context, link: &log_engine::links::Link) {
+        match &link.target {
+            log_engine::links::LinkTarget::Url(url) => {
+                ctx.open_url(egui::OpenUrl::new_tab(url));
+            }
+            log_engine::links::LinkTarget::File { path, line_no } => {
+                let path =
+                    log_engine::links::resolve_file_path(path, &self.user_settings.file_path);
+                let command = log_engine::links::editor_command(
+                    &self.user_settings.editor_command,
+                    &path,
+                    *line_no,
+                );
+                let Some((program, args)) = command.split_first() else {
+                    eprintln!("No editor command to open {} with", path);
+                    return;
+                };
+
+                if let Err(e) = std::process::Command::new(program).args(args).spawn() {
+                    eprintln!("Failed to run the editor: {}, error: {}", program, e);
+                }
+            }
+        }
+    }
+
+    fn toggle_reload_on_change(&mut self) {
+        if self.state.file_watcher.is_some() {
+            self.state.file_watcher = None;
+            return;
+        }
+
+        if let Some(opened_file) = self.state.opened_file.as_ref() {
+            self.state.line_stream = None;
+            self.state.file_watcher = Some(log_engine::file_watcher::FileWatcher::watch(
+                &opened_file.path,
+            ));
+        }
+    }
+
+    fn receive_file_changes(&mut self, ctx: &egui::Context) {
+        let Some(file_watcher) = &self.state.file_watcher else {
+            return;
+        };
+
+        // Nothing triggers a repaint when the file changes, so poll periodically.
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+
+        if file_watcher.poll_changed() {
+            // Comments are carried over by the reload itself, the place is restored afterwards.
+            self.state.reload_requested = true;
+            self.state.top_line_restore_requested = Some(self.top_original_line_no());
+        }
+    }
+
+    // The timestamps are taken the same way as for the histogram, or from the start of the lines
+    // if there's no pattern for them.
+    fn merge_files(&mut self, paths: &[std::path::PathBuf]) {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let timestamp_pattern = [
+            &self.user_settings_staging.histogram_timestamp_pattern,
+            &self.user_settings.log_format.pattern,
+        ]
+        .into_iter()
+        .find(|pattern| !pattern.is_empty())
+        .map_or(
+            log_engine::histogram::LEADING_TIMESTAMP_PATTERN,
+            |pattern| pattern.as_str(),
+        );
+        let timestamp_regex = match regex::Regex::new(timestamp_pattern) {
+            Ok(timestamp_regex) => timestamp_regex,
+            Err(e) => {
+                eprintln!(
+                    "Invalid timestamp pattern, merging by the leading timestamps: {}",
+                    e
+                );
+                regex::Regex::new(log_engine::histogram::LEADING_TIMESTAMP_PATTERN).unwrap()
+            }
+        };
+
+        match log_engine::load_and_merge(&paths, &timestamp_regex, &self.user_settings) {
+            Ok(opened_file) => {
+                // Path matches the opened "file", so it's never reloaded from disk.
+                self.user_settings.file_path = opened_file.path.clone();
+                self.user_settings.expanded_duplicates.clear();
+                self.user_settings.expanded_json_lines.clear();
+                self.user_settings.folded_ranges.clear();
+                self.state.fold_first_line_no = None;
+                self.state.file_load_error = None;
+                self.state.line_stream = None;
+                self.state.file_watcher = None;
+                self.state.file_loader = None;
+
+                self.user_settings_cached = self.user_settings.clone();
+                self.state
+                    .recalc_worker
+                    .request(opened_file.content.clone(), &self.user_settings);
+                self.state.opened_file = Some(opened_file);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                self.state.file_load_error = Some(e);
+            }
+        }
+    }
+
+    fn start_stream(
+        &mut self,
+        opened_file: OpenedFileMetadata,
+        line_stream: log_engine::line_stream::LineStream,
+    ) {
+        // Path matches the opened "file", so it's never reloaded from disk.
+        self.user_settings.file_path = opened_file.path.clone();
+        self.user_settings.expanded_duplicates.clear();
+        self.user_settings.expanded_json_lines.clear();
+        self.user_settings.folded_ranges.clear();
+        self.state.fold_first_line_no = None;
+        self.state.file_load_error = None;
+        self.state.file_watcher = None;
+        self.state.file_loader = None;
+        self.state.stream_address = None;
+        self.state.following_paused = false;
+        self.state.paused_lines.clear();
+
+        self.user_settings_cached = self.user_settings.clone();
+        self.state
+            .recalc_worker
+            .request(opened_file.content.clone(), &self.user_settings);
+        self.state.opened_file = Some(opened_file);
+        self.state.line_stream = Some(line_stream);
+        self.state.scroll_to_bottom_requested = self.user_settings.autoscroll;
+    }
+
+    fn open_stream(&mut self, address: &str) {
+        match log_engine::connect_stream(address) {
+            Ok((opened_file, line_stream)) => {
+                self.start_stream(opened_file, line_stream);
+                self.state.stream_address = Some(address.to_string());
+                self.state.stream_connect_error = None;
+                self.state.win_open_stream_open = false;
+            }
+            Err(e) => {
+                eprintln!("Failed to connect to: {}, error: {}", address, e);
+                self.state.stream_connect_error = Some(format!("Failed to connect: {}", e));
+            }
+        }
+    }
+
+    // The lines received so far are kept, the new ones are appended after them.
+    fn reconnect_stream(&mut self) {
+        let Some(address) = &self.state.stream_address else {
+            return;
+        };
+
+        match log_engine::line_stream::LineStream::connect_tcp(address) {
+            Ok(line_stream) => {
+                self.state.line_stream = Some(line_stream);
+                self.state.stream_connect_error = None;
+            }
+            Err(e) => {
+                eprintln!("Failed to reconnect to: {}, error: {}", address, e);
+                self.state.stream_connect_error = Some(format!("Failed to reconnect: {}", e));
+            }
+        }
+    }
+
+    fn show_open_stream_window(&mut self, ctx: &egui::Context) {
+        let mut connect_requested = false;
+
+        egui::Window::new("Open Stream")
+            .auto_sized()
+            .collapsible(false)
+            .open(&mut self.state.win_open_stream_open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Address:");
+                    let address_edit = ui.add(
+                        egui::TextEdit::singleline(&mut self.state.stream_address_input)
+                            .hint_text("host:port"),
+                    );
+                    if address_edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        connect_requested = true;
+                    }
+
+                    let address_entered = !self.state.stream_address_input.trim().is_empty();
+                    if ui
+                        .add_enabled(address_entered, egui::Button::new("Connect"))
+                        .clicked()
+                    {
+                        connect_requested = true;
+                    }
+                });
+
+                if let Some(stream_connect_error) = &self.state.stream_connect_error {
+                    ui.colored_label(egui::Color32::RED, stream_connect_error);
+                }
+            });
+
+        let address = self.state.stream_address_input.trim().to_string();
+        if connect_requested && !address.is_empty() {
+            self.open_stream(&address);
+        }
+    }
+
+    #[cfg(feature = "journald")]
+    fn show_open_journal_window(&mut self, ctx: &egui::Context) {
+        let mut open_requested = false;
+
+        egui::Window::new("Open Journal")
+            .auto_sized()
+            .collapsible(false)
+            .open(&mut self.state.win_open_journal_open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Unit:");
+                    let unit_edit = ui.add(
+                        egui::TextEdit::singleline(&mut self.state.journal_unit_input)
+                            .hint_text("i.e. ssh.service"),
+                    );
+                    if unit_edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        open_requested = true;
+                    }
+
+                    let unit_entered = !self.state.journal_unit_input.trim().is_empty();
+                    if ui
+                        .add_enabled(unit_entered, egui::Button::new("Open"))
+                        .clicked()
+                    {
+                        open_requested = true;
+                    }
+                });
+
+                if let Some(journal_open_error) = &self.state.journal_open_error {
+                    ui.colored_label(egui::Color32::RED, journal_open_error);
+                }
+            });
+
+        let unit = self.state.journal_unit_input.trim().to_string();
+        if !open_requested || unit.is_empty() {
+            return;
+        }
+
+        match log_engine::follow_journal(&unit) {
+            Ok((opened_file, line_stream)) => {
+                self.start_stream(opened_file, line_stream);
+                self.state.journal_open_error = None;
+                self.state.win_open_journal_open = false;
+            }
+            Err(e) => {
+                eprintln!("Failed to run journalctl for: {}, error: {}", unit, e);
+                self.state.journal_open_error = Some(format!("Failed to run journalctl: {}", e));
+            }
+        }
+    }
+
+    fn show_table_view_controls(&mut self, ui: &mut egui::Ui, file_opened: bool) {
+        let checkbox_table = ui.add_enabled(
+            file_opened,
+            egui::Checkbox::new(&mut self.user_settings.table_view, "Table"),
+        );
+        match &self.state.column_table_error {
+            Some(column_table_error) if self.user_settings.table_view => {
+                checkbox_table.on_hover_text(column_table_error);
+                ui.colored_label(egui::Color32::ORANGE, "⚠")
+                    .on_hover_text(column_table_error);
+            }
+            _ => {
+                checkbox_table.on_hover_text("Show the named groups of the log format in columns.");
+            }
+        }
+
+        let group_names = log_engine::columns::named_groups(&self.user_settings.log_format.pattern)
+            .unwrap_or_default();
+        ui.add_enabled_ui(
+            self.user_settings.table_view && !group_names.is_empty(),
+            |ui| {
+                ui.menu_button("Columns", |ui| {
+                    // Nothing picked shows all, so the first pick starts from all of them.
+                    let table_columns = &mut self.user_settings.table_columns;
+                    for group_name in &group_names {
+                        let mut shown =
+                            table_columns.is_empty() || table_columns.contains(group_name);
+                        if ui.checkbox(&mut shown, group_name).changed() {
+                            if table_columns.is_empty() {
+                                *table_columns = group_names.clone();
+                            }
+                            if shown {
+                                table_columns.push(group_name.clone());
+                            } else if table_columns.len() > 1 {
+                                table_columns.retain(|name| name != group_name);
+                            }
+                        }
+                    }
+
+                    if ui.button("Show all").clicked() {
+                        table_columns.clear();
+                    }
+                });
+            },
+        );
+    }
+
+    fn show_import_tokens_window(&mut self, ctx: &egui::Context) {
+        let mut import_requested = false;
+
+        egui::Window::new("Import Tokens")
+            .collapsible(false)
+            .default_width(400.0)
+            .open(&mut self.state.win_import_tokens_open)
+            .show(ctx, |ui| {
+                ui.label("One token per line, the color is optional: \"token,#RRGGBB\".");
+                ui.label("Tokens without a color get the colors of the selected palette.");
+
+                egui::ScrollArea::vertical()
+                    .id_salt("import_tokens_scroll")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut self.state.import_tokens_text)
+                                .code_editor()
+                                .desired_width(f32::INFINITY)
+                                .hint_text("ERROR,#FF0000\ntimeout"),
+                        );
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Load file").clicked()
+                        && let Some(path) = rfd::FileDialog::new().pick_file()
+                    {
+                        match std::fs::read_to_string(&path) {
+                            Ok(text) => {
+                                self.state.import_tokens_text = text;
+                                self.state.import_tokens_errors.clear();
+                            }
+                            Err(e) => {
+                                eprintln!(
+                                    "Failed to read the token list: {:?}, error: {}",
+                                    path, e
+                                );
+                                self.state.import_tokens_errors =
+                                    vec![format!("Failed to read the file: {}", e)];
+                            }
+                        }
+                    }
+
+                    let text_entered = !self.state.import_tokens_text.trim().is_empty();
+                    if ui
+                        .add_enabled(text_entered, egui::Button::new("Import"))
+                        .on_hover_text("Added to the token list, press Apply there to use them.")
+                        .clicked()
+                    {
+                        import_requested = true;
+                    }
+                });
+
+                for error in &self.state.import_tokens_errors {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+
+        if !import_requested {
+            return;
+        }
+
+        let (tokens, errors) =
+            log_engine::token_list::parse_token_list(&self.state.import_tokens_text);
+        self.user_settings_staging
+            .add_tokens(tokens, self.state.token_palette);
+
+        // Only the bad lines are left to fix.
+        let bad_lines: Vec<&str> = self
+            .state
+            .import_tokens_text
+            .lines()
+            .enumerate()
+            .filter(|(line_index, _)| errors.iter().any(|(line_no, _)| *line_no == line_index + 1))
+            .map(|(_, line)| line)
+            .collect();
+        self.state.import_tokens_text = bad_lines.join("\n");
+        self.state.import_tokens_errors = errors
+            .iter()
+            .map(|(line_no, message)| format!("Line {}: {}", line_no, message))
+            .collect();
+        self.state.panel_token_colors_open = true;
+    }
+
+    fn show_stream_disconnected_banner(&mut self, ui: &mut egui::Ui) {
+        let disconnected = self
+            .state
+            .line_stream
+            .as_ref()
+            .is_some_and(|line_stream| line_stream.has_ended());
+        let Some(address) = self.state.stream_address.clone() else {
+            return;
+        };
+        if !disconnected {
+            return;
+        }
+
+        egui::Frame::new()
+            .fill(egui::Color32::DARK_RED)
+            .inner_margin(6.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::WHITE,
+                        format!("Disconnected from {}", address),
+                    );
+                    if ui.button("Reconnect").clicked() {
+                        self.reconnect_stream();
+                    }
+                    if let Some(stream_connect_error) = &self.state.stream_connect_error {
+                        ui.colored_label(egui::Color32::WHITE, stream_connect_error);
+                    }
+                });
+            });
+    }
+
+    fn toggle_follow(&mut self) {
+        if self.state.line_stream.is_some() {
+            // The lines buffered while paused were already read past, they'd be lost otherwise.
+            let paused_lines = std::mem::take(&mut self.state.paused_lines);
+            self.append_followed_lines(&paused_lines);
+            self.state.line_stream = None;
+            return;
+        }
+
+        if let Some(opened_file) = self.state.opened_file.as_mut() {
+            self.state.line_stream = Some(opened_file.follow());
+            self.state.scroll_to_bottom_requested = self.user_settings.autoscroll;
+
+            // Following may have dropped a partial last line, so start from scratch.
+            self.state
+                .recalc_worker
+                .request(opened_file.content.clone(), &self.user_settings);
+        }
+    }
+
+    fn receive_followed_lines(&mut self, ctx: &egui::Context) {
+        let Some(line_stream) = &self.state.line_stream else {
+            // The lines buffered while paused belonged to the stream that was closed.
+            self.state.following_paused = false;
+            self.state.paused_lines.clear();
+            return;
+        };
+
+        // Nothing triggers a repaint when the file grows, so poll periodically.
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+
+        let new_lines = line_stream.poll_lines();
+        if new_lines.is_empty() {
+            return;
+        }
+
+        if self.state.following_paused {
+            self.state.paused_lines.extend(new_lines);
+            return;
+        }
+
+        self.append_followed_lines(&new_lines);
+        self.state.scroll_to_bottom_requested |= self.user_settings.autoscroll;
+    }
+
+    fn append_followed_lines(&mut self, new_lines: &[String]) {
+        if new_lines.is_empty() {
+            return;
+        }
+
+        if let Some(opened_file) = self.state.opened_file.as_mut() {
+            opened_file.append_lines(new_lines);
+
+            let max_lines = if opened_file.is_stream {
+                self.user_settings.stream_max_lines
+            } else {
+                self.user_settings.follow_max_lines
+            };
+            if opened_file.limit_lines(max_lines) > 0 {
+                // The rows are processed again, all of them moved.
+                self.state.wrap_cache.clear();
+                self.state.max_line_width_cache.clear();
+                self.state.column_table = None;
+                self.state.search_found_showing_index = 0;
+                self.state.search_found_last_shown_index = None;
+            }
+
+            log_engine::recalculate_log_job_incremental(
+                &mut self.state.jobs,
+                &opened_file.content,
+                &self.user_settings,
+            );
+        }
+    }
+
+    // Catches up with the lines received while paused and jumps to the end.
+    fn resume_following(&mut self) {
+        self.state.following_paused = false;
+
+        let paused_lines = std::mem::take(&mut self.state.paused_lines);
+        self.append_followed_lines(&paused_lines);
+        self.state.scroll_to_bottom_requested = true;
+    }
+
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let mut window_title = format!("Logalyzer ({})", env!("CARGO_PKG_VERSION"));
+
+        if let Some(opened_file) = &self.state.opened_file {
+            window_title.push_str(&format!(" - {}", opened_file.path));
+
+            if let Some(compression_label) = opened_file.compression.label() {
+                window_title.push_str(&format!(" ({})", compression_label));
+            }
+        }
+
+        if window_title != self.state.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(window_title.clone()));
+            self.state.window_title = window_title;
+        }
+    }
+
+    fn show_line_numbers_scrollarea(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        visible_log_lines: usize,
+        scroll_area_width_max: &mut f32,
+        width_left_after_adding_line_numbers: &mut f32,
+    ) {
+        let mut opened_file_max_line_bytes = 0;
+        let mut opened_file_line_count = 0;
+        if let Some(opened_file) = &self.state.opened_file {
+            opened_file_max_line_bytes = opened_file.content.max_line_bytes();
+            // The original line numbers go on after the dropped lines.
+            opened_file_line_count =
+                opened_file.content.dropped_line_count() + opened_file.content.line_count();
+        }
+
+        // Show the line numbers scroll area only if a file is opened.
+        if opened_file_max_line_bytes > 0 {
+            let char_width = self.monospace_char_width(ctx);
+            let line_numbers_width =
+                Self::line_numbers_chars(visible_log_lines, opened_file_line_count) as f32
+                    * char_width;
+            let row_height = self.row_height(ctx);
+
+            // Never scrolls horizontally, so the line numbers and markers don't depend on how the
+            // log content is scrolled and are always visible.
+            egui::ScrollArea::vertical()
+                .id_salt("line_numbers")
+                .max_width(line_numbers_width)
+                .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
+                .vertical_scroll_offset(self.state.vertical_scroll_offset)
+                .animated(false)
+                .scroll_source(self.scroll_sources_allowed)
+                .show_rows(ui, row_height, visible_log_lines, |ui, row_range| {
+                    ui.set_min_height(ui.available_height());
+                    ui.set_width(line_numbers_width);
+
+                    ui.vertical(|ui| {
+                        let row_indices: Vec<usize> = row_range
+                            .clone()
+                            .map(|shown_row| self.row_at(shown_row))
+                            .collect();
+                        let line_wrapped_by: Vec<usize> = row_indices
+                            .iter()
+                            .map(|row_index| self.determine_wrapping(ctx, ui, *row_index))
+                            .collect();
+                        let gutter_rows = log_engine::wrap_cache::gutter_rows(
+                            &row_indices,
+                            &line_wrapped_by,
+                            &self.state.jobs.visible_line_offsets,
+                        );
+
+                        for gutter_row in gutter_rows {
+                            let row_original_line_no = gutter_row.original_line_no;
+
+                            if let Some(mut line_number) =
+                                log_engine::line_number(&self.state.jobs, gutter_row.row_index)
+                            {
+                                let row_source_path = self.line_source(row_original_line_no);
+
+                                // Lines of merged files are marked with the color of their file.
+                                if let Some((source_index, _)) = row_source_path {
+                                    let number_format = line_number
+                                        .first()
+                                        .map(|(_, part_format)| part_format.clone())
+                                        .unwrap_or_default();
+                                    line_number.insert(
+                                        0,
+                                        (
+                                            "▌".to_string(),
+                                            log_engine::style::Style {
+                                                color: log_engine::style::Color {
+                                                    a: u8::MAX,
+                                                    ..log_engine::source_tint(source_index)
+                                                },
+                                                ..number_format
+                                            },
+                                        ),
+                                    );
+                                }
+
+                                let mut job_cloned = layout_job_from_line(&line_number, row_height);
+
+                                // The wrap indicators are in the same label as the number, so the rows
+                                // get the same line spacing as the wrapped log line.
+                                if gutter_row.wrapped_by > 0 {
+                                    job_cloned.append(
+                                        WRAP_INDICATOR.repeat(gutter_row.wrapped_by).as_str(),
+                                        0.0,
+                                        self.wrap_indicator_format(ctx),
+                                    );
+                                }
+
+                                let hover_text = match row_source_path {
+                                    Some((_, source_path)) => format!(
+                                        "{}\nClick to add a comment, right click to fold lines",
+                                        source_path
+                                    ),
+                                    None => "Click to add a comment, right click to fold lines"
+                                        .to_string(),
+                                };
+                                let line_number_label = ui
+                                    .add(egui::Label::new(job_cloned).sense(egui::Sense::click()))
+                                    .on_hover_text(hover_text)
+                                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                if line_number_label.clicked() {
+                                    self.state.add_comment_request = Some(AddCommentRequest {
+                                        line_no: row_original_line_no,
+                                        ..Default::default()
+                                    });
+                                    self.state.add_comment_window_open = true;
+                                }
+
+                                // The fold is selected with two right clicks, on its first and last line.
+                                line_number_label.context_menu(|ui| {
+                                    match self.state.fold_first_line_no {
+                                        Some(fold_first_line_no) => {
+                                            let fold_label = format!(
+                                                "Fold lines {}-{}",
+                                                fold_first_line_no.min(row_original_line_no),
+                                                fold_first_line_no.max(row_original_line_no)
+                                            );
+                                            if ui.button(fold_label).clicked() {
+                                                self.user_settings.fold_lines(
+                                                    fold_first_line_no,
+                                                    row_original_line_no,
+                                                );
+                                                self.state.fold_first_line_no = None;
+                                            }
+                                            if ui.button("Cancel folding").clicked() {
+                                                self.state.fold_first_line_no = None;
+                                            }
+                                        }
+                                        None => {
+                                            if ui.button("Start folding here").clicked() {
+                                                self.state.fold_first_line_no =
+                                                    Some(row_original_line_no);
+                                            }
+                                        }
+                                    }
+
+                                    if !self.user_settings.folded_ranges.is_empty()
+                                        && ui.button("Unfold all").clicked()
+                                    {
+                                        self.user_settings.folded_ranges.clear();
+                                    }
+                                });
+
+                                if self.user_settings.comments_visible {
+                                    let comment_for_this_line =
+                                        self.state.opened_file.as_ref().and_then(|opened_file| {
+                                            opened_file.log_comments.get(&row_original_line_no)
+                                        });
+
+                                    if let Some(comment_text) = comment_for_this_line {
+                                        // Account for comment line as well, wrapped the same as in the log.
+                                        let comment_wrapped_by =
+                                            self.determine_comment_wrapping(ctx, ui, comment_text);
+                                        let mut comment_job_dummy = LayoutJob::default();
+                                        comment_job_dummy.append(
+                                            "c",
+                                            0.0,
+                                            egui::TextFormat {
+                                                font_id: egui::FontId::monospace(
+                                                    self.user_settings.font_size,
+                                                ),
+                                                line_height: Some(row_height),
+                                                color: self.user_settings.comment_color.into(),
+                                                italics: true,
+                                                ..Default::default()
+                                            },
+                                        );
+                                        comment_job_dummy.append(
+                                            WRAP_INDICATOR.repeat(comment_wrapped_by).as_str(),
+                                            0.0,
+                                            self.wrap_indicator_format(ctx),
+                                        );
+
+                                        ui.horizontal(|ui| {
+                                            let comment_label = ui
+                                                .add(
+                                                    egui::Label::new(comment_job_dummy)
+                                                        .sense(egui::Sense::click()),
+                                                )
+                                                .on_hover_text("Click to delete the comment")
+                                                .on_hover_cursor(egui::CursorIcon::Crosshair);
+                                            if comment_label.clicked()
+                                                && let Some(opened_file) =
+                                                    &mut self.state.opened_file
+                                            {
+                                                opened_file
+                                                    .log_comments
+                                                    .remove(&row_original_line_no);
+                                            }
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    });
+
+                    *width_left_after_adding_line_numbers = ui.available_width();
+                });
+
+            *scroll_area_width_max = if self.wrapping_lines() {
+                *width_left_after_adding_line_numbers
+            } else {
+                // The lines not matching the format are shown as they are in the table too.
+                let table_width = self.column_table().map_or(0.0, |column_table| {
+                    column_table.row_width() as f32 * self.monospace_char_width(ctx)
+                });
+                self.max_log_line_width(ctx).max(table_width)
+            };
+        }
+    }
+
+    fn scroll_to_search_result(&mut self, ui: &egui::Ui, row_range: &std::ops::Range<usize>) {
+        if !self.state.jobs.points_of_interest.is_empty() {
+            let last_shown_different_or_init = (self.state.search_found_last_shown_index.is_none())
+                || (self.state.search_found_last_shown_index.unwrap()
+                    != self.state.search_found_showing_index);
+            if last_shown_different_or_init {
+                let poi =
+                    &self.state.jobs.points_of_interest[self.state.search_found_showing_index];
+
+                if self.scroll_to_visible_line(ui, row_range, poi.line) {
+                    // Mark scrolling as completed.
+                    self.state.search_found_last_shown_index =
+                        Some(self.state.search_found_showing_index);
+                }
+            }
+        }
+    }
+
+    fn scroll_to_requested_line(&mut self, ui: &egui::Ui, row_range: &std::ops::Range<usize>) {
+        if let Some(line_of_interest) = self.state.go_to_line_target
+            && self.scroll_to_visible_line(ui, row_range, line_of_interest)
+        {
+            self.state.go_to_line_target = None;
+        }
+    }
+
+    // Scrolls towards the visible line (1-based), returns true when the line is in view.
+    fn scroll_to_visible_line(
+        &mut self,
+        ui: &egui::Ui,
+        row_range: &std::ops::Range<usize>,
+        line_of_interest: usize,
+    ) -> bool {
+        let line_of_interest = self.shown_row(line_of_interest - 1) + 1;
+        let line_before_current_range = line_of_interest - 1 < row_range.start;
+        let line_after_current_range = line_of_interest > row_range.end;
+
+        if line_before_current_range {
+            // Scrolling up.
+
+            let line_diff = row_range.start as isize - (line_of_interest as isize - 1);
+            let delta = (line_diff as f32) * self.row_height(ui.ctx());
+
+            ui.scroll_with_delta(egui::vec2(0.0, delta));
+            false
+        } else if line_after_current_range {
+            // Scrolling down.
+
+            let line_diff = (line_of_interest as isize - 1) - row_range.end as isize + 1;
+            let delta = (line_diff as f32) * self.row_height(ui.ctx());
+
+            ui.scroll_with_delta(egui::vec2(0.0, -delta));
+            false
+        } else {
+            // Reached the requested range, but do a last effort scroll to try and align
+            // the line more to center of screen. The scroll moves by whole lines, but the wrapped
+            // ones take several rows, so the top line is found by filling half of the view with
+            // the rows above the line.
+
+            let ctx = ui.ctx();
+            let target_row = line_of_interest - 1;
+            let half_view_rows = row_range.len() / 2;
+
+            let mut rows_above = self.determine_wrapping(ctx, ui, self.row_at(target_row)) / 2;
+            let mut top_row = target_row;
+            while top_row > 0 {
+                let rows = 1 + self.determine_wrapping(ctx, ui, self.row_at(top_row - 1));
+                if rows_above + rows > half_view_rows {
+                    break;
+                }
+
+                rows_above += rows;
+                top_row -= 1;
+            }
+
+            let line_diff = top_row as isize - row_range.start as isize;
+            let delta = (line_diff as f32) * self.row_height(ctx);
+
+            ui.scroll_with_delta(egui::vec2(0.0, -delta));
+            true
+        }
+    }
+
+    fn go_to_line(&mut self) {
+        let Ok(original_line_no) = self.state.go_to_line_input.trim().parse::<usize>() else {
+            self.state.go_to_line_message = "Not a line number".to_string();
+            return;
+        };
+
+        let visible_line_no =
+            log_engine::visible_line_for_original_line(&self.state.jobs, original_line_no);
+
+        match visible_line_no {
+            Some(visible_line_no) => {
+                self.state.go_to_line_target = Some(visible_line_no);
+                self.state.go_to_line_message.clear();
+            }
+            None => {
+                self.state.go_to_line_message = format!(
+                    "Line {} is filtered out or does not exist",
+                    original_line_no
+                );
+            }
+        }
+    }
+
+    fn calculate_stats(&mut self) {
+        self.state.stats = self.state.opened_file.as_ref().map(|opened_file| {
+            log_engine::stats::log_stats(&opened_file.content, &self.user_settings, STATS_TOP_LINES)
+        });
+    }
+
+    fn show_stats_window(&mut self, ctx: &egui::Context) {
+        let mut refresh_requested = false;
+
+        egui::Window::new("Stats")
+            .collapsible(false)
+            .open(&mut self.state.win_stats_open)
+            .show(ctx, |ui| {
+                let Some(stats) = &self.state.stats else {
+                    ui.label("No file opened");
+                    return;
+                };
 
-                                    ui.label("");
+                egui::Grid::new("stats_grid").num_columns(2).show(ui, |ui| {
+                    let mut row = |name: &str, value: String| {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    };
+
+                    row("Lines", thousands_separated(stats.total_lines));
+                    row("Visible lines", thousands_separated(stats.visible_lines));
+                    row(
+                        "Log format matched",
+                        thousands_separated(stats.log_format_matched_lines),
+                    );
+                    row(
+                        "Search matched",
+                        thousands_separated(stats.search_matched_lines),
+                    );
+                    row(
+                        "Tokens found",
+                        thousands_separated(stats.token_counts.len()),
+                    );
+                    if let Some((first, last, kind)) = stats.time_span {
+                        row(
+                            "Time span",
+                            format!(
+                                "{} - {}",
+                                log_engine::histogram::format_timestamp(first, kind),
+                                log_engine::histogram::format_timestamp(last, kind)
+                            ),
+                        );
+                    }
+                });
+
+                ui.separator();
+                egui::Grid::new("stats_levels_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (keyword, count) in stats.level_counts.iter().chain(&stats.token_counts)
+                        {
+                            ui.label(keyword);
+                            ui.label(thousands_separated(*count));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.label("Most frequent lines:");
+                egui::ScrollArea::vertical()
+                    .id_salt("stats_top_lines")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("stats_top_lines_grid")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for (line, count) in &stats.top_lines {
+                                    ui.label(thousands_separated(*count));
+                                    ui.add(egui::Label::new(line).truncate());
                                     ui.end_row();
                                 }
-                            }
-                        });
-                });
+                            });
+                    });
+
+                ui.separator();
+                if ui
+                    .button("Refresh")
+                    .on_hover_text("Calculate again with the current settings")
+                    .clicked()
+                {
+                    refresh_requested = true;
+                }
             });
+
+        if refresh_requested {
+            self.calculate_stats();
+        }
     }
 
-    fn recalculate_logfile_display(&mut self) {
-        // TODO: log job recalc should be offloaded to a separate thread
-        if self.user_settings.file_path.is_empty() == false {
-            if !self.state.opened_file.is_some()
-                || self.state.opened_file.as_ref().unwrap().path != self.user_settings.file_path
-            {
-                // Reload file if it was requested, or the path has changed.
-                let loaded_file_meta = log_engine::load_file(&self.user_settings);
-                self.state.opened_file = loaded_file_meta;
+    fn calculate_top_patterns(&mut self) {
+        self.state.top_patterns = self
+            .state
+            .opened_file
+            .as_ref()
+            .map(|opened_file| {
+                log_engine::patterns::top_patterns(
+                    &opened_file.content,
+                    &self.user_settings,
+                    TOP_PATTERNS,
+                )
+            })
+            .unwrap_or_default();
+    }
 
-                if let Some(opened_file) = self.state.opened_file.as_mut() {
-                    if let Some((line_no_jobs, file_jobs, _, _)) =
-                        log_engine::recalculate_log_job(opened_file, &self.user_settings)
-                    {
-                        self.state.line_no_jobs = line_no_jobs;
-                        self.state.log_jobs = file_jobs;
-                        self.state.search_found = Vec::new();
-                        self.state.search_found_showing_index = 0;
-                        self.state.search_found_last_shown_index = None;
-                    }
+    fn show_patterns_window(&mut self, ctx: &egui::Context) {
+        let mut refresh_requested = false;
+        let mut pattern_to_filter = None;
+
+        egui::Window::new("Patterns")
+            .collapsible(false)
+            .open(&mut self.state.win_patterns_open)
+            .show(ctx, |ui| {
+                ui.label("Masked out (regex):");
+                let mut mask_to_remove = None;
+                for (mask_index, mask) in self.user_settings.pattern_masks.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.add_sized(
+                            [250.0, 20.0],
+                            egui::TextEdit::singleline(mask).id_salt(("pattern_mask", mask_index)),
+                        );
+                        if ui.button("Remove").clicked() {
+                            mask_to_remove = Some(mask_index);
+                        }
+                    });
                 }
-            } else {
-                if self.user_settings != self.user_settings_cached {
-                    self.user_settings_cached = self.user_settings.clone();
-                    let opened_file = self.state.opened_file.as_ref().unwrap();
-                    if let Some((
-                        line_no_jobs,
-                        file_jobs,
-                        points_of_interest,
-                        visible_line_offsets,
-                    )) = log_engine::recalculate_log_job(opened_file, &self.user_settings)
+                if let Some(mask_index) = mask_to_remove {
+                    self.user_settings.pattern_masks.remove(mask_index);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add mask").clicked() {
+                        self.user_settings.pattern_masks.push(String::new());
+                    }
+                    if ui.button("Reset to defaults").clicked() {
+                        self.user_settings.pattern_masks =
+                            log_engine::patterns::default_pattern_masks();
+                    }
+                    if ui
+                        .button("Refresh")
+                        .on_hover_text("Group the lines again with the current masks")
+                        .clicked()
                     {
-                        self.state.line_no_jobs = line_no_jobs;
-                        self.state.log_jobs = file_jobs;
-                        self.state.search_found = points_of_interest;
-                        self.state.search_found_showing_index = 0;
-                        self.state.search_found_last_shown_index = None;
-                        self.state.visible_line_offsets = visible_line_offsets;
+                        refresh_requested = true;
                     }
-                }
-            }
+                });
+
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("top_patterns")
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("top_patterns_grid")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for (pattern, count) in &self.state.top_patterns {
+                                    ui.label(thousands_separated(*count));
+                                    if ui
+                                        .add(
+                                            egui::Label::new(pattern)
+                                                .truncate()
+                                                .sense(egui::Sense::click()),
+                                        )
+                                        .on_hover_text("Click to filter the lines of the pattern")
+                                        .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                        .clicked()
+                                    {
+                                        pattern_to_filter = Some(pattern.clone());
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        if refresh_requested {
+            self.calculate_top_patterns();
         }
-    }
 
-    fn show_line_numbers_scrollarea(
-        &mut self,
-        ctx: &egui::Context,
-        ui: &mut egui::Ui,
-        visible_log_lines: usize,
-        scroll_area_width_max: &mut f32,
-        width_left_after_adding_line_numbers: &mut f32,
-    ) {
-        let mut opened_file_max_line_chars = 0;
-        if let Some(opened_file) = &self.state.opened_file {
-            opened_file_max_line_chars = opened_file.content_max_line_chars;
+        if let Some(pattern) = pattern_to_filter {
+            self.add_filter(log_engine::user_settings::FilterSpec {
+                term: log_engine::patterns::pattern_filter_regex(&pattern),
+                regex: true,
+                match_case: true,
+                ..Default::default()
+            });
         }
+    }
 
-        // Show the line numbers scroll area only if a file is opened.
-        if opened_file_max_line_chars > 0 {
-            egui::ScrollArea::vertical()
-                .id_salt("line_numbers")
-                .scroll_bar_visibility(ScrollBarVisibility::AlwaysHidden)
-                .vertical_scroll_offset(self.state.vertical_scroll_offset)
-                .animated(false)
-                .scroll_source(self.scroll_sources_allowed)
-                .show_rows(
-                    ui,
-                    self.user_settings.font.size,
-                    visible_log_lines,
-                    |ui, row_range| {
-                        ui.set_min_height(ui.available_height());
+    fn show_export_view_window(&mut self, ctx: &egui::Context) {
+        let mut export_requested = false;
+        let mut export_html_requested = false;
+        let mut export_matches_requested = false;
+        let mut copy_matches_requested = false;
+        let has_matches = !self.state.jobs.points_of_interest.is_empty();
 
-                        ui.vertical(|ui| {
-                            for row_index in row_range {
-                                let line_wrapped_by = self.determine_wrapping(ctx, ui, row_index);
+        egui::Window::new("Export View")
+            .auto_sized()
+            .collapsible(false)
+            .open(&mut self.state.win_export_view_open)
+            .show(ctx, |ui| {
+                ui.vertical(|ui| {
+                    ui.label("Exports the currently visible (filtered) lines.");
 
-                                if let Some(job) = self
-                                    .state
-                                    .line_no_jobs
-                                    .get(row_index - self.state.lines_wrapped)
-                                {
-                                    let mut job_cloned = job.clone();
-
-                                    // Hack to add empty line numbers for wrapped lines, as
-                                    // it's painful to do it properly with strange line spacings in single label.
-                                    if line_wrapped_by > 0 {
-                                        let text_format = egui::TextFormat {
-                                            font_id: self.user_settings.font.clone(),
-                                            ..Default::default()
-                                        };
+                    let options = &mut self.state.view_export_options;
+                    ui.checkbox(&mut options.include_line_numbers, "Original line numbers");
+                    ui.checkbox(&mut options.include_comments, "Comments");
+                    ui.checkbox(&mut options.keep_hilight_markers, "Search match markers")
+                        .on_hover_text("Wrap the search matches in ** markers (text only).");
 
-                                        job_cloned.append(
-                                            "\n".repeat(line_wrapped_by).as_str(),
-                                            0.0,
-                                            text_format,
-                                        );
-                                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Export text...").clicked() {
+                            export_requested = true;
+                        }
 
-                                    let line_number_label = ui
-                                        .add(
-                                            egui::Label::new(job_cloned)
-                                                .sense(egui::Sense::click()),
-                                        )
-                                        .on_hover_text("Click to add a comment")
-                                        .on_hover_cursor(egui::CursorIcon::PointingHand);
-                                    if line_number_label.clicked() {
-                                        self.state.add_comment_request = Some(AddCommentRequest {
-                                            line_no: self
-                                                .state
-                                                .visible_line_offsets
-                                                .get_offset_for_visible_line(row_index + 1)
-                                                + row_index
-                                                + 1,
-                                            ..Default::default()
-                                        });
-                                        self.state.add_comment_window_open = true;
-                                    }
+                        if ui.button("Export HTML...").clicked() {
+                            export_requested = true;
+                            export_html_requested = true;
+                        }
+                    });
 
-                                    if self.user_settings.comments_visible {
-                                        let original_line_no = self
-                                            .state
-                                            .visible_line_offsets
-                                            .get_offset_for_visible_line(row_index + 1)
-                                            + row_index
-                                            + 1;
-                                        let comment_for_this_line_exists =
-                                            self.state.opened_file.is_some() && {
-                                                self.state
-                                                    .opened_file
-                                                    .as_ref()
-                                                    .unwrap()
-                                                    .log_comments
-                                                    .contains_key(&original_line_no)
-                                            };
-
-                                        if comment_for_this_line_exists {
-                                            // Account for comment line as well.
-                                            // TODO: take into consideration wrapping of the comment too!
-                                            let mut comment_job_dummy = LayoutJob::default();
-                                            comment_job_dummy.append(
-                                                "c",
-                                                0.0,
-                                                egui::TextFormat {
-                                                    font_id: self.user_settings.font.clone(),
-                                                    color: egui::Color32::LIGHT_GREEN,
-                                                    italics: true,
-                                                    ..Default::default()
-                                                },
-                                            );
+                    ui.separator();
+                    ui.label("Lists the lines with a search match, with their line numbers.");
 
-                                            ui.horizontal(|ui| {
-                                                let comment_label = ui
-                                                    .add(
-                                                        egui::Label::new(comment_job_dummy)
-                                                            .sense(egui::Sense::click()),
-                                                    )
-                                                    .on_hover_text("Click to delete the comment")
-                                                    .on_hover_cursor(egui::CursorIcon::Crosshair);
-                                                if comment_label.clicked() {
-                                                    if let Some(opened_file) =
-                                                        &mut self.state.opened_file
-                                                    {
-                                                        opened_file
-                                                            .log_comments
-                                                            .remove(&original_line_no);
-                                                    }
-                                                }
-                                            });
-                                        }
-                                    }
-                                }
-                            }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(has_matches, egui::Button::new("Export matches..."))
+                            .clicked()
+                        {
+                            export_matches_requested = true;
+                        }
 
-                            self.state.lines_wrapped = 0;
-                        });
+                        if ui
+                            .add_enabled(has_matches, egui::Button::new("Copy matches"))
+                            .clicked()
+                        {
+                            copy_matches_requested = true;
+                        }
+                    });
+                });
+            });
 
-                        *width_left_after_adding_line_numbers = ui.available_width();
-                    },
-                );
+        if copy_matches_requested {
+            ctx.copy_text(log_engine::export::matches_to_text(&self.state.jobs));
+        }
 
-            *scroll_area_width_max = if self.user_settings.wrap_text {
-                *width_left_after_adding_line_numbers
+        if export_matches_requested
+            && let Some(path) = rfd::FileDialog::new()
+                .add_filter("Text", &["txt", "log"])
+                .save_file()
+        {
+            let text = log_engine::export::matches_to_text(&self.state.jobs);
+            log_engine::export::export_to_file(&path, &text);
+        }
+
+        if !export_requested {
+            return;
+        }
+
+        let Some(opened_file) = &self.state.opened_file else {
+            return;
+        };
+
+        let save_file_dialog = if export_html_requested {
+            rfd::FileDialog::new().add_filter("HTML", &["html"])
+        } else {
+            rfd::FileDialog::new().add_filter("Text", &["txt", "log"])
+        };
+
+        if let Some(path) = save_file_dialog.save_file() {
+            let processed_lines =
+                log_engine::process_log_lines(&opened_file.content, &self.user_settings);
+
+            let export_fn = if export_html_requested {
+                log_engine::export::view_to_html
             } else {
-                (opened_file_max_line_chars as f32) * 8.0 + 50.0 // good enough
+                log_engine::export::view_to_text
             };
+            let text = export_fn(
+                &processed_lines,
+                &opened_file.log_comments,
+                &self.state.view_export_options,
+            );
+
+            log_engine::export::export_to_file(&path, &text);
         }
     }
 
-    fn scroll_to_search_result(&mut self, ui: &egui::Ui, row_range: &std::ops::Range<usize>) {
-        if !self.state.search_found.is_empty() {
-            let last_shown_different_or_init = (self.state.search_found_last_shown_index.is_none())
-                || (self.state.search_found_last_shown_index.unwrap()
-                    != self.state.search_found_showing_index);
-            if last_shown_different_or_init {
-                let poi = &self.state.search_found[self.state.search_found_showing_index];
-                let line_of_interest = poi.line;
-
-                let line_before_current_range = line_of_interest - 1 < row_range.start;
-                let line_after_current_range = line_of_interest - 1 >= row_range.end;
+    fn show_key_bindings_window(&mut self, ctx: &egui::Context) {
+        if !self.state.win_key_bindings_open {
+            self.state.key_binding_capture = None;
+            return;
+        }
 
-                if line_before_current_range {
-                    // Scrolling up.
+        // The first key pressed while capturing is bound, with the modifiers held. Escape cancels.
+        // The key is taken out of the input, so it doesn't scroll the log too.
+        let key_bindings_before = self.user_settings.key_bindings.clone();
+
+        if let Some(action) = self.state.key_binding_capture {
+            let pressed = ctx.input_mut(|i| {
+                let key_index = i
+                    .events
+                    .iter()
+                    .position(|event| matches!(event, egui::Event::Key { pressed: true, .. }))?;
+                match i.events.remove(key_index) {
+                    egui::Event::Key { key, modifiers, .. } => Some((key, modifiers)),
+                    _ => None,
+                }
+            });
 
-                    let line_diff = row_range.start as isize - (line_of_interest as isize - 1);
-                    let delta = (line_diff as f32) * self.user_settings.font.size;
+            if let Some((key, modifiers)) = pressed {
+                if key != egui::Key::Escape {
+                    self.user_settings.key_bindings.insert(
+                        action,
+                        KeyBinding {
+                            key: key.name().to_string(),
+                            ctrl: modifiers.ctrl,
+                            alt: modifiers.alt,
+                            shift: modifiers.shift,
+                        },
+                    );
+                }
+                self.state.key_binding_capture = None;
+            }
+        }
 
-                    ui.scroll_with_delta(egui::vec2(0.0, delta));
-                } else if line_after_current_range {
-                    // Scrolling down.
+        let conflicting_actions =
+            log_engine::key_bindings::conflicting_actions(&self.user_settings.key_bindings);
 
-                    let line_diff = (line_of_interest as isize - 1) - row_range.end as isize + 1;
-                    let delta = (line_diff as f32) * self.user_settings.font.size;
+        egui::Window::new("Shortcuts and Scrolling")
+            .auto_sized()
+            .collapsible(false)
+            .open(&mut self.state.win_key_bindings_open)
+            .show(ctx, |ui| {
+                egui::Grid::new("key_bindings_grid").show(ui, |ui| {
+                    for action in ShortcutAction::ALL {
+                        ui.label(action.name());
 
-                    ui.scroll_with_delta(egui::vec2(0.0, -delta));
-                } else {
-                    // Reached the requested range, but do a last effort scroll to try and align
-                    // the line more to center of screen.
+                        let binding_text = if self.state.key_binding_capture == Some(action) {
+                            "Press keys…".to_string()
+                        } else {
+                            self.user_settings
+                                .key_bindings
+                                .get(&action)
+                                .map(KeyBinding::label)
+                                .unwrap_or_else(|| KeyBinding::default().label())
+                        };
+                        if ui
+                            .button(binding_text)
+                            .on_hover_text("Click and press the new keys, Escape cancels.")
+                            .clicked()
+                        {
+                            self.state.key_binding_capture = Some(action);
+                        }
 
-                    let range_center = (row_range.start + row_range.end) / 2;
-                    let line_diff = line_of_interest as isize - 1 - range_center as isize;
-                    let delta = (line_diff as f32) * self.user_settings.font.size;
+                        if ui.button("Clear").clicked() {
+                            self.user_settings
+                                .key_bindings
+                                .insert(action, KeyBinding::default());
+                        }
 
-                    ui.scroll_with_delta(egui::vec2(0.0, -delta));
+                        if conflicting_actions.contains(&action) {
+                            ui.colored_label(egui::Color32::YELLOW, "Same keys as another action");
+                        }
+                        ui.end_row();
+                    }
+                });
 
-                    // Mark scrolling as completed.
-                    self.state.search_found_last_shown_index =
-                        Some(self.state.search_found_showing_index);
+                if ui.button("Reset to defaults").clicked() {
+                    self.user_settings.key_bindings =
+                        log_engine::key_bindings::default_key_bindings();
+                    self.state.key_binding_capture = None;
                 }
-            }
+
+                ui.separator();
+                ui.checkbox(&mut self.user_settings.smooth_scrolling, "Smooth scrolling")
+                    .on_hover_text("Animate the scrolling keys instead of jumping.");
+                ui.checkbox(&mut self.user_settings.drag_scrolling, "Drag to scroll")
+                    .on_hover_text("Drag the log to scroll it, hold Shift to select the text.");
+                ui.add_enabled(
+                    self.user_settings.drag_scrolling,
+                    egui::Checkbox::new(&mut self.user_settings.kinetic_scrolling, "Kinetic"),
+                )
+                .on_hover_text("Keep scrolling after the drag is released.");
+            });
+
+        if self.user_settings.key_bindings != key_bindings_before {
+            self.refresh_welcome_message();
         }
     }
 
@@ -1168,18 +4254,18 @@ impl LogalyzerGUI {
                         let comment_request = self.state.add_comment_request.as_mut().unwrap();
 
                         let button_add = ui.button("OK");
-                        if button_add.clicked() || should_add_comment {
-                            if !comment_request.comment_text.is_empty() {
-                                if let Some(opened_file) = &mut self.state.opened_file {
-                                    opened_file.log_comments.insert(
-                                        comment_request.line_no,
-                                        comment_request.comment_text.clone(),
-                                    );
-                                }
-
-                                self.state.add_comment_request = None;
-                                ui.close_kind(egui::UiKind::Window);
+                        if (button_add.clicked() || should_add_comment)
+                            && !comment_request.comment_text.is_empty()
+                        {
+                            if let Some(opened_file) = &mut self.state.opened_file {
+                                opened_file.log_comments.insert(
+                                    comment_request.line_no,
+                                    comment_request.comment_text.clone(),
+                                );
                             }
+
+                            self.state.add_comment_request = None;
+                            ui.close_kind(egui::UiKind::Window);
                         }
 
                         let button_cancel = ui.button("Cancel");
@@ -1216,6 +4302,11 @@ impl eframe::App for LogalyzerGUI {
         let bottom_panel_height = available_rect.height() * 0.2;
         let central_panel_height = available_rect.height() - bottom_panel_height;
 
+        // Added first, so it's below the controls.
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            self.show_status_bar(ui);
+        });
+
         egui::TopBottomPanel::bottom("controls")
             .max_height(bottom_panel_height)
             .resizable(false)
@@ -1229,17 +4320,51 @@ impl eframe::App for LogalyzerGUI {
         self.show_log_format_window(ctx);
         self.show_token_colors_panel(ctx);
         self.show_histogram_window(ctx);
+        self.show_export_view_window(ctx);
+        self.show_stats_window(ctx);
+        self.show_patterns_window(ctx);
+        self.show_key_bindings_window(ctx);
+        self.show_open_stream_window(ctx);
+        self.show_import_tokens_window(ctx);
+        #[cfg(feature = "journald")]
+        self.show_open_journal_window(ctx);
+
+        self.receive_file_changes(ctx);
+        if let Some(remaining) = self.input_debounce_remaining() {
+            // Still typing, the settings are applied once the input settles.
+            ctx.request_repaint_after(remaining);
+        } else {
+            self.recalculate_logfile_display();
+        }
+        self.receive_loaded_file(ctx);
+        self.receive_recalculated_jobs(ctx);
+        self.receive_followed_lines(ctx);
+        self.update_column_table();
+        self.restore_top_line();
+        self.update_window_title(ctx);
 
-        self.recalculate_logfile_display();
-
-        let visible_log_lines = self.state.line_no_jobs.len();
+        let visible_log_lines = log_engine::visible_line_count(&self.state.jobs);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.set_min_height(central_panel_height);
 
+            if let Some(file_load_error) = &self.state.file_load_error {
+                egui::Frame::new()
+                    .fill(egui::Color32::DARK_RED)
+                    .inner_margin(6.0)
+                    .show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.colored_label(egui::Color32::WHITE, file_load_error.to_string());
+                    });
+            }
+            self.show_file_loading_banner(ui);
+            self.show_stream_disconnected_banner(ui);
+            self.show_table_header(ctx, ui);
+
             ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
                 let mut width_left_after_adding_line_numbers = ui.available_width();
                 let mut scroll_area_width_max = ui.available_width();
+                let line_numbers_left = ui.cursor().min.x;
 
                 self.show_line_numbers_scrollarea(
                     ctx,
@@ -1248,101 +4373,336 @@ impl eframe::App for LogalyzerGUI {
                     &mut scroll_area_width_max,
                     &mut width_left_after_adding_line_numbers,
                 );
+                self.state.table_header_indent = ui.cursor().min.x - line_numbers_left;
 
                 self.show_comment_add_window(ctx);
 
                 let scroll_delta_keyboard = self.get_scroll_delta_based_on_keypress(
-                    ctx,
                     ui,
                     central_panel_height,
                     width_left_after_adding_line_numbers,
                 );
+                let scroll_delta_keyboard = self.smooth_scroll_step(ctx, scroll_delta_keyboard);
 
-                let log_file_contents_scroll_area_resp = egui::ScrollArea::both()
+                let mut log_file_contents_scroll_area = egui::ScrollArea::both()
                     .id_salt("log_file")
                     .scroll_bar_visibility(ScrollBarVisibility::AlwaysVisible)
                     .animated(false)
-                    .scroll_source(self.scroll_sources_allowed)
-                    .auto_shrink(false)
-                    .show_rows(
-                        ui,
-                        self.user_settings.font.size,
-                        visible_log_lines,
-                        |ui, row_range| {
-                            ui.take_available_space();
-                            ui.set_min_height(ui.available_height());
-                            ui.scroll_with_delta(scroll_delta_keyboard);
-
-                            self.scroll_to_search_result(ui, &row_range);
-
-                            let mut text_wrapping = TextWrapping::default();
-                            if self.user_settings.wrap_text {
-                                text_wrapping.break_anywhere = false;
-                            } else {
-                                ui.set_width(scroll_area_width_max);
-                            }
+                    .scroll_source(self.log_scroll_sources(ctx))
+                    .auto_shrink(false);
+
+                if self.state.scroll_to_bottom_requested {
+                    // The offset is clamped to the content size, so this lands on the last line.
+                    log_file_contents_scroll_area =
+                        log_file_contents_scroll_area.vertical_scroll_offset(f32::MAX);
+                    self.state.scroll_to_bottom_requested = false;
+                }
 
-                            text_wrapping.max_width = scroll_area_width_max;
+                let row_height = self.row_height(ctx);
 
-                            ui.vertical(|ui| {
-                                for row_index in row_range {
-                                    if let Some(job) = self.state.log_jobs.get(row_index) {
-                                        let mut job_cloned = job.clone();
-                                        job_cloned.wrap = text_wrapping.clone();
+                if let Some(top_row) = self.state.top_row_scroll_requested.take() {
+                    // Same row height as show_rows() uses.
+                    let row_height_with_spacing = row_height + ui.spacing().item_spacing.y;
+                    log_file_contents_scroll_area = log_file_contents_scroll_area
+                        .vertical_scroll_offset(top_row as f32 * row_height_with_spacing);
+                }
 
-                                        let log_line_resp = ui.add(
-                                            egui::Label::new(job_cloned)
-                                                .wrap_mode(egui::TextWrapMode::Wrap),
-                                        );
+                let log_file_contents_scroll_area_resp = log_file_contents_scroll_area.show_rows(
+                    ui,
+                    row_height,
+                    visible_log_lines,
+                    |ui, row_range| {
+                        ui.take_available_space();
+                        ui.set_min_height(ui.available_height());
+                        ui.scroll_with_delta(scroll_delta_keyboard);
+                        self.state.top_visible_row = self.row_at(row_range.start);
+
+                        self.scroll_to_search_result(ui, &row_range);
+                        self.scroll_to_requested_line(ui, &row_range);
 
-                                        if log_line_resp.hovered() {
-                                            log_line_resp.highlight();
+                        if !self.wrapping_lines() {
+                            ui.set_width(scroll_area_width_max);
+                        }
+                        let text_wrapping = self.text_wrapping(scroll_area_width_max);
+
+                        ui.vertical(|ui| {
+                            for shown_row in row_range {
+                                let row_index = self.row_at(shown_row);
+                                let row_line = match self.column_table() {
+                                    Some(column_table) => column_table.row_line(
+                                        &self.state.jobs,
+                                        row_index,
+                                        &self.user_settings,
+                                    ),
+                                    None => log_engine::search_hilighted_line(
+                                        &self.state.jobs,
+                                        row_index,
+                                        &self.user_settings,
+                                    ),
+                                };
+                                if let Some(mut line) = row_line {
+                                    // The current search result is colored differently than the rest.
+                                    // The table lays the line out in cells, it's moved there.
+                                    let current_poi = self
+                                        .state
+                                        .jobs
+                                        .points_of_interest
+                                        .get(self.state.search_found_showing_index)
+                                        .filter(|poi| poi.line == row_index + 1)
+                                        .and_then(|poi| match self.column_table() {
+                                            Some(column_table) => column_table
+                                                .row_point_of_interest(
+                                                    &line,
+                                                    &self.state.jobs,
+                                                    poi,
+                                                    &self.user_settings,
+                                                ),
+                                            None => Some(poi.clone()),
+                                        });
+                                    if let Some(poi) = current_poi {
+                                        log_engine::hilight_point_of_interest(
+                                            &mut line,
+                                            &poi,
+                                            log_engine::style::Color::ORANGE,
+                                            log_engine::style::Color::BLACK,
+                                        );
+                                    }
+                                    let original_line_no = self
+                                        .state
+                                        .jobs
+                                        .visible_line_offsets
+                                        .get_original_line_for_row(row_index);
+
+                                    // Lines of merged files are tinted by their file, where
+                                    // nothing else colored them.
+                                    if let Some((source_index, _)) =
+                                        self.line_source(original_line_no)
+                                    {
+                                        for (_, part_format) in line.iter_mut() {
+                                            if part_format.background.is_transparent() {
+                                                part_format.background =
+                                                    log_engine::source_tint(source_index);
+                                            }
                                         }
+                                    }
+
+                                    // Only the styles change from here on, not the text.
+                                    let line_text = log_engine::linevec_text(&line);
+                                    let links = if self.user_settings.links_clickable {
+                                        log_engine::links::find_links(&line_text)
+                                    } else {
+                                        Vec::new()
+                                    };
+                                    log_engine::links::underline_links(&mut line, &links);
+
+                                    // The table cells are padded with spaces, these aren't in
+                                    // the log.
+                                    let drawn_line = if self.user_settings.show_whitespace
+                                        && self.column_table().is_none()
+                                    {
+                                        Cow::Owned(log_engine::whitespace::whitespace_shown(
+                                            &line,
+                                            self.user_settings.whitespace_color,
+                                        ))
+                                    } else {
+                                        Cow::Borrowed(&line)
+                                    };
+
+                                    let mut job_cloned =
+                                        layout_job_from_line(&drawn_line, row_height);
+                                    job_cloned.wrap = text_wrapping.clone();
+
+                                    // Rows standing for the lines the user folded unfold on click.
+                                    let fold_first_line_no =
+                                        log_engine::fold_placeholder_first_line(
+                                            &self.state.jobs,
+                                            row_index,
+                                        );
+
+                                    // Rows standing for several duplicates expand on click, and
+                                    // the first row of the expanded ones collapses them back.
+                                    let collapsed = fold_first_line_no.is_none()
+                                        && self
+                                            .state
+                                            .jobs
+                                            .collapsed_counts
+                                            .get(row_index)
+                                            .is_some_and(|collapsed_count| *collapsed_count > 1);
+                                    let expanded = self
+                                        .user_settings
+                                        .expanded_duplicates
+                                        .contains(&original_line_no);
+
+                                    // Otherwise JSON lines switch between single line and pretty
+                                    // printed on click.
+                                    let json_line = self.user_settings.json_mode
+                                        && fold_first_line_no.is_none()
+                                        && !collapsed
+                                        && !expanded
+                                        && log_engine::line_handlers::is_json_line(&line_text);
+                                    let json_pretty_printed = self
+                                        .user_settings
+                                        .expanded_json_lines
+                                        .contains(&original_line_no);
+
+                                    let log_line_label = egui::Label::new(job_cloned)
+                                        .wrap_mode(egui::TextWrapMode::Wrap)
+                                        .selectable(!self.drag_scrolling_active(ctx))
+                                        .sense(egui::Sense::click());
+                                    let label_wrapping = self.text_wrapping(ui.available_width());
+                                    let mut log_line_resp = ui.add(log_line_label);
+
+                                    let hovered_link = if links.is_empty() {
+                                        None
+                                    } else {
+                                        hovered_link(
+                                            ui,
+                                            layout_job_from_line(&drawn_line, row_height),
+                                            &label_wrapping,
+                                            &log_line_resp,
+                                            &line_text,
+                                            &links,
+                                        )
+                                    };
 
-                                        if self.user_settings.comments_visible {
-                                            if let Some(opened_file) = &self.state.opened_file {
-                                                let original_line_no = self
-                                                    .state
-                                                    .visible_line_offsets
-                                                    .get_offset_for_visible_line(row_index + 1)
-                                                    + row_index
-                                                    + 1;
-
-                                                let comment_for_this_line =
-                                                    opened_file.log_comments.get(&original_line_no);
-                                                if let Some(comment_text) = comment_for_this_line {
-                                                    let mut comment_job = LayoutJob::default();
-                                                    comment_job.append(
-                                                        format!("\t// {}", comment_text).as_str(),
-                                                        0.0,
-                                                        egui::TextFormat {
-                                                            font_id: self
-                                                                .user_settings
-                                                                .font
-                                                                .clone(),
-                                                            color: egui::Color32::LIGHT_GREEN,
-                                                            italics: true,
-                                                            ..Default::default()
-                                                        },
-                                                    );
-                                                    ui.horizontal(|ui| {
-                                                        ui.add(egui::Label::new(comment_job));
-                                                    });
-                                                }
+                                    if let Some(link) = &hovered_link {
+                                        let hover_text = match &link.target {
+                                            log_engine::links::LinkTarget::Url(url) => {
+                                                format!("Click to open {}", url)
                                             }
+                                            log_engine::links::LinkTarget::File {
+                                                path,
+                                                line_no,
+                                            } => format!(
+                                                "Click to open {} at line {}",
+                                                path, line_no
+                                            ),
+                                        };
+                                        log_line_resp = log_line_resp
+                                            .on_hover_text(hover_text)
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    } else if fold_first_line_no.is_some() {
+                                        log_line_resp = log_line_resp
+                                            .on_hover_text("Click to unfold")
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    } else if collapsed {
+                                        log_line_resp = log_line_resp
+                                            .on_hover_text("Click to expand the duplicates")
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    } else if expanded {
+                                        log_line_resp = log_line_resp
+                                            .on_hover_text("Click to collapse the duplicates")
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    } else if json_line {
+                                        let hover_text = if json_pretty_printed {
+                                            "Click to show on a single line"
+                                        } else {
+                                            "Click to pretty print"
+                                        };
+                                        log_line_resp = log_line_resp
+                                            .on_hover_text(hover_text)
+                                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                    }
+
+                                    if log_line_resp.clicked() {
+                                        if let Some(link) = &hovered_link {
+                                            self.open_link(ctx, link);
+                                        } else if let Some(fold_first_line_no) = fold_first_line_no
+                                        {
+                                            self.user_settings
+                                                .folded_ranges
+                                                .remove(&fold_first_line_no);
+                                        } else if expanded {
+                                            self.user_settings
+                                                .expanded_duplicates
+                                                .remove(&original_line_no);
+                                        } else if collapsed {
+                                            self.user_settings
+                                                .expanded_duplicates
+                                                .insert(original_line_no);
+                                        } else if json_line && json_pretty_printed {
+                                            self.user_settings
+                                                .expanded_json_lines
+                                                .remove(&original_line_no);
+                                        } else if json_line {
+                                            self.user_settings
+                                                .expanded_json_lines
+                                                .insert(original_line_no);
+                                        }
+                                    }
+
+                                    let clicked_byte_offset = if log_line_resp.secondary_clicked()
+                                        || log_line_resp.double_clicked()
+                                    {
+                                        hovered_byte_offset(
+                                            ui,
+                                            layout_job_from_line(&drawn_line, row_height),
+                                            &label_wrapping,
+                                            &log_line_resp,
+                                            &line_text,
+                                        )
+                                    } else {
+                                        None
+                                    };
+                                    let clicked_word =
+                                        clicked_byte_offset.and_then(|byte_offset| {
+                                            log_engine::word_at(&line_text, byte_offset)
+                                        });
+
+                                    if log_line_resp.secondary_clicked() {
+                                        self.state.log_line_menu_word =
+                                            clicked_word.map(str::to_string);
+                                    }
+                                    log_line_resp.context_menu(|ui| {
+                                        self.show_log_line_menu(
+                                            ctx,
+                                            ui,
+                                            original_line_no,
+                                            &line_text,
+                                        );
+                                    });
+
+                                    if log_line_resp.double_clicked()
+                                        && let Some(word) = clicked_word
+                                        && let Some(byte_offset) = clicked_byte_offset
+                                    {
+                                        self.search_word(word, row_index + 1, byte_offset);
+                                    }
+
+                                    if log_line_resp.hovered() {
+                                        log_line_resp.highlight();
+                                    }
+
+                                    if self.user_settings.comments_visible
+                                        && let Some(opened_file) = &self.state.opened_file
+                                    {
+                                        let comment_for_this_line =
+                                            opened_file.log_comments.get(&original_line_no);
+                                        if let Some(comment_text) = comment_for_this_line {
+                                            let mut comment_job =
+                                                self.comment_layout_job(ctx, comment_text);
+                                            comment_job.wrap = text_wrapping.clone();
+                                            ui.add(
+                                                egui::Label::new(comment_job)
+                                                    .wrap_mode(egui::TextWrapMode::Wrap),
+                                            );
                                         }
                                     }
                                 }
-                            });
-                        },
-                    );
+                            }
+                        });
+                    },
+                );
 
                 // Keep the line numbers scroll area and log file content scroll area synchronized while scrolling.
                 self.state.vertical_scroll_offset =
                     log_file_contents_scroll_area_resp.state.offset.y;
                 self.state.log_scroll_area_width =
                     log_file_contents_scroll_area_resp.content_size.x;
+                self.state.table_scroll_x = log_file_contents_scroll_area_resp.state.offset.x;
             });
         });
+
+        self.state.focused_last_frame = ctx.memory(|mem| mem.focused().is_some());
     }
 }
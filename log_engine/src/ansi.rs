@@ -0,0 +1,248 @@
+use std::borrow::Cow;
+
+use crate::linevec::*;
+use crate::style::{Color, Style};
+use crate::user_settings::AnsiCodes;
+
+const ESC: char = '\x1b';
+
+// The usual xterm colors, the bright ones follow the normal ones.
+const ANSI_COLORS: [Color; 16] = [
+    Color::from_rgb(0, 0, 0),
+    Color::from_rgb(205, 49, 49),
+    Color::from_rgb(13, 188, 121),
+    Color::from_rgb(229, 229, 16),
+    Color::from_rgb(36, 114, 200),
+    Color::from_rgb(188, 63, 188),
+    Color::from_rgb(17, 168, 205),
+    Color::from_rgb(229, 229, 229),
+    Color::from_rgb(102, 102, 102),
+    Color::from_rgb(241, 76, 76),
+    Color::from_rgb(35, 209, 139),
+    Color::from_rgb(245, 245, 67),
+    Color::from_rgb(59, 142, 234),
+    Color::from_rgb(214, 112, 214),
+    Color::from_rgb(41, 184, 219),
+    Color::from_rgb(255, 255, 255),
+];
+
+enum AnsiPiece<'a> {
+    Text(&'a str),
+    Sgr(&'a str), // the parameters of "ESC [ ... m", i.e. "1;31"
+}
+
+// Splits the line into the text and the SGR sequences. Other escape sequences (cursor movement,
+// window titles) mean nothing in a log and are left out, so is a sequence cut off by the line end.
+fn ansi_pieces(line: &str) -> Vec<AnsiPiece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+
+    while let Some(esc_pos) = rest.find(ESC) {
+        if esc_pos > 0 {
+            pieces.push(AnsiPiece::Text(&rest[..esc_pos]));
+        }
+
+        let sequence = &rest[esc_pos + ESC.len_utf8()..];
+        let sequence_len = match sequence.chars().next() {
+            // CSI: parameters and intermediate bytes, ended by a byte from '@' to '~'.
+            Some('[') => match sequence[1..].find(|c: char| ('@'..='~').contains(&c)) {
+                Some(end) => {
+                    if sequence[1 + end..].starts_with('m') {
+                        pieces.push(AnsiPiece::Sgr(&sequence[1..1 + end]));
+                    }
+                    1 + end + 1
+                }
+                None => sequence.len(),
+            },
+            // OSC: ended by BEL or by "ESC \".
+            Some(']') => match sequence.find(['\x07', ESC]) {
+                Some(end) if sequence[end..].starts_with('\x07') => end + 1,
+                Some(end) if sequence[end + 1..].starts_with('\\') => end + 2,
+                Some(end) => end,
+                None => sequence.len(),
+            },
+            Some(c) => c.len_utf8(),
+            None => 0,
+        };
+
+        rest = &sequence[sequence_len..];
+    }
+
+    if !rest.is_empty() {
+        pieces.push(AnsiPiece::Text(rest));
+    }
+
+    pieces
+}
+
+fn ansi_256_color(index: u16) -> Color {
+    match index {
+        0..=15 => ANSI_COLORS[index as usize],
+        16..=231 => {
+            let level = |value: u16| {
+                if value == 0 {
+                    0
+                } else {
+                    (55 + value * 40) as u8
+                }
+            };
+            let index = index - 16;
+            Color::from_rgb(level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = (8 + (index.min(255) - 232) * 10) as u8;
+            Color::from_rgb(gray, gray, gray)
+        }
+    }
+}
+
+// "38;5;n" and "38;2;r;g;b" (48 for the background), None if the parameters are cut off.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(ansi_256_color(params.next()?)),
+        2 => {
+            let mut component = || params.next().map(|value| value.min(255) as u8);
+            Some(Color::from_rgb(component()?, component()?, component()?))
+        }
+        _ => None,
+    }
+}
+
+// Unknown codes are ignored, so are the ones only a terminal can show (blinking, fonts).
+fn apply_sgr(style: &mut Style, sgr_params: &str, default_style: &Style) {
+    // "ESC [ m" is a reset, same as "ESC [ 0 m". Malformed numbers are taken for 0 too.
+    let mut params = sgr_params
+        .split([';', ':'])
+        .map(|param| param.parse::<u16>().unwrap_or(0));
+
+    while let Some(code) = params.next() {
+        match code {
+            0 => *style = default_style.clone(),
+            4 => style.underline = style.color,
+            24 => style.underline = Color::TRANSPARENT,
+            30..=37 => style.color = ANSI_COLORS[(code - 30) as usize],
+            90..=97 => style.color = ANSI_COLORS[(code - 90 + 8) as usize],
+            39 => style.color = default_style.color,
+            40..=47 => style.background = ANSI_COLORS[(code - 40) as usize],
+            100..=107 => style.background = ANSI_COLORS[(code - 100 + 8) as usize],
+            49 => style.background = default_style.background,
+            38 => {
+                if let Some(color) = extended_color(&mut params) {
+                    style.color = color;
+                }
+            }
+            48 => {
+                if let Some(color) = extended_color(&mut params) {
+                    style.background = color;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// The line without the escape sequences, borrowed if there are none.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains(ESC) {
+        return Cow::Borrowed(line);
+    }
+
+    Cow::Owned(
+        ansi_pieces(line)
+            .into_iter()
+            .filter_map(|piece| match piece {
+                AnsiPiece::Text(text) => Some(text),
+                AnsiPiece::Sgr(_) => None,
+            })
+            .collect(),
+    )
+}
+
+// The text as shown, for matching the line without coloring it (i.e. the filters).
+pub fn visible_text(line: &str, ansi_codes: AnsiCodes) -> Cow<'_, str> {
+    match ansi_codes {
+        AnsiCodes::Kept => Cow::Borrowed(line),
+        AnsiCodes::Colored | AnsiCodes::Stripped => strip_ansi(line),
+    }
+}
+
+// The line colored by its SGR sequences, the parts with no sequence have the default style.
+// There's always at least one part, even for an empty line.
+pub fn ansi_line(line: &str, default_style: &Style) -> LineVec {
+    let mut line_parts: LineVec = Vec::new();
+    let mut style = default_style.clone();
+
+    for piece in ansi_pieces(line) {
+        match piece {
+            AnsiPiece::Text(text) => match line_parts.last_mut() {
+                Some((part_str, part_style)) if *part_style == style => part_str.push_str(text),
+                _ => line_parts.push((text.to_string(), style.clone())),
+            },
+            AnsiPiece::Sgr(sgr_params) => apply_sgr(&mut style, sgr_params, default_style),
+        }
+    }
+
+    if line_parts.is_empty() {
+        line_parts.push((String::new(), default_style.clone()));
+    }
+
+    line_parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_sequences_stripped() {
+        assert_eq!(
+            strip_ansi("\x1b[1;31mERROR\x1b[0m disk \x1b[2Kfull"),
+            "ERROR disk full"
+        );
+        assert_eq!(strip_ansi("\x1b]0;title\x07a\x1b]8;;http://x\x1b\\b"), "ab");
+        assert!(matches!(strip_ansi("plain"), Cow::Borrowed(_)));
+
+        // Cut off at the line end.
+        assert_eq!(strip_ansi("done\x1b[3"), "done");
+        assert_eq!(strip_ansi("done\x1b"), "done");
+    }
+
+    #[test]
+    fn sgr_codes_colored() {
+        let default_style = Style::default();
+        let line = ansi_line(
+            "\x1b[31mred\x1b[42;4m on green\x1b[39m default\x1b[m reset \x1b[38;5;196mx\x1b[38;2;1;2;3my\x1b[38;5m z",
+            &default_style,
+        );
+
+        let parts: Vec<(&str, Color, Color, bool)> = line
+            .iter()
+            .map(|(part_str, style)| {
+                (
+                    part_str.as_str(),
+                    style.color,
+                    style.background,
+                    !style.underline.is_transparent(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("red", ANSI_COLORS[1], Color::TRANSPARENT, false),
+                (" on green", ANSI_COLORS[1], ANSI_COLORS[2], true),
+                (" default", default_style.color, ANSI_COLORS[2], true),
+                (" reset ", default_style.color, Color::TRANSPARENT, false),
+                ("x", Color::from_rgb(255, 0, 0), Color::TRANSPARENT, false),
+                // The cut off "38;5" keeps the color.
+                ("y z", Color::from_rgb(1, 2, 3), Color::TRANSPARENT, false),
+            ]
+        );
+
+        assert_eq!(
+            ansi_line("\x1b[0m", &default_style),
+            vec![(String::new(), default_style.clone())]
+        );
+        assert_eq!(linevec_text(&ansi_line("a\x1b[99mb", &default_style)), "ab");
+    }
+}
@@ -0,0 +1,602 @@
+use regex::Regex;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::ansi::visible_text;
+use crate::line_handlers::{
+    LineHandler, RedactLineHandler, SearchHilight, SearchLineHandler, search_hilight_split,
+};
+use crate::linevec::*;
+use crate::log_content::LogContent;
+use crate::style::{Color, Style};
+use crate::user_settings::UserSettings;
+use crate::{
+    CONTEXT_LINE_FADE, LogJobs, PointOfInterest, fold_placeholder_first_line, log_line,
+    search_hilighted_line, visible_line_count,
+};
+
+// Between the cells of a row, and between the names in the header.
+pub const COLUMN_SEPARATOR: &str = " │ ";
+
+// After the name of the column the rows are sorted by, there's room left for it in every column.
+const SORT_ASCENDING_MARKER: &str = " ▲";
+const SORT_DESCENDING_MARKER: &str = " ▼";
+
+// Names of the named capture groups, in their order in the pattern. None for an invalid pattern.
+pub fn named_groups(pattern: &str) -> Option<Vec<String>> {
+    let regex = Regex::new(pattern).ok()?;
+
+    Some(
+        regex
+            .capture_names()
+            .flatten()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+// The named groups of the log format laid out in aligned columns, a row for each visible line.
+// The cells are taken from the (redacted) original lines, so the groups the format hides can be
+// columns too.
+pub struct ColumnTable {
+    pub names: Vec<String>,
+    pub widths: Vec<usize>, // in chars, of the widest cell (or the name) of each column
+    rows: Vec<Option<Vec<String>>>, // by visible row, None where the line doesn't match the format
+    sort: Option<(usize, bool)>, // the column index and whether it's descending
+    row_order: Vec<usize>,  // the visible row of each table row, empty if not sorted
+    table_rows: Vec<usize>, // the table row of each visible row
+    regex: Regex,
+    group_indices: Vec<usize>,
+    styles: Vec<Style>,
+    font_size: f32,
+}
+
+impl ColumnTable {
+    // The columns picked in table_columns, or all the named groups if none are picked.
+    pub fn new(user_settings: &UserSettings) -> Result<Self, String> {
+        let log_format = &user_settings.log_format;
+        if log_format.pattern.is_empty() {
+            return Err("No log format pattern to take the columns from".to_string());
+        }
+
+        let regex = Regex::new(&log_format.pattern)
+            .map_err(|e| format!("Invalid log format pattern: {}", e))?;
+
+        let mut names = Vec::new();
+        let mut group_indices = Vec::new();
+        let mut styles = Vec::new();
+        for (group_index, group_name) in regex.capture_names().enumerate() {
+            let Some(group_name) = group_name else {
+                continue;
+            };
+            if !user_settings.table_columns.is_empty()
+                && !user_settings
+                    .table_columns
+                    .iter()
+                    .any(|name| name == group_name)
+            {
+                continue;
+            }
+
+            let mut style = Style {
+                font_size: user_settings.font_size,
+                ..Default::default()
+            };
+            if let Some(coloring_index) =
+                log_format.coloring_index(group_index - 1, Some(group_name))
+            {
+                if let Some(background) = log_format.pattern_coloring.get(coloring_index) {
+                    style.background = *background;
+                }
+                let use_original = log_format
+                    .pattern_coloring_text_use_original
+                    .get(coloring_index)
+                    .copied()
+                    .unwrap_or(true);
+                if !use_original
+                    && let Some(text_color) = log_format.pattern_coloring_text.get(coloring_index)
+                {
+                    style.color = *text_color;
+                }
+            }
+
+            names.push(group_name.to_string());
+            group_indices.push(group_index);
+            styles.push(style);
+        }
+
+        if names.is_empty() {
+            return Err("The log format has no named groups, i.e. (?<level>\\w+)".to_string());
+        }
+
+        Ok(Self {
+            widths: Self::name_widths(&names),
+            names,
+            rows: Vec::new(),
+            sort: None,
+            row_order: Vec::new(),
+            table_rows: Vec::new(),
+            regex,
+            group_indices,
+            styles,
+            font_size: user_settings.font_size,
+        })
+    }
+
+    // Extracts the cells of the rows added to the log jobs since the last update, i.e. the lines
+    // appended to a followed file.
+    pub fn update(
+        &mut self,
+        content: &LogContent,
+        log_jobs: &LogJobs,
+        user_settings: &UserSettings,
+    ) {
+        if self.rows.len() > visible_line_count(log_jobs) {
+            self.rows.clear();
+            self.widths = Self::name_widths(&self.names);
+        }
+
+        let mut redact_handler = RedactLineHandler::new(user_settings);
+
+        for row_index in self.rows.len()..visible_line_count(log_jobs) {
+            // The folds show what's folded, not the cells of their first line.
+            let original_line = if fold_placeholder_first_line(log_jobs, row_index).is_some() {
+                None
+            } else {
+                content.original_line(
+                    log_jobs
+                        .visible_line_offsets
+                        .get_original_line_for_row(row_index),
+                )
+            };
+
+            let cells = original_line.and_then(|line| {
+                let mut line_parts: LineVec = vec![(
+                    visible_text(&line, user_settings.ansi_codes).into_owned(),
+                    Style::default(),
+                )];
+                if let Some(handler) = &mut redact_handler {
+                    handler.process_line(&mut line_parts);
+                }
+
+                self.cells(&linevec_text(&line_parts))
+            });
+
+            if let Some(cells) = &cells {
+                for (width, cell) in self.widths.iter_mut().zip(cells) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            self.rows.push(cells);
+        }
+
+        if self.sort.is_some() && self.row_order.len() != self.rows.len() {
+            self.sort_by(self.sort);
+        }
+    }
+
+    // The cells are kept, only the font they're shown with changes.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size;
+        for style in &mut self.styles {
+            style.font_size = font_size;
+        }
+    }
+
+    pub fn sort(&self) -> Option<(usize, bool)> {
+        self.sort
+    }
+
+    // Stable, the rows with equal cells stay in the log order. The cells are compared as numbers
+    // if both are numbers, the numbers come first. The rows not matching the format stay below
+    // the row above them, so the continuation lines stay with their entry. None for the log order.
+    pub fn sort_by(&mut self, sort: Option<(usize, bool)>) {
+        self.sort = sort.filter(|(column_index, _)| *column_index < self.names.len());
+        self.row_order.clear();
+        self.table_rows.clear();
+
+        let Some((column_index, descending)) = self.sort else {
+            return;
+        };
+
+        let mut entries: Vec<Range<usize>> = Vec::new();
+        for row_index in 0..self.rows.len() {
+            match entries.last_mut() {
+                Some(entry) if self.rows[row_index].is_none() => entry.end += 1,
+                _ => entries.push(row_index..row_index + 1),
+            }
+        }
+
+        let cell = |row_index: usize| {
+            self.rows[row_index]
+                .as_ref()
+                .map(|cells| cells[column_index].as_str())
+        };
+        entries.sort_by(|a, b| {
+            let ordering = compare_cells(cell(a.start), cell(b.start));
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        self.row_order = entries.into_iter().flatten().collect();
+        self.table_rows = vec![0; self.row_order.len()];
+        for (table_row, visible_row) in self.row_order.iter().enumerate() {
+            self.table_rows[*visible_row] = table_row;
+        }
+    }
+
+    // The visible row shown at the table row, they're the same if the table isn't sorted.
+    pub fn visible_row(&self, table_row: usize) -> usize {
+        self.row_order.get(table_row).copied().unwrap_or(table_row)
+    }
+
+    pub fn table_row(&self, visible_row: usize) -> usize {
+        self.table_rows
+            .get(visible_row)
+            .copied()
+            .unwrap_or(visible_row)
+    }
+
+    fn name_widths(names: &[String]) -> Vec<usize> {
+        names
+            .iter()
+            .map(|name| name.chars().count() + SORT_ASCENDING_MARKER.chars().count())
+            .collect()
+    }
+
+    fn cells(&self, line: &str) -> Option<Vec<String>> {
+        let captures = self.regex.captures(line)?;
+
+        Some(
+            self.group_indices
+                .iter()
+                .map(|group_index| {
+                    captures
+                        .get(*group_index)
+                        .map_or(String::new(), |group_match| {
+                            group_match.as_str().to_string()
+                        })
+                })
+                .collect(),
+        )
+    }
+
+    // Width of a whole row in chars.
+    pub fn row_width(&self) -> usize {
+        self.widths.iter().sum::<usize>()
+            + COLUMN_SEPARATOR.chars().count() * self.widths.len().saturating_sub(1)
+    }
+
+    pub fn header_line(&self) -> LineVec {
+        let header_style = Style {
+            color: Color::WHITE,
+            font_size: self.font_size,
+            ..Default::default()
+        };
+
+        let names: Vec<String> = self
+            .names
+            .iter()
+            .enumerate()
+            .map(|(column_index, name)| match self.sort {
+                Some((sort_column, false)) if sort_column == column_index => {
+                    format!("{}{}", name, SORT_ASCENDING_MARKER)
+                }
+                Some((sort_column, true)) if sort_column == column_index => {
+                    format!("{}{}", name, SORT_DESCENDING_MARKER)
+                }
+                _ => name.clone(),
+            })
+            .collect();
+
+        self.aligned_line(&names, |_| header_style.clone())
+    }
+
+    // The column at the char offset in a row, None on the separators.
+    pub fn column_at(&self, char_offset: usize) -> Option<usize> {
+        let separator_width = COLUMN_SEPARATOR.chars().count();
+        let mut column_start = 0;
+        for (column_index, width) in self.widths.iter().enumerate() {
+            if char_offset < column_start + width {
+                return (char_offset >= column_start).then_some(column_index);
+            }
+            column_start += width + separator_width;
+        }
+
+        None
+    }
+
+    // The row's cells, the search matches in them hilighted. The lines not matching the format
+    // are shown as they are in the log.
+    pub fn row_line(
+        &self,
+        log_jobs: &LogJobs,
+        row_index: usize,
+        user_settings: &UserSettings,
+    ) -> Option<LineVec> {
+        let Some(Some(cells)) = self.rows.get(row_index) else {
+            return search_hilighted_line(log_jobs, row_index, user_settings);
+        };
+
+        let is_context = log_jobs
+            .context_lines
+            .get(row_index)
+            .copied()
+            .unwrap_or(false);
+        let fade = if is_context { CONTEXT_LINE_FADE } else { 1.0 };
+
+        let mut line = self.aligned_line(cells, |column_index| {
+            let style = &self.styles[column_index];
+            Style {
+                color: style.color.gamma_multiply(fade),
+                background: style.background.gamma_multiply(fade),
+                ..style.clone()
+            }
+        });
+
+        if let Some(search_handler) = SearchLineHandler::new(user_settings) {
+            let split_points = search_handler
+                .find_points_of_interest(&line)
+                .into_iter()
+                .map(|poi| poi.split_point)
+                .collect();
+            search_hilight_split(
+                &mut line,
+                split_points,
+                SearchHilight::new(user_settings),
+                fade,
+            );
+        }
+
+        Some(line)
+    }
+
+    // The search result moved into the row as shown, its byte range is in the log line and the
+    // cells are laid out differently. It's the row's match with the same text, the n-th of them if
+    // the text repeats. None if the cells don't have it, i.e. it's in a group that isn't a column.
+    pub fn row_point_of_interest(
+        &self,
+        row_line: &LineVec,
+        log_jobs: &LogJobs,
+        poi: &PointOfInterest,
+        user_settings: &UserSettings,
+    ) -> Option<PointOfInterest> {
+        let row_index = poi.line.checked_sub(1)?;
+        let Some(Some(_)) = self.rows.get(row_index) else {
+            return Some(poi.clone());
+        };
+
+        let line_text = linevec_text(&log_line(log_jobs, row_index)?);
+        let poi_text = line_text.get(poi.byte_range.clone())?;
+        let same_text_before = log_jobs
+            .points_of_interest
+            .iter()
+            .filter(|other| {
+                other.line == poi.line
+                    && other.byte_range.start < poi.byte_range.start
+                    && line_text.get(other.byte_range.clone()) == Some(poi_text)
+            })
+            .count();
+
+        let row_text = linevec_text(row_line);
+        SearchLineHandler::new(user_settings)?
+            .find_points_of_interest(row_line)
+            .into_iter()
+            .filter(|row_poi| row_text.get(row_poi.byte_range.clone()) == Some(poi_text))
+            .nth(same_text_before)
+            .map(|row_poi| PointOfInterest {
+                line: poi.line,
+                ..row_poi
+            })
+    }
+
+    fn aligned_line(&self, cells: &[String], cell_style: impl Fn(usize) -> Style) -> LineVec {
+        let separator_style = Style {
+            font_size: self.font_size,
+            ..Default::default()
+        };
+
+        let mut line = LineVec::new();
+        for (column_index, (cell, width)) in cells.iter().zip(&self.widths).enumerate() {
+            if column_index > 0 {
+                line.push((COLUMN_SEPARATOR.to_string(), separator_style.clone()));
+            }
+
+            let padding = width.saturating_sub(cell.chars().count());
+            line.push((
+                format!("{}{}", cell, " ".repeat(padding)),
+                cell_style(column_index),
+            ));
+        }
+
+        line
+    }
+}
+
+fn compare_cells(a: Option<&str>, b: Option<&str>) -> Ordering {
+    let (Some(a), Some(b)) = (a, b) else {
+        return a.is_some().cmp(&b.is_some());
+    };
+
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a_number), Ok(b_number)) => a_number.total_cmp(&b_number),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recalculate_log_job;
+
+    fn table_user_settings() -> UserSettings {
+        let mut user_settings = UserSettings::default();
+        user_settings.log_format.pattern =
+            r"^(?<time>\S+) (?<level>\w+) (\w+): (?<message>.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&Regex::new(&user_settings.log_format.pattern).unwrap());
+
+        user_settings
+    }
+
+    #[test]
+    fn named_groups_listed() {
+        assert_eq!(
+            named_groups(r"(?<time>\S+) (\w+) (?<level>\w+)"),
+            Some(vec!["time".to_string(), "level".to_string()])
+        );
+        assert_eq!(named_groups("("), None);
+        assert!(
+            ColumnTable::new(&UserSettings {
+                log_format: crate::user_settings::LogFormat {
+                    pattern: r"(\S+) (\w+)".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn cells_aligned_in_columns() {
+        let content = LogContent::from_string(
+            "12:00:01 INFO app: started\n12:00:02 WARNING db: slow query\n  continued\n"
+                .to_string(),
+        );
+        let mut user_settings = table_user_settings();
+        user_settings.table_columns = vec!["level".to_string(), "message".to_string()];
+        user_settings.search_term = "slow".to_string();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        let mut column_table = ColumnTable::new(&user_settings).unwrap();
+        column_table.update(&content, &log_jobs, &user_settings);
+        assert_eq!(column_table.names, vec!["level", "message"]);
+        assert_eq!(column_table.widths, vec![7, 10]);
+        assert_eq!(column_table.row_width(), 7 + 3 + 10);
+        assert_eq!(column_table.column_at(6), Some(0));
+        assert_eq!(column_table.column_at(8), None);
+        assert_eq!(column_table.column_at(10), Some(1));
+        assert_eq!(column_table.column_at(20), None);
+
+        assert_eq!(
+            linevec_text(&column_table.header_line()),
+            "level   │ message   "
+        );
+        column_table.sort_by(Some((1, true)));
+        assert_eq!(
+            linevec_text(&column_table.header_line()),
+            "level   │ message ▼ "
+        );
+        column_table.sort_by(None);
+        let row_text = |row_index| {
+            linevec_text(
+                &column_table
+                    .row_line(&log_jobs, row_index, &user_settings)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(row_text(0), "INFO    │ started   ");
+        assert_eq!(row_text(1), "WARNING │ slow query");
+        assert_eq!(row_text(2), "  continued");
+
+        // The search matches are found in the cells.
+        let row_line = column_table.row_line(&log_jobs, 1, &user_settings).unwrap();
+        assert!(row_line.iter().any(|(part_str, format)| part_str == "slow"
+            && format.background == user_settings.search_highlight_background));
+    }
+
+    #[test]
+    fn current_result_found_in_later_column() {
+        let content = LogContent::from_string(
+            "12:00:01 INFO app: started\n12:00:02 WARNING db: WARNING repeated\n".to_string(),
+        );
+        let mut user_settings = table_user_settings();
+        user_settings.table_columns = vec!["level".to_string(), "message".to_string()];
+        user_settings.search_term = "WARNING".to_string();
+        user_settings.search_match_case = true;
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), 2);
+
+        let mut column_table = ColumnTable::new(&user_settings).unwrap();
+        column_table.update(&content, &log_jobs, &user_settings);
+        let row_line = column_table.row_line(&log_jobs, 1, &user_settings).unwrap();
+        let row_text = linevec_text(&row_line);
+        assert_eq!(row_text, "WARNING │ WARNING repeated");
+
+        // The second match of the log line is in the message column, past the end of its cell
+        // text if the byte range of the log line was used.
+        let poi = &log_jobs.points_of_interest[1];
+        let row_poi = column_table
+            .row_point_of_interest(&row_line, &log_jobs, poi, &user_settings)
+            .unwrap();
+        let message_start = row_text.find("WARNING repeated").unwrap();
+        assert_eq!(row_poi.byte_range, message_start..message_start + 7);
+        assert_eq!(row_poi.line, poi.line);
+
+        let mut line = row_line.clone();
+        crate::hilight_point_of_interest(&mut line, &row_poi, Color::ORANGE, Color::BLACK);
+        let current: Vec<&str> = line
+            .iter()
+            .filter(|(_, style)| style.background == Color::ORANGE)
+            .map(|(part_str, _)| part_str.as_str())
+            .collect();
+        assert_eq!(current, vec!["WARNING"]);
+        assert_eq!(linevec_text(&line), row_text);
+
+        // The first one stays in the level column.
+        let row_poi = column_table
+            .row_point_of_interest(
+                &row_line,
+                &log_jobs,
+                &log_jobs.points_of_interest[0],
+                &user_settings,
+            )
+            .unwrap();
+        assert_eq!(row_poi.byte_range, 0..7);
+    }
+
+    #[test]
+    fn rows_sorted_by_column() {
+        let content = LogContent::from_string(
+            "12:00:01 INFO app: took 100\n12:00:02 WARN db: took 9\n  continued\n\
+            12:00:03 INFO app: took 9\n12:00:04 ERROR app: took x\n"
+                .to_string(),
+        );
+        let mut user_settings = table_user_settings();
+        user_settings.log_format.pattern =
+            r"^(?<time>\S+) (?<level>\w+) (\w+): took (?<took>.*)$".to_string();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        let mut column_table = ColumnTable::new(&user_settings).unwrap();
+        column_table.update(&content, &log_jobs, &user_settings);
+        let table_order = |column_table: &ColumnTable| -> Vec<usize> {
+            (0..5).map(|row| column_table.visible_row(row)).collect()
+        };
+        assert_eq!(table_order(&column_table), vec![0, 1, 2, 3, 4]);
+
+        // Numbers by value, equal ones in the log order, the continuation after its entry.
+        column_table.sort_by(Some((2, false)));
+        assert_eq!(table_order(&column_table), vec![1, 2, 3, 0, 4]);
+        column_table.sort_by(Some((2, true)));
+        assert_eq!(table_order(&column_table), vec![4, 0, 1, 2, 3]);
+        column_table.sort_by(Some((1, false)));
+        assert_eq!(table_order(&column_table), vec![4, 0, 3, 1, 2]);
+        for visible_row in 0..5 {
+            assert_eq!(
+                column_table.visible_row(column_table.table_row(visible_row)),
+                visible_row
+            );
+        }
+
+        column_table.sort_by(None);
+        assert_eq!(column_table.sort(), None);
+        assert_eq!(table_order(&column_table), vec![0, 1, 2, 3, 4]);
+    }
+}
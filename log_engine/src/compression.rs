@@ -0,0 +1,95 @@
+use std::io::Read;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum CompressionKind {
+    #[default]
+    None,
+    Gzip,
+    Bzip2,
+}
+
+impl CompressionKind {
+    // Detects the compression by magic bytes, the extension is not reliable for rotated logs
+    // (i.e. "syslog.2.gz" vs "syslog.1").
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(GZIP_MAGIC) {
+            CompressionKind::Gzip
+        } else if bytes.starts_with(BZIP2_MAGIC) {
+            CompressionKind::Bzip2
+        } else {
+            CompressionKind::None
+        }
+    }
+
+    // Short name to show to the user, i.e. in the window title.
+    pub fn label(&self) -> Option<&'static str> {
+        match self {
+            CompressionKind::None => None,
+            CompressionKind::Gzip => Some("gz"),
+            CompressionKind::Bzip2 => Some("bz2"),
+        }
+    }
+}
+
+pub fn decompress(bytes: Vec<u8>, kind: CompressionKind) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+
+    match kind {
+        CompressionKind::None => return Ok(bytes),
+        CompressionKind::Gzip => {
+            flate2::read::MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)?;
+        }
+        CompressionKind::Bzip2 => {
+            bzip2::read::MultiBzDecoder::new(bytes.as_slice()).read_to_end(&mut decompressed)?;
+        }
+    }
+
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const LOG: &[u8] = b"[0.1] first line\n[0.2] second line\n";
+
+    #[test]
+    fn gzip_detected_and_decompressed() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(LOG).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let kind = CompressionKind::detect(&compressed);
+        assert_eq!(kind, CompressionKind::Gzip);
+        assert_eq!(decompress(compressed, kind).unwrap(), LOG);
+    }
+
+    #[test]
+    fn bzip2_detected_and_decompressed() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(LOG).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let kind = CompressionKind::detect(&compressed);
+        assert_eq!(kind, CompressionKind::Bzip2);
+        assert_eq!(decompress(compressed, kind).unwrap(), LOG);
+    }
+
+    #[test]
+    fn plain_text_passes_through() {
+        let kind = CompressionKind::detect(LOG);
+        assert_eq!(kind, CompressionKind::None);
+        assert_eq!(decompress(LOG.to_vec(), kind).unwrap(), LOG);
+    }
+
+    #[test]
+    fn corrupted_gzip_fails() {
+        let kind = CompressionKind::detect(&[0x1f, 0x8b, 0x00, 0x01]);
+        assert_eq!(kind, CompressionKind::Gzip);
+        assert!(decompress(vec![0x1f, 0x8b, 0x00, 0x01], kind).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::histogram::LEADING_TIMESTAMP_PATTERN;
+
+static LEADING_TIMESTAMP_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(LEADING_TIMESTAMP_PATTERN).unwrap());
+
+// Lines with the same key are duplicates, the timestamp of a repeated message doesn't matter.
+pub fn duplicate_key(line: &str) -> &str {
+    match LEADING_TIMESTAMP_REGEX.find(line) {
+        Some(timestamp_match) => &line[timestamp_match.end()..],
+        None => line,
+    }
+}
+
+// Consecutive duplicates seen so far, they are shown as a single row unless expanded.
+pub(crate) struct DuplicatesGroup {
+    pub key: String,
+    pub last_original_line_no: usize,
+    pub is_context: bool,
+    pub expanded: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_timestamps_ignored() {
+        assert_eq!(
+            duplicate_key("2024-01-31 12:34:56.789 retrying"),
+            " retrying"
+        );
+        assert_eq!(duplicate_key("2024-01-31T12:34:56 retrying"), " retrying");
+        assert_eq!(duplicate_key("12:34:56,5 retrying"), " retrying");
+        assert_eq!(duplicate_key("[  12.345678] retrying"), " retrying");
+    }
+
+    #[test]
+    fn lines_without_timestamp_kept() {
+        assert_eq!(duplicate_key("retrying"), "retrying");
+        assert_eq!(
+            duplicate_key("retrying at 12:34:56"),
+            "retrying at 12:34:56"
+        );
+        assert_eq!(duplicate_key("[worker] retrying"), "[worker] retrying");
+        assert_eq!(duplicate_key(""), "");
+    }
+}
@@ -0,0 +1,44 @@
+use std::borrow::Cow;
+
+pub const ENCODING_UTF8: &str = "utf-8";
+pub const ENCODING_LATIN1: &str = "latin1";
+
+pub const SUPPORTED_ENCODINGS: &[&str] = &[ENCODING_UTF8, ENCODING_LATIN1];
+
+// Decodes raw log bytes, never fails - invalid UTF-8 sequences are replaced with U+FFFD, as
+// logs with a few broken bytes are still worth reading. Unknown encodings fall back to UTF-8.
+pub fn decode<'a>(bytes: &'a [u8], encoding: &str) -> Cow<'a, str> {
+    match encoding.to_lowercase().as_str() {
+        "latin1" | "latin-1" | "iso-8859-1" => {
+            // Latin-1 maps each byte directly to the unicode code point of the same value.
+            Cow::Owned(bytes.iter().map(|b| *b as char).collect())
+        }
+        _ => String::from_utf8_lossy(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_decoded() {
+        assert_eq!(decode("zażółć".as_bytes(), ENCODING_UTF8), "zażółć");
+    }
+
+    #[test]
+    fn invalid_utf8_replaced() {
+        assert_eq!(decode(b"caf\xe9 ok", ENCODING_UTF8), "caf\u{fffd} ok");
+    }
+
+    #[test]
+    fn latin1_decoded() {
+        assert_eq!(decode(b"caf\xe9 \xb1", ENCODING_LATIN1), "café ±");
+        assert_eq!(decode(b"caf\xe9", "ISO-8859-1"), "café");
+    }
+
+    #[test]
+    fn unknown_encoding_falls_back_to_utf8() {
+        assert_eq!(decode("żółw".as_bytes(), "klingon"), "żółw");
+    }
+}
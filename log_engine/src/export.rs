@@ -0,0 +1,409 @@
+use crate::style::{Color, Style};
+use std::collections::HashMap;
+
+use crate::{LogJobs, MAX_POINTS_OF_INTEREST, ProcessedLine, RowBuilder, linevec_text};
+
+// Search matches are wrapped in these when the markers are kept.
+const HILIGHT_MARKER_START: &str = "**";
+const HILIGHT_MARKER_END: &str = "**";
+
+#[derive(PartialEq, Clone, Default)]
+pub struct ViewExportOptions {
+    pub include_line_numbers: bool, // original ones, not the visible
+    pub include_comments: bool,
+    pub keep_hilight_markers: bool,
+}
+
+fn line_to_text(processed_line: &ProcessedLine, keep_hilight_markers: bool) -> String {
+    let mut line_text: String = processed_line
+        .parts
+        .iter()
+        .map(|(part_str, _)| part_str.as_str())
+        .collect();
+
+    if keep_hilight_markers {
+        let mut byte_ranges: Vec<_> = processed_line
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.byte_range.clone())
+            .collect();
+        byte_ranges.sort_by_key(|byte_range| byte_range.start);
+
+        // From the end, so the earlier ranges stay valid.
+        for byte_range in byte_ranges.into_iter().rev() {
+            line_text.insert_str(byte_range.end, HILIGHT_MARKER_END);
+            line_text.insert_str(byte_range.start, HILIGHT_MARKER_START);
+        }
+    }
+
+    line_text
+}
+
+pub fn view_to_text(
+    processed_lines: &[ProcessedLine],
+    log_comments: &HashMap<usize, String>,
+    options: &ViewExportOptions,
+) -> String {
+    let mut text = String::new();
+
+    for processed_line in processed_lines {
+        if options.include_line_numbers {
+            text.push_str(&format!("{}: ", processed_line.original_line_no));
+        }
+
+        text.push_str(&line_to_text(processed_line, options.keep_hilight_markers));
+
+        if options.include_comments
+            && let Some(comment_text) = log_comments.get(&processed_line.original_line_no)
+        {
+            text.push_str(&format!("\t// {}", comment_text));
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+// Every line with a search match as "<original line number>: <text>", the matches are the ones
+// already found for the view, so the lines are exported as they're shown.
+pub fn matches_to_text(log_jobs: &LogJobs) -> String {
+    // Sorted by line, a line can have several.
+    let mut matched_rows: Vec<usize> = log_jobs
+        .points_of_interest
+        .iter()
+        .map(|poi| poi.line - 1)
+        .collect();
+    matched_rows.dedup();
+
+    let mut text = format!("{} matching lines", matched_rows.len());
+    if log_jobs.points_of_interest_capped {
+        text.push_str(&format!(
+            " (only the first {} matches)",
+            MAX_POINTS_OF_INTEREST
+        ));
+    }
+    text.push('\n');
+
+    let mut row_builder = RowBuilder::new(log_jobs);
+    for row_index in matched_rows {
+        let original_line_no = log_jobs
+            .visible_line_offsets
+            .get_original_line_for_row(row_index);
+        text.push_str(&format!(
+            "{}: {}\n",
+            original_line_no,
+            linevec_text(&row_builder.row(row_index).unwrap_or_default())
+        ));
+    }
+
+    text
+}
+
+fn css_color(color: Color) -> String {
+    let Color { r, g, b, a } = color;
+    if a == u8::MAX {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("rgba({}, {}, {}, {:.2})", r, g, b, a as f32 / 255.0)
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn part_to_html(part_str: &str, part_format: &Style) -> String {
+    let mut style = format!("color:{}", css_color(part_format.color));
+    if part_format.background != Color::TRANSPARENT {
+        style.push_str(&format!(
+            ";background:{}",
+            css_color(part_format.background)
+        ));
+    }
+    if !part_format.underline.is_transparent() {
+        style.push_str(&format!(
+            ";text-decoration:underline {}",
+            css_color(part_format.underline)
+        ));
+    }
+
+    format!("<span style=\"{}\">{}</span>", style, html_escape(part_str))
+}
+
+// A self-contained HTML page with the same colors as shown in the GUI.
+pub fn view_to_html(
+    processed_lines: &[ProcessedLine],
+    log_comments: &HashMap<usize, String>,
+    options: &ViewExportOptions,
+) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Logalyzer export</title>\n\
+        <style>\n\
+        body { background: #1b1b1b; }\n\
+        pre { font-family: monospace; }\n\
+        .line-number { color: #8c8c8c; }\n\
+        .context { opacity: 0.5; }\n\
+        .comment { color: #90ee90; font-style: italic; }\n\
+        </style>\n</head>\n<body>\n<pre>\n",
+    );
+
+    for processed_line in processed_lines {
+        if processed_line.is_context {
+            html.push_str("<span class=\"context\">");
+        }
+
+        if options.include_line_numbers {
+            html.push_str(&format!(
+                "<span class=\"line-number\">{}: </span>",
+                processed_line.original_line_no
+            ));
+        }
+
+        for (part_str, part_format) in &processed_line.parts {
+            // Splitting leaves empty parts behind, no need to litter the output with them.
+            if !part_str.is_empty() {
+                html.push_str(&part_to_html(part_str, part_format));
+            }
+        }
+
+        if processed_line.is_context {
+            html.push_str("</span>");
+        }
+
+        if options.include_comments
+            && let Some(comment_text) = log_comments.get(&processed_line.original_line_no)
+        {
+            html.push_str(&format!(
+                "<span class=\"comment\">\t// {}</span>",
+                html_escape(comment_text)
+            ));
+        }
+
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+
+    html
+}
+
+// 24-bit ANSI escapes, uncolored text keeps the terminal's own color.
+fn part_to_ansi(part_str: &str, part_format: &Style) -> String {
+    let mut codes = Vec::new();
+    if part_format.color != Style::default().color {
+        let Color { r, g, b, .. } = part_format.color;
+        codes.push(format!("38;2;{};{};{}", r, g, b));
+    }
+    if !part_format.background.is_transparent() {
+        let Color { r, g, b, .. } = part_format.background;
+        codes.push(format!("48;2;{};{};{}", r, g, b));
+    }
+    if !part_format.underline.is_transparent() {
+        codes.push("4".to_string());
+    }
+
+    if codes.is_empty() {
+        part_str.to_string()
+    } else {
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), part_str)
+    }
+}
+
+// Same as the text export, but colored for a terminal; context lines are dimmed.
+pub fn view_to_ansi(
+    processed_lines: &[ProcessedLine],
+    log_comments: &HashMap<usize, String>,
+    options: &ViewExportOptions,
+) -> String {
+    let mut text = String::new();
+
+    for processed_line in processed_lines {
+        if options.include_line_numbers {
+            text.push_str(&format!(
+                "\x1b[2m{}: \x1b[0m",
+                processed_line.original_line_no
+            ));
+        }
+
+        for (part_str, part_format) in &processed_line.parts {
+            if part_str.is_empty() {
+                continue;
+            }
+
+            if processed_line.is_context {
+                text.push_str(&format!("\x1b[2m{}\x1b[0m", part_str));
+            } else {
+                text.push_str(&part_to_ansi(part_str, part_format));
+            }
+        }
+
+        if options.include_comments
+            && let Some(comment_text) = log_comments.get(&processed_line.original_line_no)
+        {
+            text.push_str(&format!("\x1b[3;32m\t// {}\x1b[0m", comment_text));
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+pub fn export_to_file(file_path: &std::path::Path, text: &str) {
+    eprintln!("Trying to export view to: {}", file_path.to_string_lossy());
+
+    let write_result = std::fs::write(file_path, text);
+    if let Err(e) = write_result {
+        eprintln!("Error writing view to file: {}", e);
+        return;
+    }
+
+    eprintln!("View exported successfully.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_content::LogContent;
+    use crate::user_settings::{TokenColor, UserSettings};
+    use crate::{process_log_lines, recalculate_log_job};
+
+    fn processed_lines() -> Vec<ProcessedLine> {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        user_settings.filters[0].term = "error".to_string();
+
+        let content = LogContent::from_string(
+            "info: started\nerror: disk full, disk gone\ninfo: retry\nerror: timeout\n".to_string(),
+        );
+
+        process_log_lines(&content, &user_settings)
+    }
+
+    #[test]
+    fn filtered_view_exported_as_plain_text() {
+        let text = view_to_text(
+            &processed_lines(),
+            &HashMap::new(),
+            &ViewExportOptions::default(),
+        );
+        assert_eq!(text, "error: disk full, disk gone\nerror: timeout\n");
+    }
+
+    #[test]
+    fn view_exported_with_line_numbers_comments_and_markers() {
+        let log_comments = HashMap::from([(4, "too slow".to_string()), (1, "hidden".to_string())]);
+        let options = ViewExportOptions {
+            include_line_numbers: true,
+            include_comments: true,
+            keep_hilight_markers: true,
+        };
+
+        let text = view_to_text(&processed_lines(), &log_comments, &options);
+        assert_eq!(
+            text,
+            "2: error: **disk** full, **disk** gone\n4: error: timeout\t// too slow\n"
+        );
+    }
+
+    #[test]
+    fn search_matches_listed_with_line_numbers() {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        user_settings.filters[0].term = "error".to_string();
+
+        let content = LogContent::from_string(
+            "info: disk ok\nerror: disk full, disk gone\nerror: timeout\nerror: disk\n".to_string(),
+        );
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        assert_eq!(
+            matches_to_text(&log_jobs),
+            "2 matching lines\n2: error: disk full, disk gone\n4: error: disk\n"
+        );
+    }
+
+    #[test]
+    fn colored_tokens_exported_as_spans() {
+        let mut user_settings = UserSettings::default();
+        user_settings.token_colors[0].token = "error".to_string();
+        user_settings.token_colors[0].color = Color::RED;
+        user_settings.token_colors.push(TokenColor {
+            token: "disk".to_string(),
+            color: Color::from_rgb(200, 200, 0),
+            ..TokenColor::new_empty(1)
+        });
+
+        let content = LogContent::from_string("error on disk <1>\n".to_string());
+        let processed_lines = process_log_lines(&content, &user_settings);
+
+        let html = view_to_html(
+            &processed_lines,
+            &HashMap::new(),
+            &ViewExportOptions::default(),
+        );
+
+        let expected_line = "<span style=\"color:#ffffff;background:#ff0000\">error</span>\
+            <span style=\"color:#a0a0a0\"> on </span>\
+            <span style=\"color:#000000;background:#c8c800\">disk</span>\
+            <span style=\"color:#a0a0a0\"> &lt;1&gt;</span>\n";
+        assert!(html.contains(expected_line), "{}", html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn colored_tokens_exported_as_ansi() {
+        let mut user_settings = UserSettings::default();
+        user_settings.token_colors[0].token = "error".to_string();
+        user_settings.token_colors[0].color = Color::RED;
+
+        let content = LogContent::from_string("error on disk\n".to_string());
+        let processed_lines = process_log_lines(&content, &user_settings);
+
+        let text = view_to_ansi(
+            &processed_lines,
+            &HashMap::new(),
+            &ViewExportOptions::default(),
+        );
+        assert_eq!(
+            text,
+            "\x1b[38;2;255;255;255;48;2;255;0;0merror\x1b[0m on disk\n"
+        );
+    }
+
+    #[test]
+    fn context_lines_dimmed_in_ansi() {
+        let mut user_settings = UserSettings {
+            filter_context_before: 1,
+            ..Default::default()
+        };
+        user_settings.filters[0].term = "error".to_string();
+
+        let content = LogContent::from_string("info: started\nerror: disk full\n".to_string());
+        let processed_lines = process_log_lines(&content, &user_settings);
+
+        let text = view_to_ansi(
+            &processed_lines,
+            &HashMap::new(),
+            &ViewExportOptions::default(),
+        );
+        assert_eq!(text, "\x1b[2minfo: started\x1b[0m\nerror: disk full\n");
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::user_settings::UserSettings;
+use crate::{FileLoadError, OpenedFileMetadata, load_file_with_progress};
+
+// Shared between the loading thread and the one waiting for it.
+#[derive(Default)]
+pub struct LoadProgress {
+    bytes_loaded: AtomicU64,
+    total_bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    pub(crate) fn set_total_bytes(&self, total_bytes: u64) {
+        self.total_bytes.store(total_bytes, Ordering::Relaxed);
+    }
+
+    // Returns false once the load is cancelled, the loading should stop then.
+    pub(crate) fn set_bytes_loaded(&self, bytes_loaded: u64) -> bool {
+        self.bytes_loaded.store(bytes_loaded, Ordering::Relaxed);
+
+        !self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // From 0 to 1, 0 until the size of the file is known.
+    pub fn fraction(&self) -> f32 {
+        let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+        if total_bytes == 0 {
+            return 0.0;
+        }
+
+        (self.bytes_loaded.load(Ordering::Relaxed) as f64 / total_bytes as f64).min(1.0) as f32
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+// Runs load_file on a background thread, so a big file doesn't freeze the UI while it's read.
+pub struct FileLoader {
+    pub path: String,
+    pub encoding: String,
+    progress: Arc<LoadProgress>,
+    result_receiver: mpsc::Receiver<Result<OpenedFileMetadata, FileLoadError>>,
+}
+
+impl FileLoader {
+    pub fn start(user_settings: &UserSettings) -> Self {
+        let progress = Arc::new(LoadProgress::default());
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let thread_progress = Arc::clone(&progress);
+        let thread_user_settings = user_settings.clone();
+        thread::spawn(move || {
+            let loaded = load_file_with_progress(&thread_user_settings, &thread_progress);
+            // Nobody listens anymore if the load was cancelled.
+            let _ = result_sender.send(loaded);
+        });
+
+        Self {
+            path: user_settings.file_path.clone(),
+            encoding: user_settings.encoding.clone(),
+            progress,
+            result_receiver,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress.fraction()
+    }
+
+    pub fn poll_result(&self) -> Option<Result<OpenedFileMetadata, FileLoadError>> {
+        self.result_receiver.try_recv().ok()
+    }
+}
+
+// Dropping the loader cancels the load, the thread stops at the next chunk it reads.
+impl Drop for FileLoader {
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait_for_result(file_loader: &FileLoader) -> Result<OpenedFileMetadata, FileLoadError> {
+        for _ in 0..500 {
+            if let Some(loaded) = file_loader.poll_result() {
+                return loaded;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("No file load result received");
+    }
+
+    #[test]
+    fn file_loaded_in_background() {
+        let path = std::env::temp_dir().join("logalyzer_file_loaded_in_background.log");
+        std::fs::write(&path, "first\nsecond\n".repeat(1000)).unwrap();
+        let user_settings = UserSettings {
+            file_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let file_loader = FileLoader::start(&user_settings);
+        let opened_file = wait_for_result(&file_loader).unwrap();
+        assert_eq!(opened_file.content.line_count(), 2000);
+        assert_eq!(file_loader.progress(), 1.0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cancelled_load_stops() {
+        let progress = LoadProgress::default();
+        assert_eq!(progress.fraction(), 0.0);
+        progress.set_total_bytes(200);
+        assert!(progress.set_bytes_loaded(50));
+        assert_eq!(progress.fraction(), 0.25);
+
+        progress.cancel();
+        assert!(!progress.set_bytes_loaded(100));
+
+        let path = std::env::temp_dir().join("logalyzer_cancelled_load_stops.log");
+        std::fs::write(&path, "line\n").unwrap();
+        let user_settings = UserSettings {
+            file_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let cancelled = load_file_with_progress(&user_settings, &progress);
+        assert_eq!(
+            cancelled.err().map(|e| e.kind()),
+            Some(std::io::ErrorKind::Interrupted)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
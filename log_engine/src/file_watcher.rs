@@ -0,0 +1,162 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// A file being rewritten changes several times in a row, it's reported once it settles down.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Size and modification time, None while the file is missing (i.e. deleted before being rewritten).
+type FileStamp = Option<(u64, Option<SystemTime>)>;
+
+fn file_stamp(path: &str) -> FileStamp {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+struct ChangeDebouncer {
+    last_stamp: FileStamp,
+    changed_at: Option<Instant>,
+}
+
+impl ChangeDebouncer {
+    fn new(stamp: FileStamp) -> Self {
+        Self {
+            last_stamp: stamp,
+            changed_at: None,
+        }
+    }
+
+    // True once a change was seen and the file stayed the same for the debounce time since.
+    fn update(&mut self, stamp: FileStamp, now: Instant) -> bool {
+        if stamp != self.last_stamp {
+            self.last_stamp = stamp;
+            self.changed_at = Some(now);
+            return false;
+        }
+
+        let settled = self
+            .changed_at
+            .is_some_and(|changed_at| now.duration_since(changed_at) >= WATCH_DEBOUNCE);
+
+        // A missing file can't be reloaded, wait until it's back.
+        if settled && self.last_stamp.is_some() {
+            self.changed_at = None;
+            return true;
+        }
+
+        false
+    }
+}
+
+// Watches the file on a background thread for being modified (i.e. rewritten as a whole by some
+// tool), unlike LineStream which only picks up the appended lines.
+pub struct FileWatcher {
+    pub path: String,
+    receiver: mpsc::Receiver<()>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl FileWatcher {
+    pub fn watch(path: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread_path = path.to_string();
+        let thread_stop_requested = stop_requested.clone();
+
+        thread::spawn(move || {
+            let mut debouncer = ChangeDebouncer::new(file_stamp(&thread_path));
+
+            while !thread_stop_requested.load(Ordering::Relaxed) {
+                thread::sleep(WATCH_POLL_INTERVAL);
+
+                if debouncer.update(file_stamp(&thread_path), Instant::now())
+                    && sender.send(()).is_err()
+                {
+                    // Nobody listens anymore.
+                    return;
+                }
+            }
+        });
+
+        Self {
+            path: path.to_string(),
+            receiver,
+            stop_requested,
+        }
+    }
+
+    // True if the file changed since the last call, never blocks.
+    pub fn poll_changed(&self) -> bool {
+        self.receiver.try_iter().count() > 0
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop_requested.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapid_changes_reported_once_settled() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let stamp = |len| Some((len, None));
+
+        let mut debouncer = ChangeDebouncer::new(stamp(10));
+        assert!(!debouncer.update(stamp(10), at(250)));
+
+        // Still being written.
+        assert!(!debouncer.update(stamp(20), at(500)));
+        assert!(!debouncer.update(stamp(30), at(750)));
+        assert!(!debouncer.update(stamp(30), at(1000)));
+
+        assert!(debouncer.update(stamp(30), at(1250)));
+        assert!(!debouncer.update(stamp(30), at(2000)));
+    }
+
+    #[test]
+    fn missing_file_waited_for() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut debouncer = ChangeDebouncer::new(Some((10, None)));
+        assert!(!debouncer.update(None, at(250)));
+        assert!(!debouncer.update(None, at(1000)));
+
+        assert!(!debouncer.update(Some((5, None)), at(1250)));
+        assert!(debouncer.update(Some((5, None)), at(1750)));
+    }
+
+    #[test]
+    fn rewritten_file_reported() {
+        let path = std::env::temp_dir().join("logalyzer_rewritten_file_reported.log");
+        std::fs::write(&path, "first\n").unwrap();
+
+        let file_watcher = FileWatcher::watch(&path.to_string_lossy());
+        thread::sleep(WATCH_POLL_INTERVAL);
+        std::fs::write(&path, "rewritten\nwith more lines\n").unwrap();
+
+        let mut changed = false;
+        for _ in 0..50 {
+            changed = file_watcher.poll_changed();
+            if changed {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(changed);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
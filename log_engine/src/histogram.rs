@@ -0,0 +1,405 @@
+use regex::{Regex, RegexBuilder};
+use std::sync::LazyLock;
+
+use crate::log_content::LogContent;
+
+// i.e. "2024-01-31 12:34:56.789" or "2024-01-31T12:34:56"
+static DATE_TIME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2}(?:[.,]\d+)?)").unwrap()
+});
+
+// i.e. "12:34:56.789"
+static TIME_OF_DAY_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d{2}):(\d{2}):(\d{2}(?:[.,]\d+)?)").unwrap());
+
+// Leading timestamp in any of the forms parse_timestamp() understands, optionally in brackets,
+// i.e. "2024-01-31 12:34:56.789", "12:34:56" or "[  12.345678]".
+pub const LEADING_TIMESTAMP_PATTERN: &str = r"^\[?\s*(?:\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?|\d{2}:\d{2}:\d{2}(?:[.,]\d+)?|\d+\.\d+)\]?";
+
+const SECONDS_IN_DAY: f64 = 86400.0;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TimestampKind {
+    Seconds,   // i.e. kernel log "[12.345678]", seconds since boot
+    TimeOfDay, // seconds since midnight
+    DateTime,  // seconds since unix epoch, timezone is ignored
+}
+
+// Days since 1970-01-01 for given date in the proleptic gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+// Inverse of days_from_civil, returns (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn parse_seconds_fraction(text: &str) -> Option<f64> {
+    text.replace(',', ".").parse::<f64>().ok()
+}
+
+// Parses the timestamp to seconds, the kind tells what the seconds are relative to.
+pub fn parse_timestamp(text: &str) -> Option<(f64, TimestampKind)> {
+    let text = text
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim();
+
+    if let Some(captures) = DATE_TIME_REGEX.captures(text) {
+        let number = |index: usize| captures[index].parse::<i64>().ok();
+
+        let days = days_from_civil(number(1)?, number(2)?, number(3)?);
+        let seconds = parse_seconds_fraction(&captures[6])?;
+        let seconds_in_day = (number(4)? * 3600 + number(5)? * 60) as f64 + seconds;
+
+        return Some((
+            days as f64 * SECONDS_IN_DAY + seconds_in_day,
+            TimestampKind::DateTime,
+        ));
+    }
+
+    if let Some(captures) = TIME_OF_DAY_REGEX.captures(text) {
+        let number = |index: usize| captures[index].parse::<i64>().ok();
+
+        let seconds = parse_seconds_fraction(&captures[3])?;
+        return Some((
+            (number(1)? * 3600 + number(2)? * 60) as f64 + seconds,
+            TimestampKind::TimeOfDay,
+        ));
+    }
+
+    let seconds = text.parse::<f64>().ok()?;
+    seconds
+        .is_finite()
+        .then_some((seconds, TimestampKind::Seconds))
+}
+
+pub fn format_timestamp(seconds: f64, kind: TimestampKind) -> String {
+    let time_of_day = |seconds_in_day: i64| {
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds_in_day / 3600,
+            seconds_in_day % 3600 / 60,
+            seconds_in_day % 60
+        )
+    };
+
+    match kind {
+        TimestampKind::Seconds => format!("{:.3}", seconds),
+        TimestampKind::TimeOfDay => time_of_day(seconds.floor() as i64),
+        TimestampKind::DateTime => {
+            let seconds = seconds.floor() as i64;
+            let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+            format!(
+                "{:04}-{:02}-{:02} {}",
+                year,
+                month,
+                day,
+                time_of_day(seconds.rem_euclid(86400))
+            )
+        }
+    }
+}
+
+// The lines the histogram counts. A plain term is matched as an escaped regex, so the case is
+// folded once for the term instead of lowercasing every line, and by characters (i.e. "ß" and "ẞ",
+// "σ" and "ς"), not by what str::to_lowercase happens to produce.
+pub fn histogram_line_regex(term: &str, match_case: bool, regex: bool) -> Result<Regex, String> {
+    let pattern = if regex {
+        term.to_string()
+    } else {
+        regex::escape(term)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!match_case)
+        .build()
+        .map_err(|_| "Invalid regex".to_string())
+}
+
+#[derive(PartialEq, Debug)]
+pub struct LineBucket {
+    pub first_line_no: usize,
+    pub last_line_no: usize,
+    pub count: usize,
+}
+
+// The lines in range (0-based line indices, clamped to the content) split into equal buckets, the
+// last one takes the remainder. The buckets have the original line numbers.
+pub fn line_histogram(
+    content: &LogContent,
+    line_range: std::ops::Range<usize>,
+    line_matches: impl Fn(&str) -> bool,
+    number_of_buckets: usize,
+) -> Vec<LineBucket> {
+    let line_range_end = line_range.end.min(content.line_count());
+    let line_range_start = line_range.start.min(line_range_end);
+    let bucket_size = (line_range_end - line_range_start) / number_of_buckets.max(1);
+
+    (0..number_of_buckets)
+        .map(|bucket_index| {
+            let bucket_start = line_range_start + bucket_index * bucket_size;
+            let bucket_end = if bucket_index == number_of_buckets - 1 {
+                line_range_end
+            } else {
+                bucket_start + bucket_size
+            };
+
+            LineBucket {
+                first_line_no: content.dropped_line_count() + bucket_start + 1,
+                last_line_no: content.dropped_line_count() + bucket_end,
+                count: content
+                    .lines_from(bucket_start)
+                    .take(bucket_end - bucket_start)
+                    .filter(|line| line_matches(line))
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TimeBucket {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+// Takes the first capture group of the pattern as the timestamp, or the whole match if there's none.
+pub(crate) fn line_timestamp(timestamp_regex: &Regex, line: &str) -> Option<(f64, TimestampKind)> {
+    let captures = timestamp_regex.captures(line)?;
+    let timestamp_match = captures.get(1).or_else(|| captures.get(0))?;
+
+    parse_timestamp(timestamp_match.as_str())
+}
+
+// Counts the matching lines in equal time intervals between the first and the last timestamp in
+// the line range. Lines without a timestamp (i.e. continuation lines) belong to the previous
+// bucket. Returns None if no line has a timestamp.
+pub fn time_histogram(
+    content: &LogContent,
+    line_range: std::ops::Range<usize>,
+    timestamp_regex: &Regex,
+    line_matches: impl Fn(&str) -> bool,
+    number_of_buckets: usize,
+) -> Option<(Vec<TimeBucket>, TimestampKind)> {
+    let mut time_min = f64::MAX;
+    let mut time_max = f64::MIN;
+    let mut timestamp_kind = None;
+    let lines_in_range = || {
+        content
+            .lines_from(line_range.start)
+            .take(line_range.end.saturating_sub(line_range.start))
+    };
+
+    for line in lines_in_range() {
+        if let Some((seconds, kind)) = line_timestamp(timestamp_regex, &line) {
+            time_min = time_min.min(seconds);
+            time_max = time_max.max(seconds);
+            timestamp_kind.get_or_insert(kind);
+        }
+    }
+
+    let timestamp_kind = timestamp_kind?;
+    let number_of_buckets = number_of_buckets.max(1);
+    let bucket_size = (time_max - time_min) / number_of_buckets as f64;
+
+    let mut buckets: Vec<TimeBucket> = (0..number_of_buckets)
+        .map(|bucket_index| TimeBucket {
+            start: time_min + bucket_index as f64 * bucket_size,
+            end: time_min + (bucket_index + 1) as f64 * bucket_size,
+            count: 0,
+        })
+        .collect();
+
+    let mut bucket_index = 0;
+    for line in lines_in_range() {
+        if let Some((seconds, _)) = line_timestamp(timestamp_regex, &line)
+            && bucket_size > 0.0
+        {
+            bucket_index =
+                (((seconds - time_min) / bucket_size).floor() as usize).min(number_of_buckets - 1);
+        }
+
+        if line_matches(&line) {
+            buckets[bucket_index].count += 1;
+        }
+    }
+
+    Some((buckets, timestamp_kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_parsed() {
+        assert_eq!(
+            parse_timestamp("[  12.500000]"),
+            Some((12.5, TimestampKind::Seconds))
+        );
+        assert_eq!(
+            parse_timestamp("12:01:02,5"),
+            Some((43262.5, TimestampKind::TimeOfDay))
+        );
+        assert_eq!(
+            parse_timestamp("1970-01-02 00:00:01"),
+            Some((86401.0, TimestampKind::DateTime))
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T13:14:15.25"),
+            Some((1709212455.25, TimestampKind::DateTime))
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(parse_timestamp("inf"), None);
+    }
+
+    #[test]
+    fn timestamps_formatted() {
+        assert_eq!(
+            format_timestamp(1709212455.25, TimestampKind::DateTime),
+            "2024-02-29 13:14:15"
+        );
+        assert_eq!(
+            format_timestamp(43262.5, TimestampKind::TimeOfDay),
+            "12:01:02"
+        );
+        assert_eq!(format_timestamp(12.5, TimestampKind::Seconds), "12.500");
+    }
+
+    #[test]
+    fn civil_days_round_trip() {
+        for days in [-719468, -1, 0, 1, 11016, 19782, 2932896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn matches_bucketed_by_time() {
+        let content = LogContent::from_string(
+            "[0.0] error\n\
+            [1.0] info\n\
+            continued error\n\
+            [5.0] error\n\
+            [9.0] info\n\
+            [10.0] error\n"
+                .to_string(),
+        );
+        let timestamp_regex = Regex::new(r"^\[([0-9.]+)\]").unwrap();
+
+        let (buckets, kind) = time_histogram(
+            &content,
+            0..usize::MAX,
+            &timestamp_regex,
+            |line| line.contains("error"),
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(kind, TimestampKind::Seconds);
+        assert_eq!(
+            buckets,
+            vec![
+                TimeBucket {
+                    start: 0.0,
+                    end: 5.0,
+                    count: 2
+                },
+                TimeBucket {
+                    start: 5.0,
+                    end: 10.0,
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_timestamps_no_histogram() {
+        let content = LogContent::from_string("error\ninfo\n".to_string());
+        let timestamp_regex = Regex::new(r"^\[([0-9.]+)\]").unwrap();
+
+        assert!(time_histogram(&content, 0..usize::MAX, &timestamp_regex, |_| true, 10).is_none());
+    }
+
+    #[test]
+    fn histogram_lines_matched_by_characters() {
+        let line_regex = histogram_line_regex("érror σ", false, false).unwrap();
+        assert!(line_regex.is_match("ÉRROR Σ"));
+        assert!(line_regex.is_match("érror ς"));
+        assert!(!line_regex.is_match("error σ"));
+        assert!(
+            histogram_line_regex("straße", false, false)
+                .unwrap()
+                .is_match("STRAẞE")
+        );
+
+        // Nothing special about the regex characters in a plain term.
+        let line_regex = histogram_line_regex("a.b", true, false).unwrap();
+        assert!(line_regex.is_match("a.b"));
+        assert!(!line_regex.is_match("A.b"));
+        assert!(!line_regex.is_match("axb"));
+
+        assert!(
+            histogram_line_regex("a.b", true, true)
+                .unwrap()
+                .is_match("axb")
+        );
+        assert!(histogram_line_regex("(", false, true).is_err());
+    }
+
+    #[test]
+    fn histogram_limited_to_line_range() {
+        let content = LogContent::from_string("error\n".repeat(20));
+        let counts = |buckets: Vec<LineBucket>| -> Vec<(usize, usize, usize)> {
+            buckets
+                .iter()
+                .map(|bucket| (bucket.first_line_no, bucket.last_line_no, bucket.count))
+                .collect()
+        };
+
+        assert_eq!(
+            counts(line_histogram(&content, 0..usize::MAX, |_| true, 3)),
+            vec![(1, 6, 6), (7, 12, 6), (13, 20, 8)]
+        );
+        // Lines 10-17.
+        assert_eq!(
+            counts(line_histogram(&content, 9..17, |_| true, 3)),
+            vec![(10, 11, 2), (12, 13, 2), (14, 17, 4)]
+        );
+
+        let content = LogContent::from_string(
+            "[0.0] error\n[1.0] error\n[2.0] error\n[3.0] error\n[9.0] error\n".to_string(),
+        );
+        let timestamp_regex = Regex::new(r"^\[([0-9.]+)\]").unwrap();
+        let (buckets, _) = time_histogram(&content, 1..4, &timestamp_regex, |_| true, 2).unwrap();
+        assert_eq!((buckets[0].start, buckets[1].end), (1.0, 3.0));
+        assert_eq!(buckets[0].count + buckets[1].count, 3);
+    }
+}
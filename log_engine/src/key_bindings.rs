@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ShortcutAction {
+    FocusSearch,
+    FocusFilter,
+    FocusGoToLine,
+    ToggleTokens,
+    ToggleHistogram,
+    ToggleSearchMatchCase,
+    ToggleSearchWholeWord,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+}
+
+impl ShortcutAction {
+    pub const ALL: [ShortcutAction; 11] = [
+        ShortcutAction::FocusSearch,
+        ShortcutAction::FocusFilter,
+        ShortcutAction::FocusGoToLine,
+        ShortcutAction::ToggleTokens,
+        ShortcutAction::ToggleHistogram,
+        ShortcutAction::ToggleSearchMatchCase,
+        ShortcutAction::ToggleSearchWholeWord,
+        ShortcutAction::ScrollUp,
+        ShortcutAction::ScrollDown,
+        ShortcutAction::ScrollLeft,
+        ShortcutAction::ScrollRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShortcutAction::FocusSearch => "Focus search",
+            ShortcutAction::FocusFilter => "Focus filter",
+            ShortcutAction::FocusGoToLine => "Focus go to line",
+            ShortcutAction::ToggleTokens => "Tokens panel",
+            ShortcutAction::ToggleHistogram => "Histogram window",
+            ShortcutAction::ToggleSearchMatchCase => "Search match case",
+            ShortcutAction::ToggleSearchWholeWord => "Search whole word",
+            ShortcutAction::ScrollUp => "Scroll up",
+            ShortcutAction::ScrollDown => "Scroll down",
+            ShortcutAction::ScrollLeft => "Scroll left",
+            ShortcutAction::ScrollRight => "Scroll right",
+        }
+    }
+}
+
+// The key is the egui key name (i.e. "F", "PageDown"), the modifiers have to match exactly.
+#[derive(PartialEq, Eq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub key: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl KeyBinding {
+    fn plain(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ctrl(key: &str) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::plain(key)
+        }
+    }
+
+    fn alt(key: &str) -> Self {
+        Self {
+            alt: true,
+            ..Self::plain(key)
+        }
+    }
+
+    // As shown to the user, i.e. "Ctrl+F".
+    pub fn label(&self) -> String {
+        if self.key.is_empty() {
+            return "None".to_string();
+        }
+
+        let mut label = String::new();
+        for (pressed, modifier) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+        ] {
+            if pressed {
+                label.push_str(modifier);
+            }
+        }
+        label.push_str(&self.key);
+
+        label
+    }
+}
+
+pub type KeyBindings = BTreeMap<ShortcutAction, KeyBinding>;
+
+pub fn default_key_bindings() -> KeyBindings {
+    BTreeMap::from([
+        (ShortcutAction::FocusSearch, KeyBinding::ctrl("F")),
+        (ShortcutAction::FocusFilter, KeyBinding::ctrl("I")),
+        (ShortcutAction::FocusGoToLine, KeyBinding::ctrl("L")),
+        (ShortcutAction::ToggleTokens, KeyBinding::ctrl("T")),
+        (ShortcutAction::ToggleHistogram, KeyBinding::ctrl("H")),
+        (ShortcutAction::ToggleSearchMatchCase, KeyBinding::alt("C")),
+        (ShortcutAction::ToggleSearchWholeWord, KeyBinding::alt("W")),
+        (ShortcutAction::ScrollUp, KeyBinding::plain("W")),
+        (ShortcutAction::ScrollDown, KeyBinding::plain("S")),
+        (ShortcutAction::ScrollLeft, KeyBinding::plain("A")),
+        (ShortcutAction::ScrollRight, KeyBinding::plain("D")),
+    ])
+}
+
+// Actions bound to the same keys as an earlier action, the keys would run both.
+pub fn conflicting_actions(key_bindings: &KeyBindings) -> Vec<ShortcutAction> {
+    let bound: Vec<(&ShortcutAction, &KeyBinding)> = key_bindings
+        .iter()
+        .filter(|(_, key_binding)| !key_binding.key.is_empty())
+        .collect();
+
+    bound
+        .iter()
+        .enumerate()
+        .filter(|(i, (_, key_binding))| {
+            bound[..*i]
+                .iter()
+                .any(|(_, earlier_binding)| earlier_binding == key_binding)
+        })
+        .map(|(_, (action, _))| **action)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_action_bound_by_default() {
+        let key_bindings = default_key_bindings();
+        assert_eq!(key_bindings.len(), ShortcutAction::ALL.len());
+        assert!(conflicting_actions(&key_bindings).is_empty());
+
+        assert_eq!(key_bindings[&ShortcutAction::FocusSearch].label(), "Ctrl+F");
+        assert_eq!(
+            key_bindings[&ShortcutAction::ToggleSearchWholeWord].label(),
+            "Alt+W"
+        );
+        assert_eq!(KeyBinding::default().label(), "None");
+    }
+
+    #[test]
+    fn conflicting_bindings_found() {
+        let mut key_bindings = default_key_bindings();
+        key_bindings.insert(ShortcutAction::ScrollDown, KeyBinding::plain("W"));
+        key_bindings.insert(ShortcutAction::ScrollLeft, KeyBinding::default());
+        key_bindings.insert(ShortcutAction::ScrollRight, KeyBinding::default());
+
+        assert_eq!(
+            conflicting_actions(&key_bindings),
+            vec![ShortcutAction::ScrollDown]
+        );
+    }
+}
@@ -1,56 +1,128 @@
-use egui::{
-    FontId,
-    text::{LayoutJob, TextFormat},
-};
+use crate::style::{Color, Style};
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 
+use rayon::prelude::*;
+
+pub mod ansi;
+pub mod columns;
+pub mod compression;
+mod duplicates;
+pub mod encoding;
+pub mod export;
+pub mod file_loader;
+pub mod file_watcher;
+pub mod histogram;
+pub mod key_bindings;
 pub mod line_handlers;
+pub mod line_stream;
 mod linevec;
+pub mod links;
+pub mod log_content;
+pub mod log_format_presets;
+pub mod patterns;
+pub mod recalc_worker;
+mod regex_cache;
+pub mod stats;
+pub mod style;
+pub mod token_list;
 pub mod user_settings;
+pub mod whitespace;
+#[cfg(feature = "egui")]
+pub mod wrap_cache;
 
+use crate::compression::*;
+use crate::duplicates::*;
+use crate::file_loader::LoadProgress;
+use crate::key_bindings::{KeyBindings, ShortcutAction};
 use crate::line_handlers::*;
+use crate::line_stream::*;
 use crate::linevec::*;
+use crate::log_content::*;
 use crate::user_settings::*;
 
+pub use crate::linevec::{LineVec, linevec_text};
+
 #[derive(Clone)]
 pub struct PointOfInterest {
     pub line: usize,
     pub split_point: SplitPoint,
+    pub byte_range: std::ops::Range<usize>, // in the whole line text, stays valid after the split
 }
 
 // TODO: this should be a trait and have multiple impls, depending on what really is opened
 // (a file, multiple files concantenated into one, a live log stream, etc)
+#[derive(Default)]
 pub struct OpenedFileMetadata {
     pub path: String,
-    pub content: String,
-    pub content_max_line_chars: usize,
-    pub content_line_count: usize,
+    pub content: LogContent,
     pub log_comments: HashMap<usize, String>,
+    pub is_stream: bool, // Content comes from a stream (or merged files), there is no file to reload.
+    pub compression: CompressionKind,
+    pub encoding: String,
+    // Raw file bytes up to and including the last newline, content was decoded so its length differs.
+    pub committed_bytes: u64,
+    pub file_size: u64,            // on disk (i.e. compressed), 0 for a stream
+    pub source_paths: Vec<String>, // of the merged files, empty unless merged
+    pub line_sources: Vec<usize>,  // by original line index, index into source_paths
 }
 
-impl Default for OpenedFileMetadata {
-    fn default() -> Self {
-        Self {
-            path: String::new(),
-            content: String::new(),
-            content_max_line_chars: 0,
-            content_line_count: 0,
-            log_comments: HashMap::new(),
+impl OpenedFileMetadata {
+    // Starts following the file for newly written lines. A partial last line (not terminated
+    // with a newline yet) is removed from the content, the stream delivers it again once complete.
+    pub fn follow(&mut self) -> LineStream {
+        self.content.drop_partial_last_line();
+
+        LineStream::follow_file(&self.path, self.committed_bytes, &self.encoding)
+    }
+
+    // Keeps at most max_lines (0 is no limit), the oldest lines are dropped with their comments.
+    // A tenth more than needed is dropped, as the lines are processed again after each drop.
+    // Returns how many lines were dropped.
+    pub fn limit_lines(&mut self, max_lines: usize) -> usize {
+        let line_count = self.content.line_count();
+        if max_lines == 0 || line_count <= max_lines {
+            return 0;
+        }
+
+        let drop_count = line_count - max_lines + max_lines / 10;
+        self.content.drop_first_lines(drop_count);
+
+        let first_line_no = self.content.dropped_line_count() + 1;
+        self.log_comments
+            .retain(|line_no, _| *line_no >= first_line_no);
+
+        drop_count
+    }
+
+    // Comments are keyed by original line numbers, so appending keeps them attached to their lines.
+    pub fn append_lines(&mut self, lines: &[String]) {
+        self.content.append_lines(lines);
+
+        // The followed file grew.
+        if !self.is_stream
+            && let Ok(metadata) = std::fs::metadata(&self.path)
+        {
+            self.file_size = metadata.len();
         }
     }
 }
 
+// How many original lines were hidden (filtered out, folded, collapsed as duplicates) up to each
+// visible line, built only by recalculate_log_job() while it pushes the lines. Line numbers here
+// are 1-based, only the offset changes are stored, so nothing filtered means no offsets at all.
 #[derive(Default)]
 pub struct VisibleLineOffsets {
     // (visible_line_no, offset)
-    pub offsets: Vec<(usize, usize)>, // must be sorted
+    offsets: Vec<(usize, usize)>, // must be sorted
     last_offset: usize,
 }
 
 impl VisibleLineOffsets {
-    pub fn add_offset(&mut self, original_line_no: usize, visible_line_no: usize) {
+    // The lines have to be added in order, original_line_no >= visible_line_no.
+    pub(crate) fn add_offset(&mut self, original_line_no: usize, visible_line_no: usize) {
         let offset = original_line_no - visible_line_no;
 
         if offset == self.last_offset {
@@ -62,6 +134,8 @@ impl VisibleLineOffsets {
             .push((visible_line_no, original_line_no - visible_line_no));
     }
 
+    // The original line is visible_line_no + offset, for any visible line of the jobs the offsets
+    // were built with. Past the last visible line it's the offset of the last one.
     pub fn get_offset_for_visible_line(&self, visible_line_no: usize) -> usize {
         if self.offsets.is_empty() {
             return 0;
@@ -79,246 +153,3341 @@ impl VisibleLineOffsets {
                 }
             })
     }
-}
 
-pub fn default_log_content() -> LayoutJob {
-    let mut job = LayoutJob::default();
+    // The row index is of the logical visible line (as in LogJobs::log_lines), not of the rendered
+    // rows, a wrapped line is still one row here.
+    pub fn get_original_line_for_row(&self, row_index: usize) -> usize {
+        row_index + 1 + self.get_offset_for_visible_line(row_index + 1)
+    }
+
+    // Maps the original line number back to the visible one, None if the line is filtered out.
+    pub fn get_visible_line_for_original_line(
+        &self,
+        original_line_no: usize,
+        visible_line_count: usize,
+    ) -> Option<usize> {
+        // Original line numbers grow with visible ones, so binary search the visible lines.
+        let mut low = 1;
+        let mut high = visible_line_count;
+
+        while low <= high {
+            let visible_line_no = low + (high - low) / 2;
+            let visible_original_line_no =
+                visible_line_no + self.get_offset_for_visible_line(visible_line_no);
+
+            match visible_original_line_no.cmp(&original_line_no) {
+                std::cmp::Ordering::Equal => return Some(visible_line_no),
+                std::cmp::Ordering::Less => low = visible_line_no + 1,
+                std::cmp::Ordering::Greater => high = visible_line_no - 1,
+            }
+        }
+
+        None
+    }
+
+    // The first visible line at or after the original one, so a filtered out or out of range
+    // line still lands somewhere close. None only if nothing is visible.
+    pub fn get_nearest_visible_line_for_original_line(
+        &self,
+        original_line_no: usize,
+        visible_line_count: usize,
+    ) -> Option<usize> {
+        if visible_line_count == 0 {
+            return None;
+        }
+
+        let mut low = 1;
+        let mut high = visible_line_count;
+
+        while low < high {
+            let visible_line_no = low + (high - low) / 2;
+            let visible_original_line_no =
+                visible_line_no + self.get_offset_for_visible_line(visible_line_no);
+
+            if visible_original_line_no < original_line_no {
+                low = visible_line_no + 1;
+            } else {
+                high = visible_line_no;
+            }
+        }
+
+        Some(low)
+    }
+}
 
-    let welcome_message = format!(
+// The shortcuts are listed as they're bound, unbound ones are left out.
+pub fn default_log_content(key_bindings: &KeyBindings) -> LineVec {
+    let mut welcome_message = format!(
         "Welcome to Logalyzer ({}).\n\n\
     Please select a log file to open.\n\
     Please use the settings panel to configure log formatting and highlighting options.\n\n\
     Click on a line number to add comments to log lines.\n\
     The comments can be deleted by clicking on the \"c\" next to the comment.\n\n\
-    You can use WASD to navigate through the log file.\n\n\
-    Keyboard shortcuts:\n\
-    Ctrl + F: search\n\
-    Ctrl + I: filter\n\
-    Ctrl + T: toggle token colors panel\n\
-    Ctrl + H: toggle histogram window\n\n",
+    Keyboard shortcuts:\n",
         env!("CARGO_PKG_VERSION")
     );
+    for action in ShortcutAction::ALL {
+        if let Some(key_binding) = key_bindings.get(&action)
+            && !key_binding.key.is_empty()
+        {
+            welcome_message.push_str(&format!("{}: {}\n", key_binding.label(), action.name()));
+        }
+    }
+    welcome_message.push('\n');
 
-    job.append(
-        welcome_message.as_str(),
-        0.0,
-        TextFormat {
-            font_id: FontId::monospace(12.0),
+    vec![(
+        welcome_message,
+        Style {
+            font_size: 12.0,
             ..Default::default()
         },
-    );
+    )]
+}
+
+pub fn default_log_jobs(key_bindings: &KeyBindings) -> LogJobs {
+    let welcome_line = default_log_content(key_bindings);
+
+    LogJobs {
+        collapsed_counts: vec![1],
+        row_lengths: vec![linevec_text(&welcome_line).len()],
+        welcome_line: Some(welcome_line),
+        ..Default::default()
+    }
+}
+
+// Files at least this big are memory-mapped instead of being read into memory.
+const MMAP_MIN_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+// Why a log file couldn't be opened, shown to the user as is.
+#[derive(Debug)]
+pub struct FileLoadError {
+    pub path: String,
+    pub error: std::io::Error,
+}
+
+impl FileLoadError {
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.error.kind()
+    }
+}
+
+impl std::fmt::Display for FileLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind() {
+            std::io::ErrorKind::NotFound => "file not found".to_string(),
+            std::io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+            std::io::ErrorKind::IsADirectory => "is a directory".to_string(),
+            _ => self.error.to_string(),
+        };
+
+        write!(f, "Could not open {}: {}", self.path, reason)
+    }
+}
+
+impl Error for FileLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
 
-    job
+pub fn load_file(user_settings: &UserSettings) -> Result<OpenedFileMetadata, FileLoadError> {
+    load_file_with_progress(user_settings, &LoadProgress::default())
 }
 
-pub fn load_file(user_settings: &UserSettings) -> Option<OpenedFileMetadata> {
+// Stops with an Interrupted error once the load is cancelled.
+pub fn load_file_with_progress(
+    user_settings: &UserSettings,
+    progress: &LoadProgress,
+) -> Result<OpenedFileMetadata, FileLoadError> {
     let path = user_settings.file_path.clone();
-    println!("Loading file: {}", path);
+    eprintln!("Loading file: {}", path);
 
-    let read_result = std::fs::read_to_string(&path);
-    if read_result.is_err() {
-        println!(
-            "Failed to read file: {}, error: {}",
-            path,
-            read_result.err().unwrap()
-        );
-        return None;
+    let file_load_error = |error| FileLoadError {
+        path: path.clone(),
+        error,
+    };
+
+    let file_bytes = read_file(&path, progress).map_err(file_load_error)?;
+    let compression = CompressionKind::detect(&file_bytes);
+    let file_size = file_bytes.len() as u64;
+
+    let committed_bytes = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map(|pos| pos + 1)
+            .unwrap_or(0) as u64
+    };
+
+    let (content, file_committed_bytes) = match file_bytes {
+        FileBytes::Mapped(mmap) if compression == CompressionKind::None => {
+            let file_committed_bytes = committed_bytes(&mmap);
+            let content = LogContent::from_mmap_with_progress(
+                mmap,
+                &user_settings.encoding,
+                |bytes_indexed| progress.set_bytes_loaded(bytes_indexed as u64),
+            )
+            .ok_or_else(|| file_load_error(load_cancelled_error()))?;
+            (content, file_committed_bytes)
+        }
+        file_bytes => {
+            let file_bytes =
+                decompress(file_bytes.into_vec(), compression).map_err(file_load_error)?;
+            let file_content = encoding::decode(&file_bytes, &user_settings.encoding).into_owned();
+            (
+                LogContent::from_string(file_content),
+                committed_bytes(&file_bytes),
+            )
+        }
+    };
+
+    let mut opened_file_meta = OpenedFileMetadata {
+        path: path.clone(),
+        content,
+        compression,
+        encoding: user_settings.encoding.clone(),
+        committed_bytes: file_committed_bytes,
+        file_size,
+        ..Default::default()
+    };
+
+    // Bring back the comments from the last session, if there were any.
+    let comments_path = comments_sidecar_path(&path);
+    if comments_path.exists()
+        && let Ok(log_comments) = comments_load(&comments_path)
+    {
+        opened_file_meta.log_comments = log_comments;
     }
 
-    let file_content = read_result.unwrap();
-    let file_content_max_line_chars = file_content
-        .lines()
-        .map(|line| line.len())
-        .max()
-        .unwrap_or(0);
-    let file_content_line_count = file_content.lines().count();
+    Ok(opened_file_meta)
+}
+
+enum FileBytes {
+    Read(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
 
-    let mut opened_file_meta = OpenedFileMetadata::default();
-    opened_file_meta.path = path.clone();
-    opened_file_meta.content = file_content;
-    opened_file_meta.content_max_line_chars = file_content_max_line_chars;
-    opened_file_meta.content_line_count = file_content_line_count;
+impl std::ops::Deref for FileBytes {
+    type Target = [u8];
 
-    Some(opened_file_meta)
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            FileBytes::Mapped(mmap) => mmap,
+        }
+    }
 }
 
-fn make_line_handlers(user_settings: &UserSettings) -> Vec<Box<dyn LineHandler>> {
+impl FileBytes {
+    fn into_vec(self) -> Vec<u8> {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            FileBytes::Mapped(mmap) => mmap.to_vec(),
+        }
+    }
+}
+
+fn load_cancelled_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "loading cancelled")
+}
+
+// The smaller files are read in chunks, so the progress moves. The mapped ones report it while
+// their lines are indexed, that's what takes the time.
+fn read_file(path: &str, progress: &LoadProgress) -> std::io::Result<FileBytes> {
+    const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+    let mut file = std::fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+    progress.set_total_bytes(file_size);
+
+    if file_size < MMAP_MIN_FILE_SIZE {
+        let mut file_bytes = Vec::with_capacity(file_size as usize);
+        let mut read_chunk = vec![0; READ_CHUNK_SIZE];
+        loop {
+            let read_len = match std::io::Read::read(&mut file, &mut read_chunk) {
+                Ok(0) => break,
+                Ok(read_len) => read_len,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            file_bytes.extend_from_slice(&read_chunk[..read_len]);
+
+            if !progress.set_bytes_loaded(file_bytes.len() as u64) {
+                return Err(load_cancelled_error());
+            }
+        }
+
+        return Ok(FileBytes::Read(file_bytes));
+    }
+
+    // Safety: the mapping is read-only, but if another process truncates the file while it's
+    // mapped, reading the missing part crashes. Log files are expected to only grow.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+    Ok(FileBytes::Mapped(mmap))
+}
+
+// Creates an initially empty "file" that is populated with lines read from the stream.
+pub fn load_stream(
+    name: &str,
+    reader: impl std::io::BufRead + Send + 'static,
+) -> (OpenedFileMetadata, LineStream) {
+    let opened_file_meta = OpenedFileMetadata {
+        path: name.to_string(),
+        is_stream: true,
+        ..Default::default()
+    };
+
+    (opened_file_meta, LineStream::from_reader(reader))
+}
+
+// Like load_stream(), with the lines read from a TCP connection to the "host:port".
+pub fn connect_stream(address: &str) -> std::io::Result<(OpenedFileMetadata, LineStream)> {
+    let opened_file_meta = OpenedFileMetadata {
+        path: format!("tcp://{}", address),
+        is_stream: true,
+        ..Default::default()
+    };
+
+    Ok((opened_file_meta, LineStream::connect_tcp(address)?))
+}
+
+// Like load_stream(), with the lines of the unit's journal, as printed by "journalctl -f".
+#[cfg(feature = "journald")]
+pub fn follow_journal(unit: &str) -> std::io::Result<(OpenedFileMetadata, LineStream)> {
+    let opened_file_meta = OpenedFileMetadata {
+        path: format!("journald://{}", unit),
+        is_stream: true,
+        ..Default::default()
+    };
+
+    Ok((
+        opened_file_meta,
+        LineStream::from_command(journal_command(unit))?,
+    ))
+}
+
+// The ISO timestamps are picked up by the histogram and the merging.
+#[cfg(feature = "journald")]
+fn journal_command(unit: &str) -> std::process::Command {
+    let mut command = std::process::Command::new("journalctl");
+    command.args(["-f", "-o", "short-iso", "-u", unit]);
+
+    command
+}
+
+// Interleaves the lines of the files by their timestamps into a single view, see
+// histogram::line_timestamp() for how the timestamp is taken from the line. Lines without one belong
+// to the line above them, the lines of each file stay in their order.
+pub fn load_and_merge(
+    paths: &[String],
+    timestamp_regex: &regex::Regex,
+    user_settings: &UserSettings,
+) -> Result<OpenedFileMetadata, FileLoadError> {
+    let mut loaded_files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let file_user_settings = UserSettings {
+            file_path: path.clone(),
+            ..user_settings.clone()
+        };
+        loaded_files.push(load_file(&file_user_settings)?);
+    }
+
+    // (timestamp, first line index, end line index) of each entry, by file.
+    let files_entries: Vec<Vec<(f64, usize, usize)>> = loaded_files
+        .iter()
+        .map(|loaded_file| {
+            let mut entries: Vec<(f64, usize, usize)> = Vec::new();
+            for (line_index, line) in loaded_file.content.lines().enumerate() {
+                match histogram::line_timestamp(timestamp_regex, &line) {
+                    Some((seconds, _)) => entries.push((seconds, line_index, line_index + 1)),
+                    // Lines before the first timestamp go first.
+                    None => match entries.last_mut() {
+                        Some(entry) => entry.2 = line_index + 1,
+                        None => entries.push((f64::NEG_INFINITY, line_index, line_index + 1)),
+                    },
+                }
+            }
+            entries
+        })
+        .collect();
+
+    let mut merged_content = String::new();
+    let mut line_sources = Vec::new();
+    let mut next_entries = vec![0; files_entries.len()];
+
+    // Of the entries with the same timestamp, the one from the file given first goes first.
+    while let Some(file_index) = (0..files_entries.len())
+        .filter(|file_index| next_entries[*file_index] < files_entries[*file_index].len())
+        .min_by(|a, b| {
+            let timestamp =
+                |file_index: usize| files_entries[file_index][next_entries[file_index]].0;
+            timestamp(*a).total_cmp(&timestamp(*b))
+        })
+    {
+        let (_, first_line_index, end_line_index) =
+            files_entries[file_index][next_entries[file_index]];
+        next_entries[file_index] += 1;
+
+        for line_index in first_line_index..end_line_index {
+            if let Some(line) = loaded_files[file_index].content.line(line_index) {
+                merged_content.push_str(&line);
+                merged_content.push('\n');
+                line_sources.push(file_index);
+            }
+        }
+    }
+
+    Ok(OpenedFileMetadata {
+        path: paths.join(" + "),
+        content: LogContent::from_string(merged_content),
+        is_stream: true,
+        encoding: user_settings.encoding.clone(),
+        file_size: loaded_files
+            .iter()
+            .map(|loaded_file| loaded_file.file_size)
+            .sum(),
+        source_paths: paths.to_vec(),
+        line_sources,
+        ..Default::default()
+    })
+}
+
+// Background of the lines from given merged file, faint enough for the other colors to show.
+pub fn source_tint(source_index: usize) -> Color {
+    const SOURCE_TINTS: [Color; 6] = [
+        Color::from_rgba_unmultiplied(70, 130, 220, 40),
+        Color::from_rgba_unmultiplied(220, 150, 60, 40),
+        Color::from_rgba_unmultiplied(90, 190, 110, 40),
+        Color::from_rgba_unmultiplied(200, 90, 200, 40),
+        Color::from_rgba_unmultiplied(60, 190, 200, 40),
+        Color::from_rgba_unmultiplied(210, 200, 80, 40),
+    ];
+
+    SOURCE_TINTS[source_index % SOURCE_TINTS.len()]
+}
+
+// Filters are kept separate from the other handlers, as hiding a line depends also on its neighbours
+// (the context lines around matches).
+fn make_filter_line_handlers(user_settings: &UserSettings) -> Vec<Box<dyn LineHandler>> {
     let mut handlers: Vec<Box<dyn LineHandler>> = Vec::new();
 
-    // The filter should be first, so we're not applying other handlers to lines that will be invisible anyway.
-    let filter_line_handler = FilterLineHandler::new(user_settings);
-    if let Some(handler) = filter_line_handler {
-        if handler.is_active() {
-            handlers.push(Box::from(handler));
+    for filter_spec in &user_settings.filters {
+        let filter_line_handler: Option<Box<dyn LineHandler>> = if filter_spec.numeric {
+            NumericFilterLineHandler::new(filter_spec, user_settings)
+                .map(|handler| Box::new(handler) as Box<dyn LineHandler>)
+        } else {
+            FilterLineHandler::new(filter_spec)
+                .map(|handler| Box::new(handler) as Box<dyn LineHandler>)
+        };
+        if let Some(handler) = filter_line_handler
+            && handler.is_active()
+        {
+            handlers.push(handler);
+        }
+    }
+
+    handlers
+}
+
+fn make_line_handler(
+    handler_type: LineHandlerType,
+    user_settings: &UserSettings,
+) -> Option<Box<dyn LineHandler>> {
+    let handler: Box<dyn LineHandler> = match handler_type {
+        LineHandlerType::Redact => Box::new(RedactLineHandler::new(user_settings)?),
+        LineHandlerType::LogFormat => Box::new(LogFormatLineHandler::new(user_settings)?),
+        LineHandlerType::LevelHighlight => Box::new(LevelHighlightLineHandler::new(user_settings)?),
+        LineHandlerType::TokenHilight => Box::new(TokenHilightLineHandler::new(user_settings)?),
+        LineHandlerType::JsonHighlight => Box::new(JsonHighlightLineHandler::new(user_settings)?),
+        LineHandlerType::KeyValue => Box::new(KeyValueLineHandler::new(user_settings)?),
+        LineHandlerType::Search => Box::new(SearchLineHandler::new(user_settings)?),
+        // There can be many, see make_filter_line_handlers().
+        LineHandlerType::Filter => return None,
+    };
+
+    handler.is_active().then_some(handler)
+}
+
+fn make_line_handlers(user_settings: &UserSettings) -> Vec<Box<dyn LineHandler>> {
+    let handler_order = match handler_order_problem(&user_settings.handler_order) {
+        None => user_settings.handler_order.as_slice(),
+        Some(problem) => {
+            eprintln!(
+                "Line handler order ignored, {}; using the default one",
+                problem
+            );
+            &DEFAULT_HANDLER_ORDER
         }
+    };
+
+    handler_order
+        .iter()
+        .filter_map(|handler_type| make_line_handler(*handler_type, user_settings))
+        .collect()
+}
+
+// A line that passed the filters, split into colored parts by the line handlers.
+pub struct ProcessedLine {
+    pub original_line_no: usize,
+    pub parts: LineVec,
+    pub is_context: bool,      // shown only because it's near a filter match
+    pub continues_entry: bool, // belongs to the same logical entry as the line shown above it
+    pub points_of_interest: Vec<PointOfInterest>, // line is not set, only the caller knows it
+}
+
+// Where the processing stopped, so it can continue when lines are appended.
+#[derive(Default)]
+pub(crate) struct ProcessingProgress {
+    lines_dropped: usize, // from the front of the content, the indices below start after them
+    lines_processed: usize,
+    lines_shown_until: usize, // index of the line after the last visible one
+    context_after_remaining: usize,
+    last_entry_passed: Option<bool>, // whether the filters kept the last logical entry
+    log_format_lines_processed: usize,
+    log_format_lines_matched: usize,
+    token_counts: Vec<usize>, // by token index, see token_counts()
+}
+
+// Stack traces and alike can be arbitrarily long, the entry is split so it doesn't have to be
+// kept in memory whole.
+const MAX_ENTRY_LINES: usize = 10_000;
+
+// Lines matching it continue the entry started by the line above (i.e. "^\s" or "^at "), without it
+// every line is an entry of its own.
+fn continuation_regex(user_settings: &UserSettings) -> Option<regex::Regex> {
+    if user_settings.continuation_pattern.is_empty() {
+        return None;
     }
 
-    let log_format_line_handler = LogFormatLineHandler::new(user_settings);
-    if let Some(handler) = log_format_line_handler {
-        if handler.is_active() {
-            handlers.push(Box::from(handler));
+    match regex::Regex::new(&user_settings.continuation_pattern) {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            eprintln!("Invalid continuation pattern: {}", e);
+            None
         }
     }
+}
+
+// Lines of the entry as a single one, the indentation of the continuation lines is dropped so a
+// phrase broken across the lines still matches.
+fn entry_text(mut entry_lines: impl Iterator<Item = impl AsRef<str>>) -> String {
+    let mut entry_text = entry_lines
+        .next()
+        .map(|line| line.as_ref().to_string())
+        .unwrap_or_default();
+    for line in entry_lines {
+        entry_text.push(' ');
+        entry_text.push_str(line.as_ref().trim_start());
+    }
+
+    entry_text
+}
 
-    let token_hilight_line_handler = TokenHilightLineHandler::new(user_settings);
-    if let Some(handler) = token_hilight_line_handler {
-        if handler.is_active() {
-            handlers.push(Box::from(handler));
+// The filters see the whole entry, so filtering for an exception keeps its stack trace too.
+fn entry_passes_filters(
+    filter_handlers: &mut [Box<dyn LineHandler>],
+    entry_lines: &[Cow<str>],
+    default_text_format: &Style,
+    ansi_codes: AnsiCodes,
+) -> bool {
+    let entry_text = entry_text(
+        entry_lines
+            .iter()
+            .map(|line| ansi::visible_text(line, ansi_codes)),
+    );
+    let mut line_parts: LineVec = vec![(entry_text, default_text_format.clone())];
+    for handler in filter_handlers {
+        if line_parts.is_empty() {
+            // If the entry was filtered out no need to continue processing.
+            break;
         }
+
+        handler.process_line(&mut line_parts);
+    }
+
+    !line_parts.is_empty()
+}
+
+// Runs the lines not processed yet through the line handlers, calling on_line for each visible one.
+// Without hilight_search the search matches are only found, the lines are not recolored.
+pub(crate) fn process_lines(
+    progress: &mut ProcessingProgress,
+    content: &LogContent,
+    user_settings: &UserSettings,
+    hilight_search: bool,
+    on_line: impl FnMut(ProcessedLine),
+) {
+    process_lines_cancellable(
+        progress,
+        content,
+        user_settings,
+        hilight_search,
+        &|| false,
+        on_line,
+    );
+}
+
+// How many lines are processed between the checks whether the processing was cancelled.
+const CANCEL_CHECK_LINES: usize = 1000;
+
+// A line the filters let through, waiting for the other handlers.
+struct PendingLine<'a> {
+    line: Cow<'a, str>,
+    line_index: usize,
+    is_context: bool,
+    continues_entry: bool,
+}
+
+// The handlers' output for a chunk of the pending lines.
+struct HandledLines {
+    processed_lines: Vec<ProcessedLine>,
+    log_format_lines_processed: usize,
+    log_format_lines_matched: usize,
+    token_counts: Vec<usize>,
+}
+
+// The tabs are expanded before any handler sees the line, so the match offsets (i.e. of the search)
+// are offsets in the text shown. The filters and the continuation pattern still see the tabs.
+fn expand_tabs(line: &str, tab_width: usize, start_column: usize) -> Cow<'_, str> {
+    if tab_width == 0 || !line.contains('\t') {
+        return Cow::Borrowed(line);
     }
 
-    let search_line_handler = SearchLineHandler::new(user_settings);
-    if let Some(handler) = search_line_handler {
-        if handler.is_active() {
-            handlers.push(Box::from(handler));
+    let mut expanded = String::with_capacity(line.len() + tab_width);
+    let mut column = start_column;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
         }
     }
 
-    handlers
+    Cow::Owned(expanded)
 }
 
-// Returns a tuple of (line number layout jobs, log lines layout jobs)
-// TODO: this should not return anything related to LayoutJob, Vec<Vec<String, TextFormat>> would be better.
-pub fn recalculate_log_job(
-    opened_file: &OpenedFileMetadata,
+// The line as the handlers get it, the ANSI sequences turned into colors (or stripped) and the tabs
+// expanded. The tab stops go on across the colored parts.
+fn unprocessed_line(
+    line: &str,
+    default_text_format: &Style,
     user_settings: &UserSettings,
-) -> Option<(
-    Vec<LayoutJob>,
-    Vec<LayoutJob>,
-    Vec<PointOfInterest>,
-    VisibleLineOffsets,
-)> {
-    let mut jobs_log: Vec<LayoutJob> = Vec::new();
-    let mut jobs_line_numbers: Vec<LayoutJob> = Vec::new();
-    let mut points_of_interest: Vec<PointOfInterest> = Vec::new();
+) -> LineVec {
+    let mut line_parts = match user_settings.ansi_codes {
+        AnsiCodes::Colored => ansi::ansi_line(line, default_text_format),
+        AnsiCodes::Stripped | AnsiCodes::Kept => vec![(
+            ansi::visible_text(line, user_settings.ansi_codes).into_owned(),
+            default_text_format.clone(),
+        )],
+    };
+
+    if line_parts
+        .iter()
+        .any(|(part_str, _)| part_str.contains('\t'))
+    {
+        let mut column = 0;
+        for (part_str, _) in &mut line_parts {
+            if let Cow::Owned(expanded) = expand_tabs(part_str, user_settings.tab_width, column) {
+                *part_str = expanded;
+            }
+            column += part_str.chars().count();
+        }
+    }
+
+    line_parts
+}
+
+// The handlers color the lines independently of each other, so once there's this many pending
+// lines they're split among the threads. Each chunk gets handlers of its own.
+const PARALLEL_BATCH_LINES: usize = 64 * 1024;
+const MIN_CHUNK_LINES: usize = 4096;
 
+fn handle_lines_chunk(
+    pending_lines: &[PendingLine],
+    first_line_no: usize,
+    user_settings: &UserSettings,
+    hilight_search: bool,
+) -> HandledLines {
     let mut handlers = make_line_handlers(user_settings);
 
-    let mut lines_visible = 0;
-    let mut lines_total_counter = 0;
+    let search_handler = if hilight_search {
+        None
+    } else {
+        handlers.retain(|handler| handler.handler_type() != LineHandlerType::Search);
+        SearchLineHandler::new(user_settings)
+    };
 
-    let default_text_format = TextFormat {
-        font_id: user_settings.font.clone(),
+    let default_text_format = Style {
+        font_size: user_settings.font_size,
         ..Default::default()
     };
 
-    let mut visible_line_offsets = VisibleLineOffsets::default();
+    // Counted as the lines go, a bad log format pattern shows up without scanning the lines again.
+    let mut log_format_lines_processed = 0;
+    let mut log_format_lines_matched = 0;
 
-    for line in opened_file.content.lines() {
-        lines_total_counter += 1;
-
-        let mut single_line_job = LayoutJob::default();
-
-        if !handlers.is_empty() {
-            let mut line_parts: LineVec = vec![(line.to_string(), default_text_format.clone())];
+    let processed_lines = pending_lines
+        .iter()
+        .map(|pending_line| {
+            let mut line_parts =
+                unprocessed_line(&pending_line.line, &default_text_format, user_settings);
+            let mut points_of_interest = Vec::new();
 
             for handler in &mut handlers {
-                if line_parts.is_empty() {
-                    // If the line was filtered out no need to continue processing.
-                    break;
-                }
-
+                handler.set_original_line_no(first_line_no + pending_line.line_index);
                 handler.process_line(&mut line_parts);
 
                 // This should ideally be fixed, as we're uncovering here the line handler type.
                 if handler.handler_type() == LineHandlerType::Search {
-                    let mut points_of_interest_in_line = handler.points_of_interest();
-                    if points_of_interest_in_line.is_empty() {
-                        continue;
-                    }
+                    points_of_interest.append(&mut handler.points_of_interest());
+                }
 
-                    // Set line number in each point of interest, as the line handler don't know it.
-                    for poi in &mut points_of_interest_in_line {
-                        poi.line = lines_visible + 1;
+                if handler.handler_type() == LineHandlerType::LogFormat {
+                    log_format_lines_processed += 1;
+                    if handler.line_matched() {
+                        log_format_lines_matched += 1;
                     }
-
-                    points_of_interest.append(&mut points_of_interest_in_line);
                 }
             }
 
-            for (part_str, part_format) in line_parts {
-                single_line_job.append(&part_str, 0.0, part_format);
+            if let Some(search_handler) = &search_handler {
+                points_of_interest = search_handler.find_points_of_interest(&line_parts);
             }
-        } else {
-            single_line_job.append(line, 0.0, default_text_format.clone());
+
+            ProcessedLine {
+                original_line_no: first_line_no + pending_line.line_index,
+                parts: line_parts,
+                is_context: pending_line.is_context,
+                continues_entry: pending_line.continues_entry,
+                points_of_interest,
+            }
+        })
+        .collect();
+
+    let mut token_counts: Vec<usize> = Vec::new();
+    for handler in &handlers {
+        let handler_token_counts = handler.token_counts();
+        if token_counts.len() < handler_token_counts.len() {
+            token_counts.resize(handler_token_counts.len(), 0);
+        }
+        for (total, count) in token_counts.iter_mut().zip(handler_token_counts) {
+            *total += count;
         }
+    }
 
-        if !single_line_job.is_empty() {
-            lines_visible += 1;
-            jobs_log.push(single_line_job);
+    HandledLines {
+        processed_lines,
+        log_format_lines_processed,
+        log_format_lines_matched,
+        token_counts,
+    }
+}
 
-            // Create and add a job for line numbers.
-            let mut single_line_number_job = LayoutJob::default();
+// Returns false if it was cancelled, the progress is left half way then.
+fn process_lines_cancellable<'a>(
+    progress: &mut ProcessingProgress,
+    content: &'a LogContent,
+    user_settings: &UserSettings,
+    hilight_search: bool,
+    is_cancelled: &dyn Fn() -> bool,
+    mut on_line: impl FnMut(ProcessedLine),
+) -> bool {
+    let mut filter_handlers = make_filter_line_handlers(user_settings);
 
-            // If we filter something show also the original line numbers.
-            if lines_visible != lines_total_counter {
-                single_line_number_job.append(
-                    &format!("{} ({})", lines_visible, lines_total_counter),
-                    0.0,
-                    default_text_format.clone(),
-                );
+    let default_text_format = Style {
+        font_size: user_settings.font_size,
+        ..Default::default()
+    };
 
-                visible_line_offsets.add_offset(lines_total_counter, lines_visible);
-            } else {
-                single_line_number_job.append(
-                    &format!("{}", lines_visible),
-                    0.0,
-                    default_text_format.clone(),
-                );
+    let first_line_no = content.dropped_line_count() + 1;
+    progress.lines_dropped = content.dropped_line_count();
+
+    // The lines come out of the threads in chunks, but they're handed over in the line order.
+    let mut handle_pending_lines =
+        |progress: &mut ProcessingProgress, pending_lines: &mut Vec<PendingLine>| {
+            let chunk_size = pending_lines
+                .len()
+                .div_ceil(rayon::current_num_threads())
+                .max(MIN_CHUNK_LINES);
+            let handled_chunks: Vec<HandledLines> = pending_lines
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    handle_lines_chunk(chunk, first_line_no, user_settings, hilight_search)
+                })
+                .collect();
+            pending_lines.clear();
+
+            for handled_lines in handled_chunks {
+                progress.log_format_lines_processed += handled_lines.log_format_lines_processed;
+                progress.log_format_lines_matched += handled_lines.log_format_lines_matched;
+                if progress.token_counts.len() < handled_lines.token_counts.len() {
+                    progress
+                        .token_counts
+                        .resize(handled_lines.token_counts.len(), 0);
+                }
+                for (total, count) in progress
+                    .token_counts
+                    .iter_mut()
+                    .zip(&handled_lines.token_counts)
+                {
+                    *total += count;
+                }
+
+                for processed_line in handled_lines.processed_lines {
+                    on_line(processed_line);
+                }
+            }
+        };
+
+    let continuation_regex = continuation_regex(user_settings);
+    let is_continuation = |line: &str| {
+        continuation_regex.as_ref().is_some_and(|regex| {
+            regex.is_match(&ansi::visible_text(line, user_settings.ansi_codes))
+        })
+    };
+    let lines_processed = progress.lines_processed;
+
+    let mut process_entry = |progress: &mut ProcessingProgress,
+                             pending_lines: &mut Vec<PendingLine<'a>>,
+                             entry_start: usize,
+                             entry_lines: &[Cow<'a, str>]| {
+        // Continuation lines appended to a followed file (or past MAX_ENTRY_LINES) go with the
+        // entry they continue, it was already shown or hidden.
+        let continues_previous_entry = is_continuation(&entry_lines[0]);
+        let entry_passed = match progress.last_entry_passed {
+            Some(entry_passed) if continues_previous_entry => entry_passed,
+            _ => entry_passes_filters(
+                &mut filter_handlers,
+                entry_lines,
+                &default_text_format,
+                user_settings.ansi_codes,
+            ),
+        };
+        progress.last_entry_passed = Some(entry_passed);
+
+        let mut push_line = |line, line_index, is_context, continues_entry| {
+            pending_lines.push(PendingLine {
+                line,
+                line_index,
+                is_context,
+                continues_entry,
+            });
+        };
+
+        for (entry_line_index, line) in entry_lines.iter().enumerate() {
+            let line_index = entry_start + entry_line_index;
+
+            if entry_passed {
+                // Show the hidden lines before the match, but never the ones already shown.
+                let context_start = line_index
+                    .saturating_sub(user_settings.filter_context_before)
+                    .max(progress.lines_shown_until);
+                for context_index in context_start..line_index {
+                    if let Some(context_line) = content.line(context_index) {
+                        push_line(context_line, context_index, true, false);
+                    }
+                }
+
+                let continues_entry = entry_line_index > 0
+                    || (continues_previous_entry && progress.lines_shown_until == line_index);
+                push_line(line.clone(), line_index, false, continues_entry);
+                progress.lines_shown_until = line_index + 1;
+                progress.context_after_remaining = user_settings.filter_context_after;
+            } else if user_settings.filter_dim_non_matches {
+                // Dimmed the same as the context lines, nothing is hidden then.
+                push_line(line.clone(), line_index, true, false);
+                progress.lines_shown_until = line_index + 1;
+            } else if progress.context_after_remaining > 0 {
+                progress.context_after_remaining -= 1;
+                push_line(line.clone(), line_index, true, false);
+                progress.lines_shown_until = line_index + 1;
             }
+        }
+    };
+
+    // Physical lines are gathered into logical entries first, the line numbers stay physical.
+    let mut entry_start = lines_processed;
+    let mut entry_lines = Vec::new();
+    let mut pending_lines = Vec::new();
 
-            jobs_line_numbers.push(single_line_number_job);
+    for (line_count, line) in content.lines_from(lines_processed).enumerate() {
+        if line_count % CANCEL_CHECK_LINES == CANCEL_CHECK_LINES - 1 && is_cancelled() {
+            return false;
         }
-    }
 
-    Some((
-        jobs_line_numbers,
-        jobs_log,
-        points_of_interest,
-        visible_line_offsets,
-    ))
-}
+        let continues_entry = !entry_lines.is_empty()
+            && entry_lines.len() < MAX_ENTRY_LINES
+            && is_continuation(&line);
 
-pub fn configuration_save(file_path: &std::path::Path, user_settings: &UserSettings) {
-    println!(
-        "Trying to save configuration to: {}",
-        file_path.to_string_lossy()
-    );
+        if !entry_lines.is_empty() && !continues_entry {
+            process_entry(progress, &mut pending_lines, entry_start, &entry_lines);
+            entry_start += entry_lines.len();
+            entry_lines.clear();
 
-    let serialized = user_settings.serialize();
-    if let Err(e) = serialized {
-        println!("Error serializing configuration: {}", e);
-        return;
+            if pending_lines.len() >= PARALLEL_BATCH_LINES {
+                handle_pending_lines(progress, &mut pending_lines);
+            }
+        }
+
+        entry_lines.push(line);
     }
 
-    let write_result = std::fs::write(file_path, serialized.unwrap());
-    if let Err(e) = write_result {
-        println!("Error writing configuration to file: {}", e);
-        return;
+    if !entry_lines.is_empty() {
+        process_entry(progress, &mut pending_lines, entry_start, &entry_lines);
+        entry_start += entry_lines.len();
     }
+    handle_pending_lines(progress, &mut pending_lines);
+
+    progress.lines_processed = entry_start;
 
-    println!("Configuration saved successfully.");
+    true
 }
 
-pub fn configuration_load(file_path: &std::path::Path) -> Result<UserSettings, Box<dyn Error>> {
-    println!(
-        "Trying to load configuration from: {}",
-        file_path.to_string_lossy()
+// The first lines of the file colored by the log format alone (but redacted, like everywhere else),
+// for trying out a pattern before it's applied. Lines stay uncolored if the format is not usable (i.e. invalid regex).
+pub fn log_format_preview(
+    content: &LogContent,
+    user_settings: &UserSettings,
+    line_count: usize,
+) -> Vec<LineVec> {
+    let default_text_format = Style {
+        font_size: user_settings.font_size,
+        ..Default::default()
+    };
+
+    let mut redact_handler = RedactLineHandler::new(user_settings);
+    let mut log_format_handler =
+        LogFormatLineHandler::new(user_settings).filter(|handler| handler.is_active());
+
+    content
+        .lines_from(0)
+        .take(line_count)
+        .map(|line| {
+            let mut line_parts = unprocessed_line(&line, &default_text_format, user_settings);
+            if let Some(handler) = &mut redact_handler {
+                handler.process_line(&mut line_parts);
+            }
+            if let Some(handler) = &mut log_format_handler {
+                handler.process_line(&mut line_parts);
+            }
+
+            line_parts
+        })
+        .collect()
+}
+
+// All visible lines with the handlers applied, without building any layout (i.e. for exports).
+pub fn process_log_lines(content: &LogContent, user_settings: &UserSettings) -> Vec<ProcessedLine> {
+    let mut processed_lines = Vec::new();
+    process_lines(
+        &mut ProcessingProgress::default(),
+        content,
+        user_settings,
+        true,
+        |processed_line| processed_lines.push(processed_line),
     );
 
-    let read_result = std::fs::read_to_string(file_path);
-    if let Err(e) = read_result {
-        println!("Error reading configuration file: {}", e);
-        return Err(Box::new(e));
-    }
+    processed_lines
+}
 
-    let deserialized = UserSettings::deserialize(&read_result.unwrap());
-    if let Err(e) = deserialized {
-        println!("Error deserializing configuration: {}", e);
-        return Err(e);
-    }
+// Search term and flags the points of interest were found with.
+type SearchKey = (String, bool, bool);
 
-    println!("Configuration loaded successfully.");
+fn search_key(user_settings: &UserSettings) -> SearchKey {
+    (
+        user_settings.search_term.clone(),
+        user_settings.search_match_case,
+        user_settings.search_whole_word,
+    )
+}
 
-    Ok(deserialized.unwrap())
+// Which lines are visible and how, the rows themselves are built only when asked for (see
+// log_line()), so a huge (mapped) file isn't kept in memory again as colored runs.
+// The search matches are not colored in the rows, so a changed search doesn't need the whole
+// processing again (see search_log_jobs()), use search_hilighted_line() to show a line.
+#[derive(Default)]
+pub struct LogJobs {
+    pub points_of_interest: Vec<PointOfInterest>, // sorted by line
+    pub points_of_interest_capped: bool, // more matches than MAX_POINTS_OF_INTEREST were found
+    pub search_matched_lines: usize,     // distinct visible lines with a match
+    pub visible_line_offsets: VisibleLineOffsets,
+    pub collapsed_counts: Vec<usize>, // by visible line index, how many lines the row stands for
+    context_lines: Vec<bool>,         // by visible line index
+    entry_continuations: Vec<bool>,   // by visible line index, see ProcessedLine::continues_entry
+    duplicates_group: Option<DuplicatesGroup>, // of the last visible line
+    fold_placeholder_rows: BTreeMap<usize, usize>, // by visible line index, the first line of the fold
+    row_lengths: Vec<usize>, // by visible line index, in bytes, see longest_rows()
+    welcome_line: Option<LineVec>, // the only row when no file is opened
+    content: LogContent,
+    processed_with: UserSettings, // the rows are built again with the same handlers
+    searched_with: SearchKey,
+    progress: ProcessingProgress,
+}
+
+// Takes only the content, not the whole OpenedFileMetadata, so it can run on a RecalcWorker thread.
+pub fn recalculate_log_job(content: &LogContent, user_settings: &UserSettings) -> Option<LogJobs> {
+    recalculate_log_job_cancellable(content, user_settings, &|| false)
+}
+
+// Gives up with None once is_cancelled returns true, i.e. when newer settings are waiting. It's
+// checked every CANCEL_CHECK_LINES lines.
+pub fn recalculate_log_job_cancellable(
+    content: &LogContent,
+    user_settings: &UserSettings,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Option<LogJobs> {
+    let mut log_jobs = LogJobs::default();
+    recalculate_log_job_incremental_cancellable(&mut log_jobs, content, user_settings, is_cancelled)
+        .then_some(log_jobs)
+}
+
+// Processes only the lines that were not yet processed into log_jobs, i.e. lines appended to a
+// followed file since the last recalculation.
+pub fn recalculate_log_job_incremental(
+    log_jobs: &mut LogJobs,
+    content: &LogContent,
+    user_settings: &UserSettings,
+) {
+    recalculate_log_job_incremental_cancellable(log_jobs, content, user_settings, &|| false);
+}
+
+fn recalculate_log_job_incremental_cancellable(
+    log_jobs: &mut LogJobs,
+    content: &LogContent,
+    user_settings: &UserSettings,
+    is_cancelled: &dyn Fn() -> bool,
+) -> bool {
+    let default_text_format = Style {
+        font_size: user_settings.font_size,
+        ..Default::default()
+    };
+
+    // Every visible row moves when the first lines are dropped, so it all starts over.
+    if log_jobs.progress.lines_dropped != content.dropped_line_count() {
+        *log_jobs = LogJobs::default();
+    }
+
+    let rows_before = visible_line_count(log_jobs);
+
+    let mut progress = std::mem::take(&mut log_jobs.progress);
+    let completed = process_lines_cancellable(
+        &mut progress,
+        content,
+        user_settings,
+        false,
+        is_cancelled,
+        |processed_line| {
+            if fold_processed_line(
+                log_jobs,
+                &processed_line,
+                &default_text_format,
+                user_settings,
+            ) {
+                return;
+            }
+
+            if user_settings.collapse_duplicates {
+                push_or_collapse_processed_line(log_jobs, processed_line, user_settings);
+            } else {
+                push_processed_line(log_jobs, processed_line);
+            }
+        },
+    );
+    if !completed {
+        return false;
+    }
+    log_jobs.progress = progress;
+    log_jobs.content = content.clone();
+    log_jobs.processed_with = user_settings.clone();
+    log_jobs.searched_with = search_key(user_settings);
+
+    // Matches across the lines of an entry are found only once its lines are there, the last entry
+    // may have continued in the appended lines.
+    if let Some(search_handler) = SearchLineHandler::new(user_settings)
+        && log_jobs
+            .entry_continuations
+            .get(rows_before..)
+            .is_some_and(|continuations| continuations.contains(&true))
+    {
+        let first_row = entry_first_row(log_jobs, rows_before);
+        search_rows(log_jobs, &search_handler, first_row);
+    }
+
+    count_search_matched_lines(log_jobs);
+
+    true
+}
+
+// Sets the font size of the rows built from now on, for when nothing but the layout changed (see
+// UserSettings::differs_only_in_layout()). The rest of the layout is up to the view.
+pub fn relayout_log_jobs(log_jobs: &mut LogJobs, user_settings: &UserSettings) {
+    log_jobs.processed_with.font_size = user_settings.font_size;
+    for line in log_jobs.welcome_line.iter_mut() {
+        for (_, format) in line.iter_mut() {
+            format.font_size = user_settings.font_size;
+        }
+    }
+}
+
+// Builds the rows out of the content again, the same as the processing built them. The handlers
+// color each line on its own, so any row can be built without the ones before it.
+pub(crate) struct RowBuilder<'a> {
+    log_jobs: &'a LogJobs,
+    handlers: Vec<Box<dyn LineHandler>>,
+    default_text_format: Style,
+}
+
+impl<'a> RowBuilder<'a> {
+    pub(crate) fn new(log_jobs: &'a LogJobs) -> Self {
+        let user_settings = &log_jobs.processed_with;
+        let mut handlers = make_line_handlers(user_settings);
+        // The search matches are colored when the row is shown, see search_hilighted_line().
+        handlers.retain(|handler| handler.handler_type() != LineHandlerType::Search);
+
+        RowBuilder {
+            log_jobs,
+            handlers,
+            default_text_format: Style {
+                font_size: user_settings.font_size,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub(crate) fn row(&mut self, row_index: usize) -> Option<LineVec> {
+        let log_jobs = self.log_jobs;
+        if let Some(welcome_line) = &log_jobs.welcome_line {
+            return (row_index == 0).then(|| welcome_line.clone());
+        }
+
+        let collapsed_count = *log_jobs.collapsed_counts.get(row_index)?;
+        if log_jobs.fold_placeholder_rows.contains_key(&row_index) {
+            return Some(fold_placeholder_line(
+                collapsed_count,
+                &self.default_text_format,
+            ));
+        }
+
+        let original_line_no = log_jobs
+            .visible_line_offsets
+            .get_original_line_for_row(row_index);
+        let line = log_jobs.content.original_line(original_line_no)?;
+
+        let mut line_parts =
+            unprocessed_line(&line, &self.default_text_format, &log_jobs.processed_with);
+        for handler in &mut self.handlers {
+            handler.set_original_line_no(original_line_no);
+            handler.process_line(&mut line_parts);
+        }
+
+        if log_jobs.context_lines.get(row_index) == Some(&true) {
+            fade_context_line(&mut line_parts);
+        }
+
+        Some(line_parts)
+    }
+}
+
+// How many rows there are, the folded and the collapsed lines take one row each.
+pub fn visible_line_count(log_jobs: &LogJobs) -> usize {
+    log_jobs.collapsed_counts.len()
+}
+
+// The visible line, colored by the handlers (but not by the search, see search_hilighted_line()).
+// Built every time it's asked for, for many lines at once see RowBuilder.
+pub fn log_line(log_jobs: &LogJobs, row_index: usize) -> Option<LineVec> {
+    RowBuilder::new(log_jobs).row(row_index)
+}
+
+// The visible line number, followed by the original one if they differ and by how many lines
+// the row stands for if they were collapsed.
+pub fn line_number(log_jobs: &LogJobs, row_index: usize) -> Option<LineVec> {
+    if log_jobs.welcome_line.is_some() {
+        return (row_index == 0).then(LineVec::new);
+    }
+
+    let collapsed_count = match log_jobs.fold_placeholder_rows.contains_key(&row_index) {
+        // The placeholder tells how many lines are folded.
+        true => 1,
+        false => *log_jobs.collapsed_counts.get(row_index)?,
+    };
+    let original_line_no = log_jobs
+        .visible_line_offsets
+        .get_original_line_for_row(row_index);
+
+    Some(vec![(
+        line_number_text(row_index + 1, original_line_no, collapsed_count),
+        Style {
+            font_size: log_jobs.processed_with.font_size,
+            ..Default::default()
+        },
+    )])
+}
+
+// Rows with the most bytes, the widest row is almost surely among them. Not sorted.
+pub fn longest_rows(log_jobs: &LogJobs, count: usize) -> Vec<usize> {
+    let mut longest_rows: Vec<usize> = (0..log_jobs.row_lengths.len()).collect();
+    if longest_rows.len() > count {
+        longest_rows.select_nth_unstable_by_key(count, |row_index| {
+            std::cmp::Reverse(log_jobs.row_lengths[*row_index])
+        });
+        longest_rows.truncate(count);
+    }
+
+    longest_rows
+}
+
+// How many of the visible lines the log format pattern matched, out of how many it was tried on.
+pub fn log_format_match_count(log_jobs: &LogJobs) -> (usize, usize) {
+    (
+        log_jobs.progress.log_format_lines_matched,
+        log_jobs.progress.log_format_lines_processed,
+    )
+}
+
+// How many times each token of the user settings appeared in the visible lines, by token index.
+// Zero for the tokens not hilighted (i.e. empty ones), empty if none is.
+pub fn token_counts(log_jobs: &LogJobs) -> &[usize] {
+    &log_jobs.progress.token_counts
+}
+
+// Finds the search matches again in the visible lines, for when nothing but the search changed.
+// Cheaper than recalculating, the rows are built again but the filters don't run.
+pub fn search_log_jobs(log_jobs: &mut LogJobs, user_settings: &UserSettings) {
+    if log_jobs.searched_with == search_key(user_settings) {
+        return;
+    }
+
+    log_jobs.points_of_interest.clear();
+    log_jobs.points_of_interest_capped = false;
+    log_jobs.search_matched_lines = 0;
+    log_jobs.searched_with = search_key(user_settings);
+
+    let Some(search_handler) = SearchLineHandler::new(user_settings) else {
+        return;
+    };
+
+    search_rows(log_jobs, &search_handler, 0);
+    count_search_matched_lines(log_jobs);
+}
+
+// A search for something in (almost) every line would otherwise take more memory than the lines.
+pub const MAX_POINTS_OF_INTEREST: usize = 10_000;
+
+fn push_point_of_interest(
+    points_of_interest: &mut Vec<PointOfInterest>,
+    points_of_interest_capped: &mut bool,
+    poi: PointOfInterest,
+) {
+    if points_of_interest.len() < MAX_POINTS_OF_INTEREST {
+        points_of_interest.push(poi);
+    } else {
+        *points_of_interest_capped = true;
+    }
+}
+
+// The matches are sorted by line, so the same lines are next to each other.
+fn count_search_matched_lines(log_jobs: &mut LogJobs) {
+    log_jobs.search_matched_lines = log_jobs
+        .points_of_interest
+        .chunk_by(|poi_a, poi_b| poi_a.line == poi_b.line)
+        .count();
+}
+
+// Finds the search matches in the rows from first_row on, replacing the ones found there before.
+// The rows are built again PARALLEL_BATCH_LINES at a time, so a search that reaches
+// MAX_POINTS_OF_INTEREST early doesn't build them all.
+fn search_rows(log_jobs: &mut LogJobs, search_handler: &SearchLineHandler, first_row: usize) {
+    let first_poi = log_jobs
+        .points_of_interest
+        .partition_point(|poi| poi.line <= first_row);
+    log_jobs.points_of_interest.truncate(first_poi);
+    // Unless the cap was reached already before first_row, it's reached again below if at all.
+    log_jobs.points_of_interest_capped &= first_poi == MAX_POINTS_OF_INTEREST;
+
+    let row_count = visible_line_count(log_jobs);
+    let mut batch_start = first_row;
+    while batch_start < row_count && !log_jobs.points_of_interest_capped {
+        // The batches and the chunks end with an entry, the matches across its lines are found
+        // within one chunk.
+        let batch_end = entry_end_row(
+            log_jobs,
+            (batch_start + PARALLEL_BATCH_LINES).min(row_count),
+        );
+        let chunk_size = (batch_end - batch_start)
+            .div_ceil(rayon::current_num_threads())
+            .max(MIN_CHUNK_LINES);
+        let mut chunks = Vec::new();
+        let mut chunk_start = batch_start;
+        while chunk_start < batch_end {
+            let chunk_end = entry_end_row(log_jobs, (chunk_start + chunk_size).min(batch_end));
+            chunks.push(chunk_start..chunk_end);
+            chunk_start = chunk_end;
+        }
+
+        let log_jobs_searched: &LogJobs = log_jobs;
+        let chunks_points_of_interest: Vec<Vec<PointOfInterest>> = chunks
+            .into_par_iter()
+            .map(|rows| search_row_range(log_jobs_searched, search_handler, rows))
+            .collect();
+
+        for poi in chunks_points_of_interest.into_iter().flatten() {
+            push_point_of_interest(
+                &mut log_jobs.points_of_interest,
+                &mut log_jobs.points_of_interest_capped,
+                poi,
+            );
+        }
+        batch_start = batch_end;
+    }
+}
+
+// The matches in the rows, with the line set. The range has to end with an entry.
+fn search_row_range(
+    log_jobs: &LogJobs,
+    search_handler: &SearchLineHandler,
+    rows: std::ops::Range<usize>,
+) -> Vec<PointOfInterest> {
+    let mut row_builder = RowBuilder::new(log_jobs);
+    let lines: Vec<LineVec> = rows
+        .clone()
+        .map(|row_index| row_builder.row(row_index).unwrap_or_default())
+        .collect();
+
+    let mut points_of_interest = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        let row_index = rows.start + line_index;
+        if log_jobs.fold_placeholder_rows.contains_key(&row_index) {
+            continue;
+        }
+
+        for mut poi in search_handler.find_points_of_interest(line) {
+            poi.line = row_index + 1;
+            points_of_interest.push(poi);
+        }
+
+        if log_jobs.entry_continuations.get(row_index) == Some(&true) {
+            continue;
+        }
+
+        let entry_end = entry_end_row(log_jobs, row_index + 1);
+        if entry_end > row_index + 1 {
+            let entry_rows = &lines[line_index..entry_end - rows.start];
+            for mut poi in entry_spanning_points_of_interest(search_handler, entry_rows) {
+                poi.line = row_index + 1;
+                points_of_interest.push(poi);
+            }
+        }
+    }
+
+    points_of_interest
+}
+
+// The first row from row_index on that doesn't continue the entry above it.
+fn entry_end_row(log_jobs: &LogJobs, row_index: usize) -> usize {
+    let mut entry_end = row_index;
+    while log_jobs.entry_continuations.get(entry_end) == Some(&true) {
+        entry_end += 1;
+    }
+
+    entry_end
+}
+
+// First row of the logical entry the row belongs to.
+fn entry_first_row(log_jobs: &LogJobs, row_index: usize) -> usize {
+    let mut first_row = row_index;
+    while first_row > 0 && log_jobs.entry_continuations.get(first_row) == Some(&true) {
+        first_row -= 1;
+    }
+
+    first_row
+}
+
+// Matches crossing the line breaks of a multi-line entry, the ones within a single line are found
+// per line. For navigation they point at the first line of the entry, only the part of the match
+// on that line (if any) is hilighted.
+fn entry_spanning_points_of_interest(
+    search_handler: &SearchLineHandler,
+    entry_rows: &[LineVec],
+) -> Vec<PointOfInterest> {
+    let line_texts: Vec<String> = entry_rows.iter().map(linevec_text).collect();
+    let entry_text = entry_text(line_texts.iter().map(String::as_str));
+
+    // Where each line starts in the entry text, the joining space belongs to the line before.
+    let mut line_starts = Vec::with_capacity(line_texts.len());
+    let mut line_end = 0;
+    for (line_index, line_text) in line_texts.iter().enumerate() {
+        if line_index == 0 {
+            line_starts.push(0);
+            line_end = line_text.len();
+        } else {
+            line_starts.push(line_end + 1);
+            line_end += 1 + line_text.trim_start().len();
+        }
+    }
+    let line_of = |byte: usize| line_starts.partition_point(|start| *start <= byte) - 1;
+
+    let first_line_len = line_texts[0].len();
+    let entry_line: LineVec = vec![(entry_text, Style::default())];
+
+    search_handler
+        .find_points_of_interest(&entry_line)
+        .into_iter()
+        .filter(|poi| line_of(poi.byte_range.start) != line_of(poi.byte_range.end - 1))
+        .map(|poi| {
+            let byte_range = if poi.byte_range.start < first_line_len {
+                poi.byte_range.start..first_line_len
+            } else {
+                0..0
+            };
+            let split_point = if byte_range.is_empty() {
+                ((0, 0), (0, 0))
+            } else {
+                linevec_byte_range_split_point(&entry_rows[0], byte_range.clone())
+            };
+
+            PointOfInterest {
+                line: 0, // To be filled by caller.
+                split_point,
+                byte_range,
+            }
+        })
+        .collect()
+}
+
+// The visible line with its search matches colored, the same as the handler would color them.
+// Only the hilight style is taken from the user settings, the matches are the ones already found.
+pub fn search_hilighted_line(
+    log_jobs: &LogJobs,
+    row_index: usize,
+    user_settings: &UserSettings,
+) -> Option<LineVec> {
+    let mut line = log_line(log_jobs, row_index)?;
+
+    let visible_line_no = row_index + 1;
+    let first_poi = log_jobs
+        .points_of_interest
+        .partition_point(|poi| poi.line < visible_line_no);
+    let split_points: Vec<SplitPoint> = log_jobs.points_of_interest[first_poi..]
+        .iter()
+        .take_while(|poi| poi.line == visible_line_no)
+        // Matches spanning the lines of an entry may not reach into its first line.
+        .filter(|poi| !poi.byte_range.is_empty())
+        .map(|poi| linevec_byte_range_split_point(&line, poi.byte_range.clone()))
+        .collect();
+
+    if split_points.is_empty() {
+        return Some(line);
+    }
+
+    let is_context = log_jobs
+        .context_lines
+        .get(row_index)
+        .copied()
+        .unwrap_or(false);
+    let fade = if is_context { CONTEXT_LINE_FADE } else { 1.0 };
+
+    search_hilight_split(
+        &mut line,
+        split_points,
+        SearchHilight::new(user_settings),
+        fade,
+    );
+
+    Some(line)
+}
+
+pub(crate) const CONTEXT_LINE_FADE: f32 = 0.5;
+
+fn fade_context_line(line: &mut LineVec) {
+    for (_, part_format) in line.iter_mut() {
+        part_format.color = part_format.color.gamma_multiply(CONTEXT_LINE_FADE);
+        part_format.background = part_format.background.gamma_multiply(CONTEXT_LINE_FADE);
+    }
+}
+
+// Adds the processed line as the next visible line. Only what's needed to build it again is kept,
+// see RowBuilder.
+fn push_processed_line(log_jobs: &mut LogJobs, processed_line: ProcessedLine) {
+    let lines_visible = visible_line_count(log_jobs) + 1;
+    let original_line_no = processed_line.original_line_no;
+
+    for mut poi in processed_line.points_of_interest {
+        // Set line number in each point of interest, as the line handler don't know it.
+        poi.line = lines_visible;
+        push_point_of_interest(
+            &mut log_jobs.points_of_interest,
+            &mut log_jobs.points_of_interest_capped,
+            poi,
+        );
+    }
+
+    log_jobs
+        .row_lengths
+        .push(linevec_text(&processed_line.parts).len());
+    log_jobs.context_lines.push(processed_line.is_context);
+    log_jobs
+        .entry_continuations
+        .push(processed_line.continues_entry);
+    log_jobs.collapsed_counts.push(1);
+
+    // If we filter something show also the original line numbers.
+    if lines_visible != original_line_no {
+        log_jobs
+            .visible_line_offsets
+            .add_offset(original_line_no, lines_visible);
+    }
+}
+
+fn line_number_text(
+    visible_line_no: usize,
+    original_line_no: usize,
+    collapsed_count: usize,
+) -> String {
+    let mut line_number_text = if visible_line_no != original_line_no {
+        format!("{} ({})", visible_line_no, original_line_no)
+    } else {
+        format!("{}", visible_line_no)
+    };
+
+    if collapsed_count > 1 {
+        line_number_text.push_str(&format!(" ×{}", collapsed_count));
+    }
+
+    line_number_text
+}
+
+// The word (letters, digits and underscores, as for the whole word search) at the byte offset.
+pub fn word_at(text: &str, byte_offset: usize) -> Option<&str> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    if !text.is_char_boundary(byte_offset) || !text[byte_offset..].starts_with(is_word_char) {
+        return None;
+    }
+
+    let start = text[..byte_offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(byte_offset, |(i, _)| i);
+    let end = text[byte_offset..]
+        .find(|c| !is_word_char(c))
+        .map_or(text.len(), |i| byte_offset + i);
+
+    Some(&text[start..end])
+}
+
+// I.e. "58,000", counts are easier to read that way.
+pub fn thousands_separated(number: usize) -> String {
+    let digits = number.to_string();
+    let mut separated = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            separated.push(',');
+        }
+        separated.push(digit);
+    }
+
+    separated
+}
+
+// I.e. "1.5 MiB", exact below a KiB.
+pub fn bytes_readable(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit_index = 0;
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit_index])
+}
+
+// Folds the line into the last visible row if it's the same (apart from the timestamp) and right
+// after it in the file, unless the user expanded that row. The folded lines are skipped like
+// the filtered out ones.
+fn push_or_collapse_processed_line(
+    log_jobs: &mut LogJobs,
+    processed_line: ProcessedLine,
+    user_settings: &UserSettings,
+) {
+    let line_text = linevec_text(&processed_line.parts);
+    let key = duplicate_key(&line_text);
+
+    if let Some(duplicates_group) = &mut log_jobs.duplicates_group
+        && duplicates_group.key == key
+        && duplicates_group.is_context == processed_line.is_context
+        && duplicates_group.last_original_line_no + 1 == processed_line.original_line_no
+    {
+        duplicates_group.last_original_line_no = processed_line.original_line_no;
+
+        if duplicates_group.expanded {
+            push_processed_line(log_jobs, processed_line);
+            return;
+        }
+
+        if let Some(collapsed_count) = log_jobs.collapsed_counts.last_mut() {
+            *collapsed_count += 1;
+        }
+
+        return;
+    }
+
+    log_jobs.duplicates_group = Some(DuplicatesGroup {
+        key: key.to_string(),
+        last_original_line_no: processed_line.original_line_no,
+        is_context: processed_line.is_context,
+        expanded: user_settings
+            .expanded_duplicates
+            .contains(&processed_line.original_line_no),
+    });
+
+    push_processed_line(log_jobs, processed_line);
+}
+
+const FOLD_PLACEHOLDER_BACKGROUND: Color = Color::from_rgb(45, 45, 60);
+
+fn fold_placeholder_line(hidden_count: usize, default_text_format: &Style) -> LineVec {
+    let noun = if hidden_count == 1 { "line" } else { "lines" };
+
+    vec![(
+        format!("▸ {} {} hidden", thousands_separated(hidden_count), noun),
+        Style {
+            background: FOLD_PLACEHOLDER_BACKGROUND,
+            ..default_text_format.clone()
+        },
+    )]
+}
+
+// Lines the user folded are replaced by a single placeholder row, telling how many there are.
+// Returns false if the line is not folded, so it's shown as usual.
+fn fold_processed_line(
+    log_jobs: &mut LogJobs,
+    processed_line: &ProcessedLine,
+    default_text_format: &Style,
+    user_settings: &UserSettings,
+) -> bool {
+    let Some((fold_first_line_no, _)) =
+        user_settings.fold_containing(processed_line.original_line_no)
+    else {
+        return false;
+    };
+
+    let lines_visible = visible_line_count(log_jobs);
+    if lines_visible > 0
+        && log_jobs.fold_placeholder_rows.get(&(lines_visible - 1)) == Some(&fold_first_line_no)
+    {
+        let hidden_count = &mut log_jobs.collapsed_counts[lines_visible - 1];
+        *hidden_count += 1;
+        log_jobs.row_lengths[lines_visible - 1] =
+            linevec_text(&fold_placeholder_line(*hidden_count, default_text_format)).len();
+        return true;
+    }
+
+    push_processed_line(
+        log_jobs,
+        ProcessedLine {
+            original_line_no: processed_line.original_line_no,
+            parts: fold_placeholder_line(1, default_text_format),
+            is_context: false,
+            continues_entry: false,
+            points_of_interest: Vec::new(),
+        },
+    );
+    log_jobs
+        .fold_placeholder_rows
+        .insert(lines_visible, fold_first_line_no);
+    // The lines after the fold are not duplicates of the ones before it.
+    log_jobs.duplicates_group = None;
+
+    true
+}
+
+// The first original line of the fold the row stands for, None if it's not a fold placeholder.
+pub fn fold_placeholder_first_line(log_jobs: &LogJobs, row_index: usize) -> Option<usize> {
+    log_jobs.fold_placeholder_rows.get(&row_index).copied()
+}
+
+// Like VisibleLineOffsets::get_visible_line_for_original_line(), but a line folded into
+// a collapsed row maps to that row.
+pub fn visible_line_for_original_line(
+    log_jobs: &LogJobs,
+    original_line_no: usize,
+) -> Option<usize> {
+    let offsets = &log_jobs.visible_line_offsets;
+    let visible_line_count = visible_line_count(log_jobs);
+
+    let nearest_visible_line_no =
+        offsets.get_nearest_visible_line_for_original_line(original_line_no, visible_line_count)?;
+    let original_line_no_of =
+        |visible_line_no: usize| offsets.get_original_line_for_row(visible_line_no - 1);
+
+    // The row starting at or before the line, the nearest one can be after it.
+    let visible_line_no = if original_line_no_of(nearest_visible_line_no) <= original_line_no {
+        nearest_visible_line_no
+    } else {
+        nearest_visible_line_no - 1
+    };
+    if visible_line_no == 0 {
+        return None;
+    }
+
+    let collapsed_count = log_jobs
+        .collapsed_counts
+        .get(visible_line_no - 1)
+        .copied()
+        .unwrap_or(1);
+
+    (original_line_no < original_line_no_of(visible_line_no) + collapsed_count)
+        .then_some(visible_line_no)
+}
+
+// Index of the first point of interest after the byte offset in the visible line, from the top if
+// there's none after it.
+pub fn next_point_of_interest(
+    points_of_interest: &[PointOfInterest],
+    visible_line_no: usize,
+    byte_offset: usize,
+) -> Option<usize> {
+    if points_of_interest.is_empty() {
+        return None;
+    }
+
+    let next_index = points_of_interest
+        .iter()
+        .position(|poi| (poi.line, poi.byte_range.start) > (visible_line_no, byte_offset));
+
+    Some(next_index.unwrap_or(0))
+}
+
+// Recolors the point of interest in an already processed line, i.e. to tell the currently shown
+// search result apart from the rest without recalculating everything.
+pub fn hilight_point_of_interest(
+    line: &mut LineVec,
+    poi: &PointOfInterest,
+    color_bg: Color,
+    color_text: Color,
+) {
+    if poi.byte_range.is_empty() || poi.byte_range.end > linevec_text(line).len() {
+        return;
+    }
+
+    let split_point = linevec_byte_range_split_point(line, poi.byte_range.clone());
+    linevec_split(line, vec![split_point], Some(color_bg), Some(color_text));
+}
+
+// Errors are returned with a readable message, so the caller can show them to the user.
+pub fn configuration_save(
+    file_path: &std::path::Path,
+    user_settings: &UserSettings,
+) -> Result<(), Box<dyn Error>> {
+    let serialized = user_settings
+        .serialize()
+        .map_err(|e| format!("Error serializing configuration: {}", e))?;
+
+    std::fs::write(file_path, serialized).map_err(|e| {
+        format!(
+            "Error writing configuration to {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    Ok(())
+}
+
+pub fn configuration_load(file_path: &std::path::Path) -> Result<UserSettings, Box<dyn Error>> {
+    let read_result = std::fs::read_to_string(file_path).map_err(|e| {
+        format!(
+            "Error reading configuration from {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    let user_settings = UserSettings::deserialize(&read_result).map_err(|e| {
+        format!(
+            "Error loading configuration from {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    Ok(user_settings)
+}
+
+pub fn profile_save(
+    file_path: &std::path::Path,
+    user_settings: &UserSettings,
+) -> Result<(), Box<dyn Error>> {
+    let serialized = user_settings
+        .serialize_profile()
+        .map_err(|e| format!("Error serializing profile: {}", e))?;
+
+    std::fs::write(file_path, serialized).map_err(|e| {
+        format!(
+            "Error writing profile to {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    Ok(())
+}
+
+// The profile applied over given settings. A whole configuration can be loaded as a profile too.
+pub fn profile_load(
+    file_path: &std::path::Path,
+    user_settings: &UserSettings,
+) -> Result<UserSettings, Box<dyn Error>> {
+    let read_result = std::fs::read_to_string(file_path).map_err(|e| {
+        format!(
+            "Error reading profile from {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    let profile = UserSettings::deserialize(&read_result).map_err(|e| {
+        format!(
+            "Error loading profile from {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    let mut user_settings = user_settings.clone();
+    user_settings.apply_profile(&profile);
+
+    Ok(user_settings)
+}
+
+pub const COMMENTS_SIDECAR_EXTENSION: &str = "logalyzer-comments";
+
+// Comments are kept next to the log file, i.e. "syslog.1" -> "syslog.1.logalyzer-comments".
+pub fn comments_sidecar_path(log_file_path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("{}.{}", log_file_path, COMMENTS_SIDECAR_EXTENSION))
+}
+
+pub fn comments_save(
+    file_path: &std::path::Path,
+    log_comments: &HashMap<usize, String>,
+) -> Result<(), Box<dyn Error>> {
+    // Sorted by line number, so the saved file diffs nicely.
+    let log_comments_sorted: BTreeMap<&usize, &String> = log_comments.iter().collect();
+
+    let serialized = serde_json::to_string_pretty(&log_comments_sorted)
+        .map_err(|e| format!("Error serializing comments: {}", e))?;
+
+    std::fs::write(file_path, serialized).map_err(|e| {
+        format!(
+            "Error writing comments to {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    Ok(())
+}
+
+pub fn comments_load(
+    file_path: &std::path::Path,
+) -> Result<HashMap<usize, String>, Box<dyn Error>> {
+    let read_result = std::fs::read_to_string(file_path).map_err(|e| {
+        format!(
+            "Error reading comments from {}: {}",
+            file_path.to_string_lossy(),
+            e
+        )
+    })?;
+
+    let log_comments =
+        serde_json::from_str::<HashMap<usize, String>>(&read_result).map_err(|e| {
+            format!(
+                "Error loading comments from {}: {}",
+                file_path.to_string_lossy(),
+                e
+            )
+        })?;
+
+    Ok(log_comments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_lines(log_jobs: &LogJobs) -> Vec<LineVec> {
+        let mut row_builder = RowBuilder::new(log_jobs);
+        (0..visible_line_count(log_jobs))
+            .map(|row_index| row_builder.row(row_index).unwrap())
+            .collect()
+    }
+
+    fn line_numbers(log_jobs: &LogJobs) -> Vec<LineVec> {
+        (0..visible_line_count(log_jobs))
+            .map(|row_index| line_number(log_jobs, row_index).unwrap())
+            .collect()
+    }
+
+    fn visible_lines(log_jobs: &LogJobs) -> Vec<String> {
+        log_lines(log_jobs).iter().map(linevec_text).collect()
+    }
+
+    fn context_user_settings(filter_term: &str, before: usize, after: usize) -> UserSettings {
+        let mut user_settings = UserSettings {
+            filter_context_before: before,
+            filter_context_after: after,
+            ..Default::default()
+        };
+        user_settings.filters[0].term = filter_term.to_string();
+
+        user_settings
+    }
+
+    #[test]
+    fn configuration_errors_returned() {
+        let config_path =
+            std::env::temp_dir().join("logalyzer_configuration_errors_returned.logalyzercfg");
+
+        let user_settings = UserSettings {
+            font_size: 15.0,
+            ..Default::default()
+        };
+        configuration_save(&config_path, &user_settings).unwrap();
+        assert_eq!(configuration_load(&config_path).unwrap().font_size, 15.0);
+
+        std::fs::write(&config_path, "not json").unwrap();
+        let error = configuration_load(&config_path).err().unwrap();
+        assert!(error.to_string().starts_with("Error loading configuration"));
+
+        std::fs::remove_file(&config_path).unwrap();
+        assert!(configuration_load(&config_path).is_err());
+
+        // A directory can't be written as a file.
+        assert!(configuration_save(&std::env::temp_dir(), &user_settings).is_err());
+    }
+
+    #[test]
+    fn profile_loaded_over_settings() {
+        let profile_path =
+            std::env::temp_dir().join("logalyzer_profile_loaded_over_settings.logalyzerprofile");
+
+        let mut user_settings = UserSettings::default();
+        user_settings.filters[0].term = "timeout".to_string();
+        profile_save(&profile_path, &user_settings).unwrap();
+
+        let current_settings = UserSettings {
+            font_size: 15.0,
+            ..Default::default()
+        };
+        let loaded = profile_load(&profile_path, &current_settings).unwrap();
+        assert_eq!(loaded.font_size, 15.0);
+        assert_eq!(loaded.filters[0].term, "timeout");
+
+        std::fs::write(&profile_path, "not json").unwrap();
+        let error = profile_load(&profile_path, &current_settings)
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Error loading profile"));
+
+        std::fs::remove_file(&profile_path).unwrap();
+    }
+
+    #[test]
+    fn welcome_lists_current_shortcuts() {
+        let welcome = |key_bindings: &KeyBindings| linevec_text(&default_log_content(key_bindings));
+
+        let mut key_bindings = crate::key_bindings::default_key_bindings();
+        let default_welcome = welcome(&key_bindings);
+        assert!(default_welcome.contains("Ctrl+F: Focus search\n"));
+        assert!(default_welcome.contains("Alt+W: Search whole word\n"));
+        for action in ShortcutAction::ALL {
+            assert!(default_welcome.contains(action.name()), "{:?}", action);
+        }
+
+        key_bindings.insert(
+            ShortcutAction::FocusSearch,
+            crate::key_bindings::KeyBinding {
+                key: "K".to_string(),
+                ctrl: true,
+                ..Default::default()
+            },
+        );
+        key_bindings.insert(ShortcutAction::ScrollUp, Default::default());
+        let remapped_welcome = welcome(&key_bindings);
+        assert!(remapped_welcome.contains("Ctrl+K: Focus search\n"));
+        assert!(!remapped_welcome.contains("Ctrl+F"));
+        assert!(!remapped_welcome.contains("Scroll up"));
+    }
+
+    #[test]
+    fn comments_round_trip_sorted() {
+        let path = std::env::temp_dir().join("logalyzer_comments_round_trip_sorted.log");
+        let comments_path = comments_sidecar_path(&path.to_string_lossy());
+
+        let log_comments = HashMap::from([
+            (10, "ten".to_string()),
+            (2, "two".to_string()),
+            (33, "thirty three".to_string()),
+        ]);
+        comments_save(&comments_path, &log_comments).unwrap();
+
+        let saved = std::fs::read_to_string(&comments_path).unwrap();
+        let positions: Vec<usize> = ["two", "ten", "thirty three"]
+            .iter()
+            .map(|comment| saved.find(comment).unwrap())
+            .collect();
+        assert!(positions.is_sorted());
+
+        assert_eq!(comments_load(&comments_path).unwrap(), log_comments);
+
+        // The comments are picked up when the log file is opened.
+        std::fs::write(&path, "first\nsecond\n").unwrap();
+        let user_settings = UserSettings {
+            file_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let opened_file = load_file(&user_settings).unwrap();
+        assert_eq!(opened_file.log_comments, log_comments);
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&comments_path).unwrap();
+
+        // Writing into a directory that isn't there fails, the caller gets told why.
+        let missing_dir_path = std::env::temp_dir()
+            .join("logalyzer_comments_missing_dir")
+            .join("comments.json");
+        let error = comments_save(&missing_dir_path, &log_comments)
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Error writing comments to"));
+    }
+
+    #[test]
+    fn lines_handled_in_parallel_stay_in_order() {
+        let log_text: String = (0..MIN_CHUNK_LINES * 5)
+            .map(|i| format!("{} {} request {}\n", i, ["INFO", "ERROR"][i % 2], i % 7))
+            .collect();
+        let content = LogContent::from_string(log_text);
+        let mut user_settings = context_user_settings("request [0-5]", 1, 0);
+        user_settings.filters[0].regex = true;
+        user_settings.search_term = "request 3".to_string();
+        user_settings.token_colors[0].token = "ERROR".to_string();
+        user_settings.log_format.pattern = r"^(\d+) (\w+)".to_string();
+
+        let recalculate_with_threads = |num_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+                .install(|| recalculate_log_job(&content, &user_settings).unwrap())
+        };
+        let sequential = recalculate_with_threads(1);
+        let parallel = recalculate_with_threads(4);
+
+        let poi_lines = |log_jobs: &LogJobs| -> Vec<usize> {
+            log_jobs
+                .points_of_interest
+                .iter()
+                .map(|poi| poi.line)
+                .collect()
+        };
+        assert_eq!(visible_lines(&parallel), visible_lines(&sequential));
+        assert_eq!(poi_lines(&parallel), poi_lines(&sequential));
+        assert!(poi_lines(&parallel).is_sorted());
+        assert_eq!(token_counts(&parallel), token_counts(&sequential));
+        assert_eq!(
+            log_format_match_count(&parallel),
+            log_format_match_count(&sequential)
+        );
+        for row_index in 0..visible_line_count(&parallel) {
+            assert_eq!(
+                parallel
+                    .visible_line_offsets
+                    .get_original_line_for_row(row_index),
+                sequential
+                    .visible_line_offsets
+                    .get_original_line_for_row(row_index)
+            );
+        }
+    }
+
+    #[test]
+    fn recalculation_cancelled() {
+        let content = LogContent::from_string("line\n".repeat(CANCEL_CHECK_LINES * 3));
+        let user_settings = UserSettings::default();
+
+        let checks = std::cell::Cell::new(0);
+        let cancelled_on_second_check = || {
+            checks.set(checks.get() + 1);
+            checks.get() == 2
+        };
+        assert!(
+            recalculate_log_job_cancellable(&content, &user_settings, &cancelled_on_second_check)
+                .is_none()
+        );
+        assert_eq!(checks.get(), 2);
+
+        let log_jobs = recalculate_log_job_cancellable(&content, &user_settings, &|| false);
+        assert_eq!(
+            visible_line_count(&log_jobs.unwrap()),
+            CANCEL_CHECK_LINES * 3
+        );
+    }
+
+    #[test]
+    fn file_load_errors_described() {
+        let missing_path = std::env::temp_dir().join("logalyzer_file_load_errors_described.log");
+        let user_settings = UserSettings {
+            file_path: missing_path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let error = load_file(&user_settings).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            error.to_string(),
+            format!("Could not open {}: file not found", user_settings.file_path)
+        );
+
+        // Looks compressed, but isn't.
+        std::fs::write(&missing_path, [0x1f, 0x8b, 0x00, 0x01]).unwrap();
+        let error = load_file(&user_settings).err().unwrap();
+        assert!(error.to_string().starts_with("Could not open"));
+
+        std::fs::remove_file(&missing_path).unwrap();
+    }
+
+    #[test]
+    fn files_merged_by_timestamp() {
+        let first_path = std::env::temp_dir().join("logalyzer_files_merged_first.log");
+        let second_path = std::env::temp_dir().join("logalyzer_files_merged_second.log");
+        std::fs::write(
+            &first_path,
+            "12:00:01 connecting\n12:00:03 failed\n  at connect()\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &second_path,
+            "starting\n12:00:02 listening\n12:00:03 accepted\n",
+        )
+        .unwrap();
+        let paths = vec![
+            first_path.to_string_lossy().to_string(),
+            second_path.to_string_lossy().to_string(),
+        ];
+        let timestamp_regex = regex::Regex::new(histogram::LEADING_TIMESTAMP_PATTERN).unwrap();
+
+        let merged = load_and_merge(&paths, &timestamp_regex, &UserSettings::default()).unwrap();
+        let merged_lines: Vec<String> = merged
+            .content
+            .lines()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            merged_lines,
+            vec![
+                "starting",
+                "12:00:01 connecting",
+                "12:00:02 listening",
+                "12:00:03 failed",
+                "  at connect()",
+                "12:00:03 accepted",
+            ]
+        );
+        assert_eq!(merged.line_sources, vec![1, 0, 1, 0, 0, 1]);
+        assert_eq!(merged.source_paths, paths);
+        assert!(merged.is_stream);
+
+        std::fs::remove_file(&second_path).unwrap();
+        let error = load_and_merge(&paths, &timestamp_regex, &UserSettings::default())
+            .err()
+            .unwrap();
+        assert_eq!(error.path, paths[1]);
+
+        std::fs::remove_file(&first_path).unwrap();
+    }
+
+    #[test]
+    fn comments_missing_or_corrupt() {
+        let comments_path =
+            std::env::temp_dir().join("logalyzer_comments_corrupt.log.logalyzer-comments");
+
+        assert!(comments_load(&comments_path).is_err());
+
+        std::fs::write(&comments_path, "{ \"1\": ").unwrap();
+        assert!(comments_load(&comments_path).is_err());
+
+        std::fs::remove_file(&comments_path).unwrap();
+    }
+
+    #[test]
+    fn original_line_mapped_to_visible() {
+        let content = LogContent::from_string("a\nmatch 1\nb\nc\nmatch 2\nmatch 3\n".to_string());
+        let user_settings = context_user_settings("match", 0, 0);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        let offsets = &log_jobs.visible_line_offsets;
+        let visible_line_count = visible_line_count(&log_jobs);
+
+        assert_eq!(
+            offsets.get_visible_line_for_original_line(2, visible_line_count),
+            Some(1)
+        );
+        assert_eq!(
+            offsets.get_visible_line_for_original_line(5, visible_line_count),
+            Some(2)
+        );
+        assert_eq!(
+            offsets.get_visible_line_for_original_line(6, visible_line_count),
+            Some(3)
+        );
+
+        // Filtered out or out of range.
+        for original_line_no in [0, 1, 3, 4, 7] {
+            assert_eq!(
+                offsets.get_visible_line_for_original_line(original_line_no, visible_line_count),
+                None
+            );
+        }
+
+        // Nothing filtered, the numbers are the same.
+        let offsets = VisibleLineOffsets::default();
+        assert_eq!(offsets.get_visible_line_for_original_line(4, 10), Some(4));
+        assert_eq!(offsets.get_visible_line_for_original_line(11, 10), None);
+    }
+
+    #[test]
+    fn offsets_built_for_filtered_lines() {
+        let content = LogContent::from_string("match 1\na\nmatch 3\nb\nmatch 5\n".to_string());
+        let user_settings = context_user_settings("match", 0, 0);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        let offsets = &log_jobs.visible_line_offsets;
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["match 1", "match 3", "match 5"]
+        );
+        assert_eq!(offsets.offsets, vec![(2, 1), (3, 2)]);
+
+        let offsets_by_visible_line: Vec<usize> = (1..=3)
+            .map(|visible_line_no| offsets.get_offset_for_visible_line(visible_line_no))
+            .collect();
+        assert_eq!(offsets_by_visible_line, vec![0, 1, 2]);
+        assert_eq!(offsets.get_offset_for_visible_line(10), 2);
+
+        let original_lines: Vec<usize> = (0..3)
+            .map(|row_index| offsets.get_original_line_for_row(row_index))
+            .collect();
+        assert_eq!(original_lines, vec![1, 3, 5]);
+
+        // The same offsets when the lines come in several appends.
+        let mut log_jobs = LogJobs::default();
+        let mut appended = LogContent::from_string("match 1\na\n".to_string());
+        recalculate_log_job_incremental(&mut log_jobs, &appended, &user_settings);
+        appended.append_lines(&[
+            "match 3".to_string(),
+            "b".to_string(),
+            "match 5".to_string(),
+        ]);
+        recalculate_log_job_incremental(&mut log_jobs, &appended, &user_settings);
+        assert_eq!(log_jobs.visible_line_offsets.offsets, vec![(2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn nearest_visible_line_clamped() {
+        let content = LogContent::from_string("a\nmatch 1\nb\nc\nmatch 2\nmatch 3\n".to_string());
+        let user_settings = context_user_settings("match", 0, 0);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        let offsets = &log_jobs.visible_line_offsets;
+        let visible_line_count = visible_line_count(&log_jobs);
+
+        for (original_line_no, visible_line_no) in
+            [(0, 1), (1, 1), (2, 1), (3, 2), (5, 2), (6, 3), (100, 3)]
+        {
+            assert_eq!(
+                offsets.get_nearest_visible_line_for_original_line(
+                    original_line_no,
+                    visible_line_count
+                ),
+                Some(visible_line_no),
+                "{}",
+                original_line_no
+            );
+        }
+
+        assert_eq!(
+            VisibleLineOffsets::default().get_nearest_visible_line_for_original_line(3, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn handlers_run_in_user_order() {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        user_settings.token_colors[0].token = "disk".to_string();
+        user_settings.token_colors[0].color = Color::RED;
+        let content = LogContent::from_string("error: disk full\n".to_string());
+
+        let disk_background = |user_settings: &UserSettings| {
+            let processed_lines = process_log_lines(&content, user_settings);
+            processed_lines[0]
+                .parts
+                .iter()
+                .find(|(part_str, _)| part_str == "disk")
+                .unwrap()
+                .1
+                .background
+        };
+        assert_eq!(disk_background(&user_settings), SEARCH_HILIGHT_BACKGROUND);
+
+        // The token colors are applied over the search matches.
+        let search_index = user_settings
+            .handler_order
+            .iter()
+            .position(|handler_type| *handler_type == LineHandlerType::Search)
+            .unwrap();
+        let token_index = user_settings
+            .handler_order
+            .iter()
+            .position(|handler_type| *handler_type == LineHandlerType::TokenHilight)
+            .unwrap();
+        user_settings.handler_order.swap(search_index, token_index);
+        assert_eq!(disk_background(&user_settings), Color::RED);
+
+        // The log format would recolor what the tokens did, the default order is used instead.
+        user_settings.log_format.pattern = r"^(\w+)(.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(r"^(\w+)(.*)$").unwrap());
+        let log_format_index = user_settings
+            .handler_order
+            .iter()
+            .position(|handler_type| *handler_type == LineHandlerType::LogFormat)
+            .unwrap();
+        user_settings
+            .handler_order
+            .swap(log_format_index, search_index);
+        assert_eq!(disk_background(&user_settings), SEARCH_HILIGHT_BACKGROUND);
+    }
+
+    #[test]
+    fn search_hilight_style_switched_without_searching_again() {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            filter_context_after: 1,
+            ..Default::default()
+        };
+        user_settings.filters[0].term = "error".to_string();
+
+        let content = LogContent::from_string(
+            "error: disk full
+retrying disk
+"
+            .to_string(),
+        );
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), 2);
+
+        user_settings.search_highlight_style = SearchHighlightStyle::Underline;
+        let line = search_hilighted_line(&log_jobs, 0, &user_settings).unwrap();
+        let disk = line
+            .iter()
+            .find(|(part_str, _)| part_str == "disk")
+            .unwrap();
+        assert_eq!(disk.1.underline, SEARCH_HILIGHT_BACKGROUND);
+        assert!(disk.1.background.is_transparent());
+
+        // Context lines are faded, the underline too.
+        let line = search_hilighted_line(&log_jobs, 1, &user_settings).unwrap();
+        let disk = line
+            .iter()
+            .find(|(part_str, _)| part_str == "disk")
+            .unwrap();
+        assert_eq!(
+            disk.1.underline,
+            SEARCH_HILIGHT_BACKGROUND.gamma_multiply(CONTEXT_LINE_FADE)
+        );
+    }
+
+    #[test]
+    fn search_hilight_colors_configurable() {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        let content = LogContent::from_string("error: disk full\n".to_string());
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        user_settings.search_highlight_background = Color::BLUE;
+        user_settings.search_highlight_text = Color::WHITE;
+        let line = search_hilighted_line(&log_jobs, 0, &user_settings).unwrap();
+        let disk = line
+            .iter()
+            .find(|(part_str, _)| part_str == "disk")
+            .unwrap();
+        assert_eq!(
+            (disk.1.background, disk.1.color),
+            (Color::BLUE, Color::WHITE)
+        );
+
+        user_settings.search_highlight_style = SearchHighlightStyle::Underline;
+        let line = search_hilighted_line(&log_jobs, 0, &user_settings).unwrap();
+        let disk = line
+            .iter()
+            .find(|(part_str, _)| part_str == "disk")
+            .unwrap();
+        assert_eq!(disk.1.underline, Color::BLUE);
+    }
+
+    #[test]
+    fn point_of_interest_hilighted_after_split() {
+        let mut user_settings = UserSettings {
+            search_term: "err".to_string(),
+            ..Default::default()
+        };
+        user_settings.token_colors[0].token = "error".to_string();
+
+        let content = LogContent::from_string("an error and err\n".to_string());
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), 2);
+        assert_eq!(log_jobs.points_of_interest[0].byte_range, 3..6);
+        assert_eq!(log_jobs.points_of_interest[1].byte_range, 13..16);
+
+        let poi = log_jobs.points_of_interest[1].clone();
+        let line = &mut search_hilighted_line(&log_jobs, 0, &user_settings).unwrap();
+        hilight_point_of_interest(line, &poi, Color::ORANGE, Color::BLACK);
+
+        let hilighted: Vec<&str> = line
+            .iter()
+            .filter(|(_, part_format)| part_format.background == Color::ORANGE)
+            .map(|(part_str, _)| part_str.as_str())
+            .collect();
+        assert_eq!(hilighted, vec!["err"]);
+        assert_eq!(linevec_text(line), "an error and err");
+
+        // The other match stays yellow.
+        let yellow: Vec<&str> = line
+            .iter()
+            .filter(|(part_str, part_format)| {
+                !part_str.is_empty() && part_format.background == Color::YELLOW
+            })
+            .map(|(part_str, _)| part_str.as_str())
+            .collect();
+        assert_eq!(yellow, vec!["err"]);
+    }
+
+    #[test]
+    fn search_only_change_matches_full_recalculation() {
+        let content = LogContent::from_string(
+            "ERROR disk full\nretry disk\nok\nerror: disk gone, Disk\nend\n".to_string(),
+        );
+        let mut user_settings = context_user_settings("error", 1, 1);
+        user_settings.token_colors[0].token = "gone".to_string();
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        for (search_term, match_case, whole_word) in [
+            ("disk", false, false),
+            ("Disk", true, true),
+            ("", false, false),
+        ] {
+            user_settings.search_term = search_term.to_string();
+            user_settings.search_match_case = match_case;
+            user_settings.search_whole_word = whole_word;
+
+            search_log_jobs(&mut log_jobs, &user_settings);
+            let recalculated = recalculate_log_job(&content, &user_settings).unwrap();
+
+            let lines_of = |pois: &[PointOfInterest]| -> Vec<(usize, std::ops::Range<usize>)> {
+                pois.iter()
+                    .map(|poi| (poi.line, poi.byte_range.clone()))
+                    .collect()
+            };
+            assert_eq!(
+                lines_of(&log_jobs.points_of_interest),
+                lines_of(&recalculated.points_of_interest),
+                "{}",
+                search_term
+            );
+
+            // Same colors as with the search handler in the chain, context lines dimmed too.
+            let processed_lines = process_log_lines(&content, &user_settings);
+            assert_eq!(processed_lines.len(), visible_line_count(&log_jobs));
+            for (row_index, processed_line) in processed_lines.into_iter().enumerate() {
+                let mut expected = processed_line.parts;
+                if processed_line.is_context {
+                    fade_context_line(&mut expected);
+                }
+
+                let non_empty = |line: LineVec| -> LineVec {
+                    line.into_iter()
+                        .filter(|(part_str, _)| !part_str.is_empty())
+                        .collect()
+                };
+                assert_eq!(
+                    non_empty(search_hilighted_line(&log_jobs, row_index, &user_settings).unwrap()),
+                    non_empty(expected),
+                    "{} {}",
+                    search_term,
+                    row_index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filter_context_lines_shown() {
+        let content =
+            LogContent::from_string("a\nb\nmatch 1\nc\nd\ne\nf\nmatch 2\ng\n".to_string());
+        let user_settings = context_user_settings("match", 1, 2);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["b", "match 1", "c", "d", "f", "match 2", "g"]
+        );
+
+        // Visible line 5 is "f", original line 7.
+        assert_eq!(
+            log_jobs.visible_line_offsets.get_offset_for_visible_line(1),
+            1
+        );
+        assert_eq!(
+            log_jobs.visible_line_offsets.get_offset_for_visible_line(4),
+            1
+        );
+        assert_eq!(
+            log_jobs.visible_line_offsets.get_offset_for_visible_line(5),
+            2
+        );
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[4]), "5 (7)");
+    }
+
+    #[test]
+    fn original_line_found_for_each_row() {
+        let text: String = (1..=600)
+            .map(|line_no| {
+                let kind = if line_no % 3 == 0 { "drop" } else { "keep" };
+                format!("{} {}\n", line_no, kind)
+            })
+            .collect();
+        let content = LogContent::from_string(text);
+        let user_settings = context_user_settings("keep", 0, 0);
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        let offsets = &log_jobs.visible_line_offsets;
+
+        for (row_index, line) in log_lines(&log_jobs).iter().enumerate() {
+            let original_line_no = offsets.get_original_line_for_row(row_index);
+            assert_eq!(
+                linevec_text(line),
+                format!("{} keep", original_line_no),
+                "{}",
+                row_index
+            );
+        }
+    }
+
+    #[test]
+    fn filter_non_matches_dimmed() {
+        let content = LogContent::from_string("a\nmatch 1\nb\nmatch 2\n".to_string());
+        let mut user_settings = context_user_settings("match", 1, 0);
+        user_settings.filter_dim_non_matches = true;
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["a", "match 1", "b", "match 2"]
+        );
+        assert_eq!(log_jobs.context_lines, vec![true, false, true, false]);
+        assert_eq!(
+            log_lines(&log_jobs)[0][0].1.color,
+            Style::default().color.gamma_multiply(CONTEXT_LINE_FADE)
+        );
+        assert_eq!(log_lines(&log_jobs)[1][0].1.color, Style::default().color);
+
+        // The matches are dimmed instead.
+        user_settings.filters[0].negative = true;
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.context_lines, vec![false, true, false, true]);
+    }
+
+    #[test]
+    fn filter_context_overlap_not_duplicated() {
+        let content = LogContent::from_string("match 1\nx\nmatch 2\ny\nz\n".to_string());
+        let user_settings = context_user_settings("match", 2, 1);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["match 1", "x", "match 2", "y"]
+        );
+        assert!(log_jobs.visible_line_offsets.offsets.is_empty());
+    }
+
+    #[test]
+    fn filter_context_continues_after_append() {
+        let mut content = LogContent::from_string("a\nmatch\n".to_string());
+        let user_settings = context_user_settings("match", 0, 1);
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(visible_lines(&log_jobs), vec!["match"]);
+
+        content.append_lines(&["b".to_string(), "c".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+        assert_eq!(visible_lines(&log_jobs), vec!["match", "b"]);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[1]), "2 (3)");
+    }
+
+    fn stack_trace_content() -> LogContent {
+        LogContent::from_string(
+            "started\n\
+            Exception in thread main: NullPointerException\n\
+            \tat Foo.bar(Foo.java:10)\n\
+            \tat Foo.main(Foo.java:3)\n\
+            retrying\n\
+            \tat Foo.retry(Foo.java:20)\n"
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn continuation_lines_filtered_with_their_entry() {
+        let content = stack_trace_content();
+        let mut user_settings = context_user_settings("NullPointer", 0, 0);
+        user_settings.continuation_pattern = r"^\s".to_string();
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec![
+                "Exception in thread main: NullPointerException",
+                "    at Foo.bar(Foo.java:10)",
+                "    at Foo.main(Foo.java:3)"
+            ]
+        );
+        // Line numbers stay physical.
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[2]), "3 (4)");
+
+        // Matching a continuation line keeps the whole entry too.
+        user_settings.filters[0].term = "Foo.retry".to_string();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["retrying", "    at Foo.retry(Foo.java:20)"]
+        );
+
+        // Without the pattern every line is on its own.
+        user_settings.continuation_pattern.clear();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["    at Foo.retry(Foo.java:20)"]
+        );
+    }
+
+    #[test]
+    fn tabs_expanded_to_tab_stops() {
+        assert_eq!(expand_tabs("a\tbc\tdefg\th", 4, 0), "a   bc  defg    h");
+        assert_eq!(expand_tabs("žl\tx", 4, 0), "žl  x");
+        assert!(matches!(expand_tabs("no tabs", 4, 0), Cow::Borrowed(_)));
+        assert_eq!(expand_tabs("a\tb", 0, 0), "a\tb");
+        assert_eq!(expand_tabs("\tx", 4, 2), "  x");
+
+        // The search matches are found in the expanded text, the filters still see the tabs.
+        let content = LogContent::from_string("id\terror\nid\tok\n".to_string());
+        let mut user_settings = context_user_settings(r"d\te", 0, 0);
+        user_settings.filters[0].regex = true;
+        user_settings.search_term = "error".to_string();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(visible_lines(&log_jobs), vec!["id  error"]);
+        assert_eq!(log_jobs.points_of_interest[0].byte_range, 4..9);
+    }
+
+    #[test]
+    fn ansi_codes_colored_or_stripped() {
+        let content = LogContent::from_string(
+            "\x1b[31mERROR\x1b[0m\tdisk full\n\x1b[32mINFO\x1b[0m ok\n".to_string(),
+        );
+        let mut user_settings = context_user_settings("ERROR", 0, 0);
+        user_settings.filters[0].whole_word = true;
+        user_settings.search_term = "disk".to_string();
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(visible_lines(&log_jobs), vec!["ERROR   disk full"]);
+        assert_eq!(log_jobs.points_of_interest[0].byte_range, 8..12);
+        let red_part = &log_lines(&log_jobs)[0][0];
+        assert_eq!(red_part.0, "ERROR");
+        assert_ne!(red_part.1.color, Style::default().color);
+
+        user_settings.ansi_codes = AnsiCodes::Stripped;
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_lines(&log_jobs)[0].len(), 1);
+        assert_eq!(visible_lines(&log_jobs), vec!["ERROR   disk full"]);
+
+        user_settings.ansi_codes = AnsiCodes::Kept;
+        user_settings.filters[0].whole_word = false;
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["\x1b[31mERROR\x1b[0m  disk full"]
+        );
+    }
+
+    #[test]
+    fn continuation_lines_negative_filter_hides_entry() {
+        let content = stack_trace_content();
+        let mut user_settings = context_user_settings("retrying", 0, 0);
+        user_settings.filters[0].negative = true;
+        user_settings.continuation_pattern = r"^\s".to_string();
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(visible_lines(&log_jobs).len(), 4);
+        assert!(
+            !visible_lines(&log_jobs)
+                .iter()
+                .any(|line| line.contains("retry"))
+        );
+    }
+
+    #[test]
+    fn continuation_lines_appended_follow_their_entry() {
+        let mut content = stack_trace_content();
+        let mut user_settings = context_user_settings("NullPointer", 0, 0);
+        user_settings.continuation_pattern = r"^\s".to_string();
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        content.append_lines(&[
+            "\tat Foo.retry(Foo.java:21)".to_string(),
+            "Exception: NullPointerException".to_string(),
+            "\tat Foo.baz(Foo.java:5)".to_string(),
+        ]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+
+        assert_eq!(
+            visible_lines(&log_jobs)[3..],
+            vec![
+                "Exception: NullPointerException",
+                "    at Foo.baz(Foo.java:5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn redacted_text_not_searched_or_exported() {
+        let content =
+            LogContent::from_string("login user=bob token=abc123\nlogout user=bob\n".to_string());
+        let user_settings = UserSettings {
+            search_term: "abc".to_string(),
+            redact_rules: vec![RedactRule {
+                pattern: r"token=\w+".to_string(),
+                replacement: "token=***".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["login user=bob token=***", "logout user=bob"]
+        );
+        assert!(log_jobs.points_of_interest.is_empty());
+
+        let processed_lines = process_log_lines(&content, &user_settings);
+        let exported = export::view_to_text(
+            &processed_lines,
+            &HashMap::new(),
+            &export::ViewExportOptions::default(),
+        );
+        assert!(!exported.contains("abc123"));
+        assert!(exported.contains("token=***"));
+    }
+
+    #[test]
+    fn token_counts_accumulated() {
+        let mut content = LogContent::from_string("disk full\nok\ndisk disk error\n".to_string());
+        let mut user_settings = UserSettings::default();
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert!(token_counts(&log_jobs).is_empty());
+
+        user_settings.token_colors = vec![
+            TokenColor::new_empty(0),
+            TokenColor {
+                token: "error".to_string(),
+                ..TokenColor::new_empty(1)
+            },
+            TokenColor {
+                token: "disk".to_string(),
+                ..TokenColor::new_empty(2)
+            },
+        ];
+        log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(token_counts(&log_jobs), [0, 1, 3]);
+
+        content.append_lines(&["disk ok".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+        assert_eq!(token_counts(&log_jobs), [0, 1, 4]);
+
+        // Only the visible lines count.
+        user_settings.filters[0].term = "full".to_string();
+        log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(token_counts(&log_jobs), [0, 0, 1]);
+    }
+
+    #[test]
+    fn search_matched_lines_counted() {
+        let content = LogContent::from_string("disk full\nok\ndisk disk\n".to_string());
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), 3);
+        assert_eq!(log_jobs.search_matched_lines, 2);
+        assert!(!log_jobs.points_of_interest_capped);
+
+        user_settings.search_term = "ok".to_string();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        assert_eq!(log_jobs.points_of_interest.len(), 1);
+        assert_eq!(log_jobs.search_matched_lines, 1);
+
+        user_settings.search_term.clear();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        assert_eq!(log_jobs.search_matched_lines, 0);
+    }
+
+    #[test]
+    fn relayout_matches_recalculation() {
+        let content =
+            LogContent::from_string("12:00:01 ERROR disk full\n12:00:02 INFO ok\n".to_string());
+        let mut user_settings = UserSettings::default();
+        user_settings.log_format.pattern = r"^(\S+) (\w+)".to_string();
+        user_settings.token_colors[0].token = "disk".to_string();
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        user_settings.font_size = 20.0;
+        relayout_log_jobs(&mut log_jobs, &user_settings);
+
+        let recalculated = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_lines(&log_jobs), log_lines(&recalculated));
+        assert_eq!(line_numbers(&log_jobs), line_numbers(&recalculated));
+    }
+
+    #[test]
+    fn next_search_match_found_after_offset() {
+        let content = LogContent::from_string("disk full\nok\ndisk disk\n".to_string());
+        let user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        let points_of_interest = &log_jobs.points_of_interest;
+
+        assert_eq!(next_point_of_interest(points_of_interest, 1, 2), Some(1));
+        assert_eq!(next_point_of_interest(points_of_interest, 2, 0), Some(1));
+        assert_eq!(next_point_of_interest(points_of_interest, 3, 0), Some(2));
+        // Past the last match it starts from the top.
+        assert_eq!(next_point_of_interest(points_of_interest, 3, 5), Some(0));
+        assert_eq!(next_point_of_interest(&[], 1, 0), None);
+    }
+
+    #[test]
+    fn search_matches_capped() {
+        let mut content = LogContent::from_string("a a\n".repeat(MAX_POINTS_OF_INTEREST));
+        let mut user_settings = UserSettings {
+            search_term: "a".to_string(),
+            ..Default::default()
+        };
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), MAX_POINTS_OF_INTEREST);
+        assert_eq!(log_jobs.search_matched_lines, MAX_POINTS_OF_INTEREST / 2);
+        assert!(log_jobs.points_of_interest_capped);
+
+        // Nothing more is kept after the cap.
+        content.append_lines(&["a".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+        assert_eq!(log_jobs.points_of_interest.len(), MAX_POINTS_OF_INTEREST);
+        assert!(log_jobs.points_of_interest_capped);
+
+        user_settings.search_term = "a a".to_string();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        assert_eq!(log_jobs.points_of_interest.len(), MAX_POINTS_OF_INTEREST);
+        assert!(!log_jobs.points_of_interest_capped);
+    }
+
+    #[test]
+    fn phrase_across_entry_lines_searched_in_batches() {
+        // Entries of three lines, so the batches and the chunks would end inside of them.
+        let entry_count = PARALLEL_BATCH_LINES / 3 + 100;
+        let log_text: String = (0..entry_count)
+            .map(|i| format!("request {}\n  to host\n  done\n", i))
+            .collect();
+        let content = LogContent::from_string(log_text);
+        let user_settings = UserSettings {
+            continuation_pattern: r"^\s".to_string(),
+            ..Default::default()
+        };
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+
+        let searched_user_settings = UserSettings {
+            search_term: "5 to host done".to_string(),
+            ..user_settings
+        };
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap()
+            .install(|| search_log_jobs(&mut log_jobs, &searched_user_settings));
+
+        let poi_lines: Vec<usize> = log_jobs
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.line)
+            .collect();
+        let expected_lines: Vec<usize> = (0..entry_count)
+            .filter(|i| i % 10 == 5)
+            .map(|i| i * 3 + 1)
+            .collect();
+        // The entry crossing the end of the first batch is among them.
+        assert!(expected_lines.contains(&(PARALLEL_BATCH_LINES / 3 * 3 + 1)));
+        assert_eq!(poi_lines, expected_lines);
+    }
+
+    fn wrapped_message_content() -> LogContent {
+        LogContent::from_string(
+            "started\n\
+            could not open the connection\n\
+            \x20   refused by the remote host\n\
+            retrying the connection\n"
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn phrase_across_entry_lines_filtered() {
+        let content = wrapped_message_content();
+        let mut user_settings = context_user_settings("connection refused", 0, 0);
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert!(visible_lines(&log_jobs).is_empty());
+
+        user_settings.continuation_pattern = r"^\s".to_string();
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec![
+                "could not open the connection",
+                "    refused by the remote host"
+            ]
+        );
+    }
+
+    #[test]
+    fn phrase_across_entry_lines_searched() {
+        let content = wrapped_message_content();
+        let mut user_settings = UserSettings {
+            search_term: "connection refused".to_string(),
+            ..Default::default()
+        };
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert!(log_jobs.points_of_interest.is_empty());
+
+        user_settings.continuation_pattern = r"^\s".to_string();
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_jobs.points_of_interest.len(), 1);
+        assert_eq!(log_jobs.points_of_interest[0].line, 2);
+        assert_eq!(log_jobs.points_of_interest[0].byte_range, 19..29);
+
+        let hilighted_line = search_hilighted_line(&log_jobs, 1, &user_settings).unwrap();
+        assert_eq!(hilighted_line[1].0, "connection");
+        assert_eq!(hilighted_line[1].1.background, SEARCH_HILIGHT_BACKGROUND);
+
+        // Matches within a line are still found per line.
+        user_settings.search_term = "connection".to_string();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        let poi_lines: Vec<_> = log_jobs
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.line)
+            .collect();
+        assert_eq!(poi_lines, vec![2, 4]);
+
+        // Only the continuation line holds the start of the match.
+        user_settings.search_term = "refused by the remote host retrying".to_string();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        assert!(log_jobs.points_of_interest.is_empty());
+    }
+
+    #[test]
+    fn phrase_across_appended_entry_lines_searched() {
+        let mut content = LogContent::from_string("started\nconnection\n".to_string());
+        let user_settings = UserSettings {
+            search_term: "connection refused".to_string(),
+            continuation_pattern: r"^\s".to_string(),
+            ..Default::default()
+        };
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert!(log_jobs.points_of_interest.is_empty());
+
+        content.append_lines(&[" refused".to_string(), "connection".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+        content.append_lines(&[" refused".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+
+        let poi_lines: Vec<_> = log_jobs
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.line)
+            .collect();
+        assert_eq!(poi_lines, vec![2, 4]);
+    }
+
+    fn duplicates_content() -> LogContent {
+        LogContent::from_string(
+            "start\n\
+            12:00:01 retrying\n\
+            12:00:02 retrying\n\
+            12:00:03 retrying\n\
+            connected\n\
+            connected\n\
+            done\n"
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn duplicates_collapsed() {
+        let user_settings = UserSettings {
+            collapse_duplicates: true,
+            ..Default::default()
+        };
+
+        let log_jobs = recalculate_log_job(&duplicates_content(), &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["start", "12:00:01 retrying", "connected", "done"]
+        );
+        assert_eq!(log_jobs.collapsed_counts, vec![1, 3, 2, 1]);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[1]), "2 ×3");
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[2]), "3 (5) ×2");
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[3]), "4 (7)");
+
+        // Folded lines map to their row.
+        for (original_line_no, visible_line_no) in
+            [(1, 1), (2, 2), (3, 2), (4, 2), (5, 3), (6, 3), (7, 4)]
+        {
+            assert_eq!(
+                visible_line_for_original_line(&log_jobs, original_line_no),
+                Some(visible_line_no),
+                "{}",
+                original_line_no
+            );
+        }
+        assert_eq!(visible_line_for_original_line(&log_jobs, 0), None);
+        assert_eq!(visible_line_for_original_line(&log_jobs, 8), None);
+    }
+
+    #[test]
+    fn collapsed_duplicates_expanded() {
+        let user_settings = UserSettings {
+            collapse_duplicates: true,
+            expanded_duplicates: [2].into(),
+            ..Default::default()
+        };
+
+        let log_jobs = recalculate_log_job(&duplicates_content(), &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec![
+                "start",
+                "12:00:01 retrying",
+                "12:00:02 retrying",
+                "12:00:03 retrying",
+                "connected",
+                "done"
+            ]
+        );
+        assert_eq!(log_jobs.collapsed_counts, vec![1, 1, 1, 1, 2, 1]);
+        assert_eq!(visible_line_for_original_line(&log_jobs, 6), Some(5));
+    }
+
+    #[test]
+    fn duplicates_around_filtered_out_line_not_folded() {
+        let content = LogContent::from_string("match\nmatch\nother\nmatch\nmatch\n".to_string());
+        let user_settings = UserSettings {
+            collapse_duplicates: true,
+            ..context_user_settings("match", 0, 0)
+        };
+
+        // Only the lines next to each other in the file are folded.
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(visible_lines(&log_jobs), vec!["match", "match"]);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[0]), "1 ×2");
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[1]), "2 (4) ×2");
+        assert_eq!(visible_line_for_original_line(&log_jobs, 3), None);
+        assert_eq!(visible_line_for_original_line(&log_jobs, 5), Some(2));
+    }
+
+    #[test]
+    fn duplicates_collapsed_after_append() {
+        let mut content = LogContent::from_string("a\nb\nb\n".to_string());
+        let user_settings = UserSettings {
+            collapse_duplicates: true,
+            ..Default::default()
+        };
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        content.append_lines(&["b".to_string(), "c".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+
+        assert_eq!(visible_lines(&log_jobs), vec!["a", "b", "c"]);
+        assert_eq!(log_jobs.collapsed_counts, vec![1, 3, 1]);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[2]), "3 (5)");
+    }
+
+    #[test]
+    fn folded_lines_shown_as_placeholder() {
+        let mut user_settings = UserSettings {
+            collapse_duplicates: true,
+            search_term: "o".to_string(),
+            ..Default::default()
+        };
+        user_settings.fold_lines(4, 2);
+
+        let mut log_jobs = recalculate_log_job(&duplicates_content(), &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["start", "▸ 3 lines hidden", "connected", "done"]
+        );
+        assert_eq!(log_jobs.collapsed_counts, vec![1, 3, 2, 1]);
+        assert_eq!(fold_placeholder_first_line(&log_jobs, 1), Some(2));
+        assert_eq!(fold_placeholder_first_line(&log_jobs, 2), None);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[2]), "3 (5) ×2");
+        assert_eq!(visible_line_for_original_line(&log_jobs, 3), Some(2));
+        assert_eq!(visible_line_for_original_line(&log_jobs, 7), Some(4));
+
+        // Nothing in the placeholder is a search match.
+        let poi_lines: Vec<_> = log_jobs
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.line)
+            .collect();
+        assert_eq!(poi_lines, vec![3, 4]);
+        user_settings.search_term = "hidden".to_string();
+        search_log_jobs(&mut log_jobs, &user_settings);
+        assert!(log_jobs.points_of_interest.is_empty());
+
+        // Only the lines passing the filters are counted.
+        user_settings.filters[0].term = "retrying".to_string();
+        user_settings.filters[0].negative = true;
+        user_settings.fold_lines(1, 5);
+        let log_jobs = recalculate_log_job(&duplicates_content(), &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["▸ 2 lines hidden", "connected", "done"]
+        );
+    }
+
+    #[test]
+    fn word_found_at_offset() {
+        let text = "user_id=42 łódź,end";
+        assert_eq!(word_at(text, 0), Some("user_id"));
+        assert_eq!(word_at(text, 5), Some("user_id"));
+        assert_eq!(word_at(text, 9), Some("42"));
+        assert_eq!(word_at(text, 13), Some("łódź"));
+        assert_eq!(word_at(text, 7), None);
+        assert_eq!(word_at(text, 12), None); // inside "ł"
+        assert_eq!(word_at(text, text.len()), None);
+    }
+
+    #[test]
+    fn thousands_separated_counts() {
+        assert_eq!(thousands_separated(0), "0");
+        assert_eq!(thousands_separated(999), "999");
+        assert_eq!(thousands_separated(3200), "3,200");
+        assert_eq!(thousands_separated(1234567), "1,234,567");
+    }
+
+    #[test]
+    fn bytes_shown_readable() {
+        assert_eq!(bytes_readable(0), "0 B");
+        assert_eq!(bytes_readable(1023), "1023 B");
+        assert_eq!(bytes_readable(1536), "1.5 KiB");
+        assert_eq!(bytes_readable(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(
+            bytes_readable(3 * 1024 * 1024 * 1024 * 1024 * 1024),
+            "3072.0 TiB"
+        );
+    }
+
+    #[test]
+    fn expanded_json_lines_pretty_printed() {
+        let content = LogContent::from_string("start\n{\"a\": 1}\n{\"b\": [2]}\n".to_string());
+        let user_settings = UserSettings {
+            json_mode: true,
+            expanded_json_lines: [3].into(),
+            ..Default::default()
+        };
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["start", "{\"a\": 1}", "{\n  \"b\": [\n    2\n  ]\n}"]
+        );
+    }
+
+    #[test]
+    fn json_lines_with_log_format() {
+        let content = LogContent::from_string("[1.5] started\n{\"a\": 1}\n".to_string());
+        let mut user_settings = UserSettings {
+            json_mode: true,
+            expanded_json_lines: [2].into(),
+            ..Default::default()
+        };
+        user_settings.log_format.pattern = r"^(\[.*\]) (.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(&user_settings.log_format.pattern).unwrap());
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(
+            visible_lines(&log_jobs),
+            vec!["[1.5]started", "{\n  \"a\": 1\n}"]
+        );
+    }
+
+    #[test]
+    fn log_format_previewed_on_first_lines() {
+        let content =
+            LogContent::from_string("[1.5] started\nnot matched\n[2.0] done\n".to_string());
+        let mut user_settings = UserSettings::default();
+
+        let preview = log_format_preview(&content, &user_settings, 2);
+        assert_eq!(preview.len(), 2);
+        assert_eq!(preview[0].len(), 1);
+
+        user_settings.log_format.pattern = r"^(\[.*\]) (.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(&user_settings.log_format.pattern).unwrap());
+
+        let preview = log_format_preview(&content, &user_settings, 10);
+        let parts: Vec<Vec<&str>> = preview
+            .iter()
+            .map(|line| line.iter().map(|(part_str, _)| part_str.as_str()).collect())
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                vec!["[1.5]", "started"],
+                vec!["not matched"],
+                vec!["[2.0]", "done"]
+            ]
+        );
+
+        // Broken pattern leaves the lines as they are.
+        user_settings.log_format.pattern = r"^(\[.*".to_string();
+        let preview = log_format_preview(&content, &user_settings, 10);
+        assert_eq!(linevec_text(&preview[0]), "[1.5] started");
+    }
+
+    #[test]
+    fn dropped_lines_keep_original_line_numbers() {
+        let mut opened_file = OpenedFileMetadata {
+            content: LogContent::from_string("l1\nl2\nl3\n".to_string()),
+            is_stream: true,
+            ..Default::default()
+        };
+        opened_file.log_comments.insert(1, "dropped".to_string());
+        opened_file.log_comments.insert(3, "kept".to_string());
+        let user_settings = UserSettings::default();
+        let mut log_jobs = recalculate_log_job(&opened_file.content, &user_settings).unwrap();
+
+        opened_file.append_lines(&["l4".to_string(), "l5".to_string()]);
+        assert_eq!(opened_file.limit_lines(0), 0);
+        assert_eq!(opened_file.limit_lines(5), 0);
+        assert_eq!(opened_file.limit_lines(3), 2);
+        assert_eq!(
+            opened_file.log_comments.keys().copied().collect::<Vec<_>>(),
+            vec![3]
+        );
+
+        // The rows processed before the drop are not valid anymore.
+        recalculate_log_job_incremental(&mut log_jobs, &opened_file.content, &user_settings);
+        assert_eq!(visible_lines(&log_jobs), vec!["l3", "l4", "l5"]);
+        assert_eq!(linevec_text(&line_numbers(&log_jobs)[0]), "1 (3)");
+        assert_eq!(
+            log_jobs.visible_line_offsets.get_original_line_for_row(2),
+            5
+        );
+
+        // A tenth more is dropped, so not every append has to start over.
+        opened_file.append_lines(&vec!["line".to_string(); 20]);
+        assert_eq!(opened_file.limit_lines(10), 14);
+        assert_eq!(opened_file.content.line_count(), 9);
+        assert_eq!(opened_file.content.dropped_line_count(), 16);
+    }
+
+    #[test]
+    fn log_format_matches_counted() {
+        let mut content =
+            LogContent::from_string("[1.5] started\nnot matched\n[2.0] done\n".to_string());
+        let mut user_settings = UserSettings::default();
+
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_format_match_count(&log_jobs), (0, 0));
+
+        user_settings.log_format.pattern = r"^(\[.*\]) (.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(&user_settings.log_format.pattern).unwrap());
+
+        let mut log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_format_match_count(&log_jobs), (2, 3));
+
+        content.append_lines(&["[2.5] appended".to_string(), "neither".to_string()]);
+        recalculate_log_job_incremental(&mut log_jobs, &content, &user_settings);
+        assert_eq!(log_format_match_count(&log_jobs), (3, 5));
+
+        // Only the visible lines count.
+        user_settings.filters = vec![FilterSpec {
+            term: "not".to_string(),
+            negative: true,
+            ..Default::default()
+        }];
+        let log_jobs = recalculate_log_job(&content, &user_settings).unwrap();
+        assert_eq!(log_format_match_count(&log_jobs), (3, 4));
+    }
+
+    #[cfg(feature = "journald")]
+    #[test]
+    fn journal_followed_with_iso_timestamps() {
+        let command = journal_command("ssh.service");
+        assert_eq!(command.get_program(), "journalctl");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec!["-f", "-o", "short-iso", "-u", "ssh.service"]
+        );
+    }
 }
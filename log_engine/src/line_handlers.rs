@@ -1,61 +1,154 @@
-use egui::text::TextFormat;
-use egui::{Color32, FontId};
+use crate::style::{Color, Style};
 
 use crate::PointOfInterest;
 use crate::linevec::*;
-use crate::user_settings::UserSettings;
+use crate::regex_cache::cached_regex;
+use crate::user_settings::{FilterSpec, SearchHighlightStyle, TokenColor, UserSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum LineHandlerType {
+    Redact,
     LogFormat,
+    LevelHighlight,
     TokenHilight,
+    JsonHighlight,
+    KeyValue,
     Filter,
     Search,
 }
 
+impl LineHandlerType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineHandlerType::Redact => "Redaction",
+            LineHandlerType::LogFormat => "Log format",
+            LineHandlerType::LevelHighlight => "Level colors",
+            LineHandlerType::TokenHilight => "Token colors",
+            LineHandlerType::JsonHighlight => "JSON",
+            LineHandlerType::KeyValue => "Key-value pairs",
+            LineHandlerType::Filter => "Filter",
+            LineHandlerType::Search => "Search",
+        }
+    }
+}
+
+// The order the line handlers run in, unless the user reorders them. The filters are not part of
+// it, they run on the whole entry before the rest.
+pub const DEFAULT_HANDLER_ORDER: [LineHandlerType; 7] = [
+    LineHandlerType::Redact,
+    LineHandlerType::LogFormat,
+    LineHandlerType::JsonHighlight,
+    LineHandlerType::LevelHighlight,
+    LineHandlerType::KeyValue,
+    LineHandlerType::TokenHilight,
+    LineHandlerType::Search,
+];
+
+// Why the handlers can't run in given order, None if they can.
+pub fn handler_order_problem(handler_order: &[LineHandlerType]) -> Option<String> {
+    if handler_order.len() != DEFAULT_HANDLER_ORDER.len()
+        || DEFAULT_HANDLER_ORDER
+            .iter()
+            .any(|handler_type| !handler_order.contains(handler_type))
+    {
+        return Some("each handler but the filter has to be listed exactly once".to_string());
+    }
+
+    let position = |handler_type: LineHandlerType| {
+        handler_order
+            .iter()
+            .position(|ordered| *ordered == handler_type)
+            .unwrap()
+    };
+
+    // No other handler should see the redacted text.
+    if position(LineHandlerType::Redact) != 0 {
+        return Some("redaction has to run first".to_string());
+    }
+
+    // The log format recolors the whole line, the coloring of these would be lost.
+    for splitting in [
+        LineHandlerType::JsonHighlight,
+        LineHandlerType::KeyValue,
+        LineHandlerType::TokenHilight,
+        LineHandlerType::Search,
+    ] {
+        if position(splitting) < position(LineHandlerType::LogFormat) {
+            return Some(format!(
+                "log format has to run before {}",
+                splitting.name().to_lowercase()
+            ));
+        }
+    }
+
+    // The matches are byte ranges in the final text, these change it.
+    for changing_text in [LineHandlerType::JsonHighlight, LineHandlerType::KeyValue] {
+        if position(LineHandlerType::Search) < position(changing_text) {
+            return Some(format!(
+                "search has to run after {}",
+                changing_text.name().to_lowercase()
+            ));
+        }
+    }
+
+    None
+}
+
 pub trait LineHandler {
     fn handler_type(&self) -> LineHandlerType;
     fn is_active(&self) -> bool;
     fn process_line(&mut self, line: &mut LineVec);
     fn points_of_interest(&self) -> Vec<PointOfInterest>;
+
+    // Called before process_line(), for the handlers that treat some lines differently.
+    fn set_original_line_no(&mut self, _original_line_no: usize) {}
+
+    // Whether the last processed line fit the handler's pattern, lines that don't are left as is.
+    fn line_matched(&self) -> bool {
+        true
+    }
+
+    // Occurrences of each of the user's tokens in the lines processed so far, by token index.
+    fn token_counts(&self) -> &[usize] {
+        &[]
+    }
 }
 
-fn calculate_text_color_from_background_color(color_background: egui::Color32) -> egui::Color32 {
-    let color = if (color_background.r() as u32
-        + color_background.g() as u32
-        + color_background.b() as u32)
-        / 3
-        > 128
+fn calculate_text_color_from_background_color(color_background: Color) -> Color {
+    if (color_background.r as u32 + color_background.g as u32 + color_background.b as u32) / 3 > 128
     {
-        Color32::BLACK
+        Color::BLACK
     } else {
-        Color32::WHITE
-    };
-
-    color
+        Color::WHITE
+    }
 }
 
 fn color_to_text_format_with_textcolor(
-    color_background: egui::Color32,
-    color_text: egui::Color32,
-    font: FontId,
-) -> TextFormat {
-    let text_format = TextFormat {
-        font_id: font,
+    color_background: Color,
+    color_text: Color,
+    font_size: f32,
+) -> Style {
+    Style {
+        font_size,
         background: color_background,
         color: color_text,
         ..Default::default()
-    };
-
-    text_format
+    }
 }
 
 pub struct LogFormatLineHandler {
     compiled_log_format_regex: regex::Regex,
-    pattern_coloring: Vec<Color32>,
-    pattern_coloring_text: Vec<Color32>,
+    pattern_coloring: Vec<Color>,
+    pattern_coloring_text: Vec<Color>,
     pattern_coloring_text_use_original: Vec<bool>,
-    default_font: FontId,
+    pattern_hidden: Vec<bool>,
+    keep_uncaptured_text: bool,
+    group_coloring_index: Vec<usize>, // for each capture group, index into the coloring vectors
+    default_font_size: f32,
+    last_line_matched: bool,
 }
 
 impl LogFormatLineHandler {
@@ -66,20 +159,34 @@ impl LogFormatLineHandler {
             return None;
         }
 
-        let compiled_regex = regex::Regex::new(&user_settings.log_format.pattern);
+        let compiled_regex = cached_regex(&user_settings.log_format.pattern, false);
         if compiled_regex.is_err() {
             return None;
         }
 
+        let compiled_regex = compiled_regex.unwrap();
+
+        // Every group has to have its coloring, otherwise the format doesn't fit the pattern.
+        let group_coloring_index = compiled_regex
+            .capture_names()
+            .enumerate()
+            .skip(1) // full match
+            .map(|(i, group_name)| user_settings.log_format.coloring_index(i - 1, group_name))
+            .collect::<Option<Vec<usize>>>()?;
+
         Some(Self {
-            compiled_log_format_regex: compiled_regex.unwrap(),
+            compiled_log_format_regex: compiled_regex,
+            group_coloring_index,
             pattern_coloring: user_settings.log_format.pattern_coloring.clone(),
-            default_font: user_settings.font.clone(),
+            default_font_size: user_settings.font_size,
             pattern_coloring_text: user_settings.log_format.pattern_coloring_text.clone(),
             pattern_coloring_text_use_original: user_settings
                 .log_format
                 .pattern_coloring_text_use_original
                 .clone(),
+            pattern_hidden: user_settings.log_format.pattern_hidden.clone(),
+            keep_uncaptured_text: user_settings.log_format.keep_uncaptured_text,
+            last_line_matched: false,
         })
     }
 }
@@ -94,35 +201,36 @@ impl LineHandler for LogFormatLineHandler {
             return false;
         }
 
-        return true;
+        true
     }
 
     fn process_line(&mut self, line: &mut LineVec) {
-        assert!(
-            line.len() == 1,
-            "LogFormatLineHandler expects a single full line, got {} parts",
-            line.len()
-        );
-
-        let line_full = &line[0].0;
-        let line_original_format = &line[0].1;
+        // Normally the line is still in a single part, but a handler splitting it may run first.
+        let line_full = &linevec_text(line);
+        let original_format_at = |byte: usize| {
+            let mut part_end = 0;
+            line.iter()
+                .find(|(part_str, _)| {
+                    part_end += part_str.len();
+                    part_end > byte
+                })
+                .or(line.last())
+                .map(|(_, part_format)| part_format.clone())
+                .unwrap_or_default()
+        };
 
         // If nothing matched do nothing.
         let line_matched_groups_res = self.compiled_log_format_regex.captures(line_full);
+        self.last_line_matched = line_matched_groups_res.is_some();
         if line_matched_groups_res.is_none() {
             return;
         }
 
         let line_matched_groups = line_matched_groups_res.unwrap();
 
-        // Verify the number of captures match the number of coloring pattern.
-        let actual_group_count = line_matched_groups.len() - 1; // 1 for original line
-        if actual_group_count != self.pattern_coloring.len() {
-            return;
-        }
-
         // Do the actual coloring.
         let mut line_result: LineVec = Vec::new();
+        let mut uncaptured_start = 0;
 
         for (i, group) in line_matched_groups.iter().enumerate() {
             // Skip first group which is always a full match.
@@ -130,36 +238,140 @@ impl LineHandler for LogFormatLineHandler {
                 continue;
             }
 
-            let group_str = group.unwrap().as_str();
+            let group_match = group.unwrap();
+            let group_str = group_match.as_str();
 
-            let group_bg_color = self.pattern_coloring[i - 1];
-            let group_text_color = self.pattern_coloring_text[i - 1];
-            let group_text_color_use_original = self.pattern_coloring_text_use_original[i - 1];
+            if self.keep_uncaptured_text {
+                // Nested groups start inside the previous one, there is no gap then.
+                line_result.extend(linevec_slice(line, uncaptured_start..group_match.start()));
+                uncaptured_start = uncaptured_start.max(group_match.end());
+            }
+
+            let coloring_index = self.group_coloring_index[i - 1];
+            if self
+                .pattern_hidden
+                .get(coloring_index)
+                .copied()
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            let group_bg_color = self.pattern_coloring[coloring_index];
+            let group_text_color = self.pattern_coloring_text[coloring_index];
+            let group_text_color_use_original =
+                self.pattern_coloring_text_use_original[coloring_index];
 
             let mut text_format = color_to_text_format_with_textcolor(
                 group_bg_color,
                 group_text_color,
-                self.default_font.clone(),
+                self.default_font_size,
             );
 
             if group_text_color_use_original {
                 // Preserve original text color.
-                text_format.color = line_original_format.color;
+                text_format.color = original_format_at(group_match.start()).color;
             }
 
             line_result.push((group_str.to_string(), text_format));
         }
 
+        if self.keep_uncaptured_text {
+            line_result.extend(linevec_slice(line, uncaptured_start..line_full.len()));
+        }
+
         *line = line_result;
     }
 
     fn points_of_interest(&self) -> Vec<PointOfInterest> {
         Vec::new()
     }
+
+    fn line_matched(&self) -> bool {
+        self.last_line_matched
+    }
+}
+
+// Index of the level keyword found in the line. The keyword appearing first wins, so
+// "INFO: error count 0" is still an INFO line.
+pub(crate) fn line_level<'a>(
+    line: &LineVec,
+    level_keywords: impl Iterator<Item = &'a str>,
+) -> Option<usize> {
+    let mut line_level = None;
+    let mut line_level_position = usize::MAX;
+
+    for (level_index, keyword) in level_keywords.enumerate() {
+        let split_points = linevec_find(line, keyword, true, true);
+        if let Some(split_point) = split_points.first() {
+            let keyword_position = linevec_split_point_byte_range(line, split_point).start;
+            if keyword_position < line_level_position {
+                line_level_position = keyword_position;
+                line_level = Some(level_index);
+            }
+        }
+    }
+
+    line_level
+}
+
+// Colors the background of the whole line by the first level keyword found in it (i.e. "ERROR").
+// Parts already colored by the log format are left alone, token hilights are applied on top.
+pub struct LevelHighlightLineHandler {
+    level_colors: Vec<(String, Color)>,
+}
+
+impl LevelHighlightLineHandler {
+    pub fn new(user_settings: &UserSettings) -> Option<Self> {
+        let mut level_colors = user_settings.level_colors.clone();
+        level_colors.retain(|(keyword, _)| !keyword.trim().is_empty());
+
+        if level_colors.is_empty() {
+            return None;
+        }
+
+        Some(Self { level_colors })
+    }
+}
+
+impl LineHandler for LevelHighlightLineHandler {
+    fn handler_type(&self) -> LineHandlerType {
+        LineHandlerType::LevelHighlight
+    }
+
+    fn is_active(&self) -> bool {
+        !self.level_colors.is_empty()
+    }
+
+    fn process_line(&mut self, line: &mut LineVec) {
+        let level_keywords = self
+            .level_colors
+            .iter()
+            .map(|(keyword, _)| keyword.as_str());
+        let Some(level_index) = line_level(line, level_keywords) else {
+            return;
+        };
+
+        // Transparent means the level is recognized but not colored (i.e. INFO).
+        let line_level_color = self.level_colors[level_index].1;
+
+        for (_, part_format) in line.iter_mut() {
+            if part_format.background == Color::TRANSPARENT {
+                part_format.background = line_level_color;
+            }
+        }
+    }
+
+    fn points_of_interest(&self) -> Vec<PointOfInterest> {
+        Vec::new()
+    }
 }
 
 pub struct TokenHilightLineHandler {
-    token_colors: Vec<(String, Color32)>,
+    token_colors: Vec<TokenColor>,
+    compiled_regexes: Vec<Option<regex::Regex>>, // for each token color, Some if it's a regex one
+    token_indices: Vec<usize>, // for each token color, its index in the user settings
+    token_counts: Vec<usize>,  // by the index in the user settings
 }
 
 impl TokenHilightLineHandler {
@@ -168,21 +380,65 @@ impl TokenHilightLineHandler {
             return None;
         }
 
-        let mut token_colors = user_settings.token_colors.clone();
+        // The counts are reported for the tokens as the user sees them, so keep where they were.
+        let mut token_colors: Vec<(usize, TokenColor)> = user_settings
+            .token_colors
+            .iter()
+            .cloned()
+            .enumerate()
+            .collect();
 
         // Remove all empty or whitespace-only tokens so we don't have to iterate over them later.
-        token_colors
-            .retain(|(token, _)| !token.is_empty() || !token.chars().all(char::is_whitespace));
+        // Invalid regexes are dropped too, the token panel shows them as such.
+        token_colors.retain(|(_, token_color)| {
+            !token_color.token.trim().is_empty()
+                && (!token_color.regex || compile_token_regex(token_color).is_ok())
+        });
+
+        // Sort the token_colors - longest tokens first, regexes after all plain tokens in the
+        // original order. On overlap the earlier one wins.
+        token_colors.sort_by_key(|(_, token_color)| {
+            let token_len = if token_color.regex {
+                0
+            } else {
+                token_color.token.len()
+            };
+
+            (token_color.regex, std::cmp::Reverse(token_len))
+        });
+
+        let (token_indices, token_colors): (Vec<usize>, Vec<TokenColor>) =
+            token_colors.into_iter().unzip();
 
-        // Sort the token_colors - longest tokens first.
-        token_colors.sort_by(|(token_a, _), (token_b, _)| token_b.len().cmp(&token_a.len()));
+        let compiled_regexes = token_colors
+            .iter()
+            .map(|token_color| {
+                token_color
+                    .regex
+                    .then(|| compile_token_regex(token_color).ok())
+                    .flatten()
+            })
+            .collect();
 
         Some(Self {
-            token_colors: token_colors,
+            token_colors,
+            compiled_regexes,
+            token_indices,
+            token_counts: vec![0; user_settings.token_colors.len()],
         })
     }
 }
 
+pub fn compile_token_regex(token_color: &TokenColor) -> Result<regex::Regex, regex::Error> {
+    let pattern = if token_color.whole_word {
+        format!(r"\b(?:{})\b", token_color.token)
+    } else {
+        token_color.token.clone()
+    };
+
+    cached_regex(&pattern, !token_color.match_case)
+}
+
 impl LineHandler for TokenHilightLineHandler {
     fn handler_type(&self) -> LineHandlerType {
         LineHandlerType::TokenHilight
@@ -193,14 +449,59 @@ impl LineHandler for TokenHilightLineHandler {
             return true;
         }
 
-        return false;
+        false
     }
 
     fn process_line(&mut self, line: &mut LineVec) {
         let mut line_result = line.clone();
 
-        for (token, color) in self.token_colors.iter() {
-            let split_points = linevec_find(&line_result, token, true, false);
+        // Splitting doesn't change the text, so byte ranges stay valid between the tokens.
+        let line_full: String = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        let mut hilighted_ranges: Vec<std::ops::Range<usize>> = Vec::new();
+
+        for ((token_color, compiled_regex), token_index) in self
+            .token_colors
+            .iter()
+            .zip(&self.compiled_regexes)
+            .zip(&self.token_indices)
+        {
+            let token_ranges: Vec<std::ops::Range<usize>> = match compiled_regex {
+                Some(compiled_regex) => compiled_regex
+                    .find_iter(&line_full)
+                    .filter(|regex_match| !regex_match.is_empty())
+                    .map(|regex_match| regex_match.range())
+                    .collect(),
+                None => linevec_find(
+                    &line_result,
+                    &token_color.token,
+                    token_color.match_case,
+                    token_color.whole_word,
+                )
+                .iter()
+                .map(|split_point| linevec_split_point_byte_range(&line_result, split_point))
+                .collect(),
+            };
+
+            // Counted even where another token is hilighted instead.
+            self.token_counts[*token_index] += token_ranges.len();
+
+            let mut split_points: Vec<SplitPoint> = Vec::new();
+            for token_range in token_ranges {
+                let overlaps_hilighted = hilighted_ranges.iter().any(|hilighted_range| {
+                    token_range.start < hilighted_range.end
+                        && hilighted_range.start < token_range.end
+                });
+                if overlaps_hilighted {
+                    continue;
+                }
+
+                split_points.push(linevec_byte_range_split_point(
+                    &line_result,
+                    token_range.clone(),
+                ));
+                hilighted_ranges.push(token_range);
+            }
+
             if split_points.is_empty() {
                 continue;
             }
@@ -208,8 +509,10 @@ impl LineHandler for TokenHilightLineHandler {
             linevec_split(
                 &mut line_result,
                 split_points,
-                Some(color.clone()),
-                Some(calculate_text_color_from_background_color(color.clone())),
+                Some(token_color.color),
+                Some(calculate_text_color_from_background_color(
+                    token_color.color,
+                )),
             );
         }
 
@@ -219,6 +522,548 @@ impl LineHandler for TokenHilightLineHandler {
     fn points_of_interest(&self) -> Vec<PointOfInterest> {
         Vec::new()
     }
+
+    fn token_counts(&self) -> &[usize] {
+        &self.token_counts
+    }
+}
+
+const JSON_KEY_COLOR: Color = Color::from_rgb(156, 220, 254);
+const JSON_STRING_COLOR: Color = Color::from_rgb(206, 145, 120);
+const JSON_NUMBER_COLOR: Color = Color::from_rgb(181, 206, 168);
+const JSON_LITERAL_COLOR: Color = Color::from_rgb(86, 156, 214); // true, false, null
+
+const JSON_INDENT: &str = "  ";
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum JsonTokenKind {
+    Whitespace,
+    Punctuation,
+    Key,
+    String,
+    Number,
+    Literal,
+}
+
+// Splits already validated JSON into tokens, the text is kept as is (i.e. key order, number format).
+fn tokenize_json(text: &str) -> Vec<(JsonTokenKind, &str)> {
+    let mut tokens = Vec::new();
+    let bytes = text.as_bytes();
+
+    let mut token_start = 0;
+    while token_start < bytes.len() {
+        let token_kind;
+        let mut token_end = token_start + 1;
+
+        match bytes[token_start] {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                token_kind = JsonTokenKind::Whitespace;
+                while token_end < bytes.len() && bytes[token_end].is_ascii_whitespace() {
+                    token_end += 1;
+                }
+            }
+            b'"' => {
+                while token_end < bytes.len() && bytes[token_end] != b'"' {
+                    // Skip the escaped char, it could be a quote.
+                    token_end += if bytes[token_end] == b'\\' { 2 } else { 1 };
+                }
+                token_end = (token_end + 1).min(bytes.len());
+
+                // A string followed by a colon is a key.
+                let is_key = text[token_end..].trim_start().starts_with(':');
+                token_kind = if is_key {
+                    JsonTokenKind::Key
+                } else {
+                    JsonTokenKind::String
+                };
+            }
+            b'-' | b'0'..=b'9' => {
+                token_kind = JsonTokenKind::Number;
+                while token_end < bytes.len()
+                    && matches!(
+                        bytes[token_end],
+                        b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-'
+                    )
+                {
+                    token_end += 1;
+                }
+            }
+            b'a'..=b'z' => {
+                token_kind = JsonTokenKind::Literal;
+                while token_end < bytes.len() && bytes[token_end].is_ascii_lowercase() {
+                    token_end += 1;
+                }
+            }
+            _ => token_kind = JsonTokenKind::Punctuation,
+        }
+
+        tokens.push((token_kind, &text[token_start..token_end]));
+        token_start = token_end;
+    }
+
+    tokens
+}
+
+// Re-indents the tokens, one value per row. Empty objects and arrays stay on a single row.
+fn pretty_print_json_tokens<'a>(
+    tokens: &[(JsonTokenKind, &'a str)],
+) -> Vec<(JsonTokenKind, std::borrow::Cow<'a, str>)> {
+    use std::borrow::Cow;
+
+    let tokens: Vec<_> = tokens
+        .iter()
+        .filter(|(token_kind, _)| *token_kind != JsonTokenKind::Whitespace)
+        .collect();
+
+    let mut pretty_tokens = Vec::new();
+    let mut depth = 0;
+    let newline = |depth: usize| Cow::Owned(format!("\n{}", JSON_INDENT.repeat(depth)));
+
+    for (token_index, (token_kind, token_text)) in tokens.iter().enumerate() {
+        let next_token_text = tokens.get(token_index + 1).map(|(_, text)| *text);
+
+        match *token_text {
+            "{" | "[" => {
+                pretty_tokens.push((*token_kind, Cow::Borrowed(*token_text)));
+                if !matches!(next_token_text, Some("}") | Some("]")) {
+                    depth += 1;
+                    pretty_tokens.push((JsonTokenKind::Whitespace, newline(depth)));
+                }
+            }
+            "}" | "]" => {
+                let previous_token_text = token_index
+                    .checked_sub(1)
+                    .and_then(|previous_index| tokens.get(previous_index))
+                    .map(|(_, text)| *text);
+                if !matches!(previous_token_text, Some("{") | Some("[")) {
+                    depth = depth.saturating_sub(1);
+                    pretty_tokens.push((JsonTokenKind::Whitespace, newline(depth)));
+                }
+                pretty_tokens.push((*token_kind, Cow::Borrowed(*token_text)));
+            }
+            "," => {
+                pretty_tokens.push((*token_kind, Cow::Borrowed(*token_text)));
+                pretty_tokens.push((JsonTokenKind::Whitespace, newline(depth)));
+            }
+            ":" => {
+                pretty_tokens.push((*token_kind, Cow::Borrowed(*token_text)));
+                pretty_tokens.push((JsonTokenKind::Whitespace, Cow::Borrowed(" ")));
+            }
+            _ => pretty_tokens.push((*token_kind, Cow::Borrowed(*token_text))),
+        }
+    }
+
+    pretty_tokens
+}
+
+// Only objects and arrays, a lone number or string is more likely just a plain line.
+pub fn is_json_line(line_text: &str) -> bool {
+    let trimmed = line_text.trim();
+    (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde::de::IgnoredAny>(trimmed).is_ok()
+}
+
+// Colors the keys and values of lines that are a JSON object or array, other lines are left alone.
+// Pretty printing makes the line take several rows, so it's done only for the lines the user
+// expanded.
+pub struct JsonHighlightLineHandler {
+    font_size: f32,
+    expanded_lines: BTreeSet<usize>,
+    pretty_print: bool, // for the line being processed
+}
+
+impl JsonHighlightLineHandler {
+    pub fn new(user_settings: &UserSettings) -> Option<Self> {
+        if !user_settings.json_mode {
+            return None;
+        }
+
+        Some(Self {
+            font_size: user_settings.font_size,
+            expanded_lines: user_settings.expanded_json_lines.clone(),
+            pretty_print: false,
+        })
+    }
+
+    fn token_style(&self, token_kind: JsonTokenKind) -> Style {
+        let color = match token_kind {
+            JsonTokenKind::Key => JSON_KEY_COLOR,
+            JsonTokenKind::String => JSON_STRING_COLOR,
+            JsonTokenKind::Number => JSON_NUMBER_COLOR,
+            JsonTokenKind::Literal => JSON_LITERAL_COLOR,
+            JsonTokenKind::Whitespace | JsonTokenKind::Punctuation => Style::default().color,
+        };
+
+        Style {
+            color,
+            font_size: self.font_size,
+            ..Default::default()
+        }
+    }
+}
+
+impl LineHandler for JsonHighlightLineHandler {
+    fn handler_type(&self) -> LineHandlerType {
+        LineHandlerType::JsonHighlight
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn set_original_line_no(&mut self, original_line_no: usize) {
+        self.pretty_print = self.expanded_lines.contains(&original_line_no);
+    }
+
+    fn process_line(&mut self, line: &mut LineVec) {
+        let line_text = linevec_text(line);
+        if !is_json_line(&line_text) {
+            return;
+        }
+
+        let tokens = tokenize_json(&line_text);
+
+        *line = if self.pretty_print {
+            pretty_print_json_tokens(&tokens)
+                .into_iter()
+                .map(|(token_kind, token_text)| {
+                    (token_text.into_owned(), self.token_style(token_kind))
+                })
+                .collect()
+        } else {
+            tokens
+                .into_iter()
+                .map(|(token_kind, token_text)| {
+                    (token_text.to_string(), self.token_style(token_kind))
+                })
+                .collect()
+        };
+    }
+
+    fn points_of_interest(&self) -> Vec<PointOfInterest> {
+        Vec::new()
+    }
+}
+
+// Replaces the text matched by the redact rules. It runs before the other handlers, so the secrets
+// don't get to the view, the search or the exports.
+pub struct RedactLineHandler {
+    rules: Vec<(regex::Regex, String)>, // pattern, replacement
+}
+
+impl RedactLineHandler {
+    pub fn new(user_settings: &UserSettings) -> Option<Self> {
+        let rules: Vec<_> = user_settings
+            .redact_rules
+            .iter()
+            .filter(|rule| !rule.pattern.is_empty())
+            .filter_map(|rule| match cached_regex(&rule.pattern, false) {
+                Ok(regex) => Some((regex, rule.replacement.clone())),
+                Err(e) => {
+                    eprintln!("Invalid redact pattern \"{}\": {}", rule.pattern, e);
+                    None
+                }
+            })
+            .collect();
+
+        if rules.is_empty() {
+            return None;
+        }
+
+        Some(Self { rules })
+    }
+}
+
+impl LineHandler for RedactLineHandler {
+    fn handler_type(&self) -> LineHandlerType {
+        LineHandlerType::Redact
+    }
+
+    fn is_active(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    // Matches are looked for in each part, being first the line is still a single one.
+    fn process_line(&mut self, line: &mut LineVec) {
+        for (part_str, _) in line.iter_mut() {
+            for (regex, replacement) in &self.rules {
+                if let std::borrow::Cow::Owned(redacted) =
+                    regex.replace_all(part_str, replacement.as_str())
+                {
+                    *part_str = redacted;
+                }
+            }
+        }
+    }
+
+    fn points_of_interest(&self) -> Vec<PointOfInterest> {
+        Vec::new()
+    }
+}
+
+const KEY_VALUE_KEY_COLOR: Color = Color::from_rgb(120, 170, 230);
+const KEY_VALUE_VALUE_COLOR: Color = Color::from_rgb(220, 200, 120);
+
+// Colors the keys and the values of "key=value" (or "key: value" etc., depending on the separators)
+// pairs differently. Pairs with hidden keys are removed from the line.
+pub struct KeyValueLineHandler {
+    pair_regex: regex::Regex,
+    hidden_keys: Vec<String>,
+}
+
+impl KeyValueLineHandler {
+    pub fn new(user_settings: &UserSettings) -> Option<Self> {
+        if !user_settings.key_value_pairs {
+            return None;
+        }
+
+        let separators: String = user_settings
+            .key_value_separators
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| regex::escape(&c.to_string()))
+            .collect();
+        if separators.is_empty() {
+            return None;
+        }
+
+        // The key starts with a letter, so i.e. "12:34:56" is not taken for a pair. A quoted value
+        // can contain spaces, otherwise it ends at a space or a delimiter.
+        let pair_regex = cached_regex(
+            &format!(
+                r#"(?:^|[\s,;(\[{{])([A-Za-z_][\w.\-]*)\s*[{}]\s*("[^"]*"|[^\s,;)\]}}]+)"#,
+                separators
+            ),
+            false,
+        )
+        .ok()?;
+
+        let hidden_keys = user_settings
+            .key_value_hidden_keys
+            .split(',')
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+
+        Some(Self {
+            pair_regex,
+            hidden_keys,
+        })
+    }
+}
+
+impl LineHandler for KeyValueLineHandler {
+    fn handler_type(&self) -> LineHandlerType {
+        LineHandlerType::KeyValue
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn process_line(&mut self, line: &mut LineVec) {
+        let line_text = linevec_text(line);
+
+        let mut key_ranges = Vec::new();
+        let mut value_ranges = Vec::new();
+        let mut hidden_ranges = Vec::new();
+
+        for captures in self.pair_regex.captures_iter(&line_text) {
+            let (Some(key_match), Some(value_match)) = (captures.get(1), captures.get(2)) else {
+                continue;
+            };
+
+            if self.hidden_keys.iter().any(|key| key == key_match.as_str()) {
+                // Along with the spaces after it, so no gap is left behind.
+                let spaces_after = line_text[value_match.end()..].len()
+                    - line_text[value_match.end()..].trim_start().len();
+                hidden_ranges.push(key_match.start()..value_match.end() + spaces_after);
+            } else {
+                key_ranges.push(key_match.range());
+                value_ranges.push(value_match.range());
+            }
+        }
+
+        // Splitting doesn't change the text, the ranges stay valid until the hidden ones are removed.
+        for (byte_ranges, color) in [
+            (key_ranges, KEY_VALUE_KEY_COLOR),
+            (value_ranges, KEY_VALUE_VALUE_COLOR),
+        ] {
+            if byte_ranges.is_empty() {
+                continue;
+            }
+
+            let split_points = byte_ranges
+                .into_iter()
+                .map(|byte_range| linevec_byte_range_split_point(line, byte_range))
+                .collect();
+            linevec_split(line, split_points, None, Some(color));
+        }
+
+        linevec_remove_byte_ranges(line, &hidden_ranges);
+    }
+
+    fn points_of_interest(&self) -> Vec<PointOfInterest> {
+        Vec::new()
+    }
+}
+
+// Extended filter expression, i.e. "(error || warn) && !heartbeat". The usual precedence
+// applies: "!" binds tightest, then "&&", then "||".
+#[derive(PartialEq, Debug)]
+pub enum FilterExpression {
+    Term(String),
+    Not(Box<FilterExpression>),
+    And(Box<FilterExpression>, Box<FilterExpression>),
+    Or(Box<FilterExpression>, Box<FilterExpression>),
+}
+
+#[derive(PartialEq, Debug)]
+enum FilterToken {
+    Term(String),
+    Not,
+    And,
+    Or,
+    ParenOpen,
+    ParenClose,
+}
+
+fn tokenize_filter_expression(expression: &str) -> Vec<FilterToken> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+
+    while !rest.is_empty() {
+        // "!" is an operator only where a term may start, so "failed!" is still a plain term.
+        let operand_expected = matches!(
+            tokens.last(),
+            None | Some(FilterToken::Not)
+                | Some(FilterToken::And)
+                | Some(FilterToken::Or)
+                | Some(FilterToken::ParenOpen)
+        );
+
+        let (token, token_len) = if rest.starts_with("&&") {
+            (FilterToken::And, 2)
+        } else if rest.starts_with("||") {
+            (FilterToken::Or, 2)
+        } else if rest.starts_with('(') {
+            (FilterToken::ParenOpen, 1)
+        } else if rest.starts_with(')') {
+            (FilterToken::ParenClose, 1)
+        } else if operand_expected && rest.starts_with('!') {
+            (FilterToken::Not, 1)
+        } else {
+            // Terms may contain spaces, they end at the next operator or parenthesis.
+            let term_len = ["&&", "||", "(", ")"]
+                .iter()
+                .filter_map(|operator| rest.find(operator))
+                .min()
+                .unwrap_or(rest.len());
+
+            (
+                FilterToken::Term(rest[..term_len].trim_end().to_string()),
+                term_len,
+            )
+        };
+
+        tokens.push(token);
+        rest = rest[token_len..].trim_start();
+    }
+
+    tokens
+}
+
+struct FilterExpressionParser {
+    tokens: Vec<FilterToken>,
+    position: usize,
+}
+
+impl FilterExpressionParser {
+    fn next_token_is(&self, token: &FilterToken) -> bool {
+        self.tokens.get(self.position) == Some(token)
+    }
+
+    // or_expression := and_expression ("||" and_expression)*
+    fn parse_or(&mut self) -> Result<FilterExpression, String> {
+        let mut expression = self.parse_and()?;
+
+        while self.next_token_is(&FilterToken::Or) {
+            self.position += 1;
+            expression = FilterExpression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    // and_expression := unary_expression ("&&" unary_expression)*
+    fn parse_and(&mut self) -> Result<FilterExpression, String> {
+        let mut expression = self.parse_unary()?;
+
+        while self.next_token_is(&FilterToken::And) {
+            self.position += 1;
+            expression = FilterExpression::And(Box::new(expression), Box::new(self.parse_unary()?));
+        }
+
+        Ok(expression)
+    }
+
+    // unary_expression := "!" unary_expression | "(" or_expression ")" | term
+    fn parse_unary(&mut self) -> Result<FilterExpression, String> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+
+        match token {
+            Some(FilterToken::Not) => Ok(FilterExpression::Not(Box::new(self.parse_unary()?))),
+            Some(FilterToken::ParenOpen) => {
+                let expression = self.parse_or()?;
+                if !self.next_token_is(&FilterToken::ParenClose) {
+                    return Err("Missing closing parenthesis".to_string());
+                }
+
+                self.position += 1;
+                Ok(expression)
+            }
+            Some(FilterToken::Term(term)) => Ok(FilterExpression::Term(term.clone())),
+            Some(token) => Err(format!("Unexpected {:?}, expected a term", token)),
+            None => Err("Unexpected end of expression, expected a term".to_string()),
+        }
+    }
+}
+
+pub fn parse_filter_expression(expression: &str) -> Result<FilterExpression, String> {
+    let mut parser = FilterExpressionParser {
+        tokens: tokenize_filter_expression(expression),
+        position: 0,
+    };
+
+    let parsed_expression = parser.parse_or()?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        return Err(format!(
+            "Unexpected {:?} after the end of expression",
+            token
+        ));
+    }
+
+    Ok(parsed_expression)
+}
+
+impl FilterExpression {
+    fn evaluate(&self, line: &LineVec, match_case: bool, whole_word: bool) -> bool {
+        match self {
+            FilterExpression::Term(term) => {
+                !linevec_find(line, term, match_case, whole_word).is_empty()
+            }
+            FilterExpression::Not(expression) => !expression.evaluate(line, match_case, whole_word),
+            FilterExpression::And(lhs, rhs) => {
+                lhs.evaluate(line, match_case, whole_word)
+                    && rhs.evaluate(line, match_case, whole_word)
+            }
+            FilterExpression::Or(lhs, rhs) => {
+                lhs.evaluate(line, match_case, whole_word)
+                    || rhs.evaluate(line, match_case, whole_word)
+            }
+        }
+    }
 }
 
 pub struct FilterLineHandler {
@@ -226,23 +1071,61 @@ pub struct FilterLineHandler {
     match_case: bool,
     whole_word: bool,
     negative: bool,
-    extended: bool,
+    expression: Option<FilterExpression>, // Set only in extended mode.
+    compiled_regex: Option<regex::Regex>, // Set only in regex mode, extended is ignored then.
 }
 
 impl FilterLineHandler {
-    pub fn new(user_settings: &UserSettings) -> Option<Self> {
-        if user_settings.filter_term.is_empty() {
+    pub fn new(filter_spec: &FilterSpec) -> Option<Self> {
+        if filter_spec.term.is_empty() {
             return None;
         }
 
+        let mut compiled_regex = None;
+        if filter_spec.regex {
+            let pattern = if filter_spec.whole_word {
+                format!(r"\b(?:{})\b", filter_spec.term)
+            } else {
+                filter_spec.term.clone()
+            };
+
+            // The regex may be incomplete while still being typed, do not filter anything then.
+            let compiled_regex_res = cached_regex(&pattern, !filter_spec.match_case);
+            if compiled_regex_res.is_err() {
+                return None;
+            }
+
+            compiled_regex = compiled_regex_res.ok();
+        }
+
+        let mut expression = None;
+        if filter_spec.extended && !filter_spec.regex {
+            // Same as with the regex, malformed expression means no filtering.
+            expression = Some(parse_filter_expression(&filter_spec.term).ok()?);
+        }
+
         Some(Self {
-            filter_term: user_settings.filter_term.clone(),
-            match_case: user_settings.filter_match_case,
-            whole_word: user_settings.filter_whole_word,
-            negative: user_settings.filter_negative,
-            extended: user_settings.filter_extended,
+            filter_term: filter_spec.term.clone(),
+            match_case: filter_spec.match_case,
+            whole_word: filter_spec.whole_word,
+            negative: filter_spec.negative,
+            expression,
+            compiled_regex,
         })
     }
+
+    fn line_matches(&self, line: &LineVec) -> bool {
+        if let Some(compiled_regex) = &self.compiled_regex {
+            let line_full: String = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+            return compiled_regex.is_match(&line_full);
+        }
+
+        if let Some(expression) = &self.expression {
+            return expression.evaluate(line, self.match_case, self.whole_word);
+        }
+
+        !linevec_find(line, &self.filter_term, self.match_case, self.whole_word).is_empty()
+    }
 }
 
 impl LineHandler for FilterLineHandler {
@@ -255,73 +1138,184 @@ impl LineHandler for FilterLineHandler {
             return false;
         }
 
-        return true;
+        true
     }
 
     fn process_line(&mut self, line: &mut LineVec) {
-        let mut search_terms: Vec<String> = Vec::new();
-        let mut is_and_term = false;
-
-        if self.extended {
-            // Parse extended filter terms with && and ||.
-            // For simplicity, we only support terms with either only "&&"" or only "||" for now.
-            if self.filter_term.contains("&&") {
-                is_and_term = true;
-                for part in self.filter_term.split("&&") {
-                    let trimmed = part.trim();
-                    if !trimmed.is_empty() {
-                        search_terms.push(trimmed.to_string());
-                    }
-                }
-            } else if self.filter_term.contains("||") {
-                is_and_term = false;
-                for part in self.filter_term.split("||") {
-                    let trimmed = part.trim();
-                    if !trimmed.is_empty() {
-                        search_terms.push(trimmed.to_string());
-                    }
-                }
-            } else {
-                search_terms.push(self.filter_term.clone());
+        let matched = self.line_matches(line);
+
+        if !matched {
+            // Line does not match, so it should be filtered out.
+            if !self.negative {
+                // We are not in negative mode, so we clear the line.
+                line.clear();
             }
         } else {
-            search_terms.push(self.filter_term.clone());
+            // Line matches, so it should be kept.
+            if self.negative {
+                // We are in negative mode, so we clear the line.
+                line.clear();
+            }
         }
+    }
 
-        let mut matched = if is_and_term { true } else { false };
+    fn points_of_interest(&self) -> Vec<PointOfInterest> {
+        Vec::new()
+    }
+}
 
-        for filter_term in search_terms.iter() {
-            let split_points = linevec_find(&line, filter_term, self.match_case, self.whole_word);
-            let filter_term_matched = !split_points.is_empty();
-            if is_and_term {
-                matched = matched && filter_term_matched;
-                if !matched {
-                    // Since we allow only either "AND" or "OR" terms, we can break early here, as
-                    // all the rest of the term will evaluate to false anyway.
-                    break;
-                }
-            } else {
-                matched = matched || filter_term_matched;
-                if matched {
-                    // Since we allow only either "AND" or "OR" terms, we can break early here, as
-                    // all the rest of the term will evaluate to true anyway.
-                    break;
-                }
-            }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericComparison {
+    Greater(f64),
+    GreaterOrEqual(f64),
+    Less(f64),
+    LessOrEqual(f64),
+    Equal(f64),
+    Range(f64, f64), // both ends included
+}
+
+impl NumericComparison {
+    fn matches(&self, value: f64) -> bool {
+        match *self {
+            NumericComparison::Greater(limit) => value > limit,
+            NumericComparison::GreaterOrEqual(limit) => value >= limit,
+            NumericComparison::Less(limit) => value < limit,
+            NumericComparison::LessOrEqual(limit) => value <= limit,
+            NumericComparison::Equal(limit) => value == limit,
+            NumericComparison::Range(from, to) => (from..=to).contains(&value),
         }
+    }
+}
+
+const NUMBER_PATTERN: &str = r"[-+]?\d+(?:\.\d+)?";
+
+static NUMERIC_COMPARISON_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(&format!(
+        r"^(?s)(.*?)\s*(>=|<=|==|>|<)\s*({})\s*$",
+        NUMBER_PATTERN
+    ))
+    .unwrap()
+});
+
+static NUMERIC_RANGE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(&format!(
+        r"^(?s)(.*?)\s+({})\s*\.\.\s*({})\s*$",
+        NUMBER_PATTERN, NUMBER_PATTERN
+    ))
+    .unwrap()
+});
+
+// I.e. "latency > 500", "took (\d+)ms <= 20" or "status 400..499": where the number is on the left,
+// the comparison on the right.
+pub fn parse_numeric_condition(term: &str) -> Result<(String, NumericComparison), String> {
+    let parse_number = |number: &str| number.parse::<f64>().unwrap_or_default();
+
+    let (locator, comparison) = if let Some(captures) = NUMERIC_RANGE_REGEX.captures(term) {
+        let from = parse_number(&captures[2]);
+        let to = parse_number(&captures[3]);
+        if from > to {
+            return Err(format!("The range {}..{} is empty", from, to));
+        }
+
+        (captures[1].to_string(), NumericComparison::Range(from, to))
+    } else if let Some(captures) = NUMERIC_COMPARISON_REGEX.captures(term) {
+        let limit = parse_number(&captures[3]);
+        let comparison = match &captures[2] {
+            ">" => NumericComparison::Greater(limit),
+            ">=" => NumericComparison::GreaterOrEqual(limit),
+            "<" => NumericComparison::Less(limit),
+            "<=" => NumericComparison::LessOrEqual(limit),
+            _ => NumericComparison::Equal(limit),
+        };
+
+        (captures[1].to_string(), comparison)
+    } else {
+        return Err("Expected a comparison (>, >=, <, <=, ==) or a range (from..to)".to_string());
+    };
+
+    let locator = locator.trim();
+    if locator.is_empty() {
+        return Err("Nothing to compare, i.e. \"latency > 500\"".to_string());
+    }
+
+    Ok((locator.to_string(), comparison))
+}
+
+// Keeps the lines with a number matching the condition. The number is taken from the named group
+// of the log format, or if there's no such group the left side is a regex, the number is its first
+// capture group (or the whole match). Lines without a number there are no match.
+pub struct NumericFilterLineHandler {
+    locator_regex: regex::Regex,
+    group_index: usize,
+    comparison: NumericComparison,
+    negative: bool,
+}
+
+impl NumericFilterLineHandler {
+    pub fn new(filter_spec: &FilterSpec, user_settings: &UserSettings) -> Option<Self> {
+        // Incomplete while still being typed, nothing is filtered then.
+        let (locator, comparison) = parse_numeric_condition(&filter_spec.term).ok()?;
+
+        let format_group = cached_regex(&user_settings.log_format.pattern, false)
+            .ok()
+            .and_then(|format_regex| {
+                let group_index = format_regex
+                    .capture_names()
+                    .position(|group_name| group_name == Some(locator.as_str()))?;
+                Some((format_regex, group_index))
+            });
+
+        let (locator_regex, group_index) = match format_group {
+            Some(format_group) => format_group,
+            None => {
+                let pattern = if filter_spec.whole_word {
+                    format!(r"\b(?:{})\b", locator)
+                } else {
+                    locator
+                };
+                let locator_regex = cached_regex(&pattern, !filter_spec.match_case).ok()?;
+                let group_index = if locator_regex.captures_len() > 1 {
+                    1
+                } else {
+                    0
+                };
+
+                (locator_regex, group_index)
+            }
+        };
+
+        Some(Self {
+            locator_regex,
+            group_index,
+            comparison,
+            negative: filter_spec.negative,
+        })
+    }
+
+    // Any of the numbers found in the line can match.
+    fn line_matches(&self, line: &LineVec) -> bool {
+        self.locator_regex
+            .captures_iter(&linevec_text(line))
+            .filter_map(|captures| {
+                let value = captures.get(self.group_index)?.as_str().trim();
+                value.parse::<f64>().ok().filter(|value| value.is_finite())
+            })
+            .any(|value| self.comparison.matches(value))
+    }
+}
+
+impl LineHandler for NumericFilterLineHandler {
+    fn handler_type(&self) -> LineHandlerType {
+        LineHandlerType::Filter
+    }
+
+    fn is_active(&self) -> bool {
+        true
+    }
 
-        if !matched {
-            // Line does not match, so it should be filtered out.
-            if !self.negative {
-                // We are not in negative mode, so we clear the line.
-                line.clear();
-            }
-        } else {
-            // Line matches, so it should be kept.
-            if self.negative {
-                // We are in negative mode, so we clear the line.
-                line.clear();
-            }
+    fn process_line(&mut self, line: &mut LineVec) {
+        if self.line_matches(line) == self.negative {
+            line.clear();
         }
     }
 
@@ -330,10 +1324,55 @@ impl LineHandler for FilterLineHandler {
     }
 }
 
+pub const SEARCH_HILIGHT_BACKGROUND: Color = Color::YELLOW;
+pub const SEARCH_HILIGHT_TEXT: Color = Color::BLACK;
+
+// How the search matches are marked, see search_hilight_split().
+#[derive(Clone, Copy)]
+pub struct SearchHilight {
+    pub style: SearchHighlightStyle,
+    pub background: Color,
+    pub text: Color,
+}
+
+impl SearchHilight {
+    pub fn new(user_settings: &UserSettings) -> Self {
+        Self {
+            style: user_settings.search_highlight_style,
+            background: user_settings.search_highlight_background,
+            text: user_settings.search_highlight_text,
+        }
+    }
+}
+
+// Marks the search matches at the split points, faded by given factor (1.0 for not faded).
+pub fn search_hilight_split(
+    line: &mut LineVec,
+    split_points: Vec<SplitPoint>,
+    hilight: SearchHilight,
+    fade: f32,
+) {
+    match hilight.style {
+        SearchHighlightStyle::Background => linevec_split(
+            line,
+            split_points,
+            Some(hilight.background.gamma_multiply(fade)),
+            Some(hilight.text.gamma_multiply(fade)),
+        ),
+        SearchHighlightStyle::Underline => {
+            linevec_split_with(line, split_points, |original_format| Style {
+                underline: hilight.background.gamma_multiply(fade),
+                ..original_format.clone()
+            })
+        }
+    }
+}
+
 pub struct SearchLineHandler {
     search_term: String,
     match_case: bool,
     whole_word: bool,
+    hilight: SearchHilight,
     points_of_interest: Vec<PointOfInterest>,
 }
 
@@ -347,9 +1386,24 @@ impl SearchLineHandler {
             search_term: user_settings.search_term.clone(),
             match_case: user_settings.search_match_case,
             whole_word: user_settings.search_whole_word,
+            hilight: SearchHilight::new(user_settings),
             points_of_interest: Vec::new(),
         })
     }
+
+    // The matches without recoloring the line, see process_line().
+    pub fn find_points_of_interest(&self, line: &LineVec) -> Vec<PointOfInterest> {
+        let split_points = linevec_find(line, &self.search_term, self.match_case, self.whole_word);
+
+        split_points
+            .into_iter()
+            .map(|split_point| PointOfInterest {
+                line: 0,     // To be filled by caller.
+                split_point, // This is invalid as soon as the coloring split is done...
+                byte_range: linevec_split_point_byte_range(line, &split_point),
+            })
+            .collect()
+    }
 }
 
 impl LineHandler for SearchLineHandler {
@@ -362,32 +1416,22 @@ impl LineHandler for SearchLineHandler {
             return false;
         }
 
-        return true;
+        true
     }
 
     fn process_line(&mut self, line: &mut LineVec) {
-        self.points_of_interest.clear(); // Clear previous points of interest.
-
-        let split_points = linevec_find(&line, &self.search_term, self.match_case, self.whole_word);
-        if split_points.is_empty() {
+        self.points_of_interest = self.find_points_of_interest(line);
+        if self.points_of_interest.is_empty() {
             return;
         }
 
-        // Record points of interest.
-        for split_point in split_points.iter() {
-            let poi = PointOfInterest {
-                line: 0,                          // To be filled by caller.
-                split_point: split_point.clone(), // This is invalid as soon as the coloring split is done...
-            };
-            self.points_of_interest.push(poi);
-        }
+        let split_points = self
+            .points_of_interest
+            .iter()
+            .map(|poi| poi.split_point)
+            .collect();
 
-        linevec_split(
-            line,
-            split_points.clone(),
-            Some(Color32::YELLOW),
-            Some(Color32::BLACK),
-        );
+        search_hilight_split(line, split_points, self.hilight, 1.0);
     }
 
     fn points_of_interest(&self) -> Vec<PointOfInterest> {
@@ -398,6 +1442,7 @@ impl LineHandler for SearchLineHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::user_settings::{LogFormat, RedactRule};
 
     fn filter_linehandler_make(
         filter_term: &str,
@@ -411,10 +1456,31 @@ mod tests {
             match_case,
             whole_word,
             negative,
-            extended,
+            expression: if extended {
+                Some(parse_filter_expression(filter_term).unwrap())
+            } else {
+                None
+            },
+            compiled_regex: None,
         }
     }
 
+    fn filter_regex_linehandler_make(
+        filter_term: &str,
+        match_case: bool,
+        whole_word: bool,
+    ) -> FilterLineHandler {
+        let filter_spec = FilterSpec {
+            term: filter_term.to_string(),
+            match_case,
+            whole_word,
+            regex: true,
+            ..Default::default()
+        };
+
+        FilterLineHandler::new(&filter_spec).unwrap()
+    }
+
     #[test]
     fn filter_empty_term_inactive() {
         let uut = filter_linehandler_make("", false, false, false, false);
@@ -426,7 +1492,7 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 0);
     }
@@ -436,13 +1502,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
         let mut line_no_match: LineVec =
-            vec![("This is a normal line".to_string(), TextFormat::default())];
+            vec![("This is a normal line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -452,15 +1518,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error line", false, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
-        let mut line_no_match: LineVec = vec![(
-            "This is an error message".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line_no_match: LineVec =
+            vec![("This is an error message".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -470,13 +1534,13 @@ mod tests {
         let mut uut = filter_linehandler_make("Error", true, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an Error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an Error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an Error line");
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -486,15 +1550,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, true, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
-        let mut line_no_match: LineVec = vec![(
-            "This is an erroring line".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line_no_match: LineVec =
+            vec![("This is an erroring line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -504,13 +1566,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, false, true, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is a normal line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is a normal line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is a normal line");
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -520,16 +1582,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, true, true, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![(
-            "This is an erroring line".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line: LineVec = vec![("This is an erroring line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an erroring line");
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -539,13 +1598,13 @@ mod tests {
         let mut uut = filter_linehandler_make("Error", true, false, true, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
         let mut line_no_match: LineVec =
-            vec![("This is an Error line".to_string(), TextFormat::default())];
+            vec![("This is an Error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -555,15 +1614,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error && line", false, false, false, true);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
-        let mut line_no_match: LineVec = vec![(
-            "This is an error message".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line_no_match: LineVec =
+            vec![("This is an error message".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -573,19 +1630,19 @@ mod tests {
         let mut uut = filter_linehandler_make("error || warning", false, false, false, true);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
         let mut line_warning: LineVec =
-            vec![("This is a warning line".to_string(), TextFormat::default())];
+            vec![("This is a warning line".to_string(), Style::default())];
         uut.process_line(&mut line_warning);
         assert_eq!(line_warning.len(), 1);
         assert_eq!(line_warning[0].0, "This is a warning line");
 
         let mut line_no_match: LineVec =
-            vec![("This is a normal line".to_string(), TextFormat::default())];
+            vec![("This is a normal line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -595,16 +1652,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error && line", false, false, true, true);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![(
-            "This is an error message".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line: LineVec = vec![("This is an error message".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error message");
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -614,18 +1668,18 @@ mod tests {
         let mut uut = filter_linehandler_make("error || warning", false, false, true, true);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is a normal line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is a normal line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is a normal line");
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
 
         let mut line_no_match_warning: LineVec =
-            vec![("This is a warning line".to_string(), TextFormat::default())];
+            vec![("This is a warning line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match_warning);
         assert_eq!(line_no_match_warning.len(), 0);
     }
@@ -635,13 +1689,13 @@ mod tests {
         let mut uut = filter_linehandler_make("error", false, false, false, true);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![("This is an error line".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error line");
 
         let mut line_no_match: LineVec =
-            vec![("This is a normal line".to_string(), TextFormat::default())];
+            vec![("This is a normal line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
@@ -651,21 +1705,18 @@ mod tests {
         let mut uut = filter_linehandler_make("error && warning", false, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![(
-            "This is an error warning".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line: LineVec = vec![("This is an error warning".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 0);
 
         let mut line_no_match: LineVec =
-            vec![("This is an error line".to_string(), TextFormat::default())];
+            vec![("This is an error line".to_string(), Style::default())];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
 
         let mut line = vec![(
             "This is a error && warning line".to_string(),
-            TextFormat::default(),
+            Style::default(),
         )];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
@@ -677,19 +1728,927 @@ mod tests {
         let mut uut = filter_linehandler_make("error || warning", false, false, false, false);
         assert!(uut.is_active());
 
-        let mut line: LineVec = vec![(
-            "This is an error || warning".to_string(),
-            TextFormat::default(),
-        )];
+        let mut line: LineVec = vec![("This is an error || warning".to_string(), Style::default())];
         uut.process_line(&mut line);
         assert_eq!(line.len(), 1);
         assert_eq!(line[0].0, "This is an error || warning");
 
         let mut line_no_match: LineVec = vec![(
             "This is a normal error or warning line".to_string(),
-            TextFormat::default(),
+            Style::default(),
         )];
         uut.process_line(&mut line_no_match);
         assert_eq!(line_no_match.len(), 0);
     }
+
+    #[test]
+    fn filter_regex() {
+        let mut uut = filter_regex_linehandler_make(r"error \d+", false, false);
+        assert!(uut.is_active());
+
+        let mut line: LineVec = vec![("Got ERROR 42 here".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line.len(), 1);
+
+        let mut line_no_match: LineVec =
+            vec![("Got error without code".to_string(), Style::default())];
+        uut.process_line(&mut line_no_match);
+        assert_eq!(line_no_match.len(), 0);
+    }
+
+    #[test]
+    fn filter_regex_with_match_case_and_whole_word() {
+        let mut uut = filter_regex_linehandler_make("err(or)?", true, true);
+
+        let mut line: LineVec = vec![("an err happened".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line.len(), 1);
+
+        let mut line_no_match: LineVec =
+            vec![("an ERROR and errors".to_string(), Style::default())];
+        uut.process_line(&mut line_no_match);
+        assert_eq!(line_no_match.len(), 0);
+    }
+
+    #[test]
+    fn filter_regex_invalid_not_created() {
+        let filter_spec = FilterSpec {
+            term: "error (".to_string(),
+            regex: true,
+            ..Default::default()
+        };
+
+        assert!(FilterLineHandler::new(&filter_spec).is_none());
+    }
+
+    #[test]
+    fn filters_applied_in_sequence() {
+        let user_settings = UserSettings {
+            filters: vec![
+                FilterSpec {
+                    term: "error|warn".to_string(),
+                    regex: true,
+                    ..Default::default()
+                },
+                FilterSpec {
+                    term: "heartbeat".to_string(),
+                    negative: true,
+                    ..Default::default()
+                },
+                FilterSpec::default(), // Empty ones are skipped.
+            ],
+            ..Default::default()
+        };
+
+        let content = crate::log_content::LogContent::from_string(
+            "error: disk full\nwarn: heartbeat late\ninfo: started\nwarn: low memory\n".to_string(),
+        );
+        let log_jobs = crate::recalculate_log_job(&content, &user_settings).unwrap();
+
+        let lines_visible: Vec<String> = (0..crate::visible_line_count(&log_jobs))
+            .map(|row_index| linevec_text(&crate::log_line(&log_jobs, row_index).unwrap()))
+            .collect();
+        assert_eq!(lines_visible, vec!["error: disk full", "warn: low memory"]);
+    }
+
+    fn filter_expression_matches(expression: &str, line: &str) -> bool {
+        let line: LineVec = vec![(line.to_string(), Style::default())];
+        parse_filter_expression(expression)
+            .unwrap()
+            .evaluate(&line, false, false)
+    }
+
+    fn term(term: &str) -> Box<FilterExpression> {
+        Box::new(FilterExpression::Term(term.to_string()))
+    }
+
+    #[test]
+    fn filter_expression_precedence() {
+        // "&&" binds tighter than "||".
+        assert_eq!(
+            parse_filter_expression("a || b && c").unwrap(),
+            FilterExpression::Or(
+                term("a"),
+                Box::new(FilterExpression::And(term("b"), term("c")))
+            )
+        );
+        assert_eq!(
+            parse_filter_expression("a && b || c").unwrap(),
+            FilterExpression::Or(
+                Box::new(FilterExpression::And(term("a"), term("b"))),
+                term("c")
+            )
+        );
+
+        assert!(filter_expression_matches(
+            "error || warn && heartbeat",
+            "error"
+        ));
+        assert!(!filter_expression_matches(
+            "error || warn && heartbeat",
+            "warn"
+        ));
+        assert!(filter_expression_matches(
+            "error || warn && heartbeat",
+            "warn heartbeat"
+        ));
+    }
+
+    #[test]
+    fn filter_expression_nesting() {
+        let expression = "(error || warn) && (disk || (net && timeout))";
+
+        assert!(filter_expression_matches(expression, "error: disk full"));
+        assert!(filter_expression_matches(expression, "warn: net timeout"));
+        assert!(!filter_expression_matches(expression, "warn: net down"));
+        assert!(!filter_expression_matches(expression, "info: disk full"));
+    }
+
+    #[test]
+    fn filter_expression_negation() {
+        let expression = "(error || warn) && !heartbeat";
+
+        assert!(filter_expression_matches(expression, "warn: disk full"));
+        assert!(!filter_expression_matches(
+            expression,
+            "warn: heartbeat late"
+        ));
+        assert!(!filter_expression_matches(expression, "info: started"));
+
+        assert!(filter_expression_matches("!!error", "error"));
+        assert!(filter_expression_matches("!(error && warn)", "error"));
+        assert!(!filter_expression_matches("!(error || warn)", "error"));
+    }
+
+    #[test]
+    fn filter_expression_terms_with_spaces_and_bangs() {
+        assert_eq!(
+            parse_filter_expression("  stack trace && failed! ").unwrap(),
+            FilterExpression::And(term("stack trace"), term("failed!"))
+        );
+    }
+
+    #[test]
+    fn filter_expression_malformed() {
+        for expression in [
+            "",
+            "error &&",
+            "|| error",
+            "(error || warn",
+            "error || warn)",
+            "()",
+            "!",
+            "error && && warn",
+        ] {
+            assert!(
+                parse_filter_expression(expression).is_err(),
+                "expression should be malformed: {}",
+                expression
+            );
+        }
+
+        let filter_spec = FilterSpec {
+            term: "(error".to_string(),
+            extended: true,
+            ..Default::default()
+        };
+        assert!(FilterLineHandler::new(&filter_spec).is_none());
+    }
+
+    #[test]
+    fn numeric_condition_parsed() {
+        assert_eq!(
+            parse_numeric_condition("latency > 500"),
+            Ok(("latency".to_string(), NumericComparison::Greater(500.0)))
+        );
+        assert_eq!(
+            parse_numeric_condition(r"took (\d+)ms<=2.5"),
+            Ok((
+                r"took (\d+)ms".to_string(),
+                NumericComparison::LessOrEqual(2.5)
+            ))
+        );
+        assert_eq!(
+            parse_numeric_condition("(?<code>\\d+) == -1"),
+            Ok(("(?<code>\\d+)".to_string(), NumericComparison::Equal(-1.0)))
+        );
+        assert_eq!(
+            parse_numeric_condition("status 400..499"),
+            Ok(("status".to_string(), NumericComparison::Range(400.0, 499.0)))
+        );
+        assert!(parse_numeric_condition("status 499..400").is_err());
+        assert!(parse_numeric_condition("> 5").is_err());
+        assert!(parse_numeric_condition("latency").is_err());
+        assert!(parse_numeric_condition("latency > x").is_err());
+    }
+
+    #[test]
+    fn numeric_filter_compares_values() {
+        let mut user_settings = UserSettings::default();
+        user_settings.log_format.pattern = r"^(?<level>\w+) latency=(?<latency>\S+)".to_string();
+
+        let lines_kept = |term: &str, negative: bool| -> Vec<&str> {
+            let filter_spec = FilterSpec {
+                term: term.to_string(),
+                negative,
+                numeric: true,
+                ..Default::default()
+            };
+            let mut handler = NumericFilterLineHandler::new(&filter_spec, &user_settings).unwrap();
+            [
+                "INFO latency=120 took 5ms",
+                "WARN latency=950.5 took 30ms",
+                "INFO latency=n/a took 1ms",
+                "ERROR failed",
+            ]
+            .into_iter()
+            .filter(|line| {
+                let mut line_parts: LineVec = vec![(line.to_string(), Style::default())];
+                handler.process_line(&mut line_parts);
+                !line_parts.is_empty()
+            })
+            .collect()
+        };
+
+        // The log format group first, a regex of its own otherwise.
+        assert_eq!(
+            lines_kept("latency > 500", false),
+            vec!["WARN latency=950.5 took 30ms"]
+        );
+        assert_eq!(
+            lines_kept("latency 100..950.5", false),
+            vec!["INFO latency=120 took 5ms", "WARN latency=950.5 took 30ms"]
+        );
+        assert_eq!(
+            lines_kept(r"TOOK (\d+)ms < 10", false),
+            vec!["INFO latency=120 took 5ms", "INFO latency=n/a took 1ms"]
+        );
+        assert_eq!(
+            lines_kept(r"took (\d+)ms < 10", true),
+            vec!["WARN latency=950.5 took 30ms", "ERROR failed"]
+        );
+
+        // Not a number, not a match.
+        assert_eq!(lines_kept("latency == 0", false), Vec::<&str>::new());
+        assert!(
+            NumericFilterLineHandler::new(
+                &FilterSpec {
+                    term: "latency >".to_string(),
+                    numeric: true,
+                    ..Default::default()
+                },
+                &user_settings
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn log_format_colors_named_groups_by_name() {
+        let user_settings = UserSettings {
+            log_format: LogFormat {
+                pattern: r"^(?P<level>\w+)(:)(?P<msg>.*)$".to_string(),
+                // Stored in a different order than the groups appear in the pattern.
+                pattern_coloring: vec![Color::BLUE, Color::RED, Color::GREEN],
+                pattern_coloring_text: vec![Color::WHITE; 3],
+                pattern_coloring_text_use_original: vec![false; 3],
+                pattern_hidden: vec![false; 3],
+                pattern_group_names: vec!["msg".to_string(), "".to_string(), "level".to_string()],
+                keep_uncaptured_text: false,
+            },
+            ..Default::default()
+        };
+
+        let mut uut = LogFormatLineHandler::new(&user_settings).unwrap();
+        let mut line: LineVec = vec![("error: disk full".to_string(), Style::default())];
+        uut.process_line(&mut line);
+
+        let colors: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                ("error", Color::GREEN),
+                (":", Color::RED),
+                (" disk full", Color::BLUE)
+            ]
+        );
+    }
+
+    #[test]
+    fn log_format_uncaptured_text_kept() {
+        let mut user_settings = UserSettings {
+            log_format: LogFormat {
+                pattern: r"(?P<time>\d\d:\d\d) \[(?P<level>\w+)\]".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(&user_settings.log_format.pattern).unwrap());
+
+        let original_format = Style {
+            color: Color::YELLOW,
+            ..Default::default()
+        };
+        let process = |user_settings: &UserSettings| {
+            let mut uut = LogFormatLineHandler::new(user_settings).unwrap();
+            let mut line: LineVec = vec![(
+                "> 12:00 [error] disk full".to_string(),
+                original_format.clone(),
+            )];
+            uut.process_line(&mut line);
+            line
+        };
+
+        let line = process(&user_settings);
+        let parts: Vec<_> = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        assert_eq!(parts, vec!["12:00", "error"]);
+
+        user_settings.log_format.keep_uncaptured_text = true;
+        let line = process(&user_settings);
+        let parts: Vec<_> = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        assert_eq!(parts, vec!["> ", "12:00", " [", "error", "] disk full"]);
+        assert_eq!(linevec_text(&line), "> 12:00 [error] disk full");
+        assert!(line[0].1 == original_format);
+        assert!(line[1].1.background == Color::RED);
+        assert!(line[4].1 == original_format);
+    }
+
+    #[test]
+    fn log_format_on_multi_part_line() {
+        let user_settings = UserSettings {
+            log_format: LogFormat {
+                pattern: r"^(\w+): (.*)$".to_string(),
+                pattern_coloring: vec![Color::BLUE, Color::GREEN],
+                pattern_coloring_text: vec![Color::WHITE; 2],
+                pattern_coloring_text_use_original: vec![true; 2],
+                pattern_hidden: vec![false; 2],
+                pattern_group_names: vec![String::new(); 2],
+                keep_uncaptured_text: true,
+            },
+            ..Default::default()
+        };
+
+        let red_text = Style {
+            color: Color::RED,
+            ..Default::default()
+        };
+        let mut uut = LogFormatLineHandler::new(&user_settings).unwrap();
+        let mut line: LineVec = vec![
+            ("err".to_string(), Style::default()),
+            ("or: disk".to_string(), red_text.clone()),
+            (" full".to_string(), Style::default()),
+        ];
+        uut.process_line(&mut line);
+        assert!(uut.line_matched());
+
+        let parts: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| {
+                (part_str.as_str(), part_format.background, part_format.color)
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("error", Color::BLUE, Style::default().color),
+                (": ", Color::TRANSPARENT, Color::RED),
+                ("disk full", Color::GREEN, Color::RED),
+            ]
+        );
+
+        let mut empty_line: LineVec = Vec::new();
+        uut.process_line(&mut empty_line);
+        assert!(!uut.line_matched());
+        assert!(empty_line.is_empty());
+    }
+
+    #[test]
+    fn log_format_hidden_groups_skipped() {
+        let user_settings = UserSettings {
+            log_format: LogFormat {
+                pattern: r"^(?P<time>\S+ )(?P<level>\w+)(:)(?P<msg>.*)$".to_string(),
+                pattern_coloring: vec![Color::BLUE, Color::RED, Color::GREEN, Color::YELLOW],
+                pattern_coloring_text: vec![Color::WHITE; 4],
+                pattern_coloring_text_use_original: vec![false; 4],
+                pattern_hidden: vec![true, false, false, false],
+                pattern_group_names: vec![
+                    "time".to_string(),
+                    "level".to_string(),
+                    "".to_string(),
+                    "msg".to_string(),
+                ],
+                keep_uncaptured_text: false,
+            },
+            ..Default::default()
+        };
+
+        let mut uut = LogFormatLineHandler::new(&user_settings).unwrap();
+        let mut line: LineVec = vec![(
+            "12:00:01.123 error: disk full".to_string(),
+            Style::default(),
+        )];
+        uut.process_line(&mut line);
+
+        let colors: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                ("error", Color::RED),
+                (":", Color::GREEN),
+                (" disk full", Color::YELLOW)
+            ]
+        );
+    }
+
+    #[test]
+    fn redact_rules_replace_matches() {
+        let user_settings = UserSettings {
+            redact_rules: vec![
+                RedactRule {
+                    pattern: r"\d+\.\d+\.\d+\.\d+".to_string(),
+                    replacement: "x.x.x.x".to_string(),
+                },
+                RedactRule {
+                    pattern: r"(password)=\S+".to_string(),
+                    replacement: "$1=***".to_string(),
+                },
+                // Invalid, skipped.
+                RedactRule {
+                    pattern: r"(".to_string(),
+                    replacement: String::new(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut uut = RedactLineHandler::new(&user_settings).unwrap();
+        let mut line: LineVec = vec![(
+            "from 10.0.0.1 password=hunter2 to 10.0.0.2".to_string(),
+            Style::default(),
+        )];
+        uut.process_line(&mut line);
+        assert_eq!(linevec_text(&line), "from x.x.x.x password=*** to x.x.x.x");
+
+        let no_rules = UserSettings {
+            redact_rules: vec![RedactRule::default()],
+            ..Default::default()
+        };
+        assert!(RedactLineHandler::new(&no_rules).is_none());
+    }
+
+    #[test]
+    fn level_highlight_colors_whole_line() {
+        let user_settings = UserSettings {
+            level_colors: vec![
+                ("ERROR".to_string(), Color::RED),
+                ("INFO".to_string(), Color::TRANSPARENT),
+            ],
+            ..Default::default()
+        };
+        let mut uut = LevelHighlightLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![
+            ("12:00 ".to_string(), Style::default()),
+            (
+                "ERROR".to_string(),
+                Style {
+                    background: Color::BLUE,
+                    ..Default::default()
+                },
+            ),
+            (" disk full".to_string(), Style::default()),
+        ];
+        uut.process_line(&mut line);
+
+        let backgrounds: Vec<_> = line
+            .iter()
+            .map(|(_, part_format)| part_format.background)
+            .collect();
+        assert_eq!(backgrounds, vec![Color::RED, Color::BLUE, Color::RED]);
+    }
+
+    #[test]
+    fn level_highlight_first_keyword_wins() {
+        let user_settings = UserSettings {
+            level_colors: vec![
+                ("ERROR".to_string(), Color::RED),
+                ("INFO".to_string(), Color::TRANSPARENT),
+            ],
+            ..Default::default()
+        };
+        let mut uut = LevelHighlightLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![("INFO: ERROR count 0".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line[0].1.background, Color::TRANSPARENT);
+
+        // Whole words only.
+        let mut line: LineVec = vec![("NOERRORS here".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line[0].1.background, Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn level_highlight_custom_levels() {
+        // Added in the settings next to the default ones, the row just added has no keyword yet.
+        let mut user_settings = UserSettings::default();
+        user_settings
+            .level_colors
+            .push(("NOTICE".to_string(), Color::BLUE));
+        user_settings
+            .level_colors
+            .push((String::new(), Color::GREEN));
+        let mut uut = LevelHighlightLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![("12:00 NOTICE disk 80%".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line[0].1.background, Color::BLUE);
+
+        let mut line: LineVec = vec![("12:00 disk 80%".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line[0].1.background, Color::TRANSPARENT);
+
+        // The default ones can be removed.
+        let user_settings = UserSettings {
+            level_colors: vec![("CRIT".to_string(), Color::RED)],
+            ..Default::default()
+        };
+        let mut uut = LevelHighlightLineHandler::new(&user_settings).unwrap();
+        let mut line: LineVec = vec![("12:00 ERROR CRIT".to_string(), Style::default())];
+        uut.process_line(&mut line);
+        assert_eq!(line[0].1.background, Color::RED);
+    }
+
+    #[test]
+    fn token_hilight_whole_word_and_case() {
+        let token_color = |token: &str, match_case: bool, whole_word: bool| TokenColor {
+            token: token.to_string(),
+            color: Color::RED,
+            match_case,
+            whole_word,
+            regex: false,
+        };
+        let hilighted_parts = |token_colors: Vec<TokenColor>, line_str: &str| {
+            let user_settings = UserSettings {
+                token_colors,
+                ..Default::default()
+            };
+            let mut uut = TokenHilightLineHandler::new(&user_settings).unwrap();
+
+            let mut line: LineVec = vec![(line_str.to_string(), Style::default())];
+            uut.process_line(&mut line);
+
+            line.into_iter()
+                .filter(|(_, part_format)| part_format.background == Color::RED)
+                .map(|(part_str, _)| part_str)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            hilighted_parts(vec![token_color("io", true, false)], "region io"),
+            vec!["io", "io"]
+        );
+        assert_eq!(
+            hilighted_parts(vec![token_color("io", true, true)], "region io"),
+            vec!["io"]
+        );
+        assert_eq!(
+            hilighted_parts(vec![token_color("io", true, true)], "region IO"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            hilighted_parts(vec![token_color("io", false, true)], "region IO"),
+            vec!["IO"]
+        );
+    }
+
+    #[test]
+    fn token_hilight_regex_first_rule_wins() {
+        let token_color = |token: &str, color: Color, regex: bool| TokenColor {
+            token: token.to_string(),
+            color,
+            match_case: true,
+            whole_word: false,
+            regex,
+        };
+        let user_settings = UserSettings {
+            token_colors: vec![
+                token_color(r"0x[0-9a-f]+", Color::RED, true),
+                token_color(r"[0-9a-f]{4}", Color::BLUE, true),
+                token_color("dead", Color::GREEN, false),
+                token_color("(", Color::YELLOW, true), // invalid, ignored
+            ],
+            ..Default::default()
+        };
+        let mut uut = TokenHilightLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![("at 0x1f00 and beef, dead".to_string(), Style::default())];
+        uut.process_line(&mut line);
+
+        let hilighted: Vec<_> = line
+            .iter()
+            .filter(|(_, part_format)| part_format.background != Color::TRANSPARENT)
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+
+        // Plain tokens go first, then the regexes in order; "1f00" is already taken by "0x1f00".
+        assert_eq!(
+            hilighted,
+            vec![
+                ("0x1f00", Color::RED),
+                ("beef", Color::BLUE),
+                ("dead", Color::GREEN)
+            ]
+        );
+    }
+
+    #[test]
+    fn token_hilight_overlapping_rules() {
+        // These used to panic in linevec_split or mangle the line text.
+        let cases = [
+            (" aéééécé", vec!["cbb", "b", "éc c", "é"]),
+            ("abcd abcd", vec!["abc", "bcd", "cd", "d a"]),
+            ("error: errors", vec!["rror", "error", "or: e", "s"]),
+        ];
+
+        for (line_str, tokens) in cases {
+            for (match_case, whole_word) in [(true, false), (false, false), (true, true)] {
+                let user_settings = UserSettings {
+                    token_colors: tokens
+                        .iter()
+                        .map(|token| TokenColor {
+                            token: token.to_string(),
+                            color: Color::RED,
+                            match_case,
+                            whole_word,
+                            regex: false,
+                        })
+                        .collect(),
+                    search_term: "b".to_string(),
+                    ..Default::default()
+                };
+                let mut token_handler = TokenHilightLineHandler::new(&user_settings).unwrap();
+                let mut search_handler = SearchLineHandler::new(&user_settings).unwrap();
+
+                let mut line: LineVec = vec![(line_str.to_string(), Style::default())];
+                token_handler.process_line(&mut line);
+                search_handler.process_line(&mut line);
+
+                let line_text: String =
+                    line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+                assert_eq!(line_text, line_str);
+            }
+        }
+    }
+
+    #[test]
+    fn handler_order_validated() {
+        assert_eq!(handler_order_problem(&DEFAULT_HANDLER_ORDER), None);
+
+        let reordered = [
+            LineHandlerType::Redact,
+            LineHandlerType::LogFormat,
+            LineHandlerType::LevelHighlight,
+            LineHandlerType::KeyValue,
+            LineHandlerType::JsonHighlight,
+            LineHandlerType::Search,
+            LineHandlerType::TokenHilight,
+        ];
+        assert_eq!(handler_order_problem(&reordered), None);
+
+        let mut missing = DEFAULT_HANDLER_ORDER.to_vec();
+        missing.pop();
+        assert!(handler_order_problem(&missing).is_some());
+
+        let mut with_filter = DEFAULT_HANDLER_ORDER.to_vec();
+        with_filter[6] = LineHandlerType::Filter;
+        assert!(handler_order_problem(&with_filter).is_some());
+
+        let mut redact_later = DEFAULT_HANDLER_ORDER.to_vec();
+        redact_later.swap(0, 1);
+        assert!(handler_order_problem(&redact_later).is_some());
+
+        let mut tokens_before_log_format = DEFAULT_HANDLER_ORDER.to_vec();
+        let token_hilight = tokens_before_log_format.remove(5);
+        tokens_before_log_format.insert(1, token_hilight);
+        assert_eq!(
+            handler_order_problem(&tokens_before_log_format).unwrap(),
+            "log format has to run before token colors"
+        );
+
+        let mut search_before_key_value = DEFAULT_HANDLER_ORDER.to_vec();
+        search_before_key_value.swap(4, 6);
+        assert_eq!(
+            handler_order_problem(&search_before_key_value).unwrap(),
+            "search has to run after key-value pairs"
+        );
+    }
+
+    #[test]
+    fn search_underline_keeps_token_colors() {
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            search_highlight_style: SearchHighlightStyle::Underline,
+            ..Default::default()
+        };
+        user_settings.token_colors[0].token = "disk full".to_string();
+        user_settings.token_colors[0].color = Color::RED;
+        let mut token_handler = TokenHilightLineHandler::new(&user_settings).unwrap();
+        let mut search_handler = SearchLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![("error: disk full".to_string(), Style::default())];
+        token_handler.process_line(&mut line);
+        search_handler.process_line(&mut line);
+
+        let underlined: Vec<&(String, Style)> = line
+            .iter()
+            .filter(|(_, part_format)| !part_format.underline.is_transparent())
+            .collect();
+        assert_eq!(underlined.len(), 1);
+        assert_eq!(underlined[0].0, "disk");
+        assert_eq!(underlined[0].1.underline, SEARCH_HILIGHT_BACKGROUND);
+        assert_eq!(underlined[0].1.background, Color::RED);
+        assert_eq!(linevec_text(&line), "error: disk full");
+    }
+
+    #[test]
+    fn token_hilight_overlap_longest_wins() {
+        let user_settings = UserSettings {
+            token_colors: vec![
+                TokenColor {
+                    token: "bcd".to_string(),
+                    color: Color::BLUE,
+                    ..TokenColor::new_empty(0)
+                },
+                TokenColor {
+                    token: "abc".to_string(),
+                    color: Color::RED,
+                    ..TokenColor::new_empty(1)
+                },
+                TokenColor {
+                    token: "abcd".to_string(),
+                    color: Color::GREEN,
+                    ..TokenColor::new_empty(2)
+                },
+            ],
+            ..Default::default()
+        };
+        let mut uut = TokenHilightLineHandler::new(&user_settings).unwrap();
+
+        let mut line: LineVec = vec![("abcd abc bcd".to_string(), Style::default())];
+        uut.process_line(&mut line);
+
+        let hilighted: Vec<_> = line
+            .iter()
+            .filter(|(_, part_format)| part_format.background != Color::TRANSPARENT)
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            hilighted,
+            vec![
+                ("abcd", Color::GREEN),
+                ("abc", Color::RED),
+                ("bcd", Color::BLUE)
+            ]
+        );
+    }
+
+    fn json_handler_make(pretty_print: bool) -> JsonHighlightLineHandler {
+        let mut json_handler = JsonHighlightLineHandler::new(&UserSettings {
+            json_mode: true,
+            expanded_json_lines: [2].into(),
+            ..Default::default()
+        })
+        .unwrap();
+        json_handler.set_original_line_no(if pretty_print { 2 } else { 1 });
+
+        json_handler
+    }
+
+    #[test]
+    fn json_line_highlighted() {
+        let line_text = r#"{"level": "warn", "retries": 3, "ok": false, "tags": []}"#;
+        let mut line: LineVec = vec![(line_text.to_string(), Style::default())];
+
+        json_handler_make(false).process_line(&mut line);
+        assert_eq!(linevec_text(&line), line_text);
+
+        let color_of = |token: &str| {
+            line.iter()
+                .find(|(part_str, _)| part_str == token)
+                .map(|(_, part_format)| part_format.color)
+        };
+        assert_eq!(color_of("\"level\""), Some(JSON_KEY_COLOR));
+        assert_eq!(color_of("\"warn\""), Some(JSON_STRING_COLOR));
+        assert_eq!(color_of("3"), Some(JSON_NUMBER_COLOR));
+        assert_eq!(color_of("false"), Some(JSON_LITERAL_COLOR));
+        assert_eq!(color_of("{"), Some(Style::default().color));
+    }
+
+    #[test]
+    fn json_line_pretty_printed() {
+        let line_text = r#"{"msg":"a \"quoted\", b","values":[1,-2.5e3],"empty":{}}"#;
+        let mut line: LineVec = vec![(line_text.to_string(), Style::default())];
+
+        json_handler_make(true).process_line(&mut line);
+        assert_eq!(
+            linevec_text(&line),
+            "{\n  \"msg\": \"a \\\"quoted\\\", b\",\n  \"values\": [\n    1,\n    -2.5e3\n  ],\n  \"empty\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn non_json_line_untouched() {
+        for line_text in [
+            "{not json}",
+            "plain line",
+            "42",
+            "\"just a string\"",
+            "{\"cut\": ",
+        ] {
+            let mut line: LineVec = vec![(line_text.to_string(), Style::default())];
+            let line_before = line.clone();
+
+            json_handler_make(true).process_line(&mut line);
+            assert!(line == line_before, "{}", line_text);
+        }
+    }
+
+    fn key_value_handler_make(separators: &str, hidden_keys: &str) -> KeyValueLineHandler {
+        KeyValueLineHandler::new(&UserSettings {
+            key_value_pairs: true,
+            key_value_separators: separators.to_string(),
+            key_value_hidden_keys: hidden_keys.to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn key_value_pairs_colored() {
+        let line_text = r#"12:00:01 request id=42, path: /api user="john doe" done"#;
+        let mut line: LineVec = vec![(line_text.to_string(), Style::default())];
+
+        key_value_handler_make("=:", "").process_line(&mut line);
+        assert_eq!(linevec_text(&line), line_text);
+
+        let colored_parts: Vec<_> = line
+            .iter()
+            .filter(|(_, part_format)| part_format.color != Style::default().color)
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.color))
+            .collect();
+        assert_eq!(
+            colored_parts,
+            vec![
+                ("id", KEY_VALUE_KEY_COLOR),
+                ("42", KEY_VALUE_VALUE_COLOR),
+                ("path", KEY_VALUE_KEY_COLOR),
+                ("/api", KEY_VALUE_VALUE_COLOR),
+                ("user", KEY_VALUE_KEY_COLOR),
+                ("\"john doe\"", KEY_VALUE_VALUE_COLOR),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_value_pairs_hidden() {
+        let mut line: LineVec = vec![(
+            "pid=123 level=warn tid=7 msg=retrying".to_string(),
+            Style::default(),
+        )];
+
+        key_value_handler_make("=", "pid, tid").process_line(&mut line);
+        assert_eq!(linevec_text(&line), "level=warn msg=retrying");
+    }
+
+    #[test]
+    fn key_value_separators_configurable() {
+        let line_text = "a=1 b:2";
+
+        let mut line: LineVec = vec![(line_text.to_string(), Style::default())];
+        key_value_handler_make(":", "").process_line(&mut line);
+        let keys: Vec<_> = line
+            .iter()
+            .filter(|(_, part_format)| part_format.color == KEY_VALUE_KEY_COLOR)
+            .map(|(part_str, _)| part_str.as_str())
+            .collect();
+        assert_eq!(keys, vec!["b"]);
+
+        assert!(
+            KeyValueLineHandler::new(&UserSettings {
+                key_value_pairs: true,
+                key_value_separators: " ".to_string(),
+                ..Default::default()
+            })
+            .is_none()
+        );
+    }
 }
@@ -0,0 +1,310 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::encoding;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A source of log lines produced on a background thread (i.e. a followed file, stdin). Only complete
+// lines (terminated with a newline) are sent, partial lines are kept in the background thread
+// until the rest of the line arrives or the stream ends.
+pub struct LineStream {
+    receiver: mpsc::Receiver<String>,
+    stop_requested: Arc<AtomicBool>,
+    ended: Arc<AtomicBool>, // nothing more will be read, i.e. the peer disconnected
+    child: Option<Child>,   // the process the lines are read from, killed with the stream
+}
+
+impl LineStream {
+    // Follows the file at given path with "tail -f" semantics, starting at byte offset.
+    pub fn follow_file(path: &str, start_offset: u64, encoding: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let path = path.to_string();
+        let encoding = encoding.to_string();
+        let thread_stop_requested = stop_requested.clone();
+
+        thread::spawn(move || {
+            let mut offset = start_offset;
+            let mut pending: Vec<u8> = Vec::new();
+
+            while !thread_stop_requested.load(Ordering::Relaxed) {
+                match read_new_bytes(&path, &mut offset) {
+                    Ok(new_bytes) => pending.extend_from_slice(&new_bytes),
+                    Err(e) => eprintln!("Failed to follow file: {}, error: {}", path, e),
+                }
+
+                for line in take_complete_lines(&mut pending, &encoding) {
+                    if sender.send(line).is_err() {
+                        // Nobody listens anymore.
+                        return;
+                    }
+                }
+
+                thread::sleep(FOLLOW_POLL_INTERVAL);
+            }
+        });
+
+        Self {
+            receiver,
+            stop_requested,
+            ended: Arc::new(AtomicBool::new(false)),
+            child: None,
+        }
+    }
+
+    // Reads lines from the reader until it's exhausted, i.e. stdin of a "journalctl -f | logalyzer".
+    pub fn from_reader(mut reader: impl BufRead + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let ended = Arc::new(AtomicBool::new(false));
+
+        let thread_stop_requested = stop_requested.clone();
+        let thread_ended = ended.clone();
+
+        thread::spawn(move || {
+            let mut line_bytes = Vec::new();
+
+            while !thread_stop_requested.load(Ordering::Relaxed) {
+                match reader.read_until(b'\n', &mut line_bytes) {
+                    Ok(0) => {
+                        // End of stream, the partial line read before a timeout is the last one.
+                        if !line_bytes.is_empty() {
+                            let _ =
+                                sender.send(bytes_to_line(&line_bytes, encoding::ENCODING_UTF8));
+                        }
+                        break;
+                    }
+                    Ok(_) => {
+                        let line = bytes_to_line(&line_bytes, encoding::ENCODING_UTF8);
+                        line_bytes.clear();
+                        if sender.send(line).is_err() {
+                            // Nobody listens anymore.
+                            break;
+                        }
+                    }
+                    // A read timeout only gives a chance to check the stop request, the bytes read
+                    // so far are kept in line_bytes.
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        eprintln!("Failed to read stream, error: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            thread_ended.store(true, Ordering::Relaxed);
+        });
+
+        Self {
+            receiver,
+            stop_requested,
+            ended,
+            child: None,
+        }
+    }
+
+    // Spawns the command and reads lines from its stdout until it exits.
+    pub fn from_command(mut command: Command) -> std::io::Result<Self> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            std::io::Error::new(ErrorKind::BrokenPipe, "command stdout not captured")
+        })?;
+
+        let mut line_stream = Self::from_reader(BufReader::new(stdout));
+        line_stream.child = Some(child);
+
+        Ok(line_stream)
+    }
+
+    // Connects to the "host:port" and reads lines until the peer disconnects.
+    pub fn connect_tcp(address: &str) -> std::io::Result<Self> {
+        let socket_addr = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "address did not resolve")
+        })?;
+        let tcp_stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)?;
+        // Otherwise a quiet peer would block the reader thread forever, even after a stop request.
+        tcp_stream.set_read_timeout(Some(FOLLOW_POLL_INTERVAL))?;
+
+        Ok(Self::from_reader(BufReader::new(tcp_stream)))
+    }
+
+    // Returns all lines received since the last call, never blocks.
+    pub fn poll_lines(&self) -> Vec<String> {
+        self.receiver.try_iter().collect()
+    }
+
+    // The lines received before the end may still be waiting for poll_lines().
+    pub fn has_ended(&self) -> bool {
+        self.ended.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for LineStream {
+    fn drop(&mut self) {
+        self.stop_requested.store(true, Ordering::Relaxed);
+
+        // The reader thread is blocked on the stdout until the process exits.
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_new_bytes(path: &str, offset: &mut u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+
+    let file_len = file.metadata()?.len();
+    if file_len < *offset {
+        // The file was truncated (i.e. rotated), start over from the beginning like tail does.
+        eprintln!("File truncated: {}", path);
+        *offset = 0;
+    }
+
+    file.seek(SeekFrom::Start(*offset))?;
+
+    let mut new_bytes = Vec::new();
+    let bytes_read = file.read_to_end(&mut new_bytes)?;
+    *offset += bytes_read as u64;
+
+    Ok(new_bytes)
+}
+
+// Removes all newline-terminated lines from the buffer and returns them, the unterminated rest
+// of the buffer is left in place.
+fn take_complete_lines(pending: &mut Vec<u8>, encoding: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    while let Some(newline_pos) = pending.iter().position(|b| *b == b'\n') {
+        let line_bytes: Vec<u8> = pending.drain(..=newline_pos).collect();
+        lines.push(bytes_to_line(&line_bytes, encoding));
+    }
+
+    lines
+}
+
+// Converts raw line bytes to a line without the line terminator.
+fn bytes_to_line(line_bytes: &[u8], encoding: &str) -> String {
+    let line_bytes = line_bytes.strip_suffix(b"\n").unwrap_or(line_bytes);
+    let line_bytes = line_bytes.strip_suffix(b"\r").unwrap_or(line_bytes);
+
+    encoding::decode(line_bytes, encoding).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_lines_are_taken() {
+        let mut pending = b"first\nsecond\r\nthird".to_vec();
+
+        let lines = take_complete_lines(&mut pending, encoding::ENCODING_UTF8);
+        assert_eq!(lines, vec!["first".to_string(), "second".to_string()]);
+        assert_eq!(pending, b"third".to_vec());
+    }
+
+    #[test]
+    fn partial_line_is_kept_until_newline() {
+        let mut pending = b"partial".to_vec();
+        assert!(take_complete_lines(&mut pending, encoding::ENCODING_UTF8).is_empty());
+
+        pending.extend_from_slice(b" line\n");
+        let lines = take_complete_lines(&mut pending, encoding::ENCODING_UTF8);
+        assert_eq!(lines, vec!["partial line".to_string()]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn complete_lines_are_decoded() {
+        let mut pending = b"caf\xe9\n".to_vec();
+
+        let lines = take_complete_lines(&mut pending, encoding::ENCODING_LATIN1);
+        assert_eq!(lines, vec!["café".to_string()]);
+    }
+
+    #[test]
+    fn reader_lines_are_streamed() {
+        let reader = std::io::Cursor::new(b"first\nsecond\r\nunterminated".to_vec());
+        let line_stream = LineStream::from_reader(reader);
+
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines.append(&mut line_stream.poll_lines());
+            if lines.len() == 3 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(lines, vec!["first", "second", "unterminated"]);
+    }
+
+    #[test]
+    fn tcp_lines_are_streamed_until_disconnect() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let peer = thread::spawn(move || {
+            use std::io::Write;
+
+            let (mut tcp_stream, _) = listener.accept().unwrap();
+            tcp_stream.write_all(b"first\nsec").unwrap();
+            // Longer than the read timeout, the partial line has to survive it.
+            thread::sleep(FOLLOW_POLL_INTERVAL * 2);
+            tcp_stream.write_all(b"ond\nlast").unwrap();
+        });
+
+        let line_stream = LineStream::connect_tcp(&address).unwrap();
+        peer.join().unwrap();
+
+        let mut lines = Vec::new();
+        for _ in 0..200 {
+            lines.append(&mut line_stream.poll_lines());
+            if line_stream.has_ended() {
+                lines.append(&mut line_stream.poll_lines());
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(line_stream.has_ended());
+        assert_eq!(lines, vec!["first", "second", "last"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_output_is_streamed() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "printf 'first\\nsecond\\n'"]);
+        let line_stream = LineStream::from_command(command).unwrap();
+
+        let mut lines = Vec::new();
+        for _ in 0..200 {
+            lines.append(&mut line_stream.poll_lines());
+            if line_stream.has_ended() {
+                lines.append(&mut line_stream.poll_lines());
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(lines, vec!["first", "second"]);
+        assert!(LineStream::from_command(Command::new("logalyzer-missing-command")).is_err());
+    }
+}
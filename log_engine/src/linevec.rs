@@ -1,36 +1,102 @@
-use egui::TextFormat;
+use crate::style::{Color, Style};
 
-pub type LineVec = Vec<(String, TextFormat)>;
+pub type LineVec = Vec<(String, Style)>;
 pub type SplitPointPartial = (usize, usize); // (index in linevec, starting/ending offset in part)
 pub type SplitPoint = (SplitPointPartial, SplitPointPartial); // (start split, end split)
 
+// The whole line text, without the coloring.
+pub fn linevec_text(line: &LineVec) -> String {
+    line.iter().map(|(part_str, _)| part_str.as_str()).collect()
+}
+
+fn parts_offsets_split_point(
+    parts_offsets: &[(usize, usize, usize)], // (index in linevec, part start, part end)
+    byte_range: std::ops::Range<usize>,
+) -> SplitPoint {
+    let mut start_split: SplitPointPartial = (0, 0);
+    let mut end_split: SplitPointPartial = (0, 0);
+
+    for (i, part_start, part_end) in parts_offsets {
+        if byte_range.start >= *part_start && byte_range.start < *part_end {
+            start_split = (*i, byte_range.start - part_start);
+        }
+
+        if byte_range.end > *part_start && byte_range.end <= *part_end {
+            end_split = (*i, byte_range.end - part_start);
+        }
+    }
+
+    (start_split, end_split)
+}
+
+fn linevec_parts_offsets(line: &LineVec) -> Vec<(usize, usize, usize)> {
+    let mut parts_offsets = Vec::new();
+    let mut current_offset = 0;
+    for (i, (part_str, _)) in line.iter().enumerate() {
+        let part_len = part_str.len();
+        parts_offsets.push((i, current_offset, current_offset + part_len));
+        current_offset += part_len;
+    }
+
+    parts_offsets
+}
+
+// Lowercasing may change the byte length of a character (i.e. "İ" becomes "i̇"), so for every
+// byte of the lowercased text this keeps the byte range of the original character it came from.
+fn lowercase_with_origins(text: &str) -> (String, Vec<std::ops::Range<usize>>) {
+    let mut lowercased = String::with_capacity(text.len());
+    let mut origins = Vec::with_capacity(text.len());
+
+    for (original_pos, c) in text.char_indices() {
+        let original_range = original_pos..original_pos + c.len_utf8();
+        for lowercase_c in c.to_lowercase() {
+            lowercased.push(lowercase_c);
+            origins.extend(std::iter::repeat_n(
+                original_range.clone(),
+                lowercase_c.len_utf8(),
+            ));
+        }
+    }
+
+    (lowercased, origins)
+}
+
 pub fn linevec_find(
     line: &LineVec,
     search_term: &str,
     match_case: bool,
     match_whole_word: bool,
 ) -> Vec<SplitPoint> {
-    let combined_str = if match_case {
-        line.iter().map(|(s, _)| s.as_str()).collect::<String>()
+    if search_term.is_empty() {
+        return Vec::new();
+    }
+
+    let original_str = line.iter().map(|(s, _)| s.as_str()).collect::<String>();
+
+    // Lowercased per character on both sides, str::to_lowercase is context dependent. ASCII
+    // keeps the byte offsets when lowercased, so most lines don't need the origins.
+    let (combined_str, combined_origins) = if match_case {
+        (original_str, None)
+    } else if original_str.is_ascii() {
+        (original_str.to_ascii_lowercase(), None)
     } else {
-        line.iter()
-            .map(|(s, _)| s.to_lowercase())
-            .collect::<String>()
+        let (lowercased, origins) = lowercase_with_origins(&original_str);
+        (lowercased, Some(origins))
     };
 
     let search_term_adjusted = if match_case {
         search_term.to_string()
     } else {
-        search_term.to_lowercase()
+        search_term.chars().flat_map(char::to_lowercase).collect()
     };
 
-    let mut parts_offsets = Vec::new();
-    let mut current_offset = 0;
-    for (i, (part_str, _)) in line.iter().enumerate() {
-        let part_len = part_str.len();
-        parts_offsets.push((i, current_offset, current_offset + part_len));
-        current_offset += part_len;
-    }
+    // Match in the searched string to the byte range in the original one.
+    let original_range = |combined_range: std::ops::Range<usize>| match &combined_origins {
+        None => combined_range,
+        Some(origins) => origins[combined_range.start].start..origins[combined_range.end - 1].end,
+    };
+
+    let parts_offsets = linevec_parts_offsets(line);
 
     let mut split_points = Vec::new();
     let mut search_start = 0;
@@ -39,57 +105,73 @@ pub fn linevec_find(
         let actual_pos = search_start + pos;
 
         if match_whole_word {
-            let is_start_boundary = actual_pos == 0
-                || !combined_str
-                    .chars()
-                    .nth(actual_pos - 1)
-                    .unwrap()
-                    .is_alphanumeric();
-            let is_end_boundary = actual_pos + search_term.len() == combined_str.len()
-                || !combined_str
-                    .chars()
-                    .nth(actual_pos + search_term.len())
-                    .unwrap()
-                    .is_alphanumeric();
+            // All offsets are in bytes, so look at the characters right around the match.
+            let match_end = actual_pos + search_term_adjusted.len();
+            let is_start_boundary = combined_str[..actual_pos]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_alphanumeric());
+            let is_end_boundary = combined_str[match_end..]
+                .chars()
+                .next()
+                .is_none_or(|c| !c.is_alphanumeric());
 
             if !is_start_boundary || !is_end_boundary {
-                search_start = actual_pos + 1;
+                // Continue from the next character, not the next byte.
+                search_start = actual_pos
+                    + combined_str[actual_pos..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
                 continue;
             }
         }
 
-        let mut start_split: SplitPointPartial = (0, 0);
-        let mut end_split: SplitPointPartial = (0, 0);
+        split_points.push(parts_offsets_split_point(
+            &parts_offsets,
+            original_range(actual_pos..actual_pos + search_term_adjusted.len()),
+        ));
+        search_start = actual_pos + search_term_adjusted.len();
+    }
 
-        for (i, part_start, part_end) in &parts_offsets {
-            if actual_pos >= *part_start && actual_pos < *part_end {
-                start_split = (*i, actual_pos - part_start);
-            }
+    split_points
+}
 
-            if actual_pos + search_term.len() > *part_start
-                && actual_pos + search_term.len() <= *part_end
-            {
-                end_split = (*i, actual_pos + search_term.len() - part_start);
-            }
-        }
+// Converts the split point to a byte range in the whole line (all parts concatenated).
+pub fn linevec_split_point_byte_range(
+    line: &LineVec,
+    split_point: &SplitPoint,
+) -> std::ops::Range<usize> {
+    let part_offset = |part_index: usize| -> usize {
+        line.iter()
+            .take(part_index)
+            .map(|(part_str, _)| part_str.len())
+            .sum()
+    };
 
-        split_points.push((start_split, end_split));
-        search_start = actual_pos + search_term.len();
-    }
+    let ((start_index, start_offset), (end_index, end_offset)) = *split_point;
+    (part_offset(start_index) + start_offset)..(part_offset(end_index) + end_offset)
+}
 
-    split_points
+// Inverse of linevec_split_point_byte_range, the range must not be empty.
+pub fn linevec_byte_range_split_point(
+    line: &LineVec,
+    byte_range: std::ops::Range<usize>,
+) -> SplitPoint {
+    parts_offsets_split_point(&linevec_parts_offsets(line), byte_range)
 }
 
+// Splits the parts at the split points, the text between the start and the end of each one gets
+// the middle colors. The split points don't have to be sorted. Of overlapping ones the one starting
+// first is kept (the earlier one given if they start together) and the rest is skipped, as the
+// text can have only one color. Touching ones, where one ends right where the next starts, are kept.
 pub fn linevec_split(
     line: &mut LineVec,
     split_points: Vec<SplitPoint>,
-    middle_color_bg: Option<egui::Color32>,
-    middle_color_text: Option<egui::Color32>,
+    middle_color_bg: Option<Color>,
+    middle_color_text: Option<Color>,
 ) {
-    let mut split_points = split_points;
-    split_points.sort_by_key(|(start, _)| *start);
-
-    let middle_text_format = |original_format: &TextFormat| {
+    linevec_split_with(line, split_points, |original_format| {
         let mut new_format = original_format.clone();
         if let Some(bg) = middle_color_bg {
             new_format.background = bg;
@@ -100,92 +182,169 @@ pub fn linevec_split(
         }
 
         new_format
-    };
+    });
+}
 
-    for split_point in split_points.into_iter().rev() {
-        let splitpoint_start = split_point.0;
-        let splitpoint_end = split_point.1;
+// Same as linevec_split(), but the middle text gets whatever format middle_text_format makes of its
+// original one, i.e. only an underline.
+pub fn linevec_split_with(
+    line: &mut LineVec,
+    split_points: Vec<SplitPoint>,
+    middle_text_format: impl Fn(&Style) -> Style,
+) {
+    let mut split_points = split_points;
+    split_points.sort_by_key(|(start, _)| *start);
 
-        let splitpoint_start_index = splitpoint_start.0;
-        let splitpoint_start_offset = splitpoint_start.1;
-        let splitpoint_end_index = splitpoint_end.0;
-        let splitpoint_end_offset = splitpoint_end.1;
+    // Skipping the later ones is simpler than merging, and keeps whichever hilight came first.
+    let mut last_end: Option<SplitPointPartial> = None;
+    split_points.retain(|(start, end)| {
+        if last_end.is_some_and(|last_end| *start < last_end) || end < start {
+            return false;
+        }
 
-        assert!(splitpoint_start_index <= splitpoint_end_index);
-        assert!(splitpoint_start_index < line.len());
-        assert!(splitpoint_end_index < line.len());
+        last_end = Some(*end);
+        true
+    });
 
-        let part = &mut line[splitpoint_start_index];
-        if splitpoint_start_index == splitpoint_end_index {
-            let original_text = part.0.clone();
-            let original_format = part.1.clone();
+    for ((start_index, _), (end_index, _)) in &split_points {
+        assert!(start_index <= end_index);
+        assert!(*end_index < line.len());
+    }
 
-            let before_text = original_text[..splitpoint_start_offset].to_string();
-            let middle_text =
-                original_text[splitpoint_start_offset..splitpoint_end_offset].to_string();
-            let after_text = original_text[splitpoint_end_offset..].to_string();
+    // Built anew in a single pass, inserting the parts into the middle of the line made lines with
+    // many matches (i.e. a single character search) quadratic.
+    let mut split_points = split_points.into_iter().peekable();
+    let mut open_span_end: Option<SplitPointPartial> = None; // of a split point spanning parts
+    let original_line = std::mem::replace(line, Vec::with_capacity(line.len()));
 
-            *part = (before_text, original_format.clone());
+    for (part_index, (part_text, part_format)) in original_line.into_iter().enumerate() {
+        let mut rest_offset = None; // where the text not split yet starts, if the part was split
 
-            line.insert(
-                splitpoint_start_index + 1,
-                (middle_text, middle_text_format(&original_format)),
-            );
+        if let Some((end_index, end_offset)) = open_span_end {
+            if end_index > part_index {
+                line.push((part_text, middle_text_format(&part_format)));
+                continue;
+            }
 
-            line.insert(splitpoint_start_index + 2, (after_text, original_format));
-        } else {
-            // Split start part
-            let original_text_start = part.0.clone();
-            let original_format_start = part.1.clone();
+            line.push((
+                part_text[..end_offset].to_string(),
+                middle_text_format(&part_format),
+            ));
+            rest_offset = Some(end_offset);
+            open_span_end = None;
+        }
 
-            let before_text = original_text_start[..splitpoint_start_offset].to_string();
-            let middle_text_start = original_text_start[splitpoint_start_offset..].to_string();
+        while let Some(((_, start_offset), (end_index, end_offset))) =
+            split_points.next_if(|((start_index, _), _)| *start_index == part_index)
+        {
+            line.push((
+                part_text[rest_offset.unwrap_or(0)..start_offset].to_string(),
+                part_format.clone(),
+            ));
+
+            if end_index == part_index {
+                line.push((
+                    part_text[start_offset..end_offset].to_string(),
+                    middle_text_format(&part_format),
+                ));
+                rest_offset = Some(end_offset);
+            } else {
+                // The rest of the part is in the middle, the next split points start after it ends.
+                line.push((
+                    part_text[start_offset..].to_string(),
+                    middle_text_format(&part_format),
+                ));
+                open_span_end = Some((end_index, end_offset));
+                rest_offset = None;
+                break;
+            }
+        }
 
-            *part = (before_text, original_format_start.clone());
+        if open_span_end.is_some_and(|(end_index, _)| end_index > part_index) {
+            continue;
+        }
 
-            line.insert(
-                splitpoint_start_index + 1,
-                (
-                    middle_text_start,
-                    middle_text_format(&original_format_start),
-                ),
-            );
+        match rest_offset {
+            Some(rest_offset) => line.push((part_text[rest_offset..].to_string(), part_format)),
+            None => line.push((part_text, part_format)),
+        }
+    }
+}
+
+// Removes the text in the byte ranges (sorted, not overlapping), the rest keeps its format.
+// Parts left empty are dropped.
+pub fn linevec_remove_byte_ranges(line: &mut LineVec, byte_ranges: &[std::ops::Range<usize>]) {
+    let mut part_start = 0;
 
-            // If there are middle parts and they need to be colored, do it now.
-            for middle_part_index in (splitpoint_start_index + 1)..splitpoint_end_index {
-                let middle_part = &mut line[middle_part_index];
-                let original_text_middle = middle_part.0.clone();
-                let original_format_middle = middle_part.1.clone();
+    for (part_str, _) in line.iter_mut() {
+        let part_end = part_start + part_str.len();
 
-                *middle_part = (
-                    original_text_middle,
-                    middle_text_format(&original_format_middle),
-                );
+        let mut kept_text = String::new();
+        let mut kept_from = part_start;
+        for byte_range in byte_ranges {
+            if byte_range.end <= kept_from || byte_range.start >= part_end {
+                continue;
             }
 
-            // Split end part
-            let part_end = &mut line[splitpoint_end_index + 1];
-            let original_text_end = part_end.0.clone();
-            let original_format_end = part_end.1.clone();
+            kept_text.push_str(
+                &part_str[kept_from - part_start..byte_range.start.max(kept_from) - part_start],
+            );
+            kept_from = byte_range.end.min(part_end);
+        }
+        kept_text.push_str(&part_str[kept_from - part_start..]);
 
-            let middle_text_end = original_text_end[..splitpoint_end_offset].to_string();
-            let after_text = original_text_end[splitpoint_end_offset..].to_string();
+        *part_str = kept_text;
+        part_start = part_end;
+    }
 
-            *part_end = (middle_text_end, middle_text_format(&original_format_end));
+    line.retain(|(part_str, _)| !part_str.is_empty());
+}
 
-            line.insert(splitpoint_end_index + 2, (after_text, original_format_end));
-        }
-    }
+// The parts within the byte range, cut to it and keeping their format. Parts left empty are dropped.
+pub fn linevec_slice(line: &LineVec, byte_range: std::ops::Range<usize>) -> LineVec {
+    linevec_parts_offsets(line)
+        .into_iter()
+        .filter_map(|(i, part_start, part_end)| {
+            let start = byte_range.start.max(part_start);
+            let end = byte_range.end.min(part_end);
+            let (part_str, part_format) = &line[i];
+
+            (start < end).then(|| {
+                (
+                    part_str[start - part_start..end - part_start].to_string(),
+                    part_format.clone(),
+                )
+            })
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use egui::Color32;
+
+    #[test]
+    fn byte_ranges_removed_across_parts() {
+        let red = Style {
+            color: Color::RED,
+            ..Default::default()
+        };
+        let mut line: LineVec = vec![
+            ("Hello ".to_string(), Style::default()),
+            ("cruel ".to_string(), red.clone()),
+            ("world".to_string(), Style::default()),
+        ];
+
+        linevec_remove_byte_ranges(&mut line, &[1..3, 4..7, 12..17]);
+        assert_eq!(linevec_text(&line), "Hlruel ");
+        assert!(line[1] == ("ruel ".to_string(), red));
+        // "world" removed whole.
+        assert_eq!(line.len(), 2);
+    }
 
     #[test]
     fn basic_string_searches() {
-        let line: LineVec = vec![("Hello world".to_string(), TextFormat::default())];
+        let line: LineVec = vec![("Hello world".to_string(), Style::default())];
 
         let mut split_points = linevec_find(&line, "lo wo", true, false);
         assert_eq!(split_points.len(), 1);
@@ -203,9 +362,9 @@ mod tests {
     #[test]
     fn multi_part_string_searches_simple() {
         let line: LineVec = vec![
-            ("Hello ".to_string(), TextFormat::default()),
-            ("cruel".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("cruel".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
         ];
 
         let mut split_points = linevec_find(&line, "Hello", true, false);
@@ -224,9 +383,9 @@ mod tests {
     #[test]
     fn multi_part_string_searches_across_parts() {
         let line: LineVec = vec![
-            ("Hello ".to_string(), TextFormat::default()),
-            ("cruel ".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("cruel ".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
         ];
 
         let mut split_points = linevec_find(&line, "lo cru", true, false);
@@ -245,11 +404,11 @@ mod tests {
     #[test]
     fn multi_part_string_searches_multiple_occurrences() {
         let line: LineVec = vec![
-            ("Hello ".to_string(), TextFormat::default()),
-            ("cruel ".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
-            ("Hello ".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("cruel ".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
         ];
 
         let mut split_points = linevec_find(&line, "lo", true, false);
@@ -273,10 +432,10 @@ mod tests {
     #[test]
     fn multi_part_string_searches_across_parts_multiple_occurrences() {
         let line: LineVec = vec![
-            ("ab".to_string(), TextFormat::default()),
-            ("cd".to_string(), TextFormat::default()),
-            ("ab".to_string(), TextFormat::default()),
-            ("cd".to_string(), TextFormat::default()),
+            ("ab".to_string(), Style::default()),
+            ("cd".to_string(), Style::default()),
+            ("ab".to_string(), Style::default()),
+            ("cd".to_string(), Style::default()),
         ];
 
         let split_points = linevec_find(&line, "bc", true, false);
@@ -287,7 +446,7 @@ mod tests {
 
     #[test]
     fn case_insensitive_searches() {
-        let line: LineVec = vec![("Hello World".to_string(), TextFormat::default())];
+        let line: LineVec = vec![("Hello World".to_string(), Style::default())];
 
         let mut split_points = linevec_find(&line, "hello", false, false);
         assert_eq!(split_points.len(), 1);
@@ -302,12 +461,32 @@ mod tests {
         assert_eq!(split_points[0], ((0, 3), (0, 8)));
     }
 
+    #[test]
+    fn case_insensitive_searches_multi_byte_lowercase() {
+        // "İ" is 2 bytes, but lowercases to 3 bytes ("i" + combining dot).
+        let mut line: LineVec = vec![
+            ("İstanbul: ".to_string(), Style::default()),
+            ("ERROR".to_string(), Style::default()),
+        ];
+
+        let split_points = linevec_find(&line, "error", false, false);
+        assert_eq!(split_points, vec![((1, 0), (1, 5))]);
+
+        let split_points = linevec_find(&line, "İSTANBUL", false, false);
+        assert_eq!(split_points, vec![((0, 0), (0, 9))]);
+
+        let split_points = linevec_find(&line, "bul: err", false, false);
+        assert_eq!(split_points, vec![((0, 6), (1, 3))]);
+
+        // The original text is kept, only the search is case insensitive.
+        linevec_split(&mut line, split_points, None, None);
+        let parts: Vec<_> = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        assert_eq!(parts, vec!["İstan", "bul: ", "ERR", "OR"]);
+    }
+
     #[test]
     fn whole_word_searches() {
-        let line: LineVec = vec![(
-            "Hello world, hello universe".to_string(),
-            TextFormat::default(),
-        )];
+        let line: LineVec = vec![("Hello world, hello universe".to_string(), Style::default())];
 
         let mut split_points = linevec_find(&line, "hello", true, true);
         assert_eq!(split_points.len(), 1);
@@ -321,13 +500,90 @@ mod tests {
         assert_eq!(split_points.len(), 0);
     }
 
+    #[test]
+    fn whole_word_searches_non_ascii() {
+        // Used to index chars by byte offsets and panic past the end of the string.
+        let line: LineVec = vec![("ääää x".to_string(), Style::default())];
+        assert_eq!(
+            linevec_find(&line, "x", true, true),
+            vec![((0, 9), (0, 10))]
+        );
+
+        // Accented letters are a part of the word.
+        let line: LineVec = vec![("éok café ok".to_string(), Style::default())];
+        assert_eq!(
+            linevec_find(&line, "ok", true, true),
+            vec![((0, 11), (0, 13))]
+        );
+        assert_eq!(linevec_find(&line, "caf", true, true), vec![]);
+        assert_eq!(
+            linevec_find(&line, "café", true, true),
+            vec![((0, 5), (0, 10))]
+        );
+
+        // Emoji are not.
+        let line: LineVec = vec![
+            ("🔥error🔥 ".to_string(), Style::default()),
+            ("errorś".to_string(), Style::default()),
+        ];
+        assert_eq!(
+            linevec_find(&line, "error", true, true),
+            vec![((0, 4), (0, 9))]
+        );
+        assert_eq!(
+            linevec_find(&line, "ERROR", false, true),
+            vec![((0, 4), (0, 9))]
+        );
+    }
+
+    #[test]
+    fn whole_word_searches_case_insensitive() {
+        let line: LineVec = vec![("Hello world, hello universe".to_string(), Style::default())];
+        assert_eq!(
+            linevec_find(&line, "HeLLo", false, true),
+            vec![((0, 0), (0, 5)), ((0, 13), (0, 18))]
+        );
+        assert_eq!(linevec_find(&line, "LO", false, true), vec![]);
+
+        // "İ" lowercases to one more byte, the boundary has to be checked after the lowercase term.
+        let line: LineVec = vec![("in İSTANBUL today".to_string(), Style::default())];
+        assert_eq!(
+            linevec_find(&line, "İstanbul", false, true),
+            vec![((0, 3), (0, 12))]
+        );
+        assert_eq!(linevec_find(&line, "İstanbul t", false, true), vec![]);
+
+        let line: LineVec = vec![
+            ("İstanbullu, ".to_string(), Style::default()),
+            ("istanbul".to_string(), Style::default()),
+        ];
+        assert_eq!(
+            linevec_find(&line, "ISTANBUL", false, true),
+            vec![((1, 0), (1, 8))]
+        );
+        assert_eq!(
+            linevec_find(&line, "İSTANBULLU", false, true),
+            vec![((0, 0), (0, 11))]
+        );
+    }
+
+    #[test]
+    fn whole_word_searches_skip_multi_byte_characters() {
+        // After a rejected match the search continues on a character boundary.
+        let line: LineVec = vec![("ééé éé".to_string(), Style::default())];
+        assert_eq!(
+            linevec_find(&line, "éé", true, true),
+            vec![((0, 7), (0, 11))]
+        );
+    }
+
     #[test]
     fn whole_word_searches_across_parts() {
         let line: LineVec = vec![
-            ("lorem ip".to_string(), TextFormat::default()),
-            ("sum, consecteur ".to_string(), TextFormat::default()),
-            ("adipiscit el".to_string(), TextFormat::default()),
-            ("it".to_string(), TextFormat::default()),
+            ("lorem ip".to_string(), Style::default()),
+            ("sum, consecteur ".to_string(), Style::default()),
+            ("adipiscit el".to_string(), Style::default()),
+            ("it".to_string(), Style::default()),
         ];
 
         let mut split_points = linevec_find(&line, "ipsum", true, true);
@@ -342,91 +598,258 @@ mod tests {
         assert_eq!(split_points.len(), 0);
     }
 
+    #[test]
+    fn overlapping_split_points_skipped() {
+        let mut line: LineVec = vec![("abcdef".to_string(), Style::default())];
+
+        linevec_split(
+            &mut line,
+            vec![((0, 0), (0, 3)), ((0, 2), (0, 5)), ((0, 4), (0, 6))],
+            None,
+            None,
+        );
+
+        let parts: Vec<_> = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        assert_eq!(parts, vec!["", "abc", "d", "ef", ""]);
+    }
+
+    #[test]
+    fn overlapping_split_points_skipped_unsorted_and_nested() {
+        let mut line: LineVec = vec![("abcdef".to_string(), Style::default())];
+
+        // Same start, the earlier one given is kept. The nested one is skipped too.
+        linevec_split(
+            &mut line,
+            vec![
+                ((0, 4), (0, 6)),
+                ((0, 1), (0, 2)),
+                ((0, 1), (0, 5)),
+                ((0, 2), (0, 3)),
+            ],
+            Some(Color::RED),
+            None,
+        );
+
+        let parts: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("a", Color::TRANSPARENT),
+                ("b", Color::RED),
+                ("", Color::TRANSPARENT),
+                ("c", Color::RED),
+                ("d", Color::TRANSPARENT),
+                ("ef", Color::RED),
+                ("", Color::TRANSPARENT)
+            ]
+        );
+        assert_eq!(linevec_text(&line), "abcdef");
+    }
+
+    #[test]
+    fn overlapping_split_points_across_parts_skipped() {
+        let mut line: LineVec = vec![
+            ("abc".to_string(), Style::default()),
+            ("def".to_string(), Style::default()),
+        ];
+
+        // The second one starts inside the first, which spans both parts.
+        linevec_split(
+            &mut line,
+            vec![((0, 1), (1, 2)), ((1, 0), (1, 3))],
+            Some(Color::RED),
+            None,
+        );
+
+        let parts: Vec<_> = line.iter().map(|(part_str, _)| part_str.as_str()).collect();
+        assert_eq!(parts, vec!["a", "bc", "de", "f"]);
+    }
+
+    #[test]
+    fn touching_split_points_kept() {
+        let mut line: LineVec = vec![
+            ("abc".to_string(), Style::default()),
+            ("def".to_string(), Style::default()),
+        ];
+
+        // Ends where the next starts, in the same part and at the part boundary.
+        linevec_split(
+            &mut line,
+            vec![((0, 0), (0, 2)), ((0, 2), (0, 3)), ((1, 0), (1, 3))],
+            Some(Color::RED),
+            None,
+        );
+
+        let parts: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("", Color::TRANSPARENT),
+                ("ab", Color::RED),
+                ("", Color::TRANSPARENT),
+                ("c", Color::RED),
+                ("", Color::TRANSPARENT),
+                ("", Color::TRANSPARENT),
+                ("def", Color::RED),
+                ("", Color::TRANSPARENT)
+            ]
+        );
+    }
+
     #[test]
     fn basic_split() {
-        let mut line: LineVec = vec![("Hello world".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("Hello world".to_string(), Style::default())];
 
         let split_points = vec![((0, 3), (0, 8))];
         linevec_split(&mut line, split_points, None, None);
 
         assert_eq!(line.len(), 3);
-        assert_eq!(line[0], ("Hel".to_string(), TextFormat::default()));
-        assert_eq!(line[1], ("lo wo".to_string(), TextFormat::default()));
-        assert_eq!(line[2], ("rld".to_string(), TextFormat::default()));
+        assert_eq!(line[0], ("Hel".to_string(), Style::default()));
+        assert_eq!(line[1], ("lo wo".to_string(), Style::default()));
+        assert_eq!(line[2], ("rld".to_string(), Style::default()));
     }
 
     #[test]
     fn multi_part_split() {
         let mut line: LineVec = vec![
-            ("Hello ".to_string(), TextFormat::default()),
-            ("cruel ".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("cruel ".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
         ];
 
         let split_points = vec![((0, 3), (1, 3))];
         linevec_split(&mut line, split_points, None, None);
 
         assert_eq!(line.len(), 5);
-        assert_eq!(line[0], ("Hel".to_string(), TextFormat::default()));
-        assert_eq!(line[1], ("lo ".to_string(), TextFormat::default()));
-        assert_eq!(line[2], ("cru".to_string(), TextFormat::default()));
-        assert_eq!(line[3], ("el ".to_string(), TextFormat::default()));
-        assert_eq!(line[4], ("world".to_string(), TextFormat::default()));
+        assert_eq!(line[0], ("Hel".to_string(), Style::default()));
+        assert_eq!(line[1], ("lo ".to_string(), Style::default()));
+        assert_eq!(line[2], ("cru".to_string(), Style::default()));
+        assert_eq!(line[3], ("el ".to_string(), Style::default()));
+        assert_eq!(line[4], ("world".to_string(), Style::default()));
+    }
+
+    #[test]
+    fn split_across_many_parts_colors_all_of_them() {
+        let mut line: LineVec = vec![
+            ("ab".to_string(), Style::default()),
+            ("cd".to_string(), Style::default()),
+            ("ef".to_string(), Style::default()),
+            ("gh".to_string(), Style::default()),
+        ];
+
+        linevec_split(
+            &mut line,
+            vec![((0, 1), (3, 1)), ((3, 1), (3, 2))],
+            Some(Color::RED),
+            None,
+        );
+
+        let parts: Vec<_> = line
+            .iter()
+            .map(|(part_str, part_format)| (part_str.as_str(), part_format.background))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("a", Color::TRANSPARENT),
+                ("b", Color::RED),
+                ("cd", Color::RED),
+                ("ef", Color::RED),
+                ("g", Color::RED),
+                ("", Color::TRANSPARENT),
+                ("h", Color::RED),
+                ("", Color::TRANSPARENT)
+            ]
+        );
+    }
+
+    #[test]
+    fn split_with_many_matches() {
+        let text = "a,".repeat(50_000);
+        let mut line: LineVec = vec![(text.clone(), Style::default())];
+
+        let split_points = linevec_find(&line, "a", true, false);
+        assert_eq!(split_points.len(), 50_000);
+
+        // Used to insert every part into the middle of the line, this took seconds.
+        let started_at = std::time::Instant::now();
+        linevec_split(&mut line, split_points, Some(Color::RED), None);
+        assert!(started_at.elapsed() < std::time::Duration::from_secs(1));
+
+        assert_eq!(line.len(), 100_001);
+        assert_eq!(linevec_text(&line), text);
+        assert_eq!(
+            line[1],
+            (
+                "a".to_string(),
+                Style {
+                    background: Color::RED,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_eq!(line[2], (",".to_string(), Style::default()));
     }
 
     #[test]
     fn basic_split_with_coloring() {
-        let mut line: LineVec = vec![("Hello world".to_string(), TextFormat::default())];
+        let mut line: LineVec = vec![("Hello world".to_string(), Style::default())];
 
         let split_points = vec![((0, 3), (0, 8))];
         linevec_split(
             &mut line,
             split_points,
-            Some(Color32::RED),
-            Some(Color32::WHITE),
+            Some(Color::RED),
+            Some(Color::WHITE),
         );
 
         assert_eq!(line.len(), 3);
-        assert_eq!(line[0], ("Hel".to_string(), TextFormat::default()));
+        assert_eq!(line[0], ("Hel".to_string(), Style::default()));
         assert_eq!(
             line[1],
             (
                 "lo wo".to_string(),
-                TextFormat {
-                    background: Color32::RED,
-                    color: Color32::WHITE,
+                Style {
+                    background: Color::RED,
+                    color: Color::WHITE,
                     ..Default::default()
                 }
             )
         );
-        assert_eq!(line[2], ("rld".to_string(), TextFormat::default()));
+        assert_eq!(line[2], ("rld".to_string(), Style::default()));
     }
 
     #[test]
     fn multi_part_split_with_coloring() {
         let mut line: LineVec = vec![
-            ("Hello ".to_string(), TextFormat::default()),
-            ("cruel ".to_string(), TextFormat::default()),
-            ("world".to_string(), TextFormat::default()),
+            ("Hello ".to_string(), Style::default()),
+            ("cruel ".to_string(), Style::default()),
+            ("world".to_string(), Style::default()),
         ];
 
         let split_points = vec![((0, 3), (1, 3))];
         linevec_split(
             &mut line,
             split_points,
-            Some(Color32::RED),
-            Some(Color32::WHITE),
+            Some(Color::RED),
+            Some(Color::WHITE),
         );
 
         assert_eq!(line.len(), 5);
-        assert_eq!(line[0], ("Hel".to_string(), TextFormat::default()));
+        assert_eq!(line[0], ("Hel".to_string(), Style::default()));
         assert_eq!(
             line[1],
             (
                 "lo ".to_string(),
-                TextFormat {
-                    background: Color32::RED,
-                    color: Color32::WHITE,
+                Style {
+                    background: Color::RED,
+                    color: Color::WHITE,
                     ..Default::default()
                 }
             )
@@ -435,14 +858,37 @@ mod tests {
             line[2],
             (
                 "cru".to_string(),
-                TextFormat {
-                    background: Color32::RED,
-                    color: Color32::WHITE,
+                Style {
+                    background: Color::RED,
+                    color: Color::WHITE,
                     ..Default::default()
                 }
             )
         );
-        assert_eq!(line[3], ("el ".to_string(), TextFormat::default()));
-        assert_eq!(line[4], ("world".to_string(), TextFormat::default()));
+        assert_eq!(line[3], ("el ".to_string(), Style::default()));
+        assert_eq!(line[4], ("world".to_string(), Style::default()));
+    }
+
+    #[test]
+    fn slice_cut_to_byte_range() {
+        let red = Style {
+            color: Color::RED,
+            ..Default::default()
+        };
+        let line: LineVec = vec![
+            ("ab".to_string(), Style::default()),
+            ("cde".to_string(), red.clone()),
+            ("f".to_string(), Style::default()),
+        ];
+
+        assert_eq!(
+            linevec_slice(&line, 1..4),
+            vec![
+                ("b".to_string(), Style::default()),
+                ("cd".to_string(), red.clone()),
+            ]
+        );
+        assert_eq!(linevec_slice(&line, 2..5), vec![("cde".to_string(), red)]);
+        assert!(linevec_slice(&line, 3..3).is_empty());
     }
 }
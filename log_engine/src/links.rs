@@ -0,0 +1,201 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::linevec::*;
+use crate::style::{Color, Style};
+
+pub const LINK_UNDERLINE: Color = Color::from_rgb(90, 160, 255);
+
+// "{file}" and "{line}" are replaced in each argument, so paths with spaces stay one argument.
+pub const DEFAULT_EDITOR_COMMAND: &str = "code --goto {file}:{line}";
+
+static URL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bhttps?://[^\s<>"'`]+"#).unwrap());
+
+// Source paths with a line number, i.e. "src/foo.rs:123" or "C:\app\main.c:7:12". The extension
+// has to start with a letter, so times ("12:30:45") and addresses ("10.0.0.1:80") aren't links.
+static FILE_LINE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\b[A-Za-z]:)?[\w./\\-]*\b[\w-]+\.[A-Za-z][A-Za-z0-9]*:(\d+)\b").unwrap()
+});
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkTarget {
+    Url(String),
+    File { path: String, line_no: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub byte_range: std::ops::Range<usize>, // in the whole line text
+    pub target: LinkTarget,
+}
+
+// Links in the line text, sorted and not overlapping.
+pub fn find_links(text: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = URL_REGEX
+        .find_iter(text)
+        .map(|url_match| {
+            // Punctuation after the URL most likely ends the sentence, not the URL.
+            let url = url_match
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}']);
+            Link {
+                byte_range: url_match.start()..url_match.start() + url.len(),
+                target: LinkTarget::Url(url.to_string()),
+            }
+        })
+        .collect();
+
+    // Hosts with a port in the URLs look like files with a line number too.
+    let file_links: Vec<Link> = FILE_LINE_REGEX
+        .captures_iter(text)
+        .filter_map(|file_line_captures| {
+            let file_line_match = file_line_captures.get(0)?;
+            let line_no_match = file_line_captures.get(1)?;
+            let byte_range = file_line_match.range();
+            if links.iter().any(|url_link| {
+                url_link.byte_range.start < byte_range.end
+                    && byte_range.start < url_link.byte_range.end
+            }) {
+                return None;
+            }
+
+            Some(Link {
+                target: LinkTarget::File {
+                    path: text[byte_range.start..line_no_match.start() - 1].to_string(),
+                    line_no: line_no_match.as_str().parse().ok()?,
+                },
+                byte_range,
+            })
+        })
+        .collect();
+
+    links.extend(file_links);
+    links.sort_by_key(|link| link.byte_range.start);
+
+    links
+}
+
+// The link at the byte offset in the whole line text.
+pub fn link_at(links: &[Link], byte_offset: usize) -> Option<&Link> {
+    links
+        .iter()
+        .find(|link| link.byte_range.contains(&byte_offset))
+}
+
+// Underlines the links, keeping the colors the line already has.
+pub fn underline_links(line: &mut LineVec, links: &[Link]) {
+    let split_points = links
+        .iter()
+        .filter(|link| !link.byte_range.is_empty())
+        .map(|link| linevec_byte_range_split_point(line, link.byte_range.clone()))
+        .collect();
+
+    linevec_split_with(line, split_points, |original_format| Style {
+        underline: LINK_UNDERLINE,
+        ..original_format.clone()
+    });
+}
+
+// Relative paths are most likely relative to the log (i.e. a build log next to the sources), if
+// there's no such file there it's left for the editor to resolve.
+pub fn resolve_file_path(path: &str, log_file_path: &str) -> String {
+    let path_buf = std::path::Path::new(path);
+    if path_buf.is_relative()
+        && let Some(log_dir) = std::path::Path::new(log_file_path).parent()
+        && log_dir.join(path_buf).exists()
+    {
+        return log_dir.join(path_buf).to_string_lossy().to_string();
+    }
+
+    path.to_string()
+}
+
+// The program and its arguments, empty if there's no command.
+pub fn editor_command(command_template: &str, path: &str, line_no: usize) -> Vec<String> {
+    command_template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{file}", path)
+                .replace("{line}", &line_no.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_found_in_text() {
+        let text = "failed at src/foo.rs:123, see https://example.com:8080/issue?id=4.";
+        let links = find_links(text);
+        assert_eq!(
+            links,
+            vec![
+                Link {
+                    byte_range: 10..24,
+                    target: LinkTarget::File {
+                        path: "src/foo.rs".to_string(),
+                        line_no: 123
+                    },
+                },
+                Link {
+                    byte_range: 30..65,
+                    target: LinkTarget::Url("https://example.com:8080/issue?id=4".to_string()),
+                },
+            ]
+        );
+        assert_eq!(link_at(&links, 12), Some(&links[0]));
+        assert_eq!(link_at(&links, 26), None);
+
+        assert!(find_links("12:30:45 connected to 10.0.0.1:80").is_empty());
+        assert_eq!(
+            find_links(r"C:\app\main.c:7:12")[0].target,
+            LinkTarget::File {
+                path: r"C:\app\main.c".to_string(),
+                line_no: 7
+            }
+        );
+    }
+
+    #[test]
+    fn links_underlined_across_parts() {
+        let mut line: LineVec = vec![
+            ("see http://".to_string(), Style::default()),
+            (
+                "a.org now".to_string(),
+                Style {
+                    color: Color::RED,
+                    ..Default::default()
+                },
+            ),
+        ];
+        let links = find_links(&linevec_text(&line));
+        underline_links(&mut line, &links);
+
+        let underlined: Vec<(&str, Color)> = line
+            .iter()
+            .filter(|(_, format)| format.underline == LINK_UNDERLINE)
+            .map(|(part_str, format)| (part_str.as_str(), format.color))
+            .collect();
+        assert_eq!(
+            underlined,
+            vec![("http://", Style::default().color), ("a.org", Color::RED)]
+        );
+        assert_eq!(linevec_text(&line), "see http://a.org now");
+    }
+
+    #[test]
+    fn editor_command_filled_in() {
+        assert_eq!(
+            editor_command(DEFAULT_EDITOR_COMMAND, "/src/my file.rs", 42),
+            vec!["code", "--goto", "/src/my file.rs:42"]
+        );
+        assert!(editor_command("  ", "a.rs", 1).is_empty());
+        assert_eq!(
+            resolve_file_path("missing.rs", "/tmp/app.log"),
+            "missing.rs"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::encoding;
+
+enum ChunkStorage {
+    Owned(String),
+    // Kept as raw bytes, lines are decoded only when requested.
+    Mapped { mmap: Mmap, encoding: String },
+}
+
+// An immutable piece of the content with an index of the lines in it.
+struct ContentChunk {
+    storage: ChunkStorage,
+    line_starts: Vec<usize>, // byte offset of each line start, sorted
+    line_max_bytes: usize,
+}
+
+// How often the indexing reports its progress.
+const INDEX_PROGRESS_STEP_BYTES: usize = 4 * 1024 * 1024;
+
+impl ContentChunk {
+    fn new(storage: ChunkStorage) -> Self {
+        Self::new_with_progress(storage, |_| true).expect("indexing is never stopped")
+    }
+
+    // Reports the bytes indexed so far, None if on_progress returns false to stop the indexing.
+    fn new_with_progress(
+        storage: ChunkStorage,
+        mut on_progress: impl FnMut(usize) -> bool,
+    ) -> Option<Self> {
+        let bytes = match &storage {
+            ChunkStorage::Owned(content) => content.as_bytes(),
+            ChunkStorage::Mapped { mmap, .. } => &mmap[..],
+        };
+
+        let mut line_starts = Vec::new();
+        let mut line_max_bytes = 0;
+
+        let mut line_start = 0;
+        let mut next_progress = INDEX_PROGRESS_STEP_BYTES;
+        while line_start < bytes.len() {
+            if line_start >= next_progress {
+                if !on_progress(line_start) {
+                    return None;
+                }
+                next_progress = line_start + INDEX_PROGRESS_STEP_BYTES;
+            }
+            line_starts.push(line_start);
+
+            let line_end = bytes[line_start..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map(|pos| line_start + pos + 1)
+                .unwrap_or(bytes.len());
+
+            line_max_bytes = line_max_bytes.max(trim_line_end(&bytes[line_start..line_end]).len());
+            line_start = line_end;
+        }
+        on_progress(bytes.len());
+
+        Some(Self {
+            storage,
+            line_starts,
+            line_max_bytes,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match &self.storage {
+            ChunkStorage::Owned(content) => content.as_bytes(),
+            ChunkStorage::Mapped { mmap, .. } => &mmap[..],
+        }
+    }
+
+    fn line(&self, index: usize) -> Cow<'_, str> {
+        let bytes = self.bytes();
+
+        let line_start = self.line_starts[index];
+        let line_end = self
+            .line_starts
+            .get(index + 1)
+            .copied()
+            .unwrap_or(bytes.len());
+        let line_bytes = trim_line_end(&bytes[line_start..line_end]);
+
+        match &self.storage {
+            // Owned content is a valid string split at newlines, so always at char boundaries.
+            ChunkStorage::Owned(content) => {
+                Cow::Borrowed(&content[line_start..][..line_bytes.len()])
+            }
+            ChunkStorage::Mapped { encoding, .. } => encoding::decode(line_bytes, encoding),
+        }
+    }
+
+    fn last_line_terminated(&self) -> bool {
+        self.bytes().last().is_none_or(|b| *b == b'\n')
+    }
+}
+
+fn trim_line_end(line_bytes: &[u8]) -> &[u8] {
+    let line_bytes = line_bytes.strip_suffix(b"\n").unwrap_or(line_bytes);
+    line_bytes.strip_suffix(b"\r").unwrap_or(line_bytes)
+}
+
+#[derive(Clone)]
+struct ChunkLines {
+    chunk: Arc<ContentChunk>,
+    lines: Range<usize>, // lines of the chunk that are part of the content
+}
+
+// Log content split into lines, either held in memory or memory-mapped from the file. Chunks are
+// shared, so cloning the content is cheap regardless of its size.
+#[derive(Clone, Default)]
+pub struct LogContent {
+    chunks: Vec<ChunkLines>,
+    line_count: usize,
+    max_line_bytes: usize,
+    dropped_line_count: usize, // from the front, see drop_first_lines()
+}
+
+impl LogContent {
+    pub fn from_string(content: String) -> Self {
+        let mut log_content = Self::default();
+        log_content.push_chunk(ContentChunk::new(ChunkStorage::Owned(content)));
+        log_content
+    }
+
+    pub fn from_mmap(mmap: Mmap, encoding: &str) -> Self {
+        Self::from_mmap_with_progress(mmap, encoding, |_| true).expect("indexing is never stopped")
+    }
+
+    // The lines of a big file take a while to index, see ContentChunk::new_with_progress().
+    pub fn from_mmap_with_progress(
+        mmap: Mmap,
+        encoding: &str,
+        on_progress: impl FnMut(usize) -> bool,
+    ) -> Option<Self> {
+        let mut log_content = Self::default();
+        log_content.push_chunk(ContentChunk::new_with_progress(
+            ChunkStorage::Mapped {
+                mmap,
+                encoding: encoding.to_string(),
+            },
+            on_progress,
+        )?);
+        Some(log_content)
+    }
+
+    fn push_chunk(&mut self, chunk: ContentChunk) {
+        if chunk.line_starts.is_empty() {
+            return;
+        }
+
+        self.line_count += chunk.line_starts.len();
+        self.max_line_bytes = self.max_line_bytes.max(chunk.line_max_bytes);
+
+        self.chunks.push(ChunkLines {
+            lines: 0..chunk.line_starts.len(),
+            chunk: Arc::new(chunk),
+        });
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    // Length in bytes of the longest line, line ends left out. Not a width, multibyte chars count
+    // as more than one.
+    pub fn max_line_bytes(&self) -> usize {
+        self.max_line_bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.line_count == 0
+    }
+
+    // The line indices start after the dropped lines, the original line numbers count them too.
+    pub fn dropped_line_count(&self) -> usize {
+        self.dropped_line_count
+    }
+
+    // By the original (1-based) line number, None for the dropped lines.
+    pub fn original_line(&self, original_line_no: usize) -> Option<Cow<'_, str>> {
+        self.line(original_line_no.checked_sub(self.dropped_line_count + 1)?)
+    }
+
+    pub fn line(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.lines_from(index).next()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        self.lines_from(0)
+    }
+
+    // Iterates lines starting at given index without touching the lines before it.
+    pub fn lines_from(&self, start: usize) -> impl Iterator<Item = Cow<'_, str>> {
+        let mut lines_to_skip = start;
+
+        self.chunks.iter().flat_map(move |chunk_lines| {
+            let chunk_line_count = chunk_lines.lines.len();
+            let skip_in_chunk = lines_to_skip.min(chunk_line_count);
+            lines_to_skip -= skip_in_chunk;
+
+            let first_line = chunk_lines.lines.start + skip_in_chunk;
+            (first_line..chunk_lines.lines.end).map(|index| chunk_lines.chunk.line(index))
+        })
+    }
+
+    pub fn append_lines(&mut self, lines: &[String]) {
+        let mut chunk_content = String::new();
+        for line in lines {
+            chunk_content.push_str(line);
+            chunk_content.push('\n');
+        }
+
+        self.push_chunk(ContentChunk::new(ChunkStorage::Owned(chunk_content)));
+    }
+
+    // Drops the oldest lines, i.e. to keep an endless stream from taking all the memory. The chunks
+    // are freed once none of their lines are left.
+    pub fn drop_first_lines(&mut self, count: usize) {
+        let mut lines_to_drop = count.min(self.line_count);
+        self.line_count -= lines_to_drop;
+        self.dropped_line_count += lines_to_drop;
+
+        let mut chunks_emptied = 0;
+        for chunk_lines in &mut self.chunks {
+            let drop_in_chunk = lines_to_drop.min(chunk_lines.lines.len());
+            chunk_lines.lines.start += drop_in_chunk;
+            lines_to_drop -= drop_in_chunk;
+
+            if !chunk_lines.lines.is_empty() {
+                break;
+            }
+            chunks_emptied += 1;
+        }
+        self.chunks.drain(..chunks_emptied);
+
+        // Only known per chunk, the longest line may be in the part of a chunk that's left.
+        self.max_line_bytes = self
+            .chunks
+            .iter()
+            .map(|chunk_lines| chunk_lines.chunk.line_max_bytes)
+            .max()
+            .unwrap_or(0);
+    }
+
+    // Removes the last line if it's not terminated with a newline (i.e. still being written).
+    pub fn drop_partial_last_line(&mut self) {
+        let Some(last_chunk) = self.chunks.last_mut() else {
+            return;
+        };
+
+        let chunk_line_count = last_chunk.chunk.line_starts.len();
+        if last_chunk.lines.end == chunk_line_count && !last_chunk.chunk.last_line_terminated() {
+            last_chunk.lines.end -= 1;
+            self.line_count -= 1;
+
+            if last_chunk.lines.is_empty() {
+                self.chunks.pop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_split_like_str_lines() {
+        let text = "first\nsecond\r\n\nlast";
+        let content = LogContent::from_string(text.to_string());
+
+        assert_eq!(content.line_count(), text.lines().count());
+        assert!(content.lines().eq(text.lines()));
+        assert_eq!(content.max_line_bytes(), 6);
+    }
+
+    #[test]
+    fn lines_across_appended_chunks() {
+        let mut content = LogContent::from_string("a\nb\n".to_string());
+        content.append_lines(&["c".to_string(), "d".to_string()]);
+
+        assert_eq!(content.line_count(), 4);
+        assert!(content.lines().eq(["a", "b", "c", "d"]));
+        assert!(content.lines_from(1).eq(["b", "c", "d"]));
+        assert!(content.lines_from(3).eq(["d"]));
+        assert_eq!(content.lines_from(4).count(), 0);
+        assert_eq!(content.line(2).unwrap(), "c");
+        assert!(content.line(4).is_none());
+    }
+
+    #[test]
+    fn partial_last_line_dropped() {
+        let mut content = LogContent::from_string("a\nb\npart".to_string());
+        content.drop_partial_last_line();
+        assert!(content.lines().eq(["a", "b"]));
+
+        // Nothing to drop anymore.
+        content.drop_partial_last_line();
+        assert!(content.lines().eq(["a", "b"]));
+
+        content.append_lines(&["partial line".to_string()]);
+        assert!(content.lines().eq(["a", "b", "partial line"]));
+    }
+
+    #[test]
+    fn first_lines_dropped() {
+        let mut content = LogContent::from_string("a\nb\n".to_string());
+        content.append_lines(&["c".to_string(), "dddd".to_string()]);
+
+        content.drop_first_lines(3);
+        assert!(content.lines().eq(["dddd"]));
+        assert_eq!(content.line_count(), 1);
+        assert_eq!(content.dropped_line_count(), 3);
+        assert_eq!(content.max_line_bytes(), 4);
+        assert_eq!(content.original_line(4).unwrap(), "dddd");
+        assert!(content.original_line(3).is_none());
+        assert!(content.original_line(0).is_none());
+
+        content.append_lines(&["e".to_string()]);
+        assert_eq!(content.original_line(5).unwrap(), "e");
+
+        content.drop_first_lines(10);
+        assert!(content.is_empty());
+        assert_eq!(content.dropped_line_count(), 5);
+    }
+
+    #[test]
+    fn mapped_lines_decoded_on_demand() {
+        let path = std::env::temp_dir().join("logalyzer_mapped_lines_decoded_on_demand.log");
+        std::fs::write(&path, b"caf\xe9\nsecond\n").unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mmap = unsafe { Mmap::map(&file).unwrap() };
+        let content = LogContent::from_mmap(mmap, encoding::ENCODING_LATIN1);
+
+        assert!(content.lines().eq(["café", "second"]));
+        assert_eq!(content.max_line_bytes(), 6);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_content() {
+        let content = LogContent::from_string(String::new());
+        assert!(content.is_empty());
+        assert_eq!(content.lines().count(), 0);
+        assert_eq!(content.max_line_bytes(), 0);
+    }
+}
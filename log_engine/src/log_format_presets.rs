@@ -0,0 +1,142 @@
+use crate::style::Color;
+use std::sync::LazyLock;
+
+use crate::user_settings::LogFormat;
+
+// Name shown in the combo box, the pattern and a background color for each of its groups.
+// Every character of a matching line has to be captured by some group, as the log format
+// handler drops anything not captured.
+pub type LogFormatPreset = (&'static str, &'static str, Vec<Color>);
+
+const SEPARATOR: Color = Color::TRANSPARENT;
+const TIME: Color = Color::from_rgb(40, 70, 120);
+const SOURCE: Color = Color::from_rgb(70, 110, 60);
+const LEVEL: Color = Color::from_rgb(140, 90, 30);
+const DETAIL: Color = Color::from_rgb(100, 60, 110);
+
+pub static LOG_FORMAT_PRESETS: LazyLock<Vec<LogFormatPreset>> = LazyLock::new(|| {
+    vec![
+        (
+            "[number.number] log message",
+            r"^(\[\s*[0-9]*)(\.)([0-9]*\])(\s.*)$",
+            vec![TIME, TIME, TIME, SEPARATOR],
+        ),
+        (
+            "YYYY-MM-DD HH:MM:SS log message",
+            r"^(\d{4}-\d{2}-\d{2}\s+\d{2}:\d{2}:\d{2})(\s+)(.*)$",
+            vec![TIME, SEPARATOR, SEPARATOR],
+        ),
+        (
+            "Syslog (RFC3164)",
+            r"^(?P<timestamp>[A-Z][a-z]{2}\s+\d{1,2} \d{2}:\d{2}:\d{2})( )(?P<host>\S+)( )(?P<tag>[^:\s\[]+(?:\[\d+\])?)(:)(?P<message>.*)$",
+            vec![
+                TIME, SEPARATOR, SOURCE, SEPARATOR, DETAIL, SEPARATOR, SEPARATOR,
+            ],
+        ),
+        (
+            "Apache/nginx combined access log",
+            r#"^(?P<host>\S+)( \S+ \S+ )(?P<timestamp>\[[^\]]+\])( )(?P<request>"[^"]*")( )(?P<status>\d{3})( )(?P<size>\d+|-)(.*)$"#,
+            vec![
+                SOURCE, SEPARATOR, TIME, SEPARATOR, DETAIL, SEPARATOR, LEVEL, SEPARATOR, SEPARATOR,
+                SEPARATOR,
+            ],
+        ),
+        (
+            "Android logcat",
+            r"^(?P<timestamp>\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{3})(\s+\d+\s+\d+ )(?P<level>[VDIWEF])( )(?P<tag>[^:]*)(:)(?P<message>.*)$",
+            vec![
+                TIME, SEPARATOR, LEVEL, SEPARATOR, SOURCE, SEPARATOR, SEPARATOR,
+            ],
+        ),
+        (
+            "Python logging",
+            r"^(?P<level>[A-Z]+)(:)(?P<logger>[^:]*)(:)(?P<message>.*)$",
+            vec![LEVEL, SEPARATOR, SOURCE, SEPARATOR, SEPARATOR],
+        ),
+    ]
+});
+
+// Log format with the preset pattern and its default coloring.
+pub fn log_format_from_preset(preset: &LogFormatPreset) -> LogFormat {
+    let (_, pattern, default_colors) = preset;
+
+    let mut log_format = LogFormat {
+        pattern: pattern.to_string(),
+        pattern_coloring: default_colors.clone(),
+        // Transparent groups are not hilighted, so the text keeps its color there.
+        pattern_coloring_text: default_colors
+            .iter()
+            .map(|color| {
+                if *color == SEPARATOR {
+                    Color::GRAY
+                } else {
+                    Color::WHITE
+                }
+            })
+            .collect(),
+        pattern_coloring_text_use_original: default_colors
+            .iter()
+            .map(|color| *color == SEPARATOR)
+            .collect(),
+        pattern_hidden: vec![false; default_colors.len()],
+        pattern_group_names: Vec::new(),
+        // Presets capture the whole line anyway.
+        keep_uncaptured_text: false,
+    };
+
+    // Only fills in the group names, the colors are kept by index.
+    let compiled_regex = regex::Regex::new(pattern).expect("preset pattern is valid");
+    log_format.update_groups(&compiled_regex);
+
+    log_format
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_LINES: [&str; 6] = [
+        "[   12.345678] usb 1-1: new high-speed USB device",
+        "2024-01-31 12:34:56 service started",
+        "Jan  5 06:25:01 myhost CRON[1234]: (root) CMD (run-parts /etc/cron.hourly)",
+        r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326 "http://example.com/" "Mozilla/4.08""#,
+        "01-12 06:25:01.123  1234  5678 E ActivityManager: ANR in com.example",
+        "WARNING:root:disk almost full",
+    ];
+
+    #[test]
+    fn presets_capture_whole_sample_lines() {
+        assert_eq!(LOG_FORMAT_PRESETS.len(), SAMPLE_LINES.len());
+
+        for (preset, sample_line) in LOG_FORMAT_PRESETS.iter().zip(SAMPLE_LINES) {
+            let log_format = log_format_from_preset(preset);
+            let compiled_regex = regex::Regex::new(&log_format.pattern).unwrap();
+            assert_eq!(
+                compiled_regex.captures_len() - 1,
+                log_format.pattern_coloring.len(),
+                "{}",
+                preset.0
+            );
+
+            let captures = compiled_regex.captures(sample_line).unwrap();
+            let captured: String = captures
+                .iter()
+                .skip(1)
+                .flatten()
+                .map(|group| group.as_str())
+                .collect();
+            assert_eq!(captured, sample_line, "{}", preset.0);
+        }
+    }
+
+    #[test]
+    fn preset_group_names_filled() {
+        let log_format = log_format_from_preset(&LOG_FORMAT_PRESETS[5]);
+        assert_eq!(
+            log_format.pattern_group_names,
+            vec!["level", "", "logger", "", "message"]
+        );
+        assert_eq!(log_format.pattern_coloring[0], LEVEL);
+        assert!(log_format.pattern_coloring_text_use_original[1]);
+    }
+}
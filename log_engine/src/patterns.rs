@@ -0,0 +1,153 @@
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::ansi::visible_text;
+use crate::duplicates::duplicate_key;
+use crate::histogram::LEADING_TIMESTAMP_PATTERN;
+use crate::line_handlers::{LineHandler, RedactLineHandler};
+use crate::linevec::*;
+use crate::log_content::LogContent;
+use crate::style::Style;
+use crate::user_settings::UserSettings;
+
+// Stands for the masked out parts in the patterns, like in Drain.
+pub const PATTERN_PLACEHOLDER: &str = "<*>";
+
+// Hex numbers, long hex strings (hashes, ids) and any other numbers.
+pub fn default_pattern_masks() -> Vec<String> {
+    vec![
+        r"0x[0-9a-fA-F]+".to_string(),
+        r"\b[0-9a-fA-F]{8,}\b".to_string(),
+        r"\d+".to_string(),
+    ]
+}
+
+// Lines differing only in the masked out parts have the same signature.
+pub struct PatternMasker {
+    mask_regexes: Vec<Regex>,
+}
+
+impl PatternMasker {
+    // Invalid masks are left out.
+    pub fn new(pattern_masks: &[String]) -> Self {
+        let mask_regexes = pattern_masks
+            .iter()
+            .filter(|mask| !mask.is_empty())
+            .filter_map(|mask| match Regex::new(mask) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    eprintln!("Invalid pattern mask: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Self { mask_regexes }
+    }
+
+    // The leading timestamp is dropped, as for the duplicates.
+    pub fn signature(&self, line: &str) -> String {
+        let mut signature = duplicate_key(line).trim().to_string();
+        for mask_regex in &self.mask_regexes {
+            signature = mask_regex
+                .replace_all(&signature, PATTERN_PLACEHOLDER)
+                .into_owned();
+        }
+
+        signature
+    }
+}
+
+// The most frequent signatures of the (redacted) lines, the most frequent first.
+pub fn top_patterns(
+    content: &LogContent,
+    user_settings: &UserSettings,
+    top_n: usize,
+) -> Vec<(String, usize)> {
+    let masker = PatternMasker::new(&user_settings.pattern_masks);
+    let mut redact_handler = RedactLineHandler::new(user_settings);
+    let mut pattern_counts: HashMap<String, usize> = HashMap::new();
+
+    for line in content.lines() {
+        let mut line_parts: LineVec = vec![(
+            visible_text(&line, user_settings.ansi_codes).into_owned(),
+            Style::default(),
+        )];
+        if let Some(handler) = &mut redact_handler {
+            handler.process_line(&mut line_parts);
+        }
+
+        *pattern_counts
+            .entry(masker.signature(&linevec_text(&line_parts)))
+            .or_default() += 1;
+    }
+
+    let mut patterns: Vec<(String, usize)> = pattern_counts.into_iter().collect();
+    patterns.sort_by(|(a_pattern, a_count), (b_pattern, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_pattern.cmp(b_pattern))
+    });
+    patterns.truncate(top_n);
+
+    patterns
+}
+
+// Regex filter matching the lines of the pattern, the placeholders match anything.
+pub fn pattern_filter_regex(pattern: &str) -> String {
+    let pattern_regex: Vec<String> = pattern
+        .split(PATTERN_PLACEHOLDER)
+        .map(regex::escape)
+        .collect();
+    let leading_timestamp = LEADING_TIMESTAMP_PATTERN.trim_start_matches('^');
+
+    format!(
+        r"^(?:{})?\s*{}\s*$",
+        leading_timestamp,
+        pattern_regex.join(".+?")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_and_hex_masked() {
+        let masker = PatternMasker::new(&default_pattern_masks());
+        assert_eq!(
+            masker.signature("12:00:01 request 42 took 0x1f ms, id deadbeef01"),
+            "request <*> took <*> ms, id <*>"
+        );
+        assert_eq!(masker.signature("no numbers"), "no numbers");
+
+        // Nothing masked out with no (valid) masks.
+        let masker = PatternMasker::new(&["(".to_string(), String::new()]);
+        assert_eq!(masker.signature("request 42"), "request 42");
+    }
+
+    #[test]
+    fn lines_grouped_by_pattern() {
+        let content = LogContent::from_string(
+            "request 1 done\nrequest 22 done\n12:00:01 request 3 done\nuser 7 logged in\n"
+                .to_string(),
+        );
+        let user_settings = UserSettings::default();
+
+        assert_eq!(
+            top_patterns(&content, &user_settings, 5),
+            vec![
+                ("request <*> done".to_string(), 3),
+                ("user <*> logged in".to_string(), 1)
+            ]
+        );
+        assert_eq!(top_patterns(&content, &user_settings, 1).len(), 1);
+    }
+
+    #[test]
+    fn pattern_filter_matches_its_lines() {
+        let filter_regex = Regex::new(&pattern_filter_regex("request <*> done (<*>%)")).unwrap();
+        assert!(filter_regex.is_match("request 1 done (50%)"));
+        assert!(filter_regex.is_match("2024-01-31 12:00:01 request 22 done (5%)"));
+        assert!(!filter_regex.is_match("request 1 done"));
+        assert!(!filter_regex.is_match("user 1 request 1 done (50%)"));
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+use crate::log_content::LogContent;
+use crate::user_settings::UserSettings;
+use crate::{LogJobs, recalculate_log_job_cancellable};
+
+struct RecalcRequest {
+    generation: u64,
+    content: LogContent,
+    user_settings: UserSettings,
+}
+
+// Runs recalculate_log_job on a background thread, so the UI doesn't freeze on big files.
+// Each request gets a generation number, only the result of the latest request is ever returned.
+// A newer request cancels the one being processed, the stale settings aren't worth finishing.
+pub struct RecalcWorker {
+    request_sender: mpsc::Sender<RecalcRequest>,
+    result_receiver: mpsc::Receiver<(u64, LogJobs)>,
+    latest_generation: Arc<AtomicU64>, // shared with the worker thread
+    received_generation: u64,
+}
+
+impl Default for RecalcWorker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RecalcWorker {
+    pub fn new() -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<RecalcRequest>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let latest_generation = Arc::new(AtomicU64::new(0));
+
+        let thread_latest_generation = Arc::clone(&latest_generation);
+        thread::spawn(move || {
+            while let Ok(mut request) = request_receiver.recv() {
+                // Coalesce rapid edits, only the newest settings are worth processing.
+                while let Ok(newer_request) = request_receiver.try_recv() {
+                    request = newer_request;
+                }
+
+                let is_stale =
+                    || thread_latest_generation.load(Ordering::Relaxed) != request.generation;
+                let log_jobs = recalculate_log_job_cancellable(
+                    &request.content,
+                    &request.user_settings,
+                    &is_stale,
+                );
+                if let Some(log_jobs) = log_jobs
+                    && result_sender.send((request.generation, log_jobs)).is_err()
+                {
+                    // Nobody listens anymore.
+                    break;
+                }
+            }
+        });
+
+        Self {
+            request_sender,
+            result_receiver,
+            latest_generation,
+            received_generation: 0,
+        }
+    }
+
+    // Content is cheap to clone, it shares the line data with the original.
+    pub fn request(&mut self, content: LogContent, user_settings: &UserSettings) {
+        let generation = self.latest_generation.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let request = RecalcRequest {
+            generation,
+            content,
+            user_settings: user_settings.clone(),
+        };
+
+        if self.request_sender.send(request).is_err() {
+            eprintln!("Recalculation worker is not running");
+        }
+    }
+
+    // Returns the result of the latest request once it's ready, stale results are discarded.
+    pub fn poll_result(&mut self) -> Option<LogJobs> {
+        let mut latest_result = None;
+
+        let latest_generation = self.latest_generation.load(Ordering::Relaxed);
+        for (generation, log_jobs) in self.result_receiver.try_iter() {
+            if generation == latest_generation {
+                self.received_generation = generation;
+                latest_result = Some(log_jobs);
+            }
+        }
+
+        latest_result
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.received_generation != self.latest_generation.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linevec_text;
+    use std::time::Duration;
+
+    fn wait_for_result(worker: &mut RecalcWorker) -> LogJobs {
+        for _ in 0..500 {
+            if let Some(log_jobs) = worker.poll_result() {
+                return log_jobs;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("No recalculation result received");
+    }
+
+    #[test]
+    fn only_latest_request_result_returned() {
+        let content = LogContent::from_string("error one\ninfo two\nerror three\n".to_string());
+        let mut worker = RecalcWorker::new();
+
+        let mut user_settings = UserSettings::default();
+        user_settings.filters[0].term = "info".to_string();
+        worker.request(content.clone(), &user_settings);
+
+        user_settings.filters[0].term = "error".to_string();
+        worker.request(content, &user_settings);
+        assert!(worker.is_busy());
+
+        let log_jobs = wait_for_result(&mut worker);
+        assert!(!worker.is_busy());
+        assert_eq!(crate::visible_line_count(&log_jobs), 2);
+        assert_eq!(
+            linevec_text(&crate::log_line(&log_jobs, 0).unwrap()),
+            "error one"
+        );
+        assert_eq!(
+            linevec_text(&crate::log_line(&log_jobs, 1).unwrap()),
+            "error three"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+// Patterns typed in by hand come and go, the whole cache is dropped rather than growing forever.
+const MAX_CACHED_REGEXES: usize = 256;
+
+static REGEX_CACHE: LazyLock<RegexCache> = LazyLock::new(RegexCache::default);
+
+// The handlers are recreated on every recalculation (and for every chunk of lines), the regexes
+// they use are compiled once per pattern. Cloning a Regex is cheap, the compiled program is shared.
+#[derive(Default)]
+pub struct RegexCache {
+    regexes: Mutex<HashMap<(String, bool), Regex>>,
+    hits: AtomicUsize,
+}
+
+impl RegexCache {
+    // Invalid patterns aren't cached, they are usually still being typed.
+    pub fn get(&self, pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+        let key = (pattern.to_string(), case_insensitive);
+        if let Some(regex) = self.regexes.lock().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(regex.clone());
+        }
+
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()?;
+
+        let mut regexes = self.regexes.lock().unwrap();
+        if regexes.len() >= MAX_CACHED_REGEXES {
+            regexes.clear();
+        }
+        regexes.insert(key, regex.clone());
+
+        Ok(regex)
+    }
+
+    #[cfg(test)]
+    fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    fn cached_count(&self) -> usize {
+        self.regexes.lock().unwrap().len()
+    }
+}
+
+pub fn cached_regex(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    REGEX_CACHE.get(pattern, case_insensitive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_patterns_hit_cache() {
+        let regex_cache = RegexCache::default();
+
+        let regex = regex_cache.get(r"^(\w+) (\d+)", false).unwrap();
+        assert_eq!(regex_cache.hits(), 0);
+        assert_eq!(
+            regex_cache.get(r"^(\w+) (\d+)", false).unwrap().as_str(),
+            regex.as_str()
+        );
+        assert_eq!(regex_cache.hits(), 1);
+
+        // Same pattern, other case sensitivity is another regex.
+        let case_insensitive = regex_cache.get(r"^(\w+) (\d+)", true).unwrap();
+        assert_eq!(regex_cache.hits(), 1);
+        assert!(case_insensitive.is_match("ABC 1"));
+        assert_eq!(regex_cache.cached_count(), 2);
+
+        assert!(regex_cache.get("(", false).is_err());
+        assert!(regex_cache.get("(", false).is_err());
+        assert_eq!(regex_cache.hits(), 1);
+        assert_eq!(regex_cache.cached_count(), 2);
+    }
+
+    #[test]
+    fn cache_bounded() {
+        let regex_cache = RegexCache::default();
+        for i in 0..MAX_CACHED_REGEXES + 1 {
+            regex_cache.get(&format!("line {}", i), false).unwrap();
+        }
+        assert_eq!(regex_cache.cached_count(), 1);
+    }
+}
@@ -0,0 +1,158 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::ansi::visible_text;
+use crate::duplicates::duplicate_key;
+use crate::histogram::{LEADING_TIMESTAMP_PATTERN, TimestampKind, line_timestamp};
+use crate::line_handlers::line_level;
+use crate::linevec::*;
+use crate::log_content::LogContent;
+use crate::user_settings::UserSettings;
+use crate::{ProcessingProgress, process_lines};
+
+static LEADING_TIMESTAMP_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(LEADING_TIMESTAMP_PATTERN).unwrap());
+
+#[derive(Default, Debug)]
+pub struct LogStats {
+    pub total_lines: usize,
+    pub visible_lines: usize, // the rest is filtered out
+    pub log_format_matched_lines: usize,
+    pub search_matched_lines: usize,
+    pub token_counts: Vec<(String, usize)>, // the tokens found at least once
+    pub level_counts: Vec<(String, usize)>, // in the order of the level colors
+    pub top_lines: Vec<(String, usize)>,    // the most frequent first, timestamps are ignored
+    pub time_span: Option<(f64, f64, TimestampKind)>, // of the leading timestamps
+}
+
+// Everything in a single pass through the line handlers, over the lines left after the filters.
+pub fn log_stats(content: &LogContent, user_settings: &UserSettings, top_n: usize) -> LogStats {
+    let level_keywords: Vec<&str> = user_settings
+        .level_colors
+        .iter()
+        .map(|(keyword, _)| keyword.as_str())
+        .filter(|keyword| !keyword.trim().is_empty())
+        .collect();
+
+    let mut stats = LogStats {
+        total_lines: content.line_count(),
+        level_counts: level_keywords
+            .iter()
+            .map(|keyword| (keyword.to_string(), 0))
+            .collect(),
+        ..Default::default()
+    };
+    let mut line_counts: HashMap<String, usize> = HashMap::new();
+
+    let mut progress = ProcessingProgress::default();
+    process_lines(
+        &mut progress,
+        content,
+        user_settings,
+        false,
+        |processed_line| {
+            stats.visible_lines += 1;
+            if !processed_line.points_of_interest.is_empty() {
+                stats.search_matched_lines += 1;
+            }
+
+            if let Some(level_index) =
+                line_level(&processed_line.parts, level_keywords.iter().copied())
+            {
+                stats.level_counts[level_index].1 += 1;
+            }
+
+            // The log format could hide the timestamp, it's taken from the original line.
+            if let Some(line) = content.original_line(processed_line.original_line_no)
+                && let Some((seconds, kind)) = line_timestamp(
+                    &LEADING_TIMESTAMP_REGEX,
+                    &visible_text(&line, user_settings.ansi_codes),
+                )
+            {
+                stats.time_span = match stats.time_span {
+                    Some((first, last, first_kind)) => {
+                        Some((first.min(seconds), last.max(seconds), first_kind))
+                    }
+                    None => Some((seconds, seconds, kind)),
+                };
+            }
+
+            let line_text = linevec_text(&processed_line.parts);
+            *line_counts
+                .entry(duplicate_key(&line_text).trim().to_string())
+                .or_default() += 1;
+        },
+    );
+
+    stats.log_format_matched_lines = progress.log_format_lines_matched;
+    stats.token_counts = progress
+        .token_counts
+        .iter()
+        .zip(&user_settings.token_colors)
+        .filter(|(count, _)| **count > 0)
+        .map(|(count, token_color)| (token_color.token.clone(), *count))
+        .collect();
+
+    let mut top_lines: Vec<(String, usize)> = line_counts.into_iter().collect();
+    top_lines.sort_by(|(a_line, a_count), (b_line, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_line.cmp(b_line))
+    });
+    top_lines.truncate(top_n);
+    stats.top_lines = top_lines;
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_counted_in_one_pass() {
+        let content = LogContent::from_string(
+            "12:00:01 ERROR disk full\n\
+            12:00:02 INFO retrying\n\
+            12:00:05 INFO retrying\n\
+            12:00:09 WARN disk slow\n\
+            debug noise\n"
+                .to_string(),
+        );
+        let mut user_settings = UserSettings {
+            search_term: "disk".to_string(),
+            ..Default::default()
+        };
+        user_settings.filters[0].term = "noise".to_string();
+        user_settings.filters[0].negative = true;
+        user_settings.token_colors[0].token = "retrying".to_string();
+
+        let stats = log_stats(&content, &user_settings, 2);
+        assert_eq!(stats.total_lines, 5);
+        assert_eq!(stats.visible_lines, 4);
+        assert_eq!(stats.search_matched_lines, 2);
+        assert_eq!(stats.token_counts, vec![("retrying".to_string(), 2)]);
+        assert_eq!(
+            stats.top_lines,
+            vec![
+                ("INFO retrying".to_string(), 2),
+                ("ERROR disk full".to_string(), 1)
+            ]
+        );
+
+        let level_count = |keyword: &str| {
+            stats
+                .level_counts
+                .iter()
+                .find(|(level_keyword, _)| level_keyword == keyword)
+                .map(|(_, count)| *count)
+        };
+        assert_eq!(level_count("ERROR"), Some(1));
+        assert_eq!(level_count("INFO"), Some(2));
+        assert_eq!(level_count("WARN"), Some(1));
+
+        assert_eq!(
+            stats.time_span,
+            Some((43201.0, 43209.0, TimestampKind::TimeOfDay))
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Engine side colors and text styles, so the handlers and settings don't need egui. The GUI
+// converts them to egui types with the "egui" feature enabled.
+
+// sRGBA, not premultiplied.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    // Same values as the egui colors of the same name.
+    pub const TRANSPARENT: Color = Color::from_rgba_unmultiplied(0, 0, 0, 0);
+    pub const BLACK: Color = Color::from_rgb(0, 0, 0);
+    pub const WHITE: Color = Color::from_rgb(255, 255, 255);
+    pub const GRAY: Color = Color::from_rgb(160, 160, 160);
+    pub const RED: Color = Color::from_rgb(255, 0, 0);
+    pub const GREEN: Color = Color::from_rgb(0, 255, 0);
+    pub const BLUE: Color = Color::from_rgb(0, 0, 255);
+    pub const YELLOW: Color = Color::from_rgb(255, 255, 0);
+    pub const ORANGE: Color = Color::from_rgb(255, 165, 0);
+    pub const LIGHT_GREEN: Color = Color::from_rgb(144, 238, 144);
+
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn from_rgba_unmultiplied(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.a == 0
+    }
+
+    // Fades the color out, i.e. for dimmed context lines.
+    pub fn gamma_multiply(self, factor: f32) -> Self {
+        Self {
+            a: (self.a as f32 * factor.clamp(0.0, 1.0)).round() as u8,
+            ..self
+        }
+    }
+}
+
+// How a part of a line is drawn, the font is always monospace.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Style {
+    pub color: Color,
+    pub background: Color,
+    pub underline: Color, // transparent for no underline
+    pub font_size: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            color: Color::GRAY,
+            background: Color::TRANSPARENT,
+            underline: Color::TRANSPARENT,
+            font_size: 12.0,
+        }
+    }
+}
+
+#[cfg(feature = "egui")]
+impl From<Color> for egui::Color32 {
+    fn from(color: Color) -> Self {
+        egui::Color32::from_rgba_unmultiplied(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(feature = "egui")]
+impl From<egui::Color32> for Color {
+    fn from(color: egui::Color32) -> Self {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        Color::from_rgba_unmultiplied(r, g, b, a)
+    }
+}
+
+#[cfg(feature = "egui")]
+impl From<&Style> for egui::TextFormat {
+    fn from(style: &Style) -> Self {
+        egui::TextFormat {
+            font_id: egui::FontId::monospace(style.font_size),
+            color: style.color.into(),
+            background: style.background.into(),
+            underline: if style.underline.is_transparent() {
+                egui::Stroke::NONE
+            } else {
+                egui::Stroke::new(2.0, style.underline)
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faded_color_keeps_its_hue() {
+        let faded = Color::from_rgb(200, 100, 50).gamma_multiply(0.5);
+        assert_eq!(faded, Color::from_rgba_unmultiplied(200, 100, 50, 128));
+        assert!(Color::TRANSPARENT.gamma_multiply(0.5).is_transparent());
+    }
+
+    #[cfg(feature = "egui")]
+    #[test]
+    fn colors_match_egui() {
+        for (color, color32) in [
+            (Color::TRANSPARENT, egui::Color32::TRANSPARENT),
+            (Color::BLACK, egui::Color32::BLACK),
+            (Color::WHITE, egui::Color32::WHITE),
+            (Color::GRAY, egui::Color32::GRAY),
+            (Color::RED, egui::Color32::RED),
+            (Color::GREEN, egui::Color32::GREEN),
+            (Color::BLUE, egui::Color32::BLUE),
+            (Color::YELLOW, egui::Color32::YELLOW),
+            (Color::ORANGE, egui::Color32::ORANGE),
+            (Color::LIGHT_GREEN, egui::Color32::LIGHT_GREEN),
+        ] {
+            assert_eq!(egui::Color32::from(color), color32);
+            assert_eq!(Color::from(color32), color);
+        }
+
+        let text_format = egui::TextFormat::from(&Style::default());
+        assert_eq!(text_format.color, egui::TextFormat::default().color);
+        assert_eq!(text_format.font_id, egui::FontId::monospace(12.0));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::style::Color;
+
+// A token and its optional color, the tokens without one get a palette color when added.
+pub type ListedToken = (String, Option<Color>);
+
+// "#RRGGBB", the alpha is always opaque.
+pub fn parse_hex_color(text: &str) -> Option<Color> {
+    let hex = text.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    Some(Color::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+// One token per line, optionally followed by ",#RRGGBB". The color is taken only after the last
+// comma, so the tokens can have commas too. Empty lines are skipped, the bad lines are left out
+// and returned as the errors, by their 1-based line numbers.
+pub fn parse_token_list(text: &str) -> (Vec<ListedToken>, Vec<(usize, String)>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (token, color) = match line.rsplit_once(',') {
+            Some((token, color_text)) if color_text.trim().starts_with('#') => {
+                match parse_hex_color(color_text) {
+                    Some(color) => (token, Some(color)),
+                    None => {
+                        errors.push((
+                            line_index + 1,
+                            format!("invalid color \"{}\", expected #RRGGBB", color_text.trim()),
+                        ));
+                        continue;
+                    }
+                }
+            }
+            _ => (line, None),
+        };
+
+        let token = token.trim();
+        if token.is_empty() {
+            errors.push((line_index + 1, "no token before the color".to_string()));
+            continue;
+        }
+
+        tokens.push((token.to_string(), color));
+    }
+
+    (tokens, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_parsed() {
+        assert_eq!(
+            parse_hex_color("#E69F00"),
+            Some(Color::from_rgb(230, 159, 0))
+        );
+        assert_eq!(
+            parse_hex_color(" #00ff7f "),
+            Some(Color::from_rgb(0, 255, 127))
+        );
+        assert_eq!(parse_hex_color("E69F00"), None);
+        assert_eq!(parse_hex_color("#E69F0"), None);
+        assert_eq!(parse_hex_color("#E69F0G"), None);
+    }
+
+    #[test]
+    fn token_list_parsed_with_bad_lines_reported() {
+        let (tokens, errors) = parse_token_list(
+            "ERROR,#ff0000\n\n  timeout  \nkey=a,b\nWARN, #12345\n,#00ff00\nuser, #00FF00\n",
+        );
+
+        assert_eq!(
+            tokens,
+            vec![
+                ("ERROR".to_string(), Some(Color::RED)),
+                ("timeout".to_string(), None),
+                ("key=a,b".to_string(), None),
+                ("user".to_string(), Some(Color::GREEN)),
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                (5, "invalid color \"#12345\", expected #RRGGBB".to_string()),
+                (6, "no token before the color".to_string()),
+            ]
+        );
+    }
+}
@@ -1,187 +1,1409 @@
-use egui::{Color32, FontId};
+use crate::line_handlers::{
+    DEFAULT_HANDLER_ORDER, LineHandlerType, SEARCH_HILIGHT_BACKGROUND, SEARCH_HILIGHT_TEXT,
+};
+use crate::style::Color;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 
+use crate::encoding;
+use crate::key_bindings::{KeyBindings, default_key_bindings};
+use crate::links;
+use crate::patterns;
+use crate::token_list::ListedToken;
+use crate::whitespace;
+
 #[derive(PartialEq, Clone, Default)]
 pub struct LogFormat {
     pub pattern: String, // matching regex (i.e. "^\[[0-9]*\.[0.9]*\] .*$")
-    pub pattern_coloring: Vec<egui::Color32>,
-    pub pattern_coloring_text: Vec<egui::Color32>,
+    pub pattern_coloring: Vec<Color>,
+    pub pattern_coloring_text: Vec<Color>,
     pub pattern_coloring_text_use_original: Vec<bool>,
+    pub pattern_hidden: Vec<bool>, // group text left out of the line
+    pub pattern_group_names: Vec<String>, // empty for unnamed groups
+    pub keep_uncaptured_text: bool, // text outside the groups is kept with original format
+}
+
+impl LogFormat {
+    // Index into the coloring vectors for given capture group. Named groups are looked up by name,
+    // so their colors survive reordering the groups; unnamed ones fall back to the group index.
+    pub fn coloring_index(&self, group_index: usize, group_name: Option<&str>) -> Option<usize> {
+        if let Some(group_name) = group_name
+            && let Some(named_index) = self
+                .pattern_group_names
+                .iter()
+                .position(|name| name == group_name)
+        {
+            return Some(named_index);
+        }
+
+        (group_index < self.pattern_coloring.len()).then_some(group_index)
+    }
+
+    // Rebuilds the coloring vectors to match the groups of the (possibly changed) pattern.
+    pub fn update_groups(&mut self, compiled_regex: &regex::Regex) {
+        let group_names: Vec<String> = compiled_regex
+            .capture_names()
+            .skip(1) // full match
+            .map(|name| name.unwrap_or_default().to_string())
+            .collect();
+
+        if group_names == self.pattern_group_names
+            && group_names.len() == self.pattern_coloring.len()
+            && group_names.len() == self.pattern_hidden.len()
+        {
+            return;
+        }
+
+        let mut pattern_coloring = Vec::with_capacity(group_names.len());
+        let mut pattern_coloring_text = Vec::with_capacity(group_names.len());
+        let mut pattern_coloring_text_use_original = Vec::with_capacity(group_names.len());
+        let mut pattern_hidden = Vec::with_capacity(group_names.len());
+
+        for (group_index, group_name) in group_names.iter().enumerate() {
+            let group_name = (!group_name.is_empty()).then_some(group_name.as_str());
+
+            match self.coloring_index(group_index, group_name) {
+                Some(old_index) => {
+                    pattern_coloring.push(self.pattern_coloring[old_index]);
+                    pattern_coloring_text.push(self.pattern_coloring_text[old_index]);
+                    pattern_coloring_text_use_original
+                        .push(self.pattern_coloring_text_use_original[old_index]);
+                    // Configs from before hiding groups was possible don't have the flags.
+                    pattern_hidden
+                        .push(self.pattern_hidden.get(old_index).copied().unwrap_or(false));
+                }
+                None => {
+                    pattern_coloring.push(Color::RED);
+                    pattern_coloring_text.push(Color::GRAY);
+                    pattern_coloring_text_use_original.push(true);
+                    pattern_hidden.push(false);
+                }
+            }
+        }
+
+        self.pattern_coloring = pattern_coloring;
+        self.pattern_coloring_text = pattern_coloring_text;
+        self.pattern_coloring_text_use_original = pattern_coloring_text_use_original;
+        self.pattern_hidden = pattern_hidden;
+        self.pattern_group_names = group_names;
+    }
+}
+
+// A single filter, all filters are applied one after another.
+#[derive(PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct FilterSpec {
+    pub term: String,
+    pub match_case: bool,
+    pub whole_word: bool,
+    pub negative: bool,
+    pub extended: bool,
+    pub regex: bool,
+    pub numeric: bool, // the term is a comparison, see parse_numeric_condition()
+}
+
+// Text matching the regex is replaced wherever it appears, i.e. to hide secrets before sharing the log.
+// The replacement can refer to the capture groups ("$1").
+#[derive(PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct RedactRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+// A token hilighted with given background wherever it appears.
+#[derive(PartialEq, Clone)]
+pub struct TokenColor {
+    pub token: String,
+    pub color: Color,
+    pub match_case: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+// Okabe-Ito without the black, it wouldn't show on the dark background.
+const OKABE_ITO: [Color; 8] = [
+    Color::from_rgb(230, 159, 0),
+    Color::from_rgb(86, 180, 233),
+    Color::from_rgb(0, 158, 115),
+    Color::from_rgb(240, 228, 66),
+    Color::from_rgb(0, 114, 178),
+    Color::from_rgb(213, 94, 0),
+    Color::from_rgb(204, 121, 167),
+    Color::from_rgb(153, 153, 153),
+];
+
+// Paul Tol's qualitative schemes.
+const TOL_BRIGHT: [Color; 7] = [
+    Color::from_rgb(68, 119, 170),
+    Color::from_rgb(102, 204, 238),
+    Color::from_rgb(34, 136, 51),
+    Color::from_rgb(204, 187, 68),
+    Color::from_rgb(238, 102, 119),
+    Color::from_rgb(170, 51, 119),
+    Color::from_rgb(187, 187, 187),
+];
+
+const TOL_MUTED: [Color; 9] = [
+    Color::from_rgb(51, 34, 136),
+    Color::from_rgb(136, 204, 238),
+    Color::from_rgb(68, 170, 153),
+    Color::from_rgb(17, 119, 51),
+    Color::from_rgb(153, 153, 51),
+    Color::from_rgb(221, 204, 119),
+    Color::from_rgb(204, 102, 119),
+    Color::from_rgb(136, 34, 85),
+    Color::from_rgb(170, 68, 153),
+];
+
+// IBM Design Library.
+const IBM: [Color; 5] = [
+    Color::from_rgb(100, 143, 255),
+    Color::from_rgb(120, 94, 240),
+    Color::from_rgb(220, 38, 127),
+    Color::from_rgb(254, 97, 0),
+    Color::from_rgb(255, 176, 0),
+];
+
+// Named sets of colors that can be told apart with the common kinds of colorblindness, the token
+// colors cycle through one.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum TokenPalette {
+    #[default]
+    OkabeIto,
+    TolBright,
+    TolMuted,
+    Ibm,
+}
+
+impl TokenPalette {
+    pub const ALL: [TokenPalette; 4] = [
+        TokenPalette::OkabeIto,
+        TokenPalette::TolBright,
+        TokenPalette::TolMuted,
+        TokenPalette::Ibm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenPalette::OkabeIto => "Okabe-Ito",
+            TokenPalette::TolBright => "Tol bright",
+            TokenPalette::TolMuted => "Tol muted",
+            TokenPalette::Ibm => "IBM",
+        }
+    }
+
+    pub fn colors(&self) -> &'static [Color] {
+        match self {
+            TokenPalette::OkabeIto => &OKABE_ITO,
+            TokenPalette::TolBright => &TOL_BRIGHT,
+            TokenPalette::TolMuted => &TOL_MUTED,
+            TokenPalette::Ibm => &IBM,
+        }
+    }
+
+    pub fn color(&self, index: usize) -> Color {
+        let colors = self.colors();
+        colors[index % colors.len()]
+    }
+}
+
+impl TokenColor {
+    // Empty token, each index gets a different color of the default palette.
+    pub fn new_empty(index: usize) -> Self {
+        Self::new_empty_from(index, TokenPalette::default())
+    }
+
+    pub fn new_empty_from(index: usize, palette: TokenPalette) -> Self {
+        Self {
+            token: String::new(),
+            color: palette.color(index),
+            match_case: true,
+            whole_word: false,
+            regex: false,
+        }
+    }
+}
+
+// How the search matches are shown. Underlined matches keep the colors of the other hilights.
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum SearchHighlightStyle {
+    #[default]
+    Background,
+    Underline,
+}
+
+// What's done with the ANSI escape sequences some programs write into their logs.
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum AnsiCodes {
+    #[default]
+    Colored, // the SGR sequences color the text, the rest is stripped
+    Stripped,
+    Kept, // shown as they are
+}
+
+impl AnsiCodes {
+    pub const ALL: [AnsiCodes; 3] = [AnsiCodes::Colored, AnsiCodes::Stripped, AnsiCodes::Kept];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnsiCodes::Colored => "Colored",
+            AnsiCodes::Stripped => "Stripped",
+            AnsiCodes::Kept => "Kept",
+        }
+    }
 }
 
 #[derive(PartialEq, Clone)]
 pub struct UserSettings {
     pub wrap_text: bool,
+    pub wrap_break_anywhere: bool, // otherwise the lines wrap between words where they can
     pub autoscroll: bool,
+    pub follow_max_lines: usize, // kept while following a file, the oldest are dropped, 0 is no limit
+    pub stream_max_lines: usize, // the same for the streams (stdin, TCP, journal)
     pub search_term: String,
     pub search_match_case: bool,
     pub search_whole_word: bool,
-    pub filter_term: String,
-    pub filter_match_case: bool,
-    pub filter_whole_word: bool,
-    pub filter_negative: bool,
-    pub filter_extended: bool,
+    pub search_highlight_style: SearchHighlightStyle,
+    pub search_highlight_background: Color, // the underline too, when underlining
+    pub search_highlight_text: Color,
+    pub filters: Vec<FilterSpec>,
+    pub filter_context_before: usize,
+    pub filter_context_after: usize,
+    pub filter_dim_non_matches: bool, // the lines the filters hide are shown dimmed instead
+    pub continuation_pattern: String, // regex of the lines that continue the previous entry
+    pub table_view: bool,             // the named groups of the log format shown in columns
+    pub table_columns: Vec<String>,   // names of the groups shown, all of them if empty
     pub file_path: String,
     pub log_format: LogFormat,
-    pub token_colors: Vec<(String, Color32)>,
-    pub font: FontId,
+    pub token_colors: Vec<TokenColor>,
+    pub level_colors: Vec<(String, Color)>, // level keyword, line background
+    pub font_size: f32,
+    pub line_spacing: f32, // multiplier of the font's row height
+    pub tab_width: usize,  // tabs are expanded up to the next multiple of it, kept if 0
+    pub ansi_codes: AnsiCodes,
     pub comments_visible: bool,
+    pub comment_color: Color,
+    pub show_whitespace: bool, // spaces and tabs drawn as glyphs, see whitespace::whitespace_shown()
+    pub whitespace_color: Color,
+    pub encoding: String,
+    pub top_line_no: usize, // original line shown at the top of the view, 0 if unknown
+    pub input_debounce_ms: u64, // wait after the last keystroke before recalculating
+    pub collapse_duplicates: bool,
+    pub expanded_duplicates: BTreeSet<usize>, // original line numbers of the expanded collapsed rows
+    pub folded_ranges: BTreeMap<usize, usize>, // first to last original line number, not overlapping
+    pub json_mode: bool,
+    pub expanded_json_lines: BTreeSet<usize>, // original line numbers of the pretty printed lines
+    pub key_value_pairs: bool,
+    pub key_value_separators: String,  // each char is a separator
+    pub key_value_hidden_keys: String, // comma separated
+    pub redact_rules: Vec<RedactRule>,
+    pub pattern_masks: Vec<String>, // regexes of the parts masked out of the patterns
+    pub handler_order: Vec<LineHandlerType>, // see line_handlers::handler_order_problem()
+    pub links_clickable: bool,
+    pub editor_command: String, // opens "{file}" at "{line}", see links::editor_command()
+    pub key_bindings: KeyBindings,
+    pub smooth_scrolling: bool, // the scrolling keys ease towards the target instead of jumping
+    pub drag_scrolling: bool,   // dragging the log scrolls it, the text is selected with Shift held
+    pub kinetic_scrolling: bool, // the log keeps moving after the drag is released
     pub histogram_search_term: String,
     pub histogram_match_case: bool,
+    pub histogram_regex: bool,
+    pub histogram_time_buckets: bool,
+    pub histogram_timestamp_pattern: String, // empty means the first group of the log format
 }
 
+#[derive(Serialize, Deserialize)]
+struct TokenColorSerDes {
+    pub token: String,
+    pub color: (u8, u8, u8, u8), // RGBA
+    pub match_case: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+// Bumped whenever a field is renamed or changes its shape, see migrate_config(). Just adding
+// a field doesn't need it, missing fields are filled with defaults.
+const CONFIG_VERSION: u64 = 1;
+
+// The fields describing a kind of log rather than a session with it (no file path, no scroll
+// position), so the profile can be shared for all the logs of that kind.
+const PROFILE_FIELDS: [&str; 12] = [
+    "log_format_pattern",
+    "log_format_pattern_coloring",
+    "log_format_pattern_coloring_text",
+    "log_format_pattern_coloring_text_use_original",
+    "log_format_pattern_hidden",
+    "log_format_pattern_group_names",
+    "log_format_keep_uncaptured_text",
+    "token_colors",
+    "level_colors",
+    "filters",
+    "continuation_pattern",
+    "table_columns",
+];
+
 // Just a struct that doesnt use egui types, for ser/des; Q&D hack.
 #[derive(Serialize, Deserialize)]
 struct UserSettingsSerDes {
+    pub version: u64,
     pub wrap_text: bool,
+    pub wrap_break_anywhere: bool,
     pub autoscroll: bool,
+    pub follow_max_lines: usize,
+    pub stream_max_lines: usize,
     pub search_term: String,
     pub search_match_case: bool,
     pub search_whole_word: bool,
-    pub filter_term: String,
-    pub filter_match_case: bool,
-    pub filter_whole_word: bool,
-    pub filter_negative: bool,
-    pub filter_extended: bool,
+    pub search_highlight_style: SearchHighlightStyle,
+    pub search_highlight_background: (u8, u8, u8, u8), // RGBA
+    pub search_highlight_text: (u8, u8, u8, u8),       // RGBA
+    pub filters: Vec<FilterSpec>,
+    pub filter_context_before: usize,
+    pub filter_context_after: usize,
+    pub filter_dim_non_matches: bool,
+    pub continuation_pattern: String,
+    pub table_view: bool,
+    pub table_columns: Vec<String>,
     pub log_format_pattern: String,
     pub log_format_pattern_coloring: Vec<(u8, u8, u8, u8)>, // RGBA
     pub log_format_pattern_coloring_text: Vec<(u8, u8, u8, u8)>, // RGBA
     pub log_format_pattern_coloring_text_use_original: Vec<bool>,
-    pub token_colors: Vec<(String, (u8, u8, u8, u8))>, // token_name, RGBA
+    pub log_format_pattern_hidden: Vec<bool>,
+    pub log_format_pattern_group_names: Vec<String>,
+    pub log_format_keep_uncaptured_text: bool,
+    pub token_colors: Vec<TokenColorSerDes>,
+    pub level_colors: Vec<(String, (u8, u8, u8, u8))>, // level keyword, RGBA
     pub font_size: f32,
+    pub line_spacing: f32,
+    pub tab_width: usize,
+    pub ansi_codes: AnsiCodes,
     pub comments_visible: bool,
+    pub comment_color: (u8, u8, u8, u8), // RGBA
+    pub show_whitespace: bool,
+    pub whitespace_color: (u8, u8, u8, u8), // RGBA
+    pub encoding: String,
+    pub top_line_no: usize,
+    pub input_debounce_ms: u64,
+    pub collapse_duplicates: bool,
+    pub json_mode: bool,
+    pub key_value_pairs: bool,
+    pub key_value_separators: String,
+    pub key_value_hidden_keys: String,
+    pub redact_rules: Vec<RedactRule>,
+    pub pattern_masks: Vec<String>,
+    pub handler_order: Vec<LineHandlerType>,
+    pub links_clickable: bool,
+    pub editor_command: String,
+    pub key_bindings: KeyBindings,
+    pub smooth_scrolling: bool,
+    pub drag_scrolling: bool,
+    pub kinetic_scrolling: bool,
+}
+
+fn rgba(color: Color) -> (u8, u8, u8, u8) {
+    (color.r, color.g, color.b, color.a)
+}
+
+fn from_rgba((r, g, b, a): (u8, u8, u8, u8)) -> Color {
+    Color::from_rgba_unmultiplied(r, g, b, a)
+}
+
+// Configs without a version were saved before the filters and token options were added:
+// one filter in "filter_*" fields and tokens as (token, RGBA) pairs.
+fn migrate_config_v0(config: &mut serde_json::Map<String, serde_json::Value>) {
+    let filter_fields = [
+        "filter_term",
+        "filter_match_case",
+        "filter_whole_word",
+        "filter_negative",
+        "filter_extended",
+    ];
+    if filter_fields
+        .iter()
+        .any(|field| config.contains_key(*field))
+    {
+        let mut filter = serde_json::Map::new();
+        for field in filter_fields {
+            if let Some(value) = config.remove(field) {
+                filter.insert(field.trim_start_matches("filter_").to_string(), value);
+            }
+        }
+        filter.insert("regex".to_string(), false.into());
+
+        config.insert("filters".to_string(), vec![filter].into());
+    }
+
+    if let Some(serde_json::Value::Array(token_colors)) = config.get_mut("token_colors") {
+        for token_color in token_colors.iter_mut() {
+            if let serde_json::Value::Array(token_and_color) = token_color
+                && let [token, color] = token_and_color.as_slice()
+            {
+                *token_color = serde_json::json!({
+                    "token": token,
+                    "color": color,
+                    "match_case": true,
+                    "whole_word": false,
+                    "regex": false,
+                });
+            }
+        }
+    }
+}
+
+// Upgrades an older config layout to the current one.
+fn migrate_config(config: &mut serde_json::Value) -> Result<(), Box<dyn Error>> {
+    let Some(config) = config.as_object_mut() else {
+        return Err("not a Logalyzer configuration".into());
+    };
+
+    let version = match config.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or("invalid configuration version")?,
+    };
+
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "configuration version {} is newer than the supported version {}, please update Logalyzer",
+            version, CONFIG_VERSION
+        )
+        .into());
+    }
+
+    if version < 1 {
+        migrate_config_v0(config);
+    }
+
+    // Fields added since the config was saved.
+    let serde_json::Value::Object(defaults) =
+        serde_json::to_value(UserSettings::default().to_ser_des())?
+    else {
+        unreachable!("settings serialize to an object");
+    };
+    for (field, default_value) in defaults {
+        config.entry(field).or_insert(default_value);
+    }
+    let serde_json::Value::Object(filter_defaults) = serde_json::to_value(FilterSpec::default())?
+    else {
+        unreachable!("filters serialize to an object");
+    };
+    if let Some(serde_json::Value::Array(filters)) = config.get_mut("filters") {
+        for filter in filters
+            .iter_mut()
+            .filter_map(|filter| filter.as_object_mut())
+        {
+            for (field, default_value) in &filter_defaults {
+                filter.entry(field.clone()).or_insert(default_value.clone());
+            }
+        }
+    }
+
+    config.insert("version".to_string(), CONFIG_VERSION.into());
+
+    Ok(())
 }
 
 impl UserSettings {
-    pub fn serialize(&self) -> Result<String, Box<dyn Error>> {
-        let ser_des = UserSettingsSerDes {
+    // True if nothing but the search changed, so the matches can be found again without
+    // processing the lines again.
+    pub fn differs_only_in_search(&self, other: &UserSettings) -> bool {
+        let other_with_this_search = UserSettings {
+            search_term: self.search_term.clone(),
+            search_match_case: self.search_match_case,
+            search_whole_word: self.search_whole_word,
+            search_highlight_style: self.search_highlight_style,
+            search_highlight_background: self.search_highlight_background,
+            search_highlight_text: self.search_highlight_text,
+            ..other.clone()
+        };
+
+        *self == other_with_this_search
+    }
+
+    // True if nothing but how the lines are laid out changed, the processed lines stay the same
+    // apart from their font size (see relayout_log_jobs()).
+    pub fn differs_only_in_layout(&self, other: &UserSettings) -> bool {
+        let other_with_this_layout = UserSettings {
+            font_size: self.font_size,
+            line_spacing: self.line_spacing,
+            wrap_text: self.wrap_text,
+            wrap_break_anywhere: self.wrap_break_anywhere,
+            show_whitespace: self.show_whitespace,
+            whitespace_color: self.whitespace_color,
+            ..other.clone()
+        };
+
+        *self == other_with_this_layout
+    }
+
+    // Colors all the tokens from the palette again, in their order.
+    pub fn recolor_tokens(&mut self, palette: TokenPalette) {
+        for (index, token_color) in self.token_colors.iter_mut().enumerate() {
+            token_color.color = palette.color(index);
+        }
+    }
+
+    // Fills the empty tokens first, the rest is appended. Tokens without a color get the palette
+    // color of the place they land in.
+    pub fn add_tokens(&mut self, tokens: Vec<ListedToken>, palette: TokenPalette) {
+        for (token, color) in tokens {
+            let index = self
+                .token_colors
+                .iter()
+                .position(|token_color| token_color.token.is_empty())
+                .unwrap_or_else(|| {
+                    self.token_colors
+                        .push(TokenColor::new_empty_from(self.token_colors.len(), palette));
+                    self.token_colors.len() - 1
+                });
+
+            let token_color = &mut self.token_colors[index];
+            token_color.token = token;
+            token_color.color = color.unwrap_or_else(|| palette.color(index));
+        }
+    }
+
+    // Folds the lines (original line numbers, inclusive), merging the folds it overlaps.
+    pub fn fold_lines(&mut self, first_line_no: usize, last_line_no: usize) {
+        let (mut first_line_no, mut last_line_no) = if first_line_no <= last_line_no {
+            (first_line_no, last_line_no)
+        } else {
+            (last_line_no, first_line_no)
+        };
+
+        let overlapping: Vec<(usize, usize)> = self
+            .folded_ranges
+            .range(..=last_line_no)
+            .filter(|(_, fold_last)| **fold_last >= first_line_no)
+            .map(|(fold_first, fold_last)| (*fold_first, *fold_last))
+            .collect();
+        for (fold_first, fold_last) in overlapping {
+            self.folded_ranges.remove(&fold_first);
+            first_line_no = first_line_no.min(fold_first);
+            last_line_no = last_line_no.max(fold_last);
+        }
+
+        self.folded_ranges.insert(first_line_no, last_line_no);
+    }
+
+    // The fold the line is in, as (first, last) original line number.
+    pub fn fold_containing(&self, original_line_no: usize) -> Option<(usize, usize)> {
+        self.folded_ranges
+            .range(..=original_line_no)
+            .next_back()
+            .filter(|(_, fold_last)| **fold_last >= original_line_no)
+            .map(|(fold_first, fold_last)| (*fold_first, *fold_last))
+    }
+
+    fn to_ser_des(&self) -> UserSettingsSerDes {
+        UserSettingsSerDes {
+            version: CONFIG_VERSION,
             wrap_text: self.wrap_text,
+            wrap_break_anywhere: self.wrap_break_anywhere,
             autoscroll: self.autoscroll,
+            follow_max_lines: self.follow_max_lines,
+            stream_max_lines: self.stream_max_lines,
             search_term: self.search_term.clone(),
             search_match_case: self.search_match_case,
             search_whole_word: self.search_whole_word,
-            filter_term: self.filter_term.clone(),
-            filter_match_case: self.filter_match_case,
-            filter_whole_word: self.filter_whole_word,
-            filter_negative: self.filter_negative,
-            filter_extended: self.filter_extended,
+            search_highlight_style: self.search_highlight_style,
+            search_highlight_background: rgba(self.search_highlight_background),
+            search_highlight_text: rgba(self.search_highlight_text),
+            filters: self.filters.clone(),
+            filter_context_before: self.filter_context_before,
+            filter_context_after: self.filter_context_after,
+            filter_dim_non_matches: self.filter_dim_non_matches,
+            continuation_pattern: self.continuation_pattern.clone(),
+            table_view: self.table_view,
+            table_columns: self.table_columns.clone(),
             log_format_pattern: self.log_format.pattern.clone(),
             log_format_pattern_coloring: self
                 .log_format
                 .pattern_coloring
                 .iter()
-                .map(|c| (c.r(), c.g(), c.b(), c.a()))
+                .map(|c| (c.r, c.g, c.b, c.a))
                 .collect(),
             log_format_pattern_coloring_text: self
                 .log_format
                 .pattern_coloring_text
                 .iter()
-                .map(|c| (c.r(), c.g(), c.b(), c.a()))
+                .map(|c| (c.r, c.g, c.b, c.a))
                 .collect(),
             log_format_pattern_coloring_text_use_original: self
                 .log_format
                 .pattern_coloring_text_use_original
                 .clone(),
+            log_format_pattern_hidden: self.log_format.pattern_hidden.clone(),
+            log_format_pattern_group_names: self.log_format.pattern_group_names.clone(),
+            log_format_keep_uncaptured_text: self.log_format.keep_uncaptured_text,
+            // Empty slots are just leftovers from editing, no need to keep them.
             token_colors: self
                 .token_colors
                 .iter()
-                .map(|(name, color)| (name.clone(), (color.r(), color.g(), color.b(), color.a())))
+                .filter(|token_color| !token_color.token.trim().is_empty())
+                .map(|token_color| TokenColorSerDes {
+                    token: token_color.token.clone(),
+                    color: (
+                        token_color.color.r,
+                        token_color.color.g,
+                        token_color.color.b,
+                        token_color.color.a,
+                    ),
+                    match_case: token_color.match_case,
+                    whole_word: token_color.whole_word,
+                    regex: token_color.regex,
+                })
                 .collect(),
-            font_size: self.font.size,
+            level_colors: self
+                .level_colors
+                .iter()
+                .map(|(keyword, color)| (keyword.clone(), (color.r, color.g, color.b, color.a)))
+                .collect(),
+            font_size: self.font_size,
+            line_spacing: self.line_spacing,
+            tab_width: self.tab_width,
+            ansi_codes: self.ansi_codes,
             comments_visible: self.comments_visible,
-        };
+            comment_color: rgba(self.comment_color),
+            show_whitespace: self.show_whitespace,
+            whitespace_color: rgba(self.whitespace_color),
+            encoding: self.encoding.clone(),
+            top_line_no: self.top_line_no,
+            input_debounce_ms: self.input_debounce_ms,
+            collapse_duplicates: self.collapse_duplicates,
+            json_mode: self.json_mode,
+            key_value_pairs: self.key_value_pairs,
+            key_value_separators: self.key_value_separators.clone(),
+            key_value_hidden_keys: self.key_value_hidden_keys.clone(),
+            // Rules without a pattern don't redact anything.
+            redact_rules: self
+                .redact_rules
+                .iter()
+                .filter(|rule| !rule.pattern.is_empty())
+                .cloned()
+                .collect(),
+            pattern_masks: self.pattern_masks.clone(),
+            handler_order: self.handler_order.clone(),
+            links_clickable: self.links_clickable,
+            editor_command: self.editor_command.clone(),
+            key_bindings: self.key_bindings.clone(),
+            smooth_scrolling: self.smooth_scrolling,
+            drag_scrolling: self.drag_scrolling,
+            kinetic_scrolling: self.kinetic_scrolling,
+        }
+    }
 
-        let serialized = serde_json::to_string_pretty(&ser_des)?;
+    pub fn serialize(&self) -> Result<String, Box<dyn Error>> {
+        let serialized = serde_json::to_string_pretty(&self.to_ser_des())?;
 
         Ok(serialized)
     }
 
-    pub fn deserialize(str: &String) -> Result<UserSettings, Box<dyn Error>> {
-        let ser_des: UserSettingsSerDes = serde_json::from_str(str)?;
+    // Only the PROFILE_FIELDS, the profile is read back with deserialize() like a whole configuration.
+    pub fn serialize_profile(&self) -> Result<String, Box<dyn Error>> {
+        let serde_json::Value::Object(mut config) = serde_json::to_value(self.to_ser_des())? else {
+            unreachable!("settings serialize to an object");
+        };
+        config.retain(|field, _| field == "version" || PROFILE_FIELDS.contains(&field.as_str()));
 
-        let log_format = LogFormat {
+        Ok(serde_json::to_string_pretty(&config)?)
+    }
+
+    // Takes the profile part of the other settings (i.e. a loaded profile), the rest stays.
+    pub fn apply_profile(&mut self, profile: &UserSettings) {
+        self.log_format = profile.log_format.clone();
+        self.token_colors = profile.token_colors.clone();
+        self.level_colors = profile.level_colors.clone();
+        self.filters = profile.filters.clone();
+        self.continuation_pattern = profile.continuation_pattern.clone();
+        self.table_columns = profile.table_columns.clone();
+    }
+
+    pub fn deserialize(str: &str) -> Result<UserSettings, Box<dyn Error>> {
+        let mut config: serde_json::Value = serde_json::from_str(str)?;
+        migrate_config(&mut config)?;
+
+        let ser_des: UserSettingsSerDes = serde_json::from_value(config)
+            .map_err(|e| format!("incompatible configuration: {}", e))?;
+
+        let mut log_format = LogFormat {
             pattern: ser_des.log_format_pattern,
             pattern_coloring: ser_des
                 .log_format_pattern_coloring
                 .iter()
-                .map(|(r, g, b, a)| Color32::from_rgba_unmultiplied(*r, *g, *b, *a))
+                .map(|(r, g, b, a)| Color::from_rgba_unmultiplied(*r, *g, *b, *a))
                 .collect(),
             pattern_coloring_text: ser_des
                 .log_format_pattern_coloring_text
                 .iter()
-                .map(|(r, g, b, a)| Color32::from_rgba_unmultiplied(*r, *g, *b, *a))
+                .map(|(r, g, b, a)| Color::from_rgba_unmultiplied(*r, *g, *b, *a))
                 .collect(),
             pattern_coloring_text_use_original: ser_des
                 .log_format_pattern_coloring_text_use_original,
+            pattern_hidden: ser_des.log_format_pattern_hidden,
+            pattern_group_names: ser_des.log_format_pattern_group_names,
+            keep_uncaptured_text: ser_des.log_format_keep_uncaptured_text,
         };
 
+        // Older configs don't know the group names or the hidden groups yet.
+        if (log_format.pattern_group_names.is_empty()
+            || log_format.pattern_hidden.len() != log_format.pattern_coloring.len())
+            && let Ok(compiled_regex) = regex::Regex::new(&log_format.pattern)
+        {
+            log_format.update_groups(&compiled_regex);
+        }
+
         let token_colors = ser_des
             .token_colors
+            .into_iter()
+            .map(|token_color| {
+                let (r, g, b, a) = token_color.color;
+                TokenColor {
+                    token: token_color.token,
+                    color: Color::from_rgba_unmultiplied(r, g, b, a),
+                    match_case: token_color.match_case,
+                    whole_word: token_color.whole_word,
+                    regex: token_color.regex,
+                }
+            })
+            .collect();
+
+        let level_colors = ser_des
+            .level_colors
             .iter()
-            .map(|(name, (r, g, b, a))| {
+            .map(|(keyword, (r, g, b, a))| {
                 (
-                    name.clone(),
-                    Color32::from_rgba_unmultiplied(*r, *g, *b, *a),
+                    keyword.clone(),
+                    Color::from_rgba_unmultiplied(*r, *g, *b, *a),
                 )
             })
             .collect();
 
+        // Actions added since the config was saved keep their default keys.
+        let mut key_bindings = ser_des.key_bindings;
+        for (action, key_binding) in default_key_bindings() {
+            key_bindings.entry(action).or_insert(key_binding);
+        }
+
         Ok(UserSettings {
             wrap_text: ser_des.wrap_text,
+            wrap_break_anywhere: ser_des.wrap_break_anywhere,
             autoscroll: ser_des.autoscroll,
+            follow_max_lines: ser_des.follow_max_lines,
+            stream_max_lines: ser_des.stream_max_lines,
             search_term: ser_des.search_term,
             search_match_case: ser_des.search_match_case,
             search_whole_word: ser_des.search_whole_word,
-            filter_term: ser_des.filter_term,
-            filter_match_case: ser_des.filter_match_case,
-            filter_whole_word: ser_des.filter_whole_word,
-            filter_negative: ser_des.filter_negative,
-            filter_extended: ser_des.filter_extended,
+            search_highlight_style: ser_des.search_highlight_style,
+            search_highlight_background: from_rgba(ser_des.search_highlight_background),
+            search_highlight_text: from_rgba(ser_des.search_highlight_text),
+            filters: ser_des.filters,
+            filter_context_before: ser_des.filter_context_before,
+            filter_context_after: ser_des.filter_context_after,
+            filter_dim_non_matches: ser_des.filter_dim_non_matches,
+            continuation_pattern: ser_des.continuation_pattern,
+            table_view: ser_des.table_view,
+            table_columns: ser_des.table_columns,
             file_path: String::new(),
             log_format,
             token_colors,
-            font: FontId::monospace(ser_des.font_size),
+            level_colors,
+            font_size: ser_des.font_size,
+            line_spacing: ser_des.line_spacing,
+            tab_width: ser_des.tab_width,
+            ansi_codes: ser_des.ansi_codes,
             comments_visible: ser_des.comments_visible,
+            comment_color: from_rgba(ser_des.comment_color),
+            show_whitespace: ser_des.show_whitespace,
+            whitespace_color: from_rgba(ser_des.whitespace_color),
+            encoding: ser_des.encoding,
+            top_line_no: ser_des.top_line_no,
+            input_debounce_ms: ser_des.input_debounce_ms,
+            collapse_duplicates: ser_des.collapse_duplicates,
+            expanded_duplicates: BTreeSet::new(),
+            folded_ranges: BTreeMap::new(),
+            json_mode: ser_des.json_mode,
+            expanded_json_lines: BTreeSet::new(),
+            key_value_pairs: ser_des.key_value_pairs,
+            key_value_separators: ser_des.key_value_separators,
+            key_value_hidden_keys: ser_des.key_value_hidden_keys,
+            redact_rules: ser_des.redact_rules,
+            pattern_masks: ser_des.pattern_masks,
+            handler_order: ser_des.handler_order,
+            links_clickable: ser_des.links_clickable,
+            editor_command: ser_des.editor_command,
+            key_bindings,
+            smooth_scrolling: ser_des.smooth_scrolling,
+            drag_scrolling: ser_des.drag_scrolling,
+            kinetic_scrolling: ser_des.kinetic_scrolling,
             histogram_search_term: String::new(),
             histogram_match_case: false,
+            histogram_regex: false,
+            histogram_time_buckets: false,
+            histogram_timestamp_pattern: String::new(),
         })
     }
 }
 
 impl Default for UserSettings {
     fn default() -> Self {
-        let mut new_instance = UserSettings {
+        UserSettings {
             wrap_text: false,
-            autoscroll: false,
+            wrap_break_anywhere: false,
+            autoscroll: true,
+            follow_max_lines: 0,
+            stream_max_lines: 1_000_000,
             search_term: String::new(),
             search_match_case: false,
             search_whole_word: false,
-            filter_term: String::new(),
-            filter_match_case: false,
-            filter_whole_word: false,
-            filter_negative: false,
-            filter_extended: false,
+            search_highlight_style: SearchHighlightStyle::Background,
+            search_highlight_background: SEARCH_HILIGHT_BACKGROUND,
+            search_highlight_text: SEARCH_HILIGHT_TEXT,
+            filters: vec![FilterSpec::default()], // One empty filter, so there's something to type in.
+            filter_context_before: 0,
+            filter_context_after: 0,
+            filter_dim_non_matches: false,
+            continuation_pattern: String::new(),
+            table_view: false,
+            table_columns: Vec::new(),
             file_path: String::new(),
             log_format: LogFormat::default(),
-            token_colors: Vec::with_capacity(25),
-            font: FontId::monospace(12.0),
+            token_colors: vec![TokenColor::new_empty(0)], // Same as filters, something to type in.
+            level_colors: vec![
+                ("ERROR".to_string(), Color::from_rgb(110, 25, 25)),
+                ("WARN".to_string(), Color::from_rgb(100, 85, 10)),
+                ("INFO".to_string(), Color::TRANSPARENT),
+                ("DEBUG".to_string(), Color::from_rgb(50, 50, 50)),
+            ],
+            font_size: 12.0,
+            line_spacing: 1.0,
+            tab_width: 4,
+            ansi_codes: AnsiCodes::Colored,
             comments_visible: true,
+            comment_color: Color::LIGHT_GREEN,
+            show_whitespace: false,
+            whitespace_color: whitespace::DEFAULT_WHITESPACE_COLOR,
+            encoding: encoding::ENCODING_UTF8.to_string(),
+            top_line_no: 0,
+            input_debounce_ms: 200,
+            collapse_duplicates: false,
+            expanded_duplicates: BTreeSet::new(),
+            folded_ranges: BTreeMap::new(),
+            json_mode: false,
+            expanded_json_lines: BTreeSet::new(),
+            key_value_pairs: false,
+            key_value_separators: "=:".to_string(),
+            key_value_hidden_keys: String::new(),
+            redact_rules: Vec::new(),
+            pattern_masks: patterns::default_pattern_masks(),
+            handler_order: DEFAULT_HANDLER_ORDER.to_vec(),
+            links_clickable: false,
+            editor_command: links::DEFAULT_EDITOR_COMMAND.to_string(),
+            key_bindings: default_key_bindings(),
+            smooth_scrolling: false,
+            drag_scrolling: false,
+            kinetic_scrolling: true,
             histogram_search_term: String::new(),
             histogram_match_case: false,
+            histogram_regex: false,
+            histogram_time_buckets: false,
+            histogram_timestamp_pattern: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::{KeyBinding, ShortcutAction};
+
+    #[test]
+    fn all_settings_round_trip() {
+        let mut user_settings = UserSettings {
+            wrap_text: true,
+            wrap_break_anywhere: true,
+            follow_max_lines: 5000,
+            stream_max_lines: 0,
+            search_highlight_style: SearchHighlightStyle::Underline,
+            search_highlight_background: Color::BLUE,
+            search_highlight_text: Color::WHITE,
+            filters: vec![
+                FilterSpec {
+                    term: "error.*timeout".to_string(),
+                    regex: true,
+                    ..Default::default()
+                },
+                FilterSpec {
+                    term: "heartbeat".to_string(),
+                    match_case: true,
+                    whole_word: true,
+                    negative: true,
+                    ..Default::default()
+                },
+                FilterSpec {
+                    term: "latency > 500".to_string(),
+                    numeric: true,
+                    ..Default::default()
+                },
+            ],
+            filter_context_before: 2,
+            filter_context_after: 3,
+            filter_dim_non_matches: true,
+            continuation_pattern: r"^\s".to_string(),
+            table_view: true,
+            table_columns: vec!["level".to_string()],
+            level_colors: vec![
+                ("FATAL".to_string(), Color::from_rgb(200, 0, 0)),
+                ("TRACE".to_string(), Color::TRANSPARENT),
+            ],
+            line_spacing: 1.5,
+            tab_width: 8,
+            ansi_codes: AnsiCodes::Stripped,
+            comment_color: Color::ORANGE,
+            show_whitespace: true,
+            whitespace_color: Color::ORANGE,
+            encoding: encoding::ENCODING_LATIN1.to_string(),
+            top_line_no: 1234,
+            collapse_duplicates: true,
+            json_mode: true,
+            key_value_pairs: true,
+            key_value_hidden_keys: "password".to_string(),
+            redact_rules: vec![RedactRule {
+                pattern: r"token=\w+".to_string(),
+                replacement: "token=***".to_string(),
+            }],
+            pattern_masks: vec![r"\d+".to_string()],
+            links_clickable: false,
+            smooth_scrolling: true,
+            drag_scrolling: true,
+            kinetic_scrolling: false,
+            ..Default::default()
+        };
+        user_settings.handler_order.swap(5, 6);
+        user_settings.token_colors[0] = TokenColor {
+            token: "io".to_string(),
+            color: Color::from_rgb(10, 20, 30),
+            match_case: false,
+            whole_word: true,
+            regex: true,
         };
+        user_settings.key_bindings.insert(
+            ShortcutAction::FocusSearch,
+            KeyBinding {
+                key: "Slash".to_string(),
+                ..Default::default()
+            },
+        );
+        user_settings.log_format.pattern = r"^(?P<time>\S+)( )(?P<level>\w+)(.*)$".to_string();
+        user_settings
+            .log_format
+            .update_groups(&regex::Regex::new(&user_settings.log_format.pattern).unwrap());
+        user_settings.log_format.pattern_hidden[0] = true;
+        user_settings.log_format.keep_uncaptured_text = true;
+
+        let serialized = user_settings.serialize().unwrap();
+        assert!(UserSettings::deserialize(&serialized).unwrap() == user_settings);
+    }
 
-        // Initialize the colors in token_colors to some default values.
-        for i in 0..new_instance.token_colors.capacity() {
-            let color = Color32::from_rgb(
-                (i * 12 % 256) as u8,
-                (i * 34 % 256) as u8,
-                (i * 56 % 256) as u8,
-            );
+    #[test]
+    fn older_config_leaves_new_settings_at_default() {
+        // Saved before any of these were there, only the version and a filter without the newer
+        // filter fields.
+        let older = UserSettings::deserialize(
+            r#"{"version": 1, "filters": [{"term": "x", "match_case": false, "whole_word": false,
+                "negative": false, "extended": false, "regex": false}]}"#,
+        )
+        .unwrap();
+        let default = UserSettings::default();
 
-            new_instance.token_colors.push((String::new(), color));
+        let fields = [
+            (
+                "wrap_break_anywhere",
+                older.wrap_break_anywhere == default.wrap_break_anywhere,
+            ),
+            (
+                "follow_max_lines",
+                older.follow_max_lines == default.follow_max_lines,
+            ),
+            (
+                "stream_max_lines",
+                older.stream_max_lines == default.stream_max_lines,
+            ),
+            (
+                "search_highlight_style",
+                older.search_highlight_style == default.search_highlight_style,
+            ),
+            (
+                "search_highlight_background",
+                older.search_highlight_background == default.search_highlight_background,
+            ),
+            (
+                "search_highlight_text",
+                older.search_highlight_text == default.search_highlight_text,
+            ),
+            ("filter_numeric", !older.filters[0].numeric),
+            (
+                "filter_dim_non_matches",
+                older.filter_dim_non_matches == default.filter_dim_non_matches,
+            ),
+            ("line_spacing", older.line_spacing == default.line_spacing),
+            ("tab_width", older.tab_width == default.tab_width),
+            ("ansi_codes", older.ansi_codes == default.ansi_codes),
+            (
+                "comment_color",
+                older.comment_color == default.comment_color,
+            ),
+            (
+                "show_whitespace",
+                older.show_whitespace == default.show_whitespace,
+            ),
+            (
+                "whitespace_color",
+                older.whitespace_color == default.whitespace_color,
+            ),
+            ("encoding", older.encoding == default.encoding),
+            ("top_line_no", older.top_line_no == default.top_line_no),
+            ("redact_rules", older.redact_rules == default.redact_rules),
+            (
+                "pattern_masks",
+                older.pattern_masks == default.pattern_masks,
+            ),
+            (
+                "handler_order",
+                older.handler_order == default.handler_order,
+            ),
+            (
+                "links_clickable",
+                older.links_clickable == default.links_clickable,
+            ),
+            ("key_bindings", older.key_bindings == default.key_bindings),
+            (
+                "smooth_scrolling",
+                older.smooth_scrolling == default.smooth_scrolling,
+            ),
+            (
+                "drag_scrolling",
+                older.drag_scrolling == default.drag_scrolling,
+            ),
+            (
+                "kinetic_scrolling",
+                older.kinetic_scrolling == default.kinetic_scrolling,
+            ),
+            (
+                "log_format_keep_uncaptured_text",
+                older.log_format.keep_uncaptured_text == default.log_format.keep_uncaptured_text,
+            ),
+        ];
+        for (field, at_default) in fields {
+            assert!(at_default, "{}", field);
         }
+        assert_eq!(older.filters[0].term, "x");
+    }
+
+    #[test]
+    fn missing_key_bindings_get_defaults() {
+        // Saved before the actions past ScrollUp could be bound.
+        let older = UserSettings::deserialize(
+            r#"{"version": 1, "key_bindings": {"ScrollUp": {"key": "K", "ctrl": false, "alt": false, "shift": false}}}"#,
+        )
+        .unwrap();
+        assert_eq!(older.key_bindings[&ShortcutAction::ScrollUp].key, "K");
+        assert_eq!(
+            older.key_bindings[&ShortcutAction::FocusSearch].label(),
+            "Ctrl+F"
+        );
+    }
+
+    #[test]
+    fn empty_tokens_and_rules_not_saved() {
+        let mut user_settings = UserSettings {
+            redact_rules: vec![
+                RedactRule {
+                    pattern: r"token=\w+".to_string(),
+                    replacement: "token=***".to_string(),
+                },
+                RedactRule::default(),
+            ],
+            ..Default::default()
+        };
+        user_settings.token_colors[0].token = "io".to_string();
+        user_settings.token_colors.push(TokenColor::new_empty(1));
+
+        let serialized = user_settings.serialize().unwrap();
+        let deserialized = UserSettings::deserialize(&serialized).unwrap();
+        assert!(deserialized.redact_rules == user_settings.redact_rules[..1]);
+        assert!(deserialized.token_colors == user_settings.token_colors[..1]);
+    }
+
+    #[test]
+    fn unversioned_config_migrated() {
+        let config = r#"{
+            "wrap_text": true,
+            "autoscroll": false,
+            "search_term": "",
+            "search_match_case": false,
+            "search_whole_word": false,
+            "filter_term": "timeout",
+            "filter_match_case": true,
+            "filter_whole_word": false,
+            "filter_negative": true,
+            "filter_extended": false,
+            "log_format_pattern": "^(?P<time>\\S+)( .*)$",
+            "log_format_pattern_coloring": [[40, 70, 120, 255], [0, 0, 0, 0]],
+            "log_format_pattern_coloring_text": [[255, 255, 255, 255], [160, 160, 160, 255]],
+            "log_format_pattern_coloring_text_use_original": [false, true],
+            "token_colors": [["disk", [200, 200, 0, 255]]],
+            "font_size": 14.0,
+            "comments_visible": false
+        }"#;
+
+        let user_settings = UserSettings::deserialize(config).unwrap();
+        assert!(user_settings.wrap_text);
+        assert_eq!(user_settings.font_size, 14.0);
+
+        let migrated_filter = FilterSpec {
+            term: "timeout".to_string(),
+            match_case: true,
+            negative: true,
+            ..Default::default()
+        };
+        assert!(user_settings.filters == vec![migrated_filter]);
+
+        assert_eq!(user_settings.token_colors.len(), 1);
+        assert_eq!(user_settings.token_colors[0].token, "disk");
+        assert_eq!(
+            user_settings.token_colors[0].color,
+            Color::from_rgb(200, 200, 0)
+        );
+        assert!(!user_settings.token_colors[0].regex);
+
+        assert_eq!(
+            user_settings.log_format.pattern_group_names,
+            vec!["time", ""]
+        );
+        assert_eq!(user_settings.log_format.pattern_hidden, vec![false, false]);
+        assert_eq!(
+            user_settings.log_format.pattern_coloring[0],
+            Color::from_rgb(40, 70, 120)
+        );
+
+        // Added later, so defaults.
+        let default_settings = UserSettings::default();
+        assert_eq!(user_settings.level_colors, default_settings.level_colors);
+        assert_eq!(user_settings.encoding, default_settings.encoding);
+        assert_eq!(
+            user_settings.input_debounce_ms,
+            default_settings.input_debounce_ms
+        );
+        assert_eq!(user_settings.line_spacing, default_settings.line_spacing);
+        assert!(!user_settings.collapse_duplicates);
+        assert!(!user_settings.json_mode);
+        assert_eq!(
+            user_settings.key_value_separators,
+            default_settings.key_value_separators
+        );
+    }
+
+    #[test]
+    fn incompatible_configs_rejected() {
+        let newer = UserSettings::default()
+            .serialize()
+            .unwrap()
+            .replace("\"version\": 1", "\"version\": 999");
+        let error = UserSettings::deserialize(&newer).err().unwrap();
+        assert!(error.to_string().contains("newer"), "{}", error);
+
+        let error = UserSettings::deserialize("[1, 2, 3]").err().unwrap();
+        assert!(error.to_string().contains("not a Logalyzer"), "{}", error);
+
+        let error = UserSettings::deserialize(r#"{"font_size": "big"}"#)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("incompatible"), "{}", error);
+    }
+
+    #[test]
+    fn search_change_told_apart() {
+        let user_settings = UserSettings::default();
+
+        let searched = UserSettings {
+            search_term: "disk".to_string(),
+            search_whole_word: true,
+            ..Default::default()
+        };
+        assert!(searched.differs_only_in_search(&user_settings));
+
+        let mut filtered = searched.clone();
+        filtered.filters[0].term = "error".to_string();
+        assert!(!filtered.differs_only_in_search(&user_settings));
+
+        // The matches are colored only when shown, their style and colors don't need processing.
+        let restyled = UserSettings {
+            search_highlight_style: SearchHighlightStyle::Underline,
+            search_highlight_background: Color::BLUE,
+            search_highlight_text: Color::WHITE,
+            ..Default::default()
+        };
+        assert!(restyled.differs_only_in_search(&user_settings));
+    }
+
+    #[test]
+    fn layout_change_told_apart() {
+        let user_settings = UserSettings::default();
+
+        let enlarged = UserSettings {
+            font_size: 16.0,
+            line_spacing: 1.5,
+            wrap_text: true,
+            wrap_break_anywhere: true,
+            show_whitespace: true,
+            ..Default::default()
+        };
+        assert!(enlarged.differs_only_in_layout(&user_settings));
+        assert!(!enlarged.differs_only_in_search(&user_settings));
+
+        let searched = UserSettings {
+            search_term: "disk".to_string(),
+            ..enlarged.clone()
+        };
+        assert!(!searched.differs_only_in_layout(&user_settings));
+    }
+
+    #[test]
+    fn overlapping_folds_merged() {
+        let mut user_settings = UserSettings::default();
+        user_settings.fold_lines(10, 20);
+        user_settings.fold_lines(30, 40);
+        user_settings.fold_lines(50, 60);
+        assert_eq!(user_settings.fold_containing(15), Some((10, 20)));
+        assert_eq!(user_settings.fold_containing(25), None);
+
+        user_settings.fold_lines(35, 20);
+        assert_eq!(
+            user_settings.folded_ranges,
+            [(10, 40), (50, 60)].into_iter().collect()
+        );
+        assert_eq!(user_settings.fold_containing(25), Some((10, 40)));
+        assert_eq!(user_settings.fold_containing(61), None);
+    }
+
+    #[test]
+    fn tokens_recolored_from_palette() {
+        let mut user_settings = UserSettings::default();
+        for index in 1..10 {
+            user_settings
+                .token_colors
+                .push(TokenColor::new_empty(index));
+        }
+        assert_eq!(
+            user_settings.token_colors[8].color,
+            user_settings.token_colors[0].color
+        );
+        assert_ne!(user_settings.token_colors[0].color, Color::BLACK);
+
+        user_settings.recolor_tokens(TokenPalette::Ibm);
+        assert_eq!(
+            user_settings.token_colors[6].color,
+            TokenPalette::Ibm.colors()[1]
+        );
+        assert_eq!(
+            TokenColor::new_empty_from(2, TokenPalette::Ibm).color,
+            Color::from_rgb(220, 38, 127)
+        );
+    }
+
+    #[test]
+    fn listed_tokens_added() {
+        let mut user_settings = UserSettings::default();
+        user_settings.add_tokens(
+            vec![
+                ("ERROR".to_string(), Some(Color::RED)),
+                ("timeout".to_string(), None),
+                ("user".to_string(), None),
+            ],
+            TokenPalette::Ibm,
+        );
+
+        let tokens: Vec<(&str, Color)> = user_settings
+            .token_colors
+            .iter()
+            .map(|token_color| (token_color.token.as_str(), token_color.color))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                ("ERROR", Color::RED),
+                ("timeout", TokenPalette::Ibm.color(1)),
+                ("user", TokenPalette::Ibm.color(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_keeps_only_analysis_settings() {
+        let mut user_settings = UserSettings {
+            file_path: "/var/log/app.log".to_string(),
+            font_size: 21.0,
+            continuation_pattern: r"^\s".to_string(),
+            table_view: true,
+            table_columns: vec!["level".to_string()],
+            ..Default::default()
+        };
+        user_settings.log_format.pattern = r"^(\S+) (.*)$".to_string();
+        user_settings.filters[0].term = "error".to_string();
+
+        let serialized = user_settings.serialize_profile().unwrap();
+        assert!(!serialized.contains("app.log"));
+        assert!(!serialized.contains("font_size"));
+
+        let profile = UserSettings::deserialize(&serialized).unwrap();
+        let mut other_settings = UserSettings {
+            file_path: "other.log".to_string(),
+            ..Default::default()
+        };
+        other_settings.apply_profile(&profile);
+        assert_eq!(other_settings.file_path, "other.log");
+        assert_eq!(other_settings.font_size, UserSettings::default().font_size);
+        assert_eq!(other_settings.log_format.pattern, r"^(\S+) (.*)$");
+        assert_eq!(other_settings.filters[0].term, "error");
+        assert_eq!(other_settings.continuation_pattern, r"^\s");
+        assert_eq!(other_settings.table_columns, vec!["level"]);
+        assert!(!other_settings.table_view);
+    }
+
+    #[test]
+    fn named_group_colors_follow_the_name() {
+        let mut log_format = LogFormat::default();
+        log_format
+            .update_groups(&regex::Regex::new(r"^(?P<time>\S+)( )(?P<level>\w+)(.*)$").unwrap());
+        assert_eq!(
+            log_format.pattern_group_names,
+            vec!["time", "", "level", ""]
+        );
+
+        log_format.pattern_coloring[0] = Color::BLUE;
+        log_format.pattern_coloring[2] = Color::YELLOW;
+        log_format.pattern_coloring[3] = Color::GREEN;
+
+        // Named groups swapped, the unnamed one keeps its index.
+        log_format
+            .update_groups(&regex::Regex::new(r"^(?P<level>\w+)( )(?P<time>\S+)(.*)$").unwrap());
+        assert_eq!(
+            log_format.pattern_group_names,
+            vec!["level", "", "time", ""]
+        );
+        assert_eq!(
+            log_format.pattern_coloring,
+            vec![Color::YELLOW, Color::RED, Color::BLUE, Color::GREEN]
+        );
+        assert_eq!(log_format.pattern_hidden, vec![false; 4]);
 
-        new_instance
+        // New group gets the default.
+        log_format.update_groups(
+            &regex::Regex::new(r"^(?P<level>\w+)( )(?P<time>\S+)(.*)(?P<tail>$)").unwrap(),
+        );
+        assert_eq!(log_format.pattern_coloring.len(), 5);
+        assert_eq!(log_format.pattern_coloring[4], Color::RED);
+        assert_eq!(log_format.coloring_index(0, Some("tail")), Some(4));
+        assert_eq!(log_format.coloring_index(7, None), None);
     }
 }
@@ -0,0 +1,160 @@
+use crate::linevec::*;
+use crate::style::{Color, Style};
+
+pub const SPACE_GLYPH: char = '·';
+pub const TAB_GLYPH: char = '→';
+
+pub const DEFAULT_WHITESPACE_COLOR: Color = Color::from_rgba_unmultiplied(160, 160, 160, 90);
+pub const TRAILING_WHITESPACE_BACKGROUND: Color = Color::from_rgba_unmultiplied(255, 90, 90, 60);
+
+#[derive(PartialEq, Clone, Copy)]
+enum CharKind {
+    Text,
+    Whitespace,
+    TrailingWhitespace, // at the end of the line, or of a row of pretty printed JSON
+}
+
+// The spaces and tabs drawn as glyphs in the whitespace color, the trailing ones on a background.
+// Only for showing the line: the glyphs replace the characters one for one, so the char offsets
+// (i.e. of the pointer) are the same as in the line the search and the exports work with.
+pub fn whitespace_shown(line: &LineVec, whitespace_color: Color) -> LineVec {
+    let text = linevec_text(line);
+    let mut trailing = vec![false; text.len()]; // by byte offset
+    let mut in_trailing = true;
+    for (byte_offset, c) in text.char_indices().rev() {
+        match c {
+            '\n' => in_trailing = true,
+            ' ' | '\t' => trailing[byte_offset] = in_trailing,
+            _ => in_trailing = false,
+        }
+    }
+
+    let mut shown_line: LineVec = Vec::with_capacity(line.len());
+    let mut byte_offset = 0;
+    for (part_str, part_style) in line {
+        let mut run = String::new();
+        let mut run_kind = CharKind::Text;
+
+        let mut push_run = |run: &mut String, run_kind: CharKind| {
+            if run.is_empty() {
+                return;
+            }
+
+            let style = match run_kind {
+                CharKind::Text => part_style.clone(),
+                CharKind::Whitespace => Style {
+                    color: whitespace_color,
+                    ..part_style.clone()
+                },
+                CharKind::TrailingWhitespace => Style {
+                    color: whitespace_color,
+                    background: TRAILING_WHITESPACE_BACKGROUND,
+                    ..part_style.clone()
+                },
+            };
+            shown_line.push((std::mem::take(run), style));
+        };
+
+        for c in part_str.chars() {
+            let (kind, shown_c) = match c {
+                ' ' | '\t' if trailing[byte_offset] => (
+                    CharKind::TrailingWhitespace,
+                    if c == ' ' { SPACE_GLYPH } else { TAB_GLYPH },
+                ),
+                ' ' => (CharKind::Whitespace, SPACE_GLYPH),
+                '\t' => (CharKind::Whitespace, TAB_GLYPH),
+                _ => (CharKind::Text, c),
+            };
+
+            if kind != run_kind {
+                push_run(&mut run, run_kind);
+                run_kind = kind;
+            }
+            run.push(shown_c);
+            byte_offset += c.len_utf8();
+        }
+        push_run(&mut run, run_kind);
+    }
+
+    shown_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_replaced_one_for_one() {
+        let red = Style {
+            color: Color::RED,
+            ..Default::default()
+        };
+        let line: LineVec = vec![
+            ("a b\t".to_string(), red.clone()),
+            ("c  ".to_string(), Style::default()),
+        ];
+
+        let shown_line = whitespace_shown(&line, DEFAULT_WHITESPACE_COLOR);
+        assert_eq!(linevec_text(&shown_line), "a·b→c··");
+        assert_eq!(
+            linevec_text(&shown_line).chars().count(),
+            linevec_text(&line).chars().count()
+        );
+
+        let kinds: Vec<(&str, Color, Color)> = shown_line
+            .iter()
+            .map(|(part_str, style)| (part_str.as_str(), style.color, style.background))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", Color::RED, Color::TRANSPARENT),
+                ("·", DEFAULT_WHITESPACE_COLOR, Color::TRANSPARENT),
+                ("b", Color::RED, Color::TRANSPARENT),
+                ("→", DEFAULT_WHITESPACE_COLOR, Color::TRANSPARENT),
+                ("c", Style::default().color, Color::TRANSPARENT),
+                (
+                    "··",
+                    DEFAULT_WHITESPACE_COLOR,
+                    TRAILING_WHITESPACE_BACKGROUND
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_found_on_each_row() {
+        let line: LineVec = vec![("{ \n  \"a\": 1 \n}".to_string(), Style::default())];
+        let shown_line = whitespace_shown(&line, DEFAULT_WHITESPACE_COLOR);
+
+        let trailing: Vec<&str> = shown_line
+            .iter()
+            .filter(|(_, style)| style.background == TRAILING_WHITESPACE_BACKGROUND)
+            .map(|(part_str, _)| part_str.as_str())
+            .collect();
+        assert_eq!(trailing, vec!["·", "·"]);
+        assert_eq!(linevec_text(&shown_line), "{·\n··\"a\":·1·\n}");
+    }
+
+    #[test]
+    fn processed_line_tabs_shown_unless_expanded() {
+        let content = crate::log_content::LogContent::from_string("id\tok \n".to_string());
+        let mut user_settings = crate::user_settings::UserSettings {
+            tab_width: 0,
+            ..Default::default()
+        };
+        let shown_text = |user_settings: &crate::user_settings::UserSettings| {
+            let log_jobs = crate::recalculate_log_job(&content, user_settings).unwrap();
+            linevec_text(&whitespace_shown(
+                &crate::log_line(&log_jobs, 0).unwrap(),
+                DEFAULT_WHITESPACE_COLOR,
+            ))
+        };
+
+        assert_eq!(shown_text(&user_settings), "id→ok·");
+
+        // Expanded to the tab stop before it's shown, so it's spaces by then.
+        user_settings.tab_width = 4;
+        assert_eq!(shown_text(&user_settings), "id··ok·");
+    }
+}
@@ -0,0 +1,436 @@
+use egui::text::{LayoutJob, TextWrapping};
+use std::collections::HashMap;
+
+use crate::VisibleLineOffsets;
+use crate::style::Color;
+
+// Shown under the log line it belongs to, so its height counts for the line numbers too.
+pub fn comment_layout_job(comment_text: &str, font_size: f32, color: Color) -> LayoutJob {
+    let mut comment_job = LayoutJob::default();
+    comment_job.append(
+        format!("\t// {}", comment_text).as_str(),
+        0.0,
+        egui::TextFormat {
+            font_id: egui::FontId::monospace(font_size),
+            color: color.into(),
+            italics: true,
+            ..Default::default()
+        },
+    );
+
+    comment_job
+}
+
+// How the log lines and their comments wrap, both when they're measured for the gutter and when
+// they're shown. If the two differed, the line numbers wouldn't line up with the rows.
+pub fn text_wrapping(max_width: f32, break_anywhere: bool) -> TextWrapping {
+    TextWrapping {
+        break_anywhere,
+        max_width,
+        ..Default::default()
+    }
+}
+
+// Number of additional rows the line takes when wrapped the given way.
+pub fn calculate_line_wrapped_by(
+    ctx: &egui::Context,
+    job: &LayoutJob,
+    wrapping: &TextWrapping,
+) -> usize {
+    let mut job_with_wrapping = job.clone();
+    job_with_wrapping.wrap = wrapping.clone();
+
+    let galley = ctx.fonts_mut(|fonts| fonts.layout_job(job_with_wrapping));
+    galley.rows.len().saturating_sub(1)
+}
+
+// Width of the line laid out in a single row.
+pub fn calculate_line_width(ctx: &egui::Context, job: &LayoutJob) -> f32 {
+    let mut job_without_wrapping = job.clone();
+    job_without_wrapping.wrap = TextWrapping::no_max_width();
+
+    let galley = ctx.fonts_mut(|fonts| fonts.layout_job(job_without_wrapping));
+    galley.size().x
+}
+
+// The widest line decides how far the log can be scrolled horizontally when it's not wrapped.
+// Measuring it means laying out lines, so it's done once per font size and content.
+#[derive(Default)]
+pub struct MaxLineWidthCache {
+    font_size: f32,
+    line_count: usize,
+    max_line_width: Option<f32>,
+}
+
+impl MaxLineWidthCache {
+    pub fn get_or_calculate(
+        &mut self,
+        font_size: f32,
+        line_count: usize,
+        calculate: impl FnOnce() -> f32,
+    ) -> f32 {
+        if self.font_size != font_size || self.line_count != line_count {
+            self.clear();
+            self.font_size = font_size;
+            self.line_count = line_count;
+        }
+
+        *self.max_line_width.get_or_insert_with(calculate)
+    }
+
+    // Has to be called whenever the jobs are replaced, the lines can differ even if their count doesn't.
+    pub fn clear(&mut self) {
+        self.max_line_width = None;
+    }
+}
+
+// Laying out the line again just to know how it wraps is costly and was done for every visible row
+// every frame. The results are kept here until any of the parameters they depend on changes.
+#[derive(Default)]
+pub struct WrapCache {
+    wrapping: TextWrapping,
+    font_size: f32,
+    wrap_text: bool,
+    line_wrapped_by: HashMap<usize, usize>,
+}
+
+impl WrapCache {
+    pub fn get_or_calculate(
+        &mut self,
+        row_index: usize,
+        wrapping: &TextWrapping,
+        font_size: f32,
+        wrap_text: bool,
+        calculate: impl FnOnce() -> usize,
+    ) -> usize {
+        if self.wrapping != *wrapping || self.font_size != font_size || self.wrap_text != wrap_text
+        {
+            self.clear();
+            self.wrapping = wrapping.clone();
+            self.font_size = font_size;
+            self.wrap_text = wrap_text;
+        }
+
+        *self
+            .line_wrapped_by
+            .entry(row_index)
+            .or_insert_with(calculate)
+    }
+
+    // Has to be called whenever the jobs are replaced, the rows are different then.
+    pub fn clear(&mut self) {
+        self.line_wrapped_by.clear();
+    }
+}
+
+// A line number in the gutter, followed by a wrap indicator for each row the line wraps onto.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GutterRow {
+    pub row_index: usize,
+    pub original_line_no: usize,
+    pub wrapped_by: usize,
+    pub first_rendered_row: usize, // counted from the first row shown, wrapped rows included
+}
+
+// Computed from scratch for the rows shown, in the order they're shown (a sorted table doesn't
+// show them in the log order), line_wrapped_by has the value for each of them.
+pub fn gutter_rows(
+    row_indices: &[usize],
+    line_wrapped_by: &[usize],
+    visible_line_offsets: &VisibleLineOffsets,
+) -> Vec<GutterRow> {
+    let mut rendered_row = 0;
+
+    row_indices
+        .iter()
+        .zip(line_wrapped_by)
+        .map(|(&row_index, &wrapped_by)| {
+            let gutter_row = GutterRow {
+                row_index,
+                original_line_no: visible_line_offsets.get_original_line_for_row(row_index),
+                wrapped_by,
+                first_rendered_row: rendered_row,
+            };
+            rendered_row += 1 + wrapped_by;
+
+            gutter_row
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::{FontId, TextFormat};
+
+    fn make_job(text: &str) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        job.append(
+            text,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(12.0),
+                ..Default::default()
+            },
+        );
+        job
+    }
+
+    #[test]
+    fn cached_values_match_uncached() {
+        let ctx = egui::Context::default();
+        // Fonts are available only after the first pass.
+        let _ = ctx.run(Default::default(), |_| {});
+
+        let jobs = [
+            make_job("short"),
+            make_job(&"long line that has to wrap ".repeat(20)),
+            make_job(""),
+            make_job(&"x".repeat(500)),
+        ];
+
+        let mut wrap_cache = WrapCache::default();
+        for max_width in [100.0, 400.0, 100.0] {
+            for (row_index, job) in jobs.iter().enumerate() {
+                let wrapping = text_wrapping(max_width, false);
+                let uncached = calculate_line_wrapped_by(&ctx, job, &wrapping);
+                let cached = wrap_cache.get_or_calculate(row_index, &wrapping, 12.0, true, || {
+                    calculate_line_wrapped_by(&ctx, job, &wrapping)
+                });
+                assert_eq!(cached, uncached);
+            }
+        }
+
+        assert_eq!(
+            calculate_line_wrapped_by(&ctx, &jobs[0], &text_wrapping(100.0, false)),
+            0
+        );
+        assert!(calculate_line_wrapped_by(&ctx, &jobs[1], &text_wrapping(100.0, false)) > 0);
+    }
+
+    #[test]
+    fn words_kept_whole_unless_breaking_anywhere() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |_| {});
+
+        // Ten characters fit a row, each word takes a row of its own unless they're broken.
+        let max_width = calculate_line_width(&ctx, &make_job(&"0".repeat(10))) + 1.0;
+        let job = make_job("aaaaaa bbbbbb cccccc");
+        assert_eq!(
+            calculate_line_wrapped_by(&ctx, &job, &text_wrapping(max_width, false)),
+            2
+        );
+        assert_eq!(
+            calculate_line_wrapped_by(&ctx, &job, &text_wrapping(max_width, true)),
+            1
+        );
+
+        let mut wrap_cache = WrapCache::default();
+        let wrapping = text_wrapping(max_width, false);
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &wrapping, 12.0, true, || 2),
+            2
+        );
+        let wrapping = text_wrapping(max_width, true);
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &wrapping, 12.0, true, || 1),
+            1
+        );
+    }
+
+    #[test]
+    fn whitespace_glyphs_as_wide_as_spaces() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |_| {});
+
+        let width = |text: &str| calculate_line_width(&ctx, &make_job(text));
+        let spaces_width = width("a  b");
+        assert_eq!(
+            width(&format!(
+                "a{}{}b",
+                crate::whitespace::SPACE_GLYPH,
+                crate::whitespace::TAB_GLYPH
+            )),
+            spaces_width
+        );
+    }
+
+    #[test]
+    fn long_comment_wraps() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |_| {});
+
+        let short_comment = comment_layout_job("checked", 12.0, Color::LIGHT_GREEN);
+        assert_eq!(
+            calculate_line_wrapped_by(&ctx, &short_comment, &text_wrapping(400.0, false)),
+            0
+        );
+
+        let long_comment = comment_layout_job(
+            &"this one was checked twice ".repeat(10),
+            12.0,
+            Color::LIGHT_GREEN,
+        );
+        let wrapped_by =
+            calculate_line_wrapped_by(&ctx, &long_comment, &text_wrapping(400.0, false));
+        assert!(wrapped_by >= 2, "{}", wrapped_by);
+        assert!(
+            calculate_line_wrapped_by(&ctx, &long_comment, &text_wrapping(200.0, false))
+                > wrapped_by
+        );
+    }
+
+    #[test]
+    fn gutter_rows_keep_original_lines_of_filtered_log() {
+        let text: String = (1..=600)
+            .map(|line_no| {
+                let kind = if line_no % 3 == 0 { "drop" } else { "keep" };
+                format!("{} {}\n", line_no, kind)
+            })
+            .collect();
+        let content = crate::log_content::LogContent::from_string(text);
+        let mut user_settings = crate::user_settings::UserSettings::default();
+        user_settings.filters[0].term = "keep".to_string();
+        let log_jobs = crate::recalculate_log_job(&content, &user_settings).unwrap();
+        let offsets = &log_jobs.visible_line_offsets;
+
+        // Every other line wraps, the rows above the line don't shift its original line.
+        let visible_line_no = offsets
+            .get_visible_line_for_original_line(500, crate::visible_line_count(&log_jobs))
+            .unwrap();
+        let first_row = visible_line_no - 10;
+        let line_wrapped_by: Vec<usize> = (0..20).map(|i| i % 2 * 3).collect();
+        let row_indices: Vec<usize> = (first_row..first_row + line_wrapped_by.len()).collect();
+        let gutter = gutter_rows(&row_indices, &line_wrapped_by, offsets);
+
+        let row_500 = gutter
+            .iter()
+            .find(|gutter_row| gutter_row.row_index == visible_line_no - 1)
+            .unwrap();
+        assert_eq!(row_500.original_line_no, 500);
+        assert_eq!(row_500.first_rendered_row, 9 + 4 * 3);
+        for gutter_row in &gutter {
+            assert_eq!(
+                gutter_row.original_line_no,
+                offsets.get_original_line_for_row(gutter_row.row_index)
+            );
+        }
+    }
+
+    #[test]
+    fn gutter_rows_line_up_with_wrapped_lines() {
+        // Lines 2 and 3 of the original filtered out.
+        let mut visible_line_offsets = VisibleLineOffsets::default();
+        visible_line_offsets.add_offset(4, 2);
+
+        // Scrolled so the second visible line is on top, it wraps onto two more rows.
+        let gutter = gutter_rows(&[1, 2, 3, 4], &[2, 0, 1, 0], &visible_line_offsets);
+        let numbered: Vec<(usize, usize, usize)> = gutter
+            .iter()
+            .map(|gutter_row| {
+                (
+                    gutter_row.row_index,
+                    gutter_row.original_line_no,
+                    gutter_row.first_rendered_row,
+                )
+            })
+            .collect();
+        assert_eq!(numbered, vec![(1, 4, 0), (2, 5, 3), (3, 6, 4), (4, 7, 6)]);
+
+        // Nothing depends on what was shown before.
+        assert_eq!(
+            gutter_rows(&[1, 2, 3, 4], &[2, 0, 1, 0], &visible_line_offsets),
+            gutter
+        );
+        assert!(gutter_rows(&[], &[], &visible_line_offsets).is_empty());
+
+        // Out of the log order, the rows keep their original lines.
+        let sorted_gutter = gutter_rows(&[3, 0], &[0, 1], &visible_line_offsets);
+        assert_eq!(sorted_gutter[0].original_line_no, 6);
+        assert_eq!(sorted_gutter[1].original_line_no, 1);
+        assert_eq!(sorted_gutter[1].first_rendered_row, 1);
+    }
+
+    #[test]
+    fn line_width_measured_with_font_size() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |_| {});
+
+        let short_width = calculate_line_width(&ctx, &make_job("short"));
+        let long_width = calculate_line_width(&ctx, &make_job(&"long line ".repeat(50)));
+        assert!(short_width > 0.0);
+        // Not wrapped, no matter how long.
+        assert!(long_width > short_width * 50.0);
+
+        let mut bigger_font_job = make_job("short");
+        bigger_font_job.sections[0].format.font_id = FontId::monospace(24.0);
+        let bigger_font_width = calculate_line_width(&ctx, &bigger_font_job);
+        // Glyphs are snapped to pixels, so it's not exactly twice as wide.
+        assert!(bigger_font_width > short_width * 1.5);
+    }
+
+    #[test]
+    fn max_line_width_recalculated_on_change() {
+        let mut max_line_width_cache = MaxLineWidthCache::default();
+
+        assert_eq!(
+            max_line_width_cache.get_or_calculate(12.0, 10, || 300.0),
+            300.0
+        );
+        // Cached, calculation is not called again.
+        assert_eq!(
+            max_line_width_cache.get_or_calculate(12.0, 10, || 500.0),
+            300.0
+        );
+
+        // Lines appended.
+        assert_eq!(
+            max_line_width_cache.get_or_calculate(12.0, 11, || 400.0),
+            400.0
+        );
+        assert_eq!(
+            max_line_width_cache.get_or_calculate(24.0, 11, || 800.0),
+            800.0
+        );
+
+        max_line_width_cache.clear();
+        assert_eq!(
+            max_line_width_cache.get_or_calculate(24.0, 11, || 700.0),
+            700.0
+        );
+    }
+
+    #[test]
+    fn cache_invalidated_on_parameter_change() {
+        let mut wrap_cache = WrapCache::default();
+
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(100.0, false), 12.0, true, || 3),
+            3
+        );
+        // Cached, calculation is not called again.
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(100.0, false), 12.0, true, || 7),
+            3
+        );
+
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(200.0, false), 12.0, true, || 1),
+            1
+        );
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(200.0, false), 14.0, true, || 2),
+            2
+        );
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(200.0, false), 14.0, false, || 0),
+            0
+        );
+
+        wrap_cache.clear();
+        assert_eq!(
+            wrap_cache.get_or_calculate(0, &text_wrapping(200.0, false), 14.0, false, || 5),
+            5
+        );
+    }
+}
@@ -1,5 +1,5 @@
 // TODO: add some favicon
 
-fn main() {
-    gui::run_gui();
+fn main() -> std::process::ExitCode {
+    gui::run()
 }